@@ -0,0 +1,59 @@
+//! Building trees from asynchronous streams of leaves.
+
+use crate::builder::MerkleTreeBuilder;
+use crate::error::MerkleTreeError;
+use crate::merkle_tree::MerkleTree;
+
+use alloy_primitives::B256;
+use std::collections::{HashMap, HashSet};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+impl MerkleTree {
+    /// Builds a tree from newline-delimited leaves read from `reader`, with the options of
+    /// `builder`.
+    ///
+    /// Each line, without its line ending, is one leaf; blank lines are skipped. Leaves are
+    /// hashed as they arrive, so only their hashes stay in memory, plus their data if the
+    /// builder retains it. Wrap sockets and files in a `tokio::io::BufReader`.
+    pub async fn from_async_reader<R>(
+        mut reader: R,
+        builder: &MerkleTreeBuilder,
+    ) -> Result<Self, MerkleTreeError>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let mut leaf_hashes: Vec<B256> = Vec::new();
+        let mut leaves: HashMap<B256, Vec<u8>> = HashMap::new();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line).await? == 0 {
+                break;
+            }
+            let leaf = line
+                .strip_suffix(b"\n")
+                .map_or(&line[..], |leaf| leaf.strip_suffix(b"\r").unwrap_or(leaf));
+            if leaf.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            builder.check_leaf(leaf_hashes.len(), leaf)?;
+            let leaf_hash = builder.hash_leaf(leaf);
+            if builder.retains_leaf_data() {
+                leaves.insert(leaf_hash, leaf.to_vec());
+            }
+            leaf_hashes.push(leaf_hash);
+        }
+
+        if builder.dedupes() {
+            let mut seen = HashSet::new();
+            leaf_hashes.retain(|hash| seen.insert(*hash));
+        }
+        if builder.sorts_leaves() {
+            leaf_hashes.sort();
+        }
+        let mut tree = builder.build_from_leaf_hashes(leaf_hashes)?;
+        tree.leaves = leaves;
+        Ok(tree)
+    }
+}
@@ -0,0 +1,191 @@
+//! Signed root attestations.
+//!
+//! An attestation binds a tree's root, size and hash function to the address that vouches
+//! for them. The signature is an EIP-191 `personal_sign` over [`SignedRoot::digest`],
+//! `keccak256(abi.encodePacked(root, uint64(leafCount), uint8(hashAlgorithmId)))`, so
+//! besides [`SignedRoot::verify`] it can be checked on-chain with OpenZeppelin's
+//! `ECDSA.recover(MessageHashUtils.toEthSignedMessageHash(digest), signature)`.
+
+use crate::error::MerkleTreeError;
+use crate::hasher::HashAlgorithm;
+use crate::merkle_tree::MerkleTree;
+use crate::schema::{self, Versioned};
+use crate::serialization::HashFormat;
+
+use alloy_primitives::{keccak256, Address, B256};
+// Later alloy-primitives 0.8 releases deprecate the signature type alloy-signer 0.3 returns
+#[allow(deprecated)]
+use alloy_signer::Signature;
+use alloy_signer::{Signer, SignerSync};
+use serde::{Deserialize, Serialize};
+
+/// A tree's root signed by an Ethereum account.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct SignedRoot {
+    /// The signed root hash.
+    #[serde(with = "crate::serialization::b256_hex")]
+    pub root: B256,
+
+    /// Number of leaves in the tree, so a root cannot be passed off for a different size.
+    pub leaf_count: usize,
+
+    /// Hash function of the tree. Dumps without it use keccak256.
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+
+    /// Address of the account that signed the root.
+    pub signer: Address,
+
+    /// The 65-byte `r || s || v` signature.
+    #[serde(with = "crate::serialization::bytes_hex")]
+    pub signature: Vec<u8>,
+}
+
+impl SignedRoot {
+    /// Signs the root, size and hash function of `tree` with `signer`.
+    pub fn sign<S>(tree: &MerkleTree, signer: &S) -> Result<Self, MerkleTreeError>
+    where
+        S: Signer + SignerSync,
+    {
+        let mut signed_root = SignedRoot {
+            root: *tree.root_hash(),
+            leaf_count: tree.leaf_count(),
+            hash_algorithm: tree.hash_algorithm,
+            signer: Address::from_slice(signer.address().as_slice()),
+            signature: Vec::new(),
+        };
+        let signature = signer
+            .sign_message_sync(signed_root.digest().as_slice())
+            .map_err(|e| MerkleTreeError::SignatureError(e.to_string()))?;
+        signed_root.signature = signature.as_bytes().to_vec();
+        Ok(signed_root)
+    }
+
+    /// Returns the digest that is signed, `keccak256(root || uint64(leaf_count) ||
+    /// uint8(hash_algorithm.id()))` with the integers big-endian.
+    pub fn digest(&self) -> B256 {
+        let mut encoded = Vec::with_capacity(32 + 8 + 1);
+        encoded.extend_from_slice(self.root.as_slice());
+        encoded.extend_from_slice(&(self.leaf_count as u64).to_be_bytes());
+        encoded.push(self.hash_algorithm.id());
+        keccak256(encoded)
+    }
+
+    /// Returns whether the signature over the digest was made by `signer`.
+    ///
+    /// Malformed signatures are errors; a well-formed signature by another account is not.
+    #[allow(deprecated)]
+    pub fn verify(&self) -> Result<bool, MerkleTreeError> {
+        let signature = Signature::try_from(self.signature.as_slice())
+            .map_err(|e| MerkleTreeError::SignatureError(e.to_string()))?;
+        let recovered = signature
+            .recover_address_from_msg(self.digest().as_slice())
+            .map_err(|e| MerkleTreeError::SignatureError(e.to_string()))?;
+        Ok(recovered.as_slice() == self.signer.as_slice())
+    }
+
+    /// Returns whether the attestation is for `tree`'s root, size and hash function.
+    pub fn covers(&self, tree: &MerkleTree) -> bool {
+        self.root == *tree.root_hash()
+            && self.leaf_count == tree.leaf_count()
+            && self.hash_algorithm == tree.hash_algorithm
+    }
+
+    /// Serializes the attestation to a JSON string.
+    pub fn to_json(&self) -> Result<String, MerkleTreeError> {
+        self.to_json_with(HashFormat::default())
+    }
+
+    /// Serializes the attestation to a JSON string, writing hashes in `format`.
+    pub fn to_json_with(&self, format: HashFormat) -> Result<String, MerkleTreeError> {
+        serde_json::to_string_pretty(&format.encoded(Versioned::new(self)))
+            .map_err(MerkleTreeError::SerdeError)
+    }
+
+    /// Deserializes an attestation from a JSON string.
+    pub fn from_json(json_str: &str) -> Result<Self, MerkleTreeError> {
+        schema::from_slice(json_str.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::b256;
+
+    #[cfg(feature = "cli")]
+    fn tree() -> MerkleTree {
+        let leaves: Vec<Vec<u8>> = ["a", "b", "c"].map(|leaf| leaf.into()).to_vec();
+        MerkleTree::new(&leaves).unwrap()
+    }
+
+    #[test]
+    fn digest_matches_the_packed_encoding() {
+        // keccak256(abi.encodePacked(bytes32(0x11..11), uint64(4), uint8(1)))
+        let signed_root = SignedRoot {
+            root: B256::repeat_byte(0x11),
+            leaf_count: 4,
+            hash_algorithm: HashAlgorithm::Keccak256,
+            signer: Address::ZERO,
+            signature: Vec::new(),
+        };
+        assert_eq!(
+            signed_root.digest(),
+            b256!("69505f73470783fe7b3f989aafe8df4c7701a4f8200c0c5eedc505273eba7910")
+        );
+    }
+
+    /// A fixed local key. alloy-signer-local, which provides local keys, is a dependency of
+    /// the CLI only.
+    #[cfg(feature = "cli")]
+    fn signer(key: u8) -> alloy_signer_local::PrivateKeySigner {
+        alloy_signer_local::PrivateKeySigner::from_bytes(&[key; 32].into()).unwrap()
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn signs_and_verifies() {
+        let tree = tree();
+        let signed_root = SignedRoot::sign(&tree, &signer(1)).unwrap();
+        assert_eq!(
+            signed_root.signer.as_slice(),
+            signer(1).address().as_slice()
+        );
+        assert!(signed_root.covers(&tree));
+        assert!(signed_root.verify().unwrap());
+
+        let reloaded = SignedRoot::from_json(&signed_root.to_json().unwrap()).unwrap();
+        assert_eq!(reloaded, signed_root);
+        assert!(reloaded.verify().unwrap());
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn rejects_other_signers_and_tampering() {
+        let tree = tree();
+        let signed_root = SignedRoot::sign(&tree, &signer(1)).unwrap();
+
+        let mut forged = signed_root.clone();
+        forged.signer = SignedRoot::sign(&tree, &signer(2)).unwrap().signer;
+        assert!(!forged.verify().unwrap());
+
+        let tamperings: [fn(&mut SignedRoot); 3] = [
+            |signed_root| signed_root.root = B256::repeat_byte(1),
+            |signed_root| signed_root.leaf_count += 1,
+            |signed_root| signed_root.hash_algorithm = HashAlgorithm::Sha256,
+        ];
+        for tamper in tamperings {
+            let mut tampered = signed_root.clone();
+            tamper(&mut tampered);
+            assert!(!tampered.covers(&tree));
+            assert!(!tampered.verify().unwrap());
+        }
+
+        let mut malformed = signed_root;
+        malformed.signature.truncate(64);
+        assert!(matches!(
+            malformed.verify(),
+            Err(MerkleTreeError::SignatureError(_))
+        ));
+    }
+}
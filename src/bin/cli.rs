@@ -1,13 +1,69 @@
 // src/bin/cli.rs
 
-use alloy_primitives::hex::{decode, encode};
-use alloy_primitives::{TxHash, B256};
-use clap::{Parser, Subcommand};
-use merkle_tree::{MerkleProof, MerkleTree, MerkleTreeError};
-use serde::ser::Error;
-
-use std::fs;
-use std::path::PathBuf;
+use alloy_primitives::hex::{decode, encode_prefixed};
+use alloy_primitives::{Address, B256, U256};
+use alloy_signer_local::PrivateKeySigner;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use indicatif::{ProgressBar, ProgressStyle};
+use merkle_tree::compression::{compress, decompress, Compression};
+use merkle_tree::envelope::{self, Envelope, EnvelopePolicy};
+use merkle_tree::fixture::Fixture;
+use merkle_tree::leaf_encoding::LeafEncoding;
+use merkle_tree::openzeppelin::{StandardMerkleTree, StandardMultiProof};
+use merkle_tree::rolling::{CheckpointPolicy, RollingTree};
+use merkle_tree::serialization::{parse_b256, HashEncoding, HashFormat};
+use merkle_tree::solidity::{generate_verifier, VerifierOptions};
+use merkle_tree::timestamp::{Attestation, TimestampProof, DEFAULT_CALENDARS};
+use merkle_tree::{
+    BuildProgress, DisplayOptions, DumpFormat, HashAlgorithm, InputPosition, MerkleProof,
+    MerkleTree, MerkleTreeBuilder, MerkleTreeError, MultiProof, OddNodePolicy, SignedRoot,
+};
+
+use notify::{RecursiveMode, Watcher};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Path that selects stdin for inputs and stdout for outputs.
+const STDIO: &str = "-";
+
+/// Exit code for a proof or tree that fails verification. Errors such as unreadable
+/// input exit with 1.
+const EXIT_INVALID: u8 = 2;
+
+/// Config file read from the current directory when `--config` is not given.
+const CONFIG_FILE: &str = "merkle.toml";
+
+/// Builds with at least this many leaves draw a progress bar on stderr.
+const PROGRESS_MIN_LEAVES: usize = 100_000;
+
+/// Commands accepted by the `repl` subcommand.
+const REPL_HELP: &str = "\
+Commands:
+  add <leaf>            Append a leaf, in the --leaf-format or --leaf-encoding encoding
+  remove <index>        Remove the leaf at <index>; later leaves move down one place
+  root                  Print the root hash
+  leaves                List the leaf hashes
+  proof <index> [file]  Print the proof of the leaf at <index>, or save it to <file>
+  verify <file> [root]  Verify a saved proof against <root> [default: the current root]
+  help                  Show this message
+  quit                  Leave the REPL";
+
+/// How long `watch` waits for a burst of file events from one save to settle.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Default chunk size of the `hash-file` subcommand, 1 MiB.
+const DEFAULT_CHUNK_SIZE: u64 = 1 << 20;
+
 /// Simple program to manage a Merkle Tree
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -16,182 +72,3690 @@ struct Cli {
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
 
+    /// TOML file of defaults for options not given on the command line
+    /// [default: `merkle.toml` in the current directory, if present]
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Print results as human-readable text or as JSON
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Print no progress bars or text status messages; results, verdicts and errors are
+    /// still printed
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Write hashes in output files and messages with a `0x` prefix
+    #[arg(long, global = true)]
+    hex_prefix: bool,
+
+    /// Encoding for hashes in output files and messages (hex or base64). Leaf data written
+    /// in base64 is prefixed with `base64:`, telling it apart from hex when read back
+    #[arg(long, global = true, value_name = "ENCODING", default_value_t = HashEncoding::Hex)]
+    hash_encoding: HashEncoding,
+
+    /// Print how long reading, hashing, building and writing took, on stderr
+    #[arg(long, global = true)]
+    timings: bool,
+
+    /// Number of threads for building trees and generating proofs [default: all cores]
+    #[arg(short, long, global = true, value_name = "N")]
+    jobs: Option<usize>,
+
+    #[command(flatten)]
+    leaves: LeafOptions,
+
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Build a Merkle Tree from a file containing transaction hashes
+    /// Build a Merkle Tree from a file containing transaction hashes or other leaves
     Build {
-        /// Input file containing transaction hashes (one per line, hex encoded)
+        /// Input file containing leaves (see `--leaf-format` and `--input-format`),
+        /// or `-` for stdin. Repeat it or pass a glob such as `'chunks/*.txt'` to
+        /// concatenate several files, in argument order and then sorted path order
+        /// [default: `input` from the config file]
         #[arg(short, long, value_name = "FILE")]
-        input: PathBuf,
+        input: Vec<String>,
+
+        /// Output file to save the Merkle Tree, or `-` for stdout
+        /// [default: `output` from the config file]
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Encoding of the output (json, compact, bincode or cbor); readers detect it
+        #[arg(long, value_name = "FORMAT", default_value_t = DumpFormat::Json)]
+        output_format: DumpFormat,
+
+        /// Compress the output (zstd or gzip)
+        #[arg(long, value_name = "ALGORITHM")]
+        compress: Option<Compression>,
+    },
+    /// Build a Merkle Tree from a column of a SQLite or Postgres table
+    ///
+    /// Binary columns are used as leaf data directly; other values are parsed like lines of
+    /// an input file (see `--leaf-format` and `--leaf-encoding`).
+    #[cfg(any(feature = "sqlite", feature = "postgres"))]
+    BuildSql {
+        /// `postgres://` URL, or path of a SQLite database
+        #[arg(long, value_name = "URL")]
+        database: String,
+
+        /// Table holding the leaves
+        #[arg(long, value_name = "TABLE")]
+        table: String,
+
+        /// Column holding the leaves
+        #[arg(long, value_name = "COLUMN")]
+        column: String,
+
+        /// Column ordering the leaves; without it, the order is up to the database
+        #[arg(long, value_name = "COLUMN")]
+        order_by: Option<String>,
+
+        /// Output file to save the Merkle Tree, or `-` for stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Encoding of the output (json, compact, bincode or cbor); readers detect it
+        #[arg(long, value_name = "FORMAT", default_value_t = DumpFormat::Json)]
+        output_format: DumpFormat,
+
+        /// Also write a proof of every leaf to this table, keyed by root and leaf index
+        #[arg(long, value_name = "TABLE")]
+        proofs_table: Option<String>,
+    },
+    /// Build a Merkle Tree from the transaction hashes of a block fetched over JSON-RPC
+    ///
+    /// Each transaction hash is a 32-byte leaf, as with `build --leaf-format hash32`.
+    #[cfg(feature = "rpc")]
+    FetchBlock {
+        /// JSON-RPC endpoint URL
+        #[arg(long, value_name = "URL")]
+        rpc: String,
+
+        /// Block number, or a tag such as `latest`, `safe` or `finalized`
+        #[arg(short, long, value_name = "BLOCK")]
+        block: alloy_rpc_types_eth::BlockNumberOrTag,
 
-        /// Output file to save the Merkle Tree JSON
+        /// Output file to save the Merkle Tree JSON, or `-` for stdout
         #[arg(short, long, value_name = "FILE")]
         output: PathBuf,
+
+        /// Compress the output (zstd or gzip)
+        #[arg(long, value_name = "ALGORITHM")]
+        compress: Option<Compression>,
     },
     /// Generate a Merkle Proof for a specific transaction hash
     Proof {
-        /// Input file containing transaction hashes (one per line, hex encoded)
+        /// Input file containing leaves (see `--leaf-format` and `--input-format`) or a
+        /// serialized tree, or `-` for stdin [default: `input` from the config file]
         #[arg(short, long, value_name = "FILE")]
-        input: PathBuf,
+        input: Option<PathBuf>,
 
-        /// The transaction hash to generate proof for (hex encoded)
+        /// The leaf to generate proof for, in the `--leaf-format` or `--leaf-encoding` encoding
         #[arg(short, long, value_name = "TX_HASH")]
         tx_hash: String,
 
-        /// Output file to save the Merkle Proof JSON
+        /// Output file to save the Merkle Proof JSON, or `-` for stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// Compress the output (zstd or gzip)
+        #[arg(long, value_name = "ALGORITHM")]
+        compress: Option<Compression>,
+    },
+    /// Print the Merkle Root of a file containing transaction hashes or other leaves
+    Root {
+        /// Input file containing leaves (see `--leaf-format` and `--input-format`),
+        /// or `-` for stdin [default: `input` from the config file]
+        #[arg(short, long, value_name = "FILE")]
+        input: Option<PathBuf>,
+    },
+    /// Print the Merkle Root of a file's fixed-size chunks
+    ///
+    /// Each chunk is a leaf hashed with `--hash`, and the last chunk may be shorter.
+    /// `--range` proves the chunks holding a byte range, `START / chunk-size` through
+    /// `(END - 1) / chunk-size`, as a multiproof for `verify-multiproof`.
+    HashFile {
+        /// File to hash, or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
+
+        /// Chunk size in bytes
+        #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_CHUNK_SIZE)]
+        #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+        chunk_size: u64,
+
+        /// Output file to save the Merkle Tree, or `-` for stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Byte range to prove, as `START..END` with `END` exclusive
+        #[arg(long, value_name = "START..END", requires = "proof")]
+        range: Option<ByteRange>,
+
+        /// Output file to save the range's multiproof JSON, or `-` for stdout
+        #[arg(long, value_name = "FILE", requires = "range")]
+        proof: Option<PathBuf>,
+    },
+    /// Print the Merkle Root of a directory's files
+    ///
+    /// Regular files are found recursively and sorted by their `/`-separated path relative
+    /// to the directory; symlinks are skipped. Each file is a leaf hashed from its path
+    /// and content as `H(H(path) || H(content))`, with `H` the `--hash` algorithm and the
+    /// outer hash prefixed with `--domain-separation`.
+    HashDir {
+        /// Directory to hash
+        #[arg(short, long, value_name = "DIR")]
+        input: PathBuf,
+
+        /// Output file to save the Merkle Tree, or `-` for stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Output file to save a JSON manifest of each file's path, content hash and leaf
+        /// index, or `-` for stdout
+        #[arg(long, value_name = "FILE")]
+        manifest: Option<PathBuf>,
+    },
+    /// Rebuild the tree whenever the input file changes, printing each new root
+    Watch {
+        /// Input file containing leaves (see `--leaf-format` and `--input-format`)
+        /// [default: `input` from the config file]
+        #[arg(short, long, value_name = "FILE")]
+        input: Option<PathBuf>,
+
+        /// Output file to save the Merkle Tree JSON after each rebuild
+        /// [default: `output` from the config file]
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Maintain a tree over streamed leaves, printing a root checkpoint after each batch
+    ///
+    /// Leaves are read line by line (see `--leaf-format`) until the input ends, or taken
+    /// from a Kafka topic as raw message payloads. Checkpoints are signed when a key is given.
+    Ingest {
+        /// File to read leaves from, such as a named pipe, or `-` for stdin
+        #[arg(short, long, value_name = "FILE", default_value = "-")]
+        input: PathBuf,
+
+        /// Tree file rewritten at each checkpoint; an existing one is resumed from
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Checkpoint after this many new leaves
+        #[arg(long, value_name = "COUNT")]
+        every: Option<usize>,
+
+        /// Checkpoint this many seconds after the last one, if new leaves arrived
+        #[arg(long, value_name = "SECONDS")]
+        interval: Option<u64>,
+
+        /// Hex-encoded private key signing each checkpoint. It is visible to other local
+        /// users in the process list; prefer `--keystore` on shared machines
+        #[arg(long, value_name = "KEY")]
+        private_key: Option<String>,
+
+        /// Encrypted JSON keystore holding the key signing each checkpoint
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with = "private_key",
+            requires = "password_file"
+        )]
+        keystore: Option<PathBuf>,
+
+        /// File holding the keystore password
+        #[arg(long, value_name = "FILE")]
+        password_file: Option<PathBuf>,
+
+        /// Kafka bootstrap servers to consume leaves from instead of `--input`
+        #[cfg(feature = "kafka")]
+        #[arg(long, value_name = "BROKERS", requires = "kafka_topic")]
+        kafka_brokers: Option<String>,
+
+        /// Kafka topic holding the leaves
+        #[cfg(feature = "kafka")]
+        #[arg(long, value_name = "TOPIC")]
+        kafka_topic: Option<String>,
+
+        /// Kafka consumer group
+        #[cfg(feature = "kafka")]
+        #[arg(long, value_name = "GROUP", default_value = "merkle-tree")]
+        kafka_group: String,
+    },
+    /// Explore a tree interactively, adding and removing leaves and checking proofs
+    ///
+    /// Type `help` at the prompt for the list of commands.
+    Repl {
+        /// Serialized Merkle Tree or input file of leaves to start from [default: an empty tree]
+        #[arg(short, long, value_name = "FILE")]
+        input: Option<PathBuf>,
+    },
+    /// Generate Merkle Proofs for every leaf in one run
+    ProofAll {
+        /// Input file containing leaves (see `--leaf-format` and `--input-format`),
+        /// or `-` for stdin [default: `input` from the config file]
+        #[arg(short, long, value_name = "FILE")]
+        input: Option<PathBuf>,
+
+        /// Output JSON file (or `-` for stdout) for `map`, output directory for `dir`
+        #[arg(short, long, value_name = "PATH")]
+        output: PathBuf,
+
+        /// Write a single JSON map keyed by leaf index, or one `<index>.json` file per leaf
+        #[arg(long, value_enum, default_value_t = ProofLayout::Map)]
+        layout: ProofLayout,
+    },
+    /// Generate Merkle Proofs for a list of target leaves, building the tree once
+    ProofBatch {
+        /// Input file containing leaves (see `--leaf-format` and `--input-format`) or a
+        /// serialized tree, or `-` for stdin [default: `input` from the config file]
+        #[arg(short, long, value_name = "FILE")]
+        input: Option<PathBuf>,
+
+        /// File with one target leaf per line, in the `--leaf-format` or `--leaf-encoding`
+        /// encoding, or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        targets: PathBuf,
+
+        /// Directory to write one `<leaf hash>.json` proof per target into
+        #[arg(long, value_name = "DIR")]
+        output_dir: PathBuf,
+    },
+    /// Generate one Merkle Proof for a set of target leaves
+    Multiproof {
+        /// Input file containing leaves (see `--leaf-format` and `--input-format`) or a
+        /// serialized tree, or `-` for stdin [default: `input` from the config file]
+        #[arg(short, long, value_name = "FILE")]
+        input: Option<PathBuf>,
+
+        /// File with one target leaf per line, in the `--leaf-format` or `--leaf-encoding`
+        /// encoding, or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        targets: PathBuf,
+
+        /// Output file to save the multiproof JSON, or `-` for stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// Write `{leaves, proof, proofFlags}` for OpenZeppelin's `multiProofVerify`;
+        /// requires `--sorted-pairs` and keccak256
+        #[arg(long)]
+        openzeppelin: bool,
+    },
+    /// Verify a multiproof, in this crate's or OpenZeppelin's encoding, against a root
+    VerifyMultiproof {
+        /// Merkle Root hash (hex encoded, `0x` optional, or base64)
+        #[arg(short, long, value_name = "ROOT_HASH")]
+        root_hash: String,
+
+        /// Multiproof JSON file, or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        proof: PathBuf,
+    },
+    /// Build an airdrop claims file from a CSV of (address, amount) rows
+    ///
+    /// Leaves use the OpenZeppelin standard encoding, `keccak256(keccak256(abi.encode(address,
+    /// uint256)))`, with sorted pairs, so proofs verify with OpenZeppelin's `MerkleProof.verify`.
+    Airdrop {
+        /// CSV file of `address,amount` rows (an optional header row is skipped), or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
+
+        /// Output file to save the claims JSON, or `-` for stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Audit a serialized Merkle Tree, recomputing every hash and checking its structure
+    VerifyTree {
+        /// Serialized Merkle Tree in any `--output-format` (optionally compressed), or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
+    },
+    /// Append leaves to a serialized Merkle Tree without rebuilding it from the original input
+    Append {
+        /// Serialized Merkle Tree in any `--output-format` (optionally compressed)
+        #[arg(short, long, value_name = "FILE")]
+        tree: PathBuf,
+
+        /// Input file containing the new leaves (see `--leaf-format` and `--input-format`),
+        /// or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
+
+        /// Output file to save the updated Merkle Tree JSON (may be the same as `--tree`),
+        /// or `-` for stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Replace one leaf of a serialized Merkle Tree and recompute the root
+    Update {
+        /// Serialized Merkle Tree in any `--output-format` (optionally compressed)
+        #[arg(short, long, value_name = "FILE")]
+        tree: PathBuf,
+
+        /// Index of the leaf to replace
+        #[arg(long, value_name = "INDEX")]
+        index: usize,
+
+        /// The new leaf, in the `--leaf-format` or `--leaf-encoding` encoding
+        #[arg(short, long, value_name = "DATA")]
+        data: String,
+
+        /// Output file to save the updated Merkle Tree JSON, or `-` for stdout
+        /// [default: overwrite `--tree`]
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Print statistics about a serialized Merkle Tree
+    Inspect {
+        /// Serialized Merkle Tree in any `--output-format` (optionally compressed), or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
+    },
+    /// Print a Merkle Tree's nodes, one hash per line
+    Print {
+        /// Serialized Merkle Tree in any `--output-format` (optionally compressed), or a file
+        /// of leaves, or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
+
+        /// Number of levels printed below the root; deeper subtrees are shown as `…`
+        #[arg(long, value_name = "LEVELS")]
+        max_depth: Option<usize>,
+
+        /// Number of hex digits printed per hash
+        #[arg(long, value_name = "DIGITS")]
+        truncate: Option<usize>,
+
+        /// Indent with spaces instead of drawing branches with box-drawing characters
+        #[arg(long)]
+        ascii: bool,
+    },
+    /// Render a serialized Merkle Tree as a diagram
+    Export {
+        /// Serialized Merkle Tree in any `--output-format` (optionally compressed), or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
+
+        /// Diagram format (`--format` selects how results are reported)
+        #[arg(long, value_enum, value_name = "FORMAT")]
+        to: DiagramFormat,
+
+        /// Highlight the proof path of the leaf at this index
+        #[arg(long, value_name = "INDEX")]
+        highlight: Option<usize>,
+
+        /// Output file for the diagram, or `-` for stdout
         #[arg(short, long, value_name = "FILE")]
         output: PathBuf,
     },
+    /// Compare two trees or leaf files, reporting differing, added and removed leaves
+    ///
+    /// Each file may be a serialized Merkle Tree or an input file of leaves.
+    Diff {
+        /// First tree or leaf file
+        #[arg(value_name = "A")]
+        a: PathBuf,
+
+        /// Second tree or leaf file
+        #[arg(value_name = "B")]
+        b: PathBuf,
+    },
     /// Verify a Merkle Proof against a given Merkle Root
     Verify {
-        /// Merkle Root hash (hex encoded)
+        /// Merkle Root hash (hex encoded, `0x` optional, or base64)
         #[arg(short, long, value_name = "ROOT_HASH")]
         root_hash: String,
 
-        /// Input file containing the Merkle Proof JSON
+        /// Merkle Proof as JSON, CBOR or bincode (optionally zstd- or gzip-compressed),
+        /// or `-` for stdin. A JSON array of proofs or a directory of proof files verifies
+        /// each one against the root, exiting with an error if any is invalid
+        #[arg(short, long, value_name = "PATH")]
+        proof: PathBuf,
+    },
+    /// Serve proofs for a serialized Merkle Tree over HTTP
+    ///
+    /// Endpoints: `GET /root`; `GET /proof/{leaf}` with the leaf in the `--leaf-format` or
+    /// `--leaf-encoding` encoding; `GET /proof/index/{index}`; `POST /verify` with
+    /// `{"proof": ..., "root": ...}`, where `root` defaults to the served root; and
+    /// `POST /leaves` with `{"leaves": [...]}`, which appends and saves the tree to `--tree`.
+    /// `POST /rpc` answers the JSON-RPC 2.0 methods `merkle_getRoot`, `merkle_getProof` and
+    /// `merkle_verifyProof`. `GET /ws` is a WebSocket pushing the root, leaf count and
+    /// version on connecting and after each append.
+    #[cfg(feature = "server")]
+    Serve {
+        /// Serialized Merkle Tree in any `--output-format` (optionally compressed)
+        #[arg(short, long, value_name = "FILE")]
+        tree: PathBuf,
+
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        host: std::net::IpAddr,
+
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+
+        /// Number of generated proofs to keep cached for repeat requests (0 disables)
+        #[arg(long, value_name = "N", default_value_t = 10_000)]
+        proof_cache: usize,
+    },
+    /// Sign a serialized tree's root, writing a signed-root attestation JSON
+    ///
+    /// The signature is an EIP-191 `personal_sign` over the keccak256 of the root, the leaf
+    /// count as a big-endian u64 and the hash algorithm's id byte.
+    SignRoot {
+        /// Serialized Merkle Tree in any `--output-format` (optionally compressed), or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
+
+        /// Hex-encoded private key. It is visible to other local users in the process
+        /// list; prefer `--keystore` on shared machines
+        #[arg(long, value_name = "KEY", required_unless_present = "keystore")]
+        private_key: Option<String>,
+
+        /// Encrypted JSON keystore holding the signing key
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with = "private_key",
+            requires = "password_file"
+        )]
+        keystore: Option<PathBuf>,
+
+        /// File holding the keystore password
+        #[arg(long, value_name = "FILE")]
+        password_file: Option<PathBuf>,
+
+        /// Output file for the attestation JSON, or `-` for stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Verify a signed-root attestation
+    VerifySignature {
+        /// Attestation JSON file, or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        attestation: PathBuf,
+
+        /// Also require the attestation to be for this tree's root and size
+        #[arg(short, long, value_name = "FILE")]
+        tree: Option<PathBuf>,
+
+        /// Also require the attestation to be signed by this address
+        #[arg(long, value_name = "ADDRESS")]
+        signer: Option<Address>,
+    },
+    /// Timestamp a tree's root with OpenTimestamps calendars
+    ///
+    /// Attestations stay pending until the calendars commit the root to Bitcoin, usually
+    /// within a few hours; then run `upgrade-timestamp` to complete the proof.
+    Timestamp {
+        /// Serialized Merkle Tree in any `--output-format` (optionally compressed), or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
+
+        /// Output file for the `.ots` proof, or `-` for stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// Calendar server to submit the root to; repeat for several
+        /// [default: the public OpenTimestamps calendars]
+        #[arg(long, value_name = "URL")]
+        calendar: Vec<String>,
+    },
+    /// Complete the pending attestations of an OpenTimestamps proof, rewriting it in place
+    UpgradeTimestamp {
+        /// `.ots` proof from `timestamp`
         #[arg(short, long, value_name = "FILE")]
         proof: PathBuf,
     },
-}
+    /// Verify that an OpenTimestamps proof is for a tree's root and list its attestations
+    ///
+    /// Bitcoin attestations name a block and the merkle root it must have; compare it with
+    /// the block header from a node or block explorer.
+    VerifyTimestamp {
+        /// Serialized Merkle Tree in any `--output-format` (optionally compressed), or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
 
-fn main() -> Result<(), MerkleTreeError> {
-    let cli = Cli::parse();
+        /// `.ots` proof from `timestamp`
+        #[arg(short, long, value_name = "FILE")]
+        proof: PathBuf,
+    },
+    /// Verify a Merkle Proof against the root currently stored in a contract
+    #[cfg(feature = "rpc")]
+    VerifyOnchain {
+        /// JSON-RPC endpoint URL
+        #[arg(long, value_name = "URL")]
+        rpc: String,
 
-    // Initialize logging
-    match cli.verbose {
-        0 => {}
-        1 => env_logger::Builder::from_default_env()
-            .filter_level(log::LevelFilter::Info)
-            .init(),
-        2 => env_logger::Builder::from_default_env()
-            .filter_level(log::LevelFilter::Debug)
-            .init(),
-        _ => env_logger::Builder::from_default_env()
-            .filter_level(log::LevelFilter::Trace)
-            .init(),
-    }
+        /// Address of the contract holding the root
+        #[arg(long, value_name = "ADDRESS")]
+        contract: Address,
 
-    match &cli.command {
-        Commands::Build { input, output } => {
-            // Read transaction hashes from input file
-            let content = fs::read_to_string(input)?;
-            let tx_hashes: Result<Vec<TxHash>, _> = content
-                .lines()
-                .map(|line| {
-                    let bytes = decode(line.trim())
-                        .map_err(|e| serde_json::Error::custom(e.to_string()))?;
-                    if bytes.len() != 32 {
-                        return Err(serde_json::Error::custom("Invalid TxHash length"));
-                    }
-                    let mut array = [0u8; 32];
-                    array.copy_from_slice(&bytes);
-                    Ok(TxHash::from_slice(&array))
-                })
-                .collect();
+        /// Storage slot holding the root
+        #[arg(long, value_name = "SLOT", required_unless_present = "selector")]
+        slot: Option<U256>,
 
-            let tx_hashes = tx_hashes?;
+        /// View function returning the root, as a signature such as `merkleRoot()` or a
+        /// 4-byte selector
+        #[arg(long, value_name = "FUNCTION", conflicts_with = "slot")]
+        selector: Option<String>,
 
-            // Build the Merkle Tree
-            let merkle_tree = MerkleTree::new(&convert_fixed_bytes_to_vec_u8(&tx_hashes))?;
+        /// Merkle Proof as JSON, CBOR or bincode (optionally zstd- or gzip-compressed),
+        /// or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        proof: PathBuf,
+    },
+    /// Set a contract's root to the root of a tree by sending a transaction
+    #[cfg(feature = "publisher")]
+    Publish {
+        /// Input file containing leaves (see `--leaf-format` and `--input-format`) or a
+        /// serialized tree, or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
 
-            // Serialize to JSON
-            let json = merkle_tree.to_json()?;
+        /// JSON-RPC endpoint URL
+        #[arg(long, value_name = "URL")]
+        rpc: String,
 
-            // Write to output file
-            fs::write(output, json)?;
+        /// Address of the contract holding the root
+        #[arg(long, value_name = "ADDRESS")]
+        contract: Address,
 
-            println!(
-                "Merkle Tree built successfully. Root Hash: {}",
-                encode(merkle_tree.root_hash())
-            );
-        }
-        Commands::Proof {
-            input,
-            tx_hash,
-            output,
-        } => {
-            // Read transaction hashes from input file
-            let content = fs::read_to_string(input)?;
-            let tx_hashes: Result<Vec<TxHash>, _> = content
-                .lines()
-                .map(|line| {
-                    let bytes = decode(line.trim())
-                        .map_err(|e| serde_json::Error::custom(e.to_string()))?;
-                    if bytes.len() != 32 {
-                        return Err(serde_json::Error::custom("Invalid TxHash length"));
-                    }
-                    let mut array = [0u8; 32];
-                    array.copy_from_slice(&bytes);
-                    Ok(TxHash::from_slice(&array))
-                })
-                .collect();
+        /// Function setting the root, taking `(bytes32)` or `(bytes32,uint256)` where the
+        /// second argument is the number of leaves
+        #[arg(
+            long,
+            value_name = "FUNCTION",
+            default_value = merkle_tree::publisher::DEFAULT_FUNCTION
+        )]
+        function: String,
 
-            let tx_hashes = tx_hashes?;
+        /// Hex-encoded private key of the sender. It is visible to other local users in the
+        /// process list; prefer `--keystore` on shared machines
+        #[arg(long, value_name = "KEY", required_unless_present = "keystore")]
+        private_key: Option<String>,
 
-            // Build the Merkle Tree
-            let merkle_tree = MerkleTree::new(&convert_fixed_bytes_to_vec_u8(&tx_hashes))?;
-
-            // Parse the target TxHash
-            let target_bytes =
-                decode(tx_hash.trim()).map_err(|e| serde_json::Error::custom(e.to_string()))?;
-            if target_bytes.len() != 32 {
-                return Err(MerkleTreeError::InvalidProof(
-                    "Invalid TxHash length".to_string(),
-                ));
-            }
-            let mut target_array = [0u8; 32];
-            target_array.copy_from_slice(&target_bytes);
-            let target_hash = TxHash::from_slice(&target_array);
+        /// Encrypted JSON keystore holding the sender's key
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with = "private_key",
+            requires = "password_file"
+        )]
+        keystore: Option<PathBuf>,
 
-            // Generate Merkle Proof
-            let proof = merkle_tree.generate_proof(&target_hash.as_slice())?;
+        /// File holding the keystore password
+        #[arg(long, value_name = "FILE")]
+        password_file: Option<PathBuf>,
 
-            // Serialize proof to JSON
-            let proof_json = serde_json::to_string_pretty(&proof)?;
+        /// Estimate the transaction without sending it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Export a tree as IPLD DAG-CBOR blocks in a CAR file, and optionally import it into IPFS
+    ///
+    /// Each node is a block linking to its children by CID, so light clients that trust
+    /// the root CID can fetch proofs block by block.
+    ExportIpld {
+        /// Input file containing leaves (see `--leaf-format` and `--input-format`) or a
+        /// serialized tree, or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
 
-            // Write to output file
-            fs::write(output, proof_json)?;
+        /// Output file for the CAR, or `-` for stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
 
-            println!("Merkle Proof generated successfully.");
-        }
-        Commands::Verify { root_hash, proof } => {
-            // Parse the Merkle Root
-            let root_bytes =
-                decode(root_hash.trim()).map_err(|e| serde_json::Error::custom(e.to_string()))?;
-            if root_bytes.len() != 32 {
-                return Err(MerkleTreeError::InvalidProof(
-                    "Invalid Merkle Root length".to_string(),
-                ));
-            }
-            let mut root_array = [0u8; 32];
-            root_array.copy_from_slice(&root_bytes);
-            let root_hash = B256::from_slice(&root_array);
+        /// HTTP API of an IPFS node to import the blocks into, such as
+        /// `http://127.0.0.1:5001`; the root is pinned
+        #[arg(long, value_name = "URL", required_unless_present = "output")]
+        ipfs_api: Option<String>,
+    },
+    /// Generate a Solidity library verifying proofs of trees built with the current options
+    ///
+    /// The library hashes leaves (per `--leaf-encoding`, or as raw bytes) and pairs as the
+    /// tree does, so on-chain verification matches it exactly.
+    GenVerifier {
+        /// Serialized tree or file of leaves to take `--hash` and `--sorted-pairs` from,
+        /// instead of the command line
+        #[arg(short, long, value_name = "FILE")]
+        input: Option<PathBuf>,
 
-            // Read and deserialize the Merkle Proof
-            let proof_content = fs::read_to_string(proof)?;
-            let merkle_proof: MerkleProof = serde_json::from_str(&proof_content)?;
+        /// Output file to save the Solidity source, or `-` for stdout
+        #[arg(short, long, value_name = "FILE")]
+        out: PathBuf,
 
-            // Verify the proof
-            let is_valid = merkle_proof.verify(&root_hash)?;
+        /// Name of the generated library
+        #[arg(long, value_name = "NAME", default_value = "MerkleVerifier")]
+        name: String,
+    },
+    /// Generate a Foundry test or JSON fixture with the root and proofs of sample leaves
+    ///
+    /// The Foundry test calls a library from `gen-verifier`, so contract tests can check
+    /// that Solidity agrees with this crate.
+    GenFixture {
+        /// Input file containing leaves (see `--leaf-format` and `--input-format`) or a
+        /// serialized tree, or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
 
-            if is_valid {
-                println!("Merkle Proof is valid.");
-            } else {
-                println!("Merkle Proof is INVALID.");
-            }
-        }
-    }
+        /// Output file to save the fixture, or `-` for stdout
+        #[arg(short, long, value_name = "FILE")]
+        out: PathBuf,
 
-    Ok(())
+        /// Number of leaves to include, spread evenly over the tree
+        #[arg(long, value_name = "COUNT", default_value_t = 8)]
+        samples: usize,
+
+        /// Write a Foundry test or a JSON fixture
+        #[arg(long, value_enum, default_value_t = FixtureFormat::Foundry)]
+        format: FixtureFormat,
+
+        /// Name of the verifier library the Foundry test calls
+        #[arg(long, value_name = "NAME", default_value = "MerkleVerifier")]
+        name: String,
+
+        /// Path the Foundry test imports the verifier library from
+        #[arg(long, value_name = "PATH", default_value = "./MerkleVerifier.sol")]
+        verifier_path: String,
+    },
+    /// Upload a tree, and optionally a proof of every leaf, to object storage
+    ///
+    /// Credentials are read from the environment, e.g. `AWS_ACCESS_KEY_ID`.
+    #[cfg(feature = "object_store")]
+    Upload {
+        /// Input file containing leaves (see `--leaf-format` and `--input-format`) or a
+        /// serialized tree, or `-` for stdin
+        #[arg(short, long, value_name = "FILE")]
+        input: PathBuf,
+
+        /// URL to save the tree at, such as `s3://bucket/airdrop/tree.json`
+        #[arg(long, value_name = "URL")]
+        to: String,
+
+        /// Encoding of the uploaded tree (json, compact, bincode or cbor)
+        #[arg(long, value_name = "FORMAT", default_value_t = DumpFormat::Json)]
+        output_format: DumpFormat,
+
+        /// URL prefix to save one `<index>.json` proof per leaf under, next to a
+        /// `root.json` naming the root
+        #[arg(long, value_name = "URL")]
+        proofs_to: Option<String>,
+    },
+    /// Print a shell completion script
+    ///
+    /// For example, `cli completions bash > /etc/bash_completion.d/cli`.
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
 }
 
-fn convert_fixed_bytes_to_vec_u8(input: &Vec<B256>) -> Vec<Vec<u8>> {
-    input
-        .iter()
-        .map(|fixed_bytes| fixed_bytes.to_vec())
-        .collect()
+/// Options controlling how leaves are read from input files.
+#[derive(Args, Clone)]
+struct LeafOptions {
+    /// How each input entry (and `--tx-hash`) is turned into leaf data
+    #[arg(long, global = true, value_enum, default_value_t = LeafFormat::Hash32)]
+    leaf_format: LeafFormat,
+
+    /// Encode each entry as comma-separated Solidity values, e.g. `packed(address,uint256)`
+    /// or `abi(address,uint256)`; overrides `--leaf-format`
+    #[arg(long, global = true, value_name = "ENCODING")]
+    leaf_encoding: Option<LeafEncoding>,
+
+    /// Layout of the input file
+    #[arg(long, global = true, value_enum, default_value_t = InputFormat::Lines)]
+    input_format: InputFormat,
+
+    /// Sort leaves by hash before building, so the root does not depend on input order
+    #[arg(long, global = true)]
+    sort_leaves: bool,
+
+    /// Keep only the first occurrence of each leaf
+    #[arg(long, global = true)]
+    dedupe: bool,
+
+    /// Hash function for leaves and internal nodes (keccak256, sha256 or blake3)
+    /// [default: keccak256]. Trees and proofs record it; when given to commands that read
+    /// them, it must match the recorded one
+    #[arg(long, global = true, value_name = "ALGORITHM")]
+    hash: Option<HashAlgorithm>,
+
+    /// Sort each pair of hashes before hashing them together, as OpenZeppelin's
+    /// `MerkleProof` does, so proofs need no left/right sides
+    #[arg(long, global = true)]
+    sorted_pairs: bool,
+
+    /// Pair the last node of an odd level with a copy of itself, as Bitcoin does, instead
+    /// of promoting it unchanged
+    #[arg(long, global = true)]
+    duplicate_odd: bool,
+
+    /// Prefix leaves with a 0x00 byte and pairs of hashes with a 0x01 byte before hashing,
+    /// as RFC 6962 does, so no leaf can hash like an internal node and 64-byte leaves are
+    /// safe to accept
+    #[arg(long, global = true)]
+    domain_separation: bool,
+
+    /// Refuse to build trees whose root another leaf list also produces, which
+    /// `--duplicate-odd` allows when a level ends in two equal nodes, and reject proofs
+    /// pairing a node with a copy of itself on its left (CVE-2012-2459)
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Accept leaves of exactly 64 bytes, such as `abi(address,uint256)` entries, without
+    /// `--domain-separation`. Their hash could equal an internal node's, letting a proof of
+    /// a crafted leaf verify, so only allow them when leaf data cannot be chosen by an
+    /// attacker
+    #[arg(long, global = true)]
+    allow_64_byte_leaves: bool,
+
+    /// Recompute every hash of the trees read by a command and refuse tampered or corrupted
+    /// ones, instead of trusting any well-formed dump
+    #[arg(long, global = true)]
+    verify: bool,
+
+    /// Wrap written trees and proofs in an envelope with a SHA-256 checksum of their
+    /// contents. Readers always check the checksum and signature of enveloped files
+    #[arg(long, global = true)]
+    checksum: bool,
+
+    /// Hex-encoded private key signing the checksum of written trees and proofs; implies
+    /// `--checksum`
+    #[arg(
+        long,
+        global = true,
+        value_name = "KEY",
+        conflicts_with = "sign_keystore"
+    )]
+    sign_key: Option<String>,
+
+    /// Encrypted JSON keystore holding the key signing written trees and proofs
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        requires = "sign_password_file"
+    )]
+    sign_keystore: Option<PathBuf>,
+
+    /// File holding the `--sign-keystore` password
+    #[arg(long, global = true, value_name = "FILE")]
+    sign_password_file: Option<PathBuf>,
+
+    /// Refuse trees and proofs read by a command unless they are signed by this address
+    #[arg(long, global = true, value_name = "ADDRESS")]
+    require_signer: Option<Address>,
+
+    /// The key loaded from `--sign-key` or `--sign-keystore`
+    #[arg(skip)]
+    signer: OnceLock<PrivateKeySigner>,
+
+    /// With `--input-format json`, read each leaf from this field of an array of objects
+    #[arg(long, global = true, value_name = "FIELD")]
+    json_field: Option<String>,
+
+    /// With `--input-format parquet`, the column to read leaves from
+    #[cfg(feature = "parquet")]
+    #[arg(long, global = true, value_name = "COLUMN")]
+    column: Option<String>,
+}
+
+/// The layout of an input file of leaves.
+#[derive(Clone, Copy, ValueEnum)]
+enum InputFormat {
+    /// One leaf per line
+    Lines,
+    /// A JSON array of strings, or of objects selected with `--json-field`
+    Json,
+    /// A Parquet file, reading the column selected with `--column`
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+/// How input entries are interpreted as leaf data. Each leaf is hashed when the tree is built.
+#[derive(Clone, Copy, ValueEnum)]
+enum LeafFormat {
+    /// The line's bytes as-is
+    Raw,
+    /// Hex-encoded bytes of any length
+    Hex,
+    /// A 32-byte hex-encoded hash, such as a transaction hash
+    Hash32,
+}
+
+impl LeafFormat {
+    fn parse(self, line: &str) -> Result<Vec<u8>, MerkleTreeError> {
+        match self {
+            LeafFormat::Raw => Ok(line.as_bytes().to_vec()),
+            LeafFormat::Hex => Ok(decode(line.trim())?),
+            LeafFormat::Hash32 => Ok(parse_b256(line)?.to_vec()),
+        }
+    }
+}
+
+/// How the `proof-all` subcommand lays out its proofs.
+#[derive(Clone, Copy, ValueEnum)]
+enum ProofLayout {
+    /// One JSON file mapping leaf indices to proofs
+    Map,
+    /// A directory with one proof file per leaf
+    Dir,
+}
+
+/// The output of the `gen-fixture` subcommand.
+#[derive(Clone, Copy, ValueEnum)]
+enum FixtureFormat {
+    /// A Foundry test contract
+    Foundry,
+    /// JSON for `vm.parseJson`
+    Json,
+}
+
+/// A diagram format for the `export` subcommand.
+#[derive(Clone, Copy, ValueEnum)]
+enum DiagramFormat {
+    /// Graphviz DOT
+    Dot,
+    /// Mermaid flowchart
+    Mermaid,
+    /// Standalone SVG image
+    Svg,
+}
+
+/// How command results are printed.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable messages
+    Text,
+    /// One JSON object per command, including errors
+    Json,
+}
+
+/// Prints command results in the `--format` chosen on the command line.
+struct Reporter {
+    format: OutputFormat,
+    quiet: bool,
+}
+
+impl Reporter {
+    /// Prints a result. Without an `output`, the result is the command's data and goes to
+    /// stdout. Commands writing their data to `output` print a status message instead: on
+    /// stderr, except JSON while stdout is free. `--quiet` drops the text message, but not
+    /// the JSON one, which scripts read for the root.
+    fn result(&self, output: Option<&Path>, text: &str, json: Value) {
+        let Some(output) = output else {
+            match self.format {
+                OutputFormat::Text => println!("{}", text),
+                OutputFormat::Json => println!("{}", json),
+            }
+            return;
+        };
+        match self.format {
+            OutputFormat::Json if !is_stdio(output) => println!("{}", json),
+            OutputFormat::Json => eprintln!("{}", json),
+            OutputFormat::Text if !self.quiet => eprintln!("{}", text),
+            OutputFormat::Text => {}
+        }
+    }
+
+    /// Prints the outcome of a verification. It is the command's result, so it is printed
+    /// even with `--quiet`.
+    fn verdict(&self, text: &str, json: Value) {
+        self.result(None, text, json);
+    }
+
+    /// Prints a failed command's error on stderr, keeping stdout for results.
+    fn error(&self, error: &MerkleTreeError) {
+        match self.format {
+            OutputFormat::Text => eprintln!("Error: {}", error),
+            OutputFormat::Json => eprintln!(
+                "{}",
+                json!({ "status": "error", "error": error.to_string() })
+            ),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // Initialize logging
+    match cli.verbose {
+        0 => {}
+        1 => env_logger::Builder::from_default_env()
+            .filter_level(log::LevelFilter::Info)
+            .init(),
+        2 => env_logger::Builder::from_default_env()
+            .filter_level(log::LevelFilter::Debug)
+            .init(),
+        _ => env_logger::Builder::from_default_env()
+            .filter_level(log::LevelFilter::Trace)
+            .init(),
+    }
+
+    let reporter = Reporter {
+        format: cli.format,
+        quiet: cli.quiet,
+    };
+    if let Err(e) = cli.apply_config(&matches) {
+        reporter.error(&e);
+        return ExitCode::FAILURE;
+    }
+
+    let mut timings = Timings::new();
+    let result = run(&cli, &reporter, &mut timings);
+    if cli.timings {
+        timings.print();
+    }
+    match result {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            reporter.error(&e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: &Cli, reporter: &Reporter, timings: &mut Timings) -> Result<ExitCode, MerkleTreeError> {
+    if let Some(jobs) = cli.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()
+            .map_err(io::Error::other)?;
+    }
+
+    match &cli.command {
+        Commands::Build {
+            input,
+            output,
+            output_format,
+            compress: compression,
+        } => {
+            if input.is_empty() {
+                return Err(missing_option("--input"));
+            }
+            let output = required(output, "--output")?;
+
+            // Read leaves from every input file
+            let mut leaves = Vec::new();
+            for path in expand_inputs(input)? {
+                leaves.extend(cli.leaves.read(&path)?);
+            }
+            timings.mark("read");
+
+            // Build the Merkle Tree
+            let merkle_tree = build_tree(cli, &leaves, timings)?;
+
+            // Write to output file
+            write_tree(cli, output, &merkle_tree, *output_format, *compression)?;
+            timings.mark("write");
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                Some(output),
+                &format!("Merkle Tree built successfully. Root Hash: {}", root),
+                json!({ "status": "ok", "root": root, "leaf_count": merkle_tree.leaf_count() }),
+            );
+        }
+        #[cfg(any(feature = "sqlite", feature = "postgres"))]
+        Commands::BuildSql {
+            database,
+            table,
+            column,
+            order_by,
+            output,
+            output_format,
+            proofs_table,
+        } => {
+            use merkle_tree::sql::{Database, LeafQuery, SqlValue};
+
+            if output.is_none() && proofs_table.is_none() {
+                return Err(missing_option("--output or --proofs-table"));
+            }
+            let mut db = Database::connect(database)?;
+            let mut query = LeafQuery::new(table.as_str(), column.as_str());
+            query.order_by = order_by.clone();
+            let leaves = db
+                .read_leaves(&query)?
+                .into_iter()
+                .map(|value| match value {
+                    SqlValue::Binary(bytes) => Ok(bytes),
+                    SqlValue::Text(text) => cli.leaves.parse(&text),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            timings.mark("read");
+
+            let merkle_tree = build_tree(cli, &leaves, timings)?;
+            if let Some(output) = output {
+                write_tree(cli, output, &merkle_tree, *output_format, None)?;
+                timings.mark("write");
+            }
+            let proof_count = match proofs_table {
+                Some(proofs_table) => {
+                    let count = db.write_proofs(proofs_table, &merkle_tree)?;
+                    timings.mark("proofs");
+                    count
+                }
+                None => 0,
+            };
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                output.as_deref(),
+                &format!(
+                    "Merkle Tree built from {} rows of {}. Root Hash: {}",
+                    merkle_tree.leaf_count(),
+                    table,
+                    root
+                ),
+                json!({
+                    "status": "ok",
+                    "root": root,
+                    "leaf_count": merkle_tree.leaf_count(),
+                    "proof_count": proof_count,
+                }),
+            );
+        }
+        #[cfg(feature = "rpc")]
+        Commands::FetchBlock {
+            rpc,
+            block,
+            output,
+            compress: compression,
+        } => {
+            let leaves = fetch_block_transactions(rpc, *block)?;
+            timings.mark("fetch");
+
+            let merkle_tree = build_tree(cli, &leaves, timings)?;
+            write_tree(cli, output, &merkle_tree, DumpFormat::Json, *compression)?;
+            timings.mark("write");
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                Some(output),
+                &format!(
+                    "Merkle Tree built from {} transactions of block {}. Root Hash: {}",
+                    merkle_tree.leaf_count(),
+                    block,
+                    root
+                ),
+                json!({ "status": "ok", "root": root, "leaf_count": merkle_tree.leaf_count() }),
+            );
+        }
+        Commands::Proof {
+            input,
+            tx_hash,
+            output,
+            compress: compression,
+        } => {
+            // Load a serialized tree, or build one from a file of leaves
+            let merkle_tree = load_or_build_tree(cli, required(input, "--input")?, timings)?;
+
+            // Parse the target leaf
+            let target = cli.leaves.parse(tx_hash)?;
+
+            // Generate Merkle Proof
+            let proof = merkle_tree.generate_proof(&target)?;
+
+            // Serialize proof to JSON
+            let proof_json = proof.to_json_with(cli.hash_format())?;
+
+            // Write to output file
+            write_proof(&cli.leaves, output, proof_json, *compression)?;
+            timings.mark("write");
+
+            reporter.result(
+                Some(output),
+                "Merkle Proof generated successfully.",
+                json!({
+                    "status": "ok",
+                    "root": cli.hash_format().encode(merkle_tree.root_hash()),
+                    "proof": cli.hash_format().encoded(&proof),
+                }),
+            );
+        }
+        Commands::Root { input } => {
+            // Read leaves from input file
+            let leaves = cli.leaves.read(required(input, "--input")?)?;
+            timings.mark("read");
+
+            // Build the Merkle Tree
+            let merkle_tree = build_tree(cli, &leaves, timings)?;
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(None, &root, json!({ "status": "ok", "root": root }));
+        }
+        Commands::HashFile {
+            input,
+            chunk_size,
+            output,
+            range,
+            proof,
+        } => {
+            let builder = cli.leaves.builder();
+            let chunk_hashes = hash_chunks(input, *chunk_size, &builder)?;
+            timings.mark("hash");
+
+            let merkle_tree = builder.build_from_leaf_hashes(chunk_hashes)?;
+            timings.mark("build");
+
+            if let Some(output) = output {
+                write_tree(cli, output, &merkle_tree, DumpFormat::Json, None)?;
+                timings.mark("write");
+            }
+            if let (Some(range), Some(proof)) = (range, proof) {
+                let chunks = range.chunks(*chunk_size, merkle_tree.leaf_count())?;
+                let indices: Vec<usize> = chunks.collect();
+                let multiproof = merkle_tree.generate_multiproof_by_indices(&indices)?;
+                write_output(proof, multiproof.to_json_with(cli.hash_format())?, None)?;
+                timings.mark("proofs");
+            }
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                None,
+                &root,
+                json!({ "status": "ok", "root": root, "chunk_count": merkle_tree.leaf_count() }),
+            );
+        }
+        Commands::HashDir {
+            input,
+            output,
+            manifest,
+        } => {
+            let mut files = Vec::new();
+            collect_files(input, String::new(), &mut files)?;
+            files.sort();
+            timings.mark("read");
+
+            let builder = cli.leaves.builder();
+            let hash_algorithm = builder.algorithm();
+            let (leaf_hashes, entries) = hash_files(input, &files, &builder, cli.hash_format())?;
+            timings.mark("hash");
+
+            let merkle_tree = builder.build_from_leaf_hashes(leaf_hashes)?;
+            timings.mark("build");
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            if let Some(output) = output {
+                write_tree(cli, output, &merkle_tree, DumpFormat::Json, None)?;
+            }
+            if let Some(manifest) = manifest {
+                let manifest_json = serde_json::to_string_pretty(&json!({
+                    "root": root,
+                    "hash_algorithm": hash_algorithm,
+                    "files": entries,
+                }))?;
+                write_output(manifest, manifest_json, None)?;
+            }
+            timings.mark("write");
+
+            reporter.result(
+                None,
+                &root,
+                json!({ "status": "ok", "root": root, "file_count": files.len() }),
+            );
+        }
+        Commands::Watch { input, output } => {
+            let input = required(input, "--input")?;
+            if is_stdio(input) {
+                return Err(MerkleTreeError::FormatError(
+                    "watch needs an input file, not stdin".to_string(),
+                ));
+            }
+
+            // Watch the directory rather than the file, since editors often save by
+            // replacing the file
+            let (sender, receiver) = mpsc::channel();
+            let mut watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+            let directory = match input.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            watcher
+                .watch(directory, RecursiveMode::NonRecursive)
+                .map_err(io::Error::other)?;
+
+            watch_rebuild(cli, reporter, input, output.as_deref());
+            for event in &receiver {
+                let event = event.map_err(io::Error::other)?;
+                let touches_input = event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == input.file_name());
+                if !touches_input || !(event.kind.is_create() || event.kind.is_modify()) {
+                    continue;
+                }
+
+                // One save produces several events; rebuild once they stop
+                while receiver.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+                watch_rebuild(cli, reporter, input, output.as_deref());
+            }
+        }
+        Commands::Ingest {
+            input,
+            output,
+            every,
+            interval,
+            private_key,
+            keystore,
+            password_file,
+            #[cfg(feature = "kafka")]
+            kafka_brokers,
+            #[cfg(feature = "kafka")]
+            kafka_topic,
+            #[cfg(feature = "kafka")]
+            kafka_group,
+        } => {
+            let policy = CheckpointPolicy {
+                every_leaves: *every,
+                interval: interval.map(Duration::from_secs),
+            };
+            let builder = cli.leaves.builder();
+            let mut rolling = match output {
+                Some(output) if output.exists() => {
+                    let merkle_tree = cli.leaves.load_tree(&fs::read(output)?)?;
+                    RollingTree::resume(merkle_tree, builder, policy)?
+                }
+                _ => RollingTree::new(builder, policy)?,
+            };
+            let signer = match (private_key, keystore) {
+                (None, None) => None,
+                _ => Some(load_signer(
+                    private_key.as_deref(),
+                    keystore.as_deref(),
+                    password_file.as_deref(),
+                )?),
+            };
+
+            // Leaves are read on another thread, so checkpoints fall due while it waits
+            #[cfg(feature = "kafka")]
+            let kafka = match (kafka_brokers, kafka_topic) {
+                (Some(brokers), Some(topic)) => Some(merkle_tree::rolling::kafka_leaves(
+                    brokers,
+                    kafka_group,
+                    topic,
+                )?),
+                _ => None,
+            };
+            #[cfg(not(feature = "kafka"))]
+            let kafka = None;
+            let (receiver, reader) = match kafka {
+                Some(receiver) => (receiver, None),
+                None => {
+                    let (sender, receiver) = mpsc::channel();
+                    let (input, leaves) = (input.clone(), cli.leaves.clone());
+                    let reader = std::thread::spawn(move || -> Result<(), MerkleTreeError> {
+                        let reader: Box<dyn BufRead> = if is_stdio(&input) {
+                            Box::new(io::stdin().lock())
+                        } else {
+                            Box::new(io::BufReader::new(File::open(&input)?))
+                        };
+                        for (line_number, line) in reader.lines().enumerate() {
+                            let line = line?;
+                            if line.trim().is_empty() {
+                                continue;
+                            }
+                            let leaf = leaves.parse_line(&input, line_number, &line)?;
+                            if sender.send(leaf).is_err() {
+                                break;
+                            }
+                        }
+                        Ok(())
+                    });
+                    (receiver, Some(reader))
+                }
+            };
+
+            rolling.run(&receiver, |checkpoint, merkle_tree| {
+                if let Some(output) = output {
+                    write_tree(cli, output, merkle_tree, DumpFormat::Json, None)?;
+                }
+                let root = cli.hash_format().encode(checkpoint.root);
+                let text = format!(
+                    "Checkpoint {}: {} leaves, Root Hash: {}",
+                    checkpoint.sequence, checkpoint.leaf_count, root
+                );
+                match &signer {
+                    Some(signer) => {
+                        let signed_root = SignedRoot::sign(merkle_tree, signer)?;
+                        reporter.result(
+                            None,
+                            &format!("{}, signed by {}", text, signed_root.signer),
+                            json!({
+                                "sequence": checkpoint.sequence,
+                                "root": root,
+                                "leaf_count": checkpoint.leaf_count,
+                                "signer": signed_root.signer,
+                                "signature": encode_prefixed(&signed_root.signature),
+                            }),
+                        );
+                    }
+                    None => reporter.result(
+                        None,
+                        &text,
+                        json!({
+                            "sequence": checkpoint.sequence,
+                            "root": root,
+                            "leaf_count": checkpoint.leaf_count,
+                        }),
+                    ),
+                }
+                Ok(())
+            })?;
+
+            // A line that failed to parse stops the reader, and the run with it
+            if let Some(reader) = reader {
+                reader
+                    .join()
+                    .map_err(|_| io::Error::other("leaf reader panicked"))??;
+            }
+        }
+        Commands::Repl { input } => {
+            let merkle_tree = match input {
+                Some(input) => Some(load_tree_or_leaves(input, &cli.leaves)?),
+                None => None,
+            };
+            run_repl(cli, reporter, merkle_tree)?;
+        }
+        Commands::ProofAll {
+            input,
+            output,
+            layout,
+        } => {
+            // Read leaves from input file
+            let leaves = cli.leaves.read(required(input, "--input")?)?;
+            timings.mark("read");
+
+            // Build the Merkle Tree once for all proofs
+            let merkle_tree = build_tree(cli, &leaves, timings)?;
+            let all_proofs = merkle_tree.par_generate_all_proofs()?;
+
+            match layout {
+                ProofLayout::Map => {
+                    let mut proofs = serde_json::Map::new();
+                    for (index, proof) in all_proofs.iter().enumerate() {
+                        proofs.insert(
+                            index.to_string(),
+                            serde_json::to_value(cli.hash_format().encoded(proof))?,
+                        );
+                    }
+                    let proofs_json = serde_json::to_string_pretty(&json!({
+                        "root": cli.hash_format().encode(merkle_tree.root_hash()),
+                        "proofs": proofs,
+                    }))?;
+                    write_output(output, proofs_json, None)?;
+                }
+                ProofLayout::Dir => {
+                    fs::create_dir_all(output)?;
+                    for (index, proof) in all_proofs.iter().enumerate() {
+                        let proof_json = cli
+                            .leaves
+                            .seal(proof.to_json_with(cli.hash_format())?.into_bytes())?;
+                        fs::write(output.join(format!("{}.json", index)), proof_json)?;
+                    }
+                }
+            }
+            timings.mark("proofs");
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                Some(output),
+                &format!(
+                    "Generated {} Merkle Proofs. Root Hash: {}",
+                    merkle_tree.leaf_count(),
+                    root
+                ),
+                json!({ "status": "ok", "root": root, "proof_count": merkle_tree.leaf_count() }),
+            );
+        }
+        Commands::ProofBatch {
+            input,
+            targets,
+            output_dir,
+        } => {
+            // Load a serialized tree, or build one from a file of leaves
+            let merkle_tree = load_or_build_tree(cli, required(input, "--input")?, timings)?;
+
+            let targets = read_targets(cli, targets)?;
+
+            fs::create_dir_all(output_dir)?;
+            for target in &targets {
+                let proof = merkle_tree.generate_proof(target)?;
+                let file = output_dir.join(format!("{}.json", encode_prefixed(proof.leaf_hash)));
+                fs::write(file, proof.to_json_with(cli.hash_format())?)?;
+            }
+            timings.mark("proofs");
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                Some(output_dir),
+                &format!(
+                    "Generated {} Merkle Proofs. Root Hash: {}",
+                    targets.len(),
+                    root
+                ),
+                json!({ "status": "ok", "root": root, "proof_count": targets.len() }),
+            );
+        }
+        Commands::Multiproof {
+            input,
+            targets,
+            output,
+            openzeppelin,
+        } => {
+            // Load a serialized tree, or build one from a file of leaves
+            let merkle_tree = load_or_build_tree(cli, required(input, "--input")?, timings)?;
+
+            let targets = read_targets(cli, targets)?;
+            let multiproof = merkle_tree.generate_multiproof(&targets)?;
+            let proof_json = if *openzeppelin {
+                serde_json::to_string_pretty(&multiproof.to_openzeppelin()?)?
+            } else {
+                multiproof.to_json_with(cli.hash_format())?
+            };
+            write_output(output, proof_json, None)?;
+            timings.mark("write");
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                Some(output),
+                &format!(
+                    "Multiproof generated for {} leaves with {} proof hashes. Root Hash: {}",
+                    multiproof.indices.len(),
+                    multiproof.proof_hashes.len(),
+                    root
+                ),
+                json!({
+                    "status": "ok",
+                    "root": root,
+                    "leaf_count": multiproof.indices.len(),
+                    "proof_hash_count": multiproof.proof_hashes.len(),
+                }),
+            );
+        }
+        Commands::VerifyMultiproof { root_hash, proof } => {
+            let root_hash = parse_b256(root_hash)?;
+
+            // OpenZeppelin multiproofs are told apart by their `proofFlags`
+            let value: Value = serde_json::from_slice(&decompress(&read_input(proof)?)?)?;
+            let is_valid = if value.get("proofFlags").is_some() {
+                serde_json::from_value::<StandardMultiProof>(value)?.verify(&root_hash)?
+            } else {
+                let multiproof = MultiProof::from_json(&value.to_string())?;
+                cli.leaves.check_hash(multiproof.hash_algorithm)?;
+                multiproof.verify(&root_hash)?
+            };
+
+            let root = cli.hash_format().encode(root_hash);
+            if !is_valid {
+                reporter.verdict(
+                    "Multiproof is INVALID.",
+                    json!({ "status": "invalid", "root": root }),
+                );
+                return Ok(ExitCode::from(EXIT_INVALID));
+            }
+            reporter.verdict(
+                "Multiproof is valid.",
+                json!({ "status": "ok", "root": root }),
+            );
+        }
+        Commands::Airdrop { input, output } => {
+            let recipients = read_airdrop_csv(input)?;
+            let tree = StandardMerkleTree::of(
+                recipients
+                    .iter()
+                    .map(|(address, amount)| {
+                        vec![json!(address.to_checksum(None)), json!(amount.to_string())]
+                    })
+                    .collect(),
+                &["address".to_string(), "uint256".to_string()],
+            )?;
+
+            // Keyed by the parsed address, so no two rows can claim under one checksum
+            let mut claims = BTreeMap::new();
+            for (index, (address, amount)) in recipients.iter().enumerate() {
+                let proof: Vec<String> =
+                    tree.get_proof(index)?.iter().map(encode_prefixed).collect();
+                claims.insert(
+                    address.to_checksum(None),
+                    json!({ "index": index, "amount": amount.to_string(), "proof": proof }),
+                );
+            }
+
+            let claims_json = serde_json::to_string_pretty(&json!({
+                "merkleRoot": encode_prefixed(tree.root()),
+                "leafEncoding": ["address", "uint256"],
+                "claims": claims,
+            }))?;
+            write_output(output, claims_json, None)?;
+
+            let root = encode_prefixed(tree.root());
+            reporter.result(
+                Some(output),
+                &format!(
+                    "Airdrop claims generated for {} recipients. Root Hash: {}",
+                    recipients.len(),
+                    root
+                ),
+                json!({ "status": "ok", "root": root, "recipient_count": recipients.len() }),
+            );
+        }
+        Commands::VerifyTree { input } => {
+            // Read and deserialize the Merkle Tree
+            let content = read_input(input)?;
+            cli.leaves.check_signer(&content)?;
+            let merkle_tree = MerkleTree::from_dump(&content)?;
+            cli.leaves.check_hash(merkle_tree.hash_algorithm)?;
+
+            // Recompute every hash, then check the shape against the leaf list
+            let failure = match merkle_tree.find_corrupt_node() {
+                Some(corrupt) => Some(format!("corrupt node at {}", corrupt)),
+                None => cli
+                    .leaves
+                    .check_structure(&merkle_tree)
+                    .err()
+                    .map(|e| e.to_string()),
+            };
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            if let Some(failure) = failure {
+                reporter.verdict(
+                    &format!("Merkle Tree is INVALID: {}", failure),
+                    json!({ "status": "invalid", "root": root, "error": failure }),
+                );
+                return Ok(ExitCode::from(EXIT_INVALID));
+            }
+            reporter.verdict(
+                &format!(
+                    "Merkle Tree is valid. {} leaves, Root Hash: {}",
+                    merkle_tree.leaf_count(),
+                    root
+                ),
+                json!({ "status": "ok", "root": root, "leaf_count": merkle_tree.leaf_count() }),
+            );
+        }
+        Commands::Append {
+            tree,
+            input,
+            output,
+        } => {
+            // Load the existing tree and the new leaves
+            let mut merkle_tree = cli.leaves.load_tree(&read_input(tree)?)?;
+            cli.leaves.check_hash(merkle_tree.hash_algorithm)?;
+            let leaves = cli.leaves.read(input)?;
+
+            merkle_tree.append(&leaves)?;
+            write_tree(cli, output, &merkle_tree, DumpFormat::Json, None)?;
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                Some(output),
+                &format!(
+                    "Appended {} leaves ({} total). Root Hash: {}",
+                    leaves.len(),
+                    merkle_tree.leaf_count(),
+                    root
+                ),
+                json!({ "status": "ok", "root": root, "leaf_count": merkle_tree.leaf_count() }),
+            );
+        }
+        Commands::Update {
+            tree,
+            index,
+            data,
+            output,
+        } => {
+            let mut merkle_tree = cli.leaves.load_tree(&read_input(tree)?)?;
+            cli.leaves.check_hash(merkle_tree.hash_algorithm)?;
+            merkle_tree.update(*index, &cli.leaves.parse(data)?)?;
+
+            let output = output.as_ref().unwrap_or(tree);
+            write_tree(cli, output, &merkle_tree, DumpFormat::Json, None)?;
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                Some(output),
+                &format!("Updated leaf {}. Root Hash: {}", index, root),
+                json!({ "status": "ok", "root": root, "leaf_count": merkle_tree.leaf_count() }),
+            );
+        }
+        Commands::Inspect { input } => {
+            let content = read_input(input)?;
+            let envelope = Envelope::detect(&content)
+                .then(|| Envelope::from_bytes(&content))
+                .transpose()?;
+            let payload = envelope.as_ref().map_or(&content[..], |e| &e.payload[..]);
+            let compression = Compression::detect(payload);
+            let format = DumpFormat::detect(&decompress(payload)?)
+                .map_or("unknown".to_string(), |f| f.to_string());
+            let merkle_tree = cli.leaves.load_tree(&content)?;
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            let compression = compression.map_or("none".to_string(), |c| c.to_string());
+            let signer = envelope.as_ref().and_then(Envelope::signer);
+            let sealing = match (&envelope, signer) {
+                (None, _) => "none".to_string(),
+                (Some(_), None) => "checksum".to_string(),
+                (Some(_), Some(signer)) => format!("checksum, signed by {}", signer),
+            };
+            reporter.result(
+                None,
+                &format!(
+                    "Root Hash:    {}\n\
+                     Leaves:       {}\n\
+                     Depth:        {}\n\
+                     Nodes:        {}\n\
+                     Hash:         {}\n\
+                     Sorted pairs: {}\n\
+                     Odd nodes:    {}\n\
+                     Prefixed:     {}\n\
+                     Format:       {}\n\
+                     File size:    {} bytes\n\
+                     Compression:  {}\n\
+                     Envelope:     {}",
+                    root,
+                    merkle_tree.leaf_count(),
+                    merkle_tree.depth(),
+                    merkle_tree.node_count(),
+                    merkle_tree.hash_algorithm,
+                    merkle_tree.sorted_pairs,
+                    merkle_tree.odd_nodes,
+                    merkle_tree.domain_separation,
+                    format,
+                    content.len(),
+                    compression,
+                    sealing
+                ),
+                json!({
+                    "status": "ok",
+                    "root": root,
+                    "leaf_count": merkle_tree.leaf_count(),
+                    "depth": merkle_tree.depth(),
+                    "node_count": merkle_tree.node_count(),
+                    "hash_algorithm": merkle_tree.hash_algorithm,
+                    "sorted_pairs": merkle_tree.sorted_pairs,
+                    "odd_nodes": merkle_tree.odd_nodes,
+                    "domain_separation": merkle_tree.domain_separation,
+                    "format": format,
+                    "file_size": content.len(),
+                    "compression": compression,
+                    "checksum": envelope.is_some(),
+                    "signer": signer,
+                }),
+            );
+        }
+        Commands::Print {
+            input,
+            max_depth,
+            truncate,
+            ascii,
+        } => {
+            let merkle_tree = load_or_build_tree(cli, input, timings)?;
+            let options = DisplayOptions {
+                max_depth: *max_depth,
+                truncate: *truncate,
+                unicode: !*ascii,
+                hex_prefix: cli.hex_prefix,
+            };
+            let text = merkle_tree.display(options).to_string();
+            let text = text.trim_end();
+            reporter.result(None, text, json!({ "status": "ok", "tree": text }));
+        }
+        Commands::Export {
+            input,
+            to,
+            highlight,
+            output,
+        } => {
+            let merkle_tree = cli.leaves.load_tree(&read_input(input)?)?;
+            let diagram = match to {
+                DiagramFormat::Dot => merkle_tree.to_dot(*highlight)?,
+                DiagramFormat::Mermaid => merkle_tree.to_mermaid(*highlight)?,
+                DiagramFormat::Svg => merkle_tree.to_svg(*highlight)?,
+            };
+            write_output(output, diagram, None)?;
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                Some(output),
+                &format!("Diagram exported. Root Hash: {}", root),
+                json!({ "status": "ok", "root": root }),
+            );
+        }
+        Commands::Diff { a, b } => {
+            let tree_a = load_tree_or_leaves(a, &cli.leaves)?;
+            let tree_b = load_tree_or_leaves(b, &cli.leaves)?;
+            let diff = tree_a.diff(&tree_b);
+
+            let root_a = cli.hash_format().encode(tree_a.root_hash());
+            let root_b = cli.hash_format().encode(tree_b.root_hash());
+            let hashes = |hashes: &[B256]| {
+                hashes
+                    .iter()
+                    .map(|hash| cli.hash_format().encode(hash))
+                    .collect::<Vec<_>>()
+            };
+
+            let mut text = if diff.roots_match {
+                format!("Roots match: {}", root_a)
+            } else {
+                format!("Roots differ: {} vs {}", root_a, root_b)
+            };
+            text.push_str(&format!(
+                "\nLeaves: {} vs {}",
+                tree_a.leaf_count(),
+                tree_b.leaf_count()
+            ));
+            for index in &diff.changed {
+                text.push_str(&format!("\n~ leaf {}", index));
+            }
+            for hash in hashes(&diff.added) {
+                text.push_str(&format!("\n+ {}", hash));
+            }
+            for hash in hashes(&diff.removed) {
+                text.push_str(&format!("\n- {}", hash));
+            }
+
+            let status = if diff.roots_match { "ok" } else { "invalid" };
+            reporter.result(
+                None,
+                &text,
+                json!({
+                    "status": status,
+                    "roots_match": diff.roots_match,
+                    "root_a": root_a,
+                    "root_b": root_b,
+                    "changed": diff.changed,
+                    "added": hashes(&diff.added),
+                    "removed": hashes(&diff.removed),
+                }),
+            );
+            if !diff.roots_match {
+                return Ok(ExitCode::from(EXIT_INVALID));
+            }
+        }
+        Commands::Verify { root_hash, proof } => {
+            // Parse the Merkle Root
+            let root_hash = parse_b256(root_hash)?;
+
+            // Verify a directory or JSON array of proofs one by one
+            if !is_stdio(proof) && proof.is_dir() {
+                let proofs = read_proof_dir(&cli.leaves, proof)?;
+                return verify_proof_set(cli, reporter, &root_hash, proofs);
+            }
+            let proof_content = read_input(proof)?;
+            cli.leaves.check_signer(&proof_content)?;
+            if let Some(proofs) = parse_proof_array(&proof_content)? {
+                return verify_proof_set(cli, reporter, &root_hash, proofs);
+            }
+
+            // Deserialize the Merkle Proof
+            let merkle_proof = MerkleProof::from_dump(&proof_content)?;
+            cli.leaves.check_hash(merkle_proof.hash_algorithm)?;
+
+            // Verify the proof
+            let is_valid = cli.leaves.verify_proof(&merkle_proof, &root_hash)?;
+
+            let root = cli.hash_format().encode(root_hash);
+            if !is_valid {
+                reporter.verdict(
+                    "Merkle Proof is INVALID.",
+                    json!({ "status": "invalid", "root": root }),
+                );
+                return Ok(ExitCode::from(EXIT_INVALID));
+            }
+            reporter.verdict(
+                "Merkle Proof is valid.",
+                json!({ "status": "ok", "root": root }),
+            );
+        }
+        #[cfg(feature = "server")]
+        Commands::Serve {
+            tree,
+            host,
+            port,
+            proof_cache,
+        } => {
+            let merkle_tree = cli.leaves.load_tree(&read_input(tree)?)?;
+            cli.leaves.check_hash(merkle_tree.hash_algorithm)?;
+
+            let address = std::net::SocketAddr::new(*host, *port);
+            reporter.result(
+                None,
+                &format!("Serving Merkle Tree on http://{}", address),
+                json!({ "status": "listening", "address": address.to_string() }),
+            );
+            let leaves = cli.leaves.clone();
+            let tree_path = tree.clone();
+            let hash_format = cli.hash_format();
+            let mut server = merkle_tree::server::Server::new(merkle_tree)
+                .parse_leaves_with(move |leaf| leaves.parse(leaf))
+                .allow_append(move |tree| {
+                    Ok(fs::write(&tree_path, tree.to_json_with(hash_format)?)?)
+                });
+            server = server.hash_format(hash_format);
+            if *proof_cache > 0 {
+                server = server.cache_proofs(*proof_cache);
+            }
+            let app = server.router();
+            tokio::runtime::Runtime::new()?.block_on(async {
+                let listener = tokio::net::TcpListener::bind(address).await?;
+                axum::serve(listener, app).await
+            })?;
+        }
+        Commands::SignRoot {
+            input,
+            private_key,
+            keystore,
+            password_file,
+            output,
+        } => {
+            let merkle_tree = cli.leaves.load_tree(&read_input(input)?)?;
+            let signer = load_signer(
+                private_key.as_deref(),
+                keystore.as_deref(),
+                password_file.as_deref(),
+            )?;
+
+            let signed_root = SignedRoot::sign(&merkle_tree, &signer)?;
+            write_output(output, signed_root.to_json_with(cli.hash_format())?, None)?;
+
+            let root = cli.hash_format().encode(signed_root.root);
+            reporter.result(
+                Some(output),
+                &format!("Root {} signed by {}.", root, signed_root.signer),
+                json!({ "status": "ok", "root": root, "signer": signed_root.signer }),
+            );
+        }
+        Commands::VerifySignature {
+            attestation,
+            tree,
+            signer,
+        } => {
+            let content = String::from_utf8(read_input(attestation)?)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let signed_root = SignedRoot::from_json(&content)?;
+
+            let failure = if !signed_root.verify()? {
+                Some(format!("signature was not made by {}", signed_root.signer))
+            } else if signer.is_some_and(|signer| signer != signed_root.signer) {
+                Some(format!("root was signed by {}", signed_root.signer))
+            } else {
+                match tree {
+                    Some(tree) => {
+                        let merkle_tree = cli.leaves.load_tree(&read_input(tree)?)?;
+                        (!signed_root.covers(&merkle_tree))
+                            .then(|| "attestation is for a different tree".to_string())
+                    }
+                    None => None,
+                }
+            };
+
+            let root = cli.hash_format().encode(signed_root.root);
+            if let Some(failure) = failure {
+                reporter.verdict(
+                    &format!("Signature is INVALID: {}.", failure),
+                    json!({ "status": "invalid", "root": root, "error": failure }),
+                );
+                return Ok(ExitCode::from(EXIT_INVALID));
+            }
+            reporter.verdict(
+                &format!("Signature by {} is valid.", signed_root.signer),
+                json!({ "status": "ok", "root": root, "signer": signed_root.signer }),
+            );
+        }
+        Commands::Timestamp {
+            input,
+            output,
+            calendar,
+        } => {
+            let merkle_tree = cli.leaves.load_tree(&read_input(input)?)?;
+            let proof = if calendar.is_empty() {
+                TimestampProof::stamp(&merkle_tree, DEFAULT_CALENDARS)?
+            } else {
+                TimestampProof::stamp(&merkle_tree, calendar.as_slice())?
+            };
+            timings.mark("stamp");
+            let mut writer = create_output(output)?;
+            writer.write_all(&proof.to_bytes())?;
+            writer.flush()?;
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            let pending = proof.timestamp.all_attestations().len();
+            reporter.result(
+                Some(output),
+                &format!(
+                    "Root {} submitted; {} calendar attestations pending.",
+                    root, pending
+                ),
+                json!({ "status": "ok", "root": root, "pending": pending }),
+            );
+        }
+        Commands::UpgradeTimestamp { proof } => {
+            let mut timestamp_proof = TimestampProof::from_bytes(&fs::read(proof)?)?;
+            let upgraded = timestamp_proof.upgrade()?;
+            if upgraded {
+                fs::write(proof, timestamp_proof.to_bytes())?;
+            }
+            timings.mark("upgrade");
+
+            let message = if upgraded {
+                "Timestamp upgraded."
+            } else {
+                "Timestamp is still pending; try again later."
+            };
+            reporter.result(
+                Some(proof),
+                message,
+                json!({ "status": "ok", "upgraded": upgraded }),
+            );
+        }
+        Commands::VerifyTimestamp { input, proof } => {
+            let merkle_tree = cli.leaves.load_tree(&read_input(input)?)?;
+            let timestamp_proof = TimestampProof::from_bytes(&fs::read(proof)?)?;
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            if !timestamp_proof.covers(&merkle_tree) {
+                let failure = "timestamp is for a different root";
+                reporter.verdict(
+                    &format!("Timestamp is INVALID: {}.", failure),
+                    json!({ "status": "invalid", "root": root, "error": failure }),
+                );
+                return Ok(ExitCode::from(EXIT_INVALID));
+            }
+
+            let mut blocks = Vec::new();
+            let mut calendars = Vec::new();
+            for verified in timestamp_proof.verify(&merkle_tree)? {
+                match verified.attestation {
+                    Attestation::Bitcoin { height } => {
+                        // Block explorers print merkle roots in reverse byte order
+                        let mut merkle_root = verified.commitment;
+                        merkle_root.reverse();
+                        blocks.push((height, cli.hash_format().encode(merkle_root)));
+                    }
+                    Attestation::Pending { uri } => calendars.push(uri),
+                    Attestation::Unknown { .. } => {}
+                }
+            }
+            blocks.sort();
+
+            let text = match blocks.first() {
+                Some((height, merkle_root)) => format!(
+                    "Root {} existed by Bitcoin block {}, if its merkle root is {}.",
+                    root, height, merkle_root
+                ),
+                None => format!(
+                    "Root {} is pending at {}; run upgrade-timestamp later.",
+                    root,
+                    calendars.join(", ")
+                ),
+            };
+            reporter.verdict(
+                &text,
+                json!({
+                    "status": if blocks.is_empty() { "pending" } else { "ok" },
+                    "root": root,
+                    "bitcoin": blocks
+                        .iter()
+                        .map(|(height, merkle_root)| {
+                            json!({ "height": height, "merkle_root": merkle_root })
+                        })
+                        .collect::<Vec<_>>(),
+                    "pending": calendars,
+                }),
+            );
+        }
+        #[cfg(feature = "rpc")]
+        Commands::VerifyOnchain {
+            rpc,
+            contract,
+            slot,
+            selector,
+            proof,
+        } => {
+            let merkle_proof = cli.leaves.load_proof(&read_input(proof)?)?;
+            cli.leaves.check_hash(merkle_proof.hash_algorithm)?;
+
+            let root_hash = fetch_onchain_root(rpc, *contract, *slot, selector.as_deref())?;
+            let root = cli.hash_format().encode(root_hash);
+            if !merkle_proof.verify(&root_hash)? {
+                reporter.verdict(
+                    &format!("Merkle Proof is INVALID against on-chain root {}.", root),
+                    json!({ "status": "invalid", "root": root }),
+                );
+                return Ok(ExitCode::from(EXIT_INVALID));
+            }
+            reporter.verdict(
+                &format!("Merkle Proof is valid against on-chain root {}.", root),
+                json!({ "status": "ok", "root": root }),
+            );
+        }
+        #[cfg(feature = "publisher")]
+        Commands::Publish {
+            input,
+            rpc,
+            contract,
+            function,
+            private_key,
+            keystore,
+            password_file,
+            dry_run,
+        } => {
+            let merkle_tree = load_or_build_tree(cli, input, timings)?;
+            let signer = load_signer(
+                private_key.as_deref(),
+                keystore.as_deref(),
+                password_file.as_deref(),
+            )?;
+
+            let publisher = merkle_tree::publisher::Publisher::new(rpc.as_str(), *contract, signer)
+                .function(function.as_str())
+                .dry_run(*dry_run);
+            let publication =
+                tokio::runtime::Runtime::new()?.block_on(publisher.publish(&merkle_tree))?;
+            timings.mark("publish");
+
+            let root = cli.hash_format().encode(publication.root);
+            match publication
+                .transaction_hash
+                .map(|hash| cli.hash_format().encode(hash))
+            {
+                Some(transaction_hash) => reporter.result(
+                    None,
+                    &format!(
+                        "Root {} published to {} in transaction {}.",
+                        root, contract, transaction_hash
+                    ),
+                    json!({
+                        "status": "ok",
+                        "root": root,
+                        "contract": contract,
+                        "transaction_hash": transaction_hash,
+                        "gas_estimate": publication.gas_estimate,
+                    }),
+                ),
+                None => reporter.result(
+                    None,
+                    &format!(
+                        "Dry run: setting root {} on {} would use about {} gas. Calldata: {}",
+                        root,
+                        contract,
+                        publication.gas_estimate,
+                        encode_prefixed(&publication.calldata)
+                    ),
+                    json!({
+                        "status": "dry_run",
+                        "root": root,
+                        "contract": contract,
+                        "calldata": encode_prefixed(&publication.calldata),
+                        "gas_estimate": publication.gas_estimate,
+                    }),
+                ),
+            }
+        }
+        Commands::ExportIpld {
+            input,
+            output,
+            ipfs_api,
+        } => {
+            let merkle_tree = load_or_build_tree(cli, input, timings)?;
+            let export = merkle_tree.to_ipld();
+            timings.mark("encode");
+
+            if let Some(output) = output {
+                let mut writer = create_output(output)?;
+                writer.write_all(&export.to_car())?;
+                writer.flush()?;
+                timings.mark("write");
+            }
+            if let Some(ipfs_api) = ipfs_api {
+                export.push(ipfs_api)?;
+                timings.mark("push");
+            }
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                output.as_deref(),
+                &format!(
+                    "Exported {} blocks. Root CID: {} (Root Hash: {})",
+                    export.blocks.len(),
+                    export.root,
+                    root
+                ),
+                json!({
+                    "status": "ok",
+                    "root": root,
+                    "cid": export.root.to_string(),
+                    "block_count": export.blocks.len(),
+                }),
+            );
+        }
+        Commands::GenVerifier { input, out, name } => {
+            let mut options = match input {
+                Some(input) => {
+                    let merkle_tree = load_or_build_tree(cli, input, timings)?;
+                    cli.leaves.check_hash(merkle_tree.hash_algorithm)?;
+                    VerifierOptions::for_tree(&merkle_tree)
+                }
+                None => VerifierOptions {
+                    hash_algorithm: cli.leaves.hash.unwrap_or_default(),
+                    domain_separation: cli.leaves.domain_separation,
+                    sorted_pairs: cli.leaves.sorted_pairs,
+                    ..VerifierOptions::default()
+                },
+            };
+            options.name = name.clone();
+            options.leaf_encoding = cli.leaves.leaf_encoding.clone();
+            write_output(out, generate_verifier(&options)?, None)?;
+
+            reporter.result(
+                Some(out),
+                &format!(
+                    "Generated Solidity verifier {} for {} hashing with {} pairs.",
+                    options.name,
+                    options.hash_algorithm,
+                    if options.sorted_pairs {
+                        "sorted"
+                    } else {
+                        "unsorted"
+                    }
+                ),
+                json!({
+                    "status": "ok",
+                    "name": options.name,
+                    "hash_algorithm": options.hash_algorithm.to_string(),
+                    "sorted_pairs": options.sorted_pairs,
+                    "domain_separation": options.domain_separation,
+                }),
+            );
+        }
+        Commands::GenFixture {
+            input,
+            out,
+            samples,
+            format,
+            name,
+            verifier_path,
+        } => {
+            let merkle_tree = load_or_build_tree(cli, input, timings)?;
+            cli.leaves.check_hash(merkle_tree.hash_algorithm)?;
+            let fixture = Fixture::sample(&merkle_tree, *samples)?;
+            timings.mark("proofs");
+
+            let contents = match format {
+                FixtureFormat::Foundry => {
+                    let options = VerifierOptions {
+                        name: name.clone(),
+                        leaf_encoding: cli.leaves.leaf_encoding.clone(),
+                        ..VerifierOptions::for_tree(&merkle_tree)
+                    };
+                    fixture.to_foundry_test(&options, verifier_path)?
+                }
+                FixtureFormat::Json => fixture.to_json()?,
+            };
+            write_output(out, contents, None)?;
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                Some(out),
+                &format!(
+                    "Generated a fixture with {} of {} proofs. Root Hash: {}",
+                    fixture.samples.len(),
+                    merkle_tree.leaf_count(),
+                    root
+                ),
+                json!({ "status": "ok", "root": root, "sample_count": fixture.samples.len() }),
+            );
+        }
+        #[cfg(feature = "object_store")]
+        Commands::Upload {
+            input,
+            to,
+            output_format,
+            proofs_to,
+        } => {
+            use merkle_tree::object_storage::{open_url, save_proofs, save_tree};
+
+            let merkle_tree = load_or_build_tree(cli, input, timings)?;
+            let runtime = tokio::runtime::Runtime::new()?;
+            let (store, location) = open_url(to)?;
+            runtime.block_on(save_tree(
+                store.as_ref(),
+                &location,
+                &merkle_tree,
+                *output_format,
+            ))?;
+            timings.mark("upload");
+
+            let proof_count = match proofs_to {
+                Some(proofs_to) => {
+                    let (store, prefix) = open_url(proofs_to)?;
+                    let count =
+                        runtime.block_on(save_proofs(store.as_ref(), &prefix, &merkle_tree))?;
+                    timings.mark("proofs");
+                    count
+                }
+                None => 0,
+            };
+
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                None,
+                &format!("Merkle Tree uploaded to {}. Root Hash: {}", to, root),
+                json!({ "status": "ok", "root": root, "proof_count": proof_count }),
+            );
+        }
+        Commands::Completions { shell } => {
+            clap_complete::generate(
+                *shell,
+                &mut Cli::command(),
+                env!("CARGO_BIN_NAME"),
+                &mut io::stdout(),
+            );
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Defaults read from `merkle.toml` or `--config`, applied to options not given on the
+/// command line.
+///
+/// Keys are the long flag names. `input` and `output` fill in the leaf file and tree file
+/// of the commands that take them, relative to the config file's directory.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Config {
+    hash: Option<String>,
+    leaf_format: Option<String>,
+    leaf_encoding: Option<String>,
+    input_format: Option<String>,
+    sort_leaves: Option<bool>,
+    dedupe: Option<bool>,
+    sorted_pairs: Option<bool>,
+    duplicate_odd: Option<bool>,
+    domain_separation: Option<bool>,
+    strict: Option<bool>,
+    allow_64_byte_leaves: Option<bool>,
+    verify: Option<bool>,
+    checksum: Option<bool>,
+    require_signer: Option<String>,
+    input: Option<String>,
+    output: Option<String>,
+}
+
+impl Cli {
+    /// Returns how hashes and bytes are written in output files and messages.
+    fn hash_format(&self) -> HashFormat {
+        HashFormat {
+            encoding: self.hash_encoding,
+            hex_prefix: self.hex_prefix,
+        }
+    }
+
+    /// Fills in options not given on the command line from `--config`, or from
+    /// `merkle.toml` when it exists.
+    fn apply_config(&mut self, matches: &ArgMatches) -> Result<(), MerkleTreeError> {
+        let path = match &self.config {
+            Some(path) => path.clone(),
+            None if Path::new(CONFIG_FILE).is_file() => PathBuf::from(CONFIG_FILE),
+            None => return Ok(()),
+        };
+        let invalid = |e: String| {
+            MerkleTreeError::FormatError(format!("invalid config file {}: {}", path.display(), e))
+        };
+        let content = fs::read_to_string(&path).map_err(|e| invalid(e.to_string()))?;
+        let config: Config = toml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+
+        let leaves = &mut self.leaves;
+        let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        // A value from the config file, such as `strict = false`, applies unless the flag
+        // was given on the command line
+        let merge = |flag: &mut bool, id: &str, value: Option<bool>| {
+            if let (false, Some(value)) = (given(id), value) {
+                *flag = value;
+            }
+        };
+        if let (false, Some(hash)) = (given("hash"), &config.hash) {
+            leaves.hash = Some(hash.parse().map_err(invalid)?);
+        }
+        if let (false, Some(format)) = (given("leaf_format"), &config.leaf_format) {
+            leaves.leaf_format =
+                <LeafFormat as ValueEnum>::from_str(format, true).map_err(invalid)?;
+        }
+        if let (false, Some(encoding)) = (given("leaf_encoding"), &config.leaf_encoding) {
+            leaves.leaf_encoding = Some(encoding.parse().map_err(invalid)?);
+        }
+        if let (false, Some(format)) = (given("input_format"), &config.input_format) {
+            leaves.input_format =
+                <InputFormat as ValueEnum>::from_str(format, true).map_err(invalid)?;
+        }
+        merge(&mut leaves.sort_leaves, "sort_leaves", config.sort_leaves);
+        merge(&mut leaves.dedupe, "dedupe", config.dedupe);
+        merge(
+            &mut leaves.sorted_pairs,
+            "sorted_pairs",
+            config.sorted_pairs,
+        );
+        merge(
+            &mut leaves.duplicate_odd,
+            "duplicate_odd",
+            config.duplicate_odd,
+        );
+        merge(
+            &mut leaves.domain_separation,
+            "domain_separation",
+            config.domain_separation,
+        );
+        merge(&mut leaves.strict, "strict", config.strict);
+        merge(
+            &mut leaves.allow_64_byte_leaves,
+            "allow_64_byte_leaves",
+            config.allow_64_byte_leaves,
+        );
+        merge(&mut leaves.verify, "verify", config.verify);
+        merge(&mut leaves.checksum, "checksum", config.checksum);
+        if let (false, Some(signer)) = (given("require_signer"), &config.require_signer) {
+            leaves.require_signer = Some(
+                signer
+                    .parse::<Address>()
+                    .map_err(|e| invalid(e.to_string()))?,
+            );
+        }
+
+        // Paths in the config file are relative to it, not to the working directory
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let resolve = |file: &String| match Path::new(file) {
+            file if is_stdio(file) => file.to_path_buf(),
+            file => directory.join(file),
+        };
+        let input = config.input.as_ref().map(resolve);
+        let output = config.output.as_ref().map(resolve);
+        match &mut self.command {
+            Commands::Build {
+                input: inputs,
+                output: tree,
+                ..
+            } => {
+                if inputs.is_empty() {
+                    inputs.extend(input.map(|input| input.to_string_lossy().into_owned()));
+                }
+                if tree.is_none() {
+                    *tree = output;
+                }
+            }
+            Commands::Watch {
+                input: leaves,
+                output: tree,
+            } => {
+                if leaves.is_none() {
+                    *leaves = input;
+                }
+                if tree.is_none() {
+                    *tree = output;
+                }
+            }
+            Commands::Proof { input: leaves, .. }
+            | Commands::Root { input: leaves }
+            | Commands::ProofAll { input: leaves, .. }
+            | Commands::ProofBatch { input: leaves, .. }
+            | Commands::Multiproof { input: leaves, .. } => {
+                if leaves.is_none() {
+                    *leaves = input;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Returns a path given on the command line or in the config file.
+fn required<'a>(path: &'a Option<PathBuf>, flag: &str) -> Result<&'a Path, MerkleTreeError> {
+    path.as_deref().ok_or_else(|| missing_option(flag))
+}
+
+/// The error for an option given neither on the command line nor in the config file.
+fn missing_option(flag: &str) -> MerkleTreeError {
+    MerkleTreeError::FormatError(format!(
+        "missing {}; pass it or set it in {}",
+        flag, CONFIG_FILE
+    ))
+}
+
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO
+}
+
+/// Returns the position of the first non-blank character of `value`, a slice of `line`, the
+/// 0-based `line_number` of `input`.
+fn input_position(input: &Path, line_number: usize, line: &str, value: &str) -> InputPosition {
+    let offset = value.as_ptr() as usize - line.as_ptr() as usize;
+    let leading = value.len() - value.trim_start().len();
+    InputPosition {
+        file: (!is_stdio(input)).then(|| input.display().to_string()),
+        line: line_number + 1,
+        column: line[..offset + leading].chars().count() + 1,
+    }
+}
+
+/// Reads a file, or stdin when `input` is `-`.
+fn read_input(input: &Path) -> Result<Vec<u8>, MerkleTreeError> {
+    if is_stdio(input) {
+        let mut content = Vec::new();
+        io::stdin().lock().read_to_end(&mut content)?;
+        Ok(content)
+    } else {
+        Ok(fs::read(input)?)
+    }
+}
+
+/// Expands glob patterns among `inputs`, keeping argument order and sorting each
+/// pattern's matches by path.
+fn expand_inputs(inputs: &[String]) -> Result<Vec<PathBuf>, MerkleTreeError> {
+    let mut paths = Vec::new();
+    for input in inputs {
+        if input == STDIO || !input.contains(['*', '?', '[']) {
+            paths.push(PathBuf::from(input));
+            continue;
+        }
+
+        let pattern_error = |e: String| {
+            MerkleTreeError::FormatError(format!("invalid input pattern '{}': {}", input, e))
+        };
+        let mut matches = glob::glob(input)
+            .map_err(|e| pattern_error(e.to_string()))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| pattern_error(e.to_string()))?;
+        if matches.is_empty() {
+            return Err(pattern_error("no files match".to_string()));
+        }
+        matches.sort();
+        paths.extend(matches);
+    }
+    Ok(paths)
+}
+
+/// Creates a file, or writes to stdout when `output` is `-`.
+fn create_output(output: &Path) -> Result<Box<dyn Write>, MerkleTreeError> {
+    if is_stdio(output) {
+        Ok(Box::new(io::stdout().lock()))
+    } else {
+        Ok(Box::new(BufWriter::new(File::create(output)?)))
+    }
+}
+
+/// Reads `repl` commands from stdin until `quit` or the end of input, prompting when stdin
+/// is a terminal. A failed command prints its error and the session carries on.
+fn run_repl(
+    cli: &Cli,
+    reporter: &Reporter,
+    mut merkle_tree: Option<MerkleTree>,
+) -> Result<(), MerkleTreeError> {
+    let interactive = io::stdin().is_terminal();
+    if interactive {
+        println!("Type `help` for the list of commands.");
+    }
+
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        if interactive {
+            print!("merkle> ");
+            io::stdout().flush()?;
+        }
+        let Some(line) = lines.next() else {
+            break;
+        };
+        match line?.trim() {
+            "" => {}
+            "quit" | "exit" => break,
+            command => match repl_command(cli, &mut merkle_tree, command) {
+                Ok(text) => println!("{}", text),
+                Err(e) => reporter.error(&e),
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Runs one `repl` command against the session's tree, returning the text to print.
+fn repl_command(
+    cli: &Cli,
+    merkle_tree: &mut Option<MerkleTree>,
+    line: &str,
+) -> Result<String, MerkleTreeError> {
+    let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let usage = |usage: &str| MerkleTreeError::FormatError(format!("usage: {}", usage));
+    let parse_index =
+        |arg: &str, usage_text: &str| arg.parse::<usize>().map_err(|_| usage(usage_text));
+
+    match (command, args.as_slice()) {
+        ("add", [_, ..]) => {
+            // Leaves may contain spaces, such as comma-separated `--leaf-encoding` values
+            let data = cli.leaves.parse(line[command.len()..].trim())?;
+            match merkle_tree.as_mut() {
+                Some(tree) => tree.append(&[data])?,
+                None => *merkle_tree = Some(cli.leaves.builder().build(&[data])?),
+            }
+            let merkle_tree = merkle_tree.as_ref().ok_or(MerkleTreeError::EmptyData)?;
+            Ok(format!(
+                "Added leaf {}. Root Hash: {}",
+                merkle_tree.leaf_count() - 1,
+                cli.hash_format().encode(merkle_tree.root_hash())
+            ))
+        }
+        ("remove", [index]) => {
+            let index = parse_index(index, "remove <index>")?;
+            let tree = merkle_tree.as_mut().ok_or(MerkleTreeError::EmptyData)?;
+            if tree.leaf_count() == 1 && index == 0 {
+                *merkle_tree = None;
+                return Ok("Removed leaf 0. The tree is now empty.".to_string());
+            }
+            tree.remove(index)?;
+            Ok(format!(
+                "Removed leaf {}. Root Hash: {}",
+                index,
+                cli.hash_format().encode(tree.root_hash())
+            ))
+        }
+        ("root", []) => {
+            let tree = merkle_tree.as_ref().ok_or(MerkleTreeError::EmptyData)?;
+            Ok(cli.hash_format().encode(tree.root_hash()))
+        }
+        ("leaves", []) => {
+            let tree = merkle_tree.as_ref().ok_or(MerkleTreeError::EmptyData)?;
+            Ok(tree
+                .leaf_hashes
+                .iter()
+                .enumerate()
+                .map(|(index, hash)| format!("{:>6}  {}", index, cli.hash_format().encode(hash)))
+                .collect::<Vec<_>>()
+                .join("\n"))
+        }
+        ("proof", [index, file @ ..]) if file.len() <= 1 => {
+            let index = parse_index(index, "proof <index> [file]")?;
+            let tree = merkle_tree.as_ref().ok_or(MerkleTreeError::EmptyData)?;
+            let proof_json = tree
+                .generate_proof_by_index(index)?
+                .to_json_with(cli.hash_format())?;
+            match file {
+                [file] => {
+                    fs::write(file, proof_json)?;
+                    Ok(format!("Merkle Proof saved to {}.", file))
+                }
+                _ => Ok(proof_json),
+            }
+        }
+        ("verify", [file, root @ ..]) if root.len() <= 1 => {
+            let root_hash = match root {
+                [root] => parse_b256(root)?,
+                _ => *merkle_tree
+                    .as_ref()
+                    .ok_or(MerkleTreeError::EmptyData)?
+                    .root_hash(),
+            };
+            let merkle_proof = cli.leaves.load_proof(&fs::read(file)?)?;
+            Ok(if merkle_proof.verify(&root_hash)? {
+                "Merkle Proof is valid.".to_string()
+            } else {
+                "Merkle Proof is INVALID.".to_string()
+            })
+        }
+        ("add", _) => Err(usage("add <leaf>")),
+        ("remove", _) => Err(usage("remove <index>")),
+        ("root", _) => Err(usage("root")),
+        ("leaves", _) => Err(usage("leaves")),
+        ("proof", _) => Err(usage("proof <index> [file]")),
+        ("verify", _) => Err(usage("verify <file> [root]")),
+        ("help", _) => Ok(REPL_HELP.to_string()),
+        _ => Err(MerkleTreeError::FormatError(format!(
+            "unknown command '{}'; type `help` for the list of commands",
+            command
+        ))),
+    }
+}
+
+/// Loads the signing key from `--private-key`, or from `--keystore` decrypted with the
+/// password in `--password-file`.
+fn load_signer(
+    private_key: Option<&str>,
+    keystore: Option<&Path>,
+    password_file: Option<&Path>,
+) -> Result<PrivateKeySigner, MerkleTreeError> {
+    match (private_key, keystore, password_file) {
+        (Some(private_key), _, _) => PrivateKeySigner::from_str(private_key.trim())
+            .map_err(|e| MerkleTreeError::SignatureError(e.to_string())),
+        (None, Some(keystore), Some(password_file)) => {
+            let password = fs::read_to_string(password_file)?;
+            PrivateKeySigner::decrypt_keystore(keystore, password.trim_end_matches(['\r', '\n']))
+                .map_err(|e| MerkleTreeError::SignatureError(e.to_string()))
+        }
+        _ => unreachable!("clap requires a key"),
+    }
+}
+
+/// Fetches the transaction hashes of `block` from the JSON-RPC endpoint at `rpc`.
+#[cfg(feature = "rpc")]
+fn fetch_block_transactions(
+    rpc: &str,
+    block: alloy_rpc_types_eth::BlockNumberOrTag,
+) -> Result<Vec<Vec<u8>>, MerkleTreeError> {
+    use alloy_provider::ProviderBuilder;
+
+    let url = rpc
+        .parse()
+        .map_err(|e| MerkleTreeError::RpcError(format!("invalid RPC URL '{}': {}", rpc, e)))?;
+    let provider = ProviderBuilder::new().on_http(url);
+    let hashes = tokio::runtime::Runtime::new()?.block_on(
+        merkle_tree::chain::block_transactions(&provider, block.into()),
+    )?;
+    Ok(hashes.iter().map(|hash| hash.to_vec()).collect())
+}
+
+/// Reads the root stored in `contract`, from storage `slot` or by calling the view
+/// function `selector`, at the latest block.
+#[cfg(feature = "rpc")]
+fn fetch_onchain_root(
+    rpc: &str,
+    contract: Address,
+    slot: Option<U256>,
+    selector: Option<&str>,
+) -> Result<B256, MerkleTreeError> {
+    let word: String = match (slot, selector) {
+        (Some(slot), _) => rpc_call(
+            rpc,
+            "eth_getStorageAt",
+            json!([contract, format!("{:#x}", slot), "latest"]),
+        )?,
+        (None, Some(selector)) => {
+            // A function signature is hashed to its selector; hex is taken as is
+            let selector = if selector.contains('(') {
+                alloy_primitives::keccak256(selector)[..4].to_vec()
+            } else {
+                decode(selector)?
+            };
+            if selector.len() != 4 {
+                return Err(MerkleTreeError::FormatError(format!(
+                    "selector must be 4 bytes, got {}",
+                    selector.len()
+                )));
+            }
+            let call = json!({ "to": contract, "data": encode_prefixed(selector) });
+            rpc_call(rpc, "eth_call", json!([call, "latest"]))?
+        }
+        (None, None) => unreachable!("clap requires --slot or --selector"),
+    };
+
+    // Storage slots are one word; a view function's first return word holds the root
+    let word = decode(&word)?;
+    if word.len() < 32 {
+        return Err(MerkleTreeError::RpcError(format!(
+            "expected a 32-byte root, got {} bytes",
+            word.len()
+        )));
+    }
+    Ok(B256::from_slice(&word[..32]))
+}
+
+/// Sends one JSON-RPC request to `rpc` and returns its result.
+#[cfg(feature = "rpc")]
+fn rpc_call<R>(rpc: &str, method: &'static str, params: Value) -> Result<R, MerkleTreeError>
+where
+    R: serde::de::DeserializeOwned + std::fmt::Debug + Send + Sync + Unpin + 'static,
+{
+    use alloy_provider::{Provider, ProviderBuilder};
+
+    let url = rpc
+        .parse()
+        .map_err(|e| MerkleTreeError::RpcError(format!("invalid RPC URL '{}': {}", rpc, e)))?;
+    let provider = ProviderBuilder::new().on_http(url);
+    tokio::runtime::Runtime::new()?
+        .block_on(provider.raw_request(method.into(), params))
+        .map_err(|e| MerkleTreeError::RpcError(e.to_string()))
+}
+
+/// Rebuilds the tree for `watch`, reporting the new root or the error and carrying on
+/// either way.
+fn watch_rebuild(cli: &Cli, reporter: &Reporter, input: &Path, output: Option<&Path>) {
+    let mut timings = Timings::new();
+    let mut rebuild = || -> Result<MerkleTree, MerkleTreeError> {
+        let leaves = cli.leaves.read(input)?;
+        timings.mark("read");
+        let merkle_tree = build_tree(cli, &leaves, &mut timings)?;
+        if let Some(output) = output {
+            write_tree(cli, output, &merkle_tree, DumpFormat::Json, None)?;
+            timings.mark("write");
+        }
+        Ok(merkle_tree)
+    };
+
+    match rebuild() {
+        Ok(merkle_tree) => {
+            let root = cli.hash_format().encode(merkle_tree.root_hash());
+            reporter.result(
+                output,
+                &format!("Merkle Tree rebuilt. Root Hash: {}", root),
+                json!({ "status": "ok", "root": root, "leaf_count": merkle_tree.leaf_count() }),
+            );
+        }
+        Err(e) => reporter.error(&e),
+    }
+    if cli.timings {
+        timings.print();
+    }
+}
+
+/// Builds a tree from `leaves` with the `LeafOptions` builder, drawing a progress bar on
+/// stderr for large inputs and recording the hashing and building phases in `timings`.
+fn build_tree(
+    cli: &Cli,
+    leaves: &[Vec<u8>],
+    timings: &mut Timings,
+) -> Result<MerkleTree, MerkleTreeError> {
+    let bar = (!cli.quiet && leaves.len() >= PROGRESS_MIN_LEAVES).then(|| {
+        let bar = ProgressBar::new(leaves.len() as u64);
+        bar.set_style(
+            ProgressStyle::with_template("{msg:>15} [{bar:40}] {pos}/{len} ({elapsed}, eta {eta})")
+                .expect("valid progress template")
+                .progress_chars("=> "),
+        );
+        bar.set_message("hashing leaves");
+        bar
+    });
+
+    // Leaves are hashed before any level is built, so the last leaf report ends hashing
+    let hashed_at = Arc::new(Mutex::new(None));
+    let builder = {
+        let bar = bar.clone();
+        let hashed_at = Arc::clone(&hashed_at);
+        cli.leaves
+            .builder()
+            .on_progress(move |progress| match progress {
+                BuildProgress::LeavesHashed { hashed, total } => {
+                    if let Some(bar) = &bar {
+                        bar.set_position(hashed as u64);
+                    }
+                    if hashed == total {
+                        *hashed_at.lock().unwrap() = Some(Instant::now());
+                    }
+                }
+                BuildProgress::LevelBuilt { level, depth } => {
+                    if let Some(bar) = &bar {
+                        if level == 1 {
+                            bar.set_message("building levels");
+                            bar.set_length(depth as u64);
+                        }
+                        bar.set_position(level as u64);
+                    }
+                }
+            })
+    };
+
+    let merkle_tree = builder.build(leaves);
+    if let Some(bar) = bar {
+        bar.finish_and_clear();
+    }
+    let merkle_tree = merkle_tree?;
+
+    if let Some(hashed_at) = *hashed_at.lock().unwrap() {
+        timings.mark_at("hash", hashed_at);
+    }
+    timings.mark("build");
+    Ok(merkle_tree)
+}
+
+/// Wall-clock time spent in each phase of a command, printed with `--timings`.
+struct Timings {
+    last: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    fn new() -> Self {
+        Timings {
+            last: Instant::now(),
+            phases: Vec::new(),
+        }
+    }
+
+    /// Ends the current phase, naming it `phase`.
+    fn mark(&mut self, phase: &'static str) {
+        self.mark_at(phase, Instant::now());
+    }
+
+    /// Ends the current phase at `at`, naming it `phase`.
+    fn mark_at(&mut self, phase: &'static str, at: Instant) {
+        self.phases
+            .push((phase, at.saturating_duration_since(self.last)));
+        self.last = at;
+    }
+
+    /// Prints each phase and the total on stderr, if any phase was recorded.
+    fn print(&self) {
+        if self.phases.is_empty() {
+            return;
+        }
+        let mut total = Duration::ZERO;
+        eprintln!("Timings:");
+        for (phase, duration) in &self.phases {
+            eprintln!("  {:<8}{:>12.3?}", phase, duration);
+            total += *duration;
+        }
+        eprintln!("  {:<8}{:>12.3?}", "total", total);
+    }
+}
+
+/// Writes a tree to a file or stdout, streaming it when it is uncompressed JSON.
+fn write_tree(
+    cli: &Cli,
+    output: &Path,
+    merkle_tree: &MerkleTree,
+    format: DumpFormat,
+    compression: Option<Compression>,
+) -> Result<(), MerkleTreeError> {
+    let leaves = &cli.leaves;
+    if format == DumpFormat::Json && compression.is_none() && !leaves.seals() {
+        return merkle_tree.to_writer_with(create_output(output)?, cli.hash_format());
+    }
+
+    let mut bytes = match format {
+        DumpFormat::Json => merkle_tree.to_json_with(cli.hash_format())?.into_bytes(),
+        format => merkle_tree.to_dump(format)?,
+    };
+    if let Some(compression) = compression {
+        bytes = compress(&bytes, compression)?;
+    }
+    let mut writer = create_output(output)?;
+    writer.write_all(&leaves.seal(bytes)?)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes a proof's JSON, compressed and then sealed as requested.
+fn write_proof(
+    leaves: &LeafOptions,
+    output: &Path,
+    json: String,
+    compression: Option<Compression>,
+) -> Result<(), MerkleTreeError> {
+    let mut bytes = json.into_bytes();
+    if let Some(compression) = compression {
+        bytes = compress(&bytes, compression)?;
+    }
+    let mut writer = create_output(output)?;
+    writer.write_all(&leaves.seal(bytes)?)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// A half-open byte range given as `START..END`.
+#[derive(Clone, Copy)]
+struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+impl FromStr for ByteRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once("..")
+            .ok_or_else(|| format!("invalid byte range '{}', expected START..END", s))?;
+        let parse = |bound: &str| {
+            bound
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| format!("invalid byte offset '{}': {}", bound, e))
+        };
+        let (start, end) = (parse(start)?, parse(end)?);
+        if start >= end {
+            return Err(format!("byte range {}..{} is empty", start, end));
+        }
+        Ok(ByteRange { start, end })
+    }
+}
+
+impl ByteRange {
+    /// Returns the indices of the chunks holding the range, checking it lies within
+    /// `chunk_count` chunks.
+    fn chunks(
+        self,
+        chunk_size: u64,
+        chunk_count: usize,
+    ) -> Result<std::ops::RangeInclusive<usize>, MerkleTreeError> {
+        let first = self.start / chunk_size;
+        let last = (self.end - 1) / chunk_size;
+        if last >= chunk_count as u64 {
+            return Err(MerkleTreeError::FormatError(format!(
+                "byte range {}..{} extends past the end of the file",
+                self.start, self.end
+            )));
+        }
+        Ok(first as usize..=last as usize)
+    }
+}
+
+/// Collects the paths of the regular files under `dir`, relative to the directory
+/// `hash-dir` started from and joined with `/`.
+fn collect_files(
+    dir: &Path,
+    prefix: String,
+    files: &mut Vec<String>,
+) -> Result<(), MerkleTreeError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().into_string().map_err(|name| {
+            MerkleTreeError::FormatError(format!("file name {:?} is not valid UTF-8", name))
+        })?;
+        let path = format!("{}{}", prefix, name);
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), format!("{}/", path), files)?;
+        } else if file_type.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns `builder` accepting 64-byte leaves, for checking leaves this tool derives itself
+/// rather than reads, such as file chunks and `H(path) || H(content)` pairs, whose length
+/// is not chosen to make them look like a pair of child hashes.
+fn derived_leaves(builder: &MerkleTreeBuilder) -> MerkleTreeBuilder {
+    builder.clone().allow_64_byte_leaves(true)
+}
+
+/// Hashes each of `files`, relative to `dir`, into a leaf `H(H(path) || H(content))` of
+/// `builder`'s trees, returning the leaf hashes and a manifest entry per file.
+fn hash_files(
+    dir: &Path,
+    files: &[String],
+    builder: &MerkleTreeBuilder,
+    hash_format: HashFormat,
+) -> Result<(Vec<B256>, Vec<Value>), MerkleTreeError> {
+    let hash_algorithm = builder.algorithm();
+    let checker = derived_leaves(builder);
+    let mut entries = Vec::with_capacity(files.len());
+    let mut leaf_hashes = Vec::with_capacity(files.len());
+    for (leaf, path) in files.iter().enumerate() {
+        let content_hash = hash_algorithm.hash(&fs::read(dir.join(path))?);
+        let path_hash = hash_algorithm.hash(path.as_bytes());
+        let leaf_data = [path_hash.0, content_hash.0].concat();
+        checker.check_leaf(leaf, &leaf_data)?;
+        leaf_hashes.push(builder.hash_leaf(&leaf_data));
+        entries.push(json!({
+            "path": path,
+            "hash": hash_format.encode(content_hash),
+            "leaf": leaf,
+        }));
+    }
+    Ok((leaf_hashes, entries))
+}
+
+/// Hashes a file, or stdin when `input` is `-`, in chunks of `chunk_size` bytes, each as a
+/// leaf of `builder`'s trees.
+fn hash_chunks(
+    input: &Path,
+    chunk_size: u64,
+    builder: &MerkleTreeBuilder,
+) -> Result<Vec<B256>, MerkleTreeError> {
+    let mut reader: Box<dyn Read> = if is_stdio(input) {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(File::open(input)?)
+    };
+
+    let checker = derived_leaves(builder);
+    let mut hashes = Vec::new();
+    let mut chunk = Vec::new();
+    loop {
+        chunk.clear();
+        (&mut reader).take(chunk_size).read_to_end(&mut chunk)?;
+        if chunk.is_empty() {
+            break;
+        }
+        checker.check_leaf(hashes.len(), &chunk)?;
+        hashes.push(builder.hash_leaf(&chunk));
+    }
+    Ok(hashes)
+}
+
+/// Reads a file of target leaves, one per line, skipping blank lines.
+fn read_targets(cli: &Cli, targets: &Path) -> Result<Vec<Vec<u8>>, MerkleTreeError> {
+    let content = String::from_utf8(read_input(targets)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(line_number, line)| cli.leaves.parse_line(targets, line_number, line))
+        .collect()
+}
+
+/// A proof read for verification, labeled by its file name or array index.
+type LabeledProof = (String, Result<MerkleProof, MerkleTreeError>);
+
+/// Reads every proof file in a directory, sorted by file name.
+fn read_proof_dir(leaves: &LeafOptions, dir: &Path) -> Result<Vec<LabeledProof>, MerkleTreeError> {
+    let mut files = fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>, io::Error>>()?;
+    files.retain(|file| file.is_file());
+    files.sort();
+    Ok(files
+        .into_iter()
+        .map(|file| {
+            let proof = fs::read(&file)
+                .map_err(MerkleTreeError::from)
+                .and_then(|content| leaves.load_proof(&content));
+            (file.display().to_string(), proof)
+        })
+        .collect())
+}
+
+/// Parses a JSON array of proofs, optionally compressed and enveloped, or returns `None` if
+/// `content` is not a JSON array.
+fn parse_proof_array(content: &[u8]) -> Result<Option<Vec<LabeledProof>>, MerkleTreeError> {
+    let content = decompress(&envelope::open(content)?)?;
+    if content.iter().find(|byte| !byte.is_ascii_whitespace()) != Some(&b'[') {
+        return Ok(None);
+    }
+    let entries: Vec<Value> = serde_json::from_slice(&content)?;
+    Ok(Some(
+        entries
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let proof = MerkleProof::from_json(&entry.to_string());
+                (format!("[{}]", index), proof)
+            })
+            .collect(),
+    ))
+}
+
+/// Verifies each proof of a set against `root_hash`, reporting one line per proof and
+/// exiting with [`EXIT_INVALID`] if any proof is invalid or unreadable.
+fn verify_proof_set(
+    cli: &Cli,
+    reporter: &Reporter,
+    root_hash: &B256,
+    proofs: Vec<LabeledProof>,
+) -> Result<ExitCode, MerkleTreeError> {
+    // An empty set proves nothing, so it must not pass for all proofs being valid
+    if proofs.is_empty() {
+        let failure = "no Merkle Proofs to verify";
+        reporter.verdict(
+            &format!("Merkle Proofs are INVALID: {}.", failure),
+            json!({ "status": "invalid", "root": cli.hash_format().encode(root_hash), "error": failure }),
+        );
+        return Ok(ExitCode::from(EXIT_INVALID));
+    }
+
+    let mut lines = Vec::new();
+    let mut results = Vec::new();
+    let mut valid = 0;
+    for (label, proof) in proofs {
+        let outcome = proof.and_then(|proof| {
+            cli.leaves.check_hash(proof.hash_algorithm)?;
+            cli.leaves.verify_proof(&proof, root_hash)
+        });
+        match outcome {
+            Ok(true) => {
+                valid += 1;
+                lines.push(format!("{}: valid", label));
+                results.push(json!({ "proof": label, "status": "ok" }));
+            }
+            Ok(false) => {
+                lines.push(format!("{}: INVALID", label));
+                results.push(json!({ "proof": label, "status": "invalid" }));
+            }
+            Err(e) => {
+                lines.push(format!("{}: ERROR ({})", label, e));
+                results.push(json!({ "proof": label, "status": "error", "error": e.to_string() }));
+            }
+        }
+    }
+
+    let total = results.len();
+    let status = if valid == total { "ok" } else { "invalid" };
+    lines.push(format!("{} of {} Merkle Proofs are valid.", valid, total));
+    reporter.verdict(
+        &lines.join("\n"),
+        json!({
+            "status": status,
+            "root": cli.hash_format().encode(root_hash),
+            "valid": valid,
+            "invalid": total - valid,
+            "results": results,
+        }),
+    );
+    if valid == total {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::from(EXIT_INVALID))
+    }
+}
+
+/// Loads a serialized tree, or builds one with `--timings` and progress reporting from a
+/// file of leaves if it is not a tree. `--require-signer` applies to both.
+fn load_or_build_tree(
+    cli: &Cli,
+    input: &Path,
+    timings: &mut Timings,
+) -> Result<MerkleTree, MerkleTreeError> {
+    let content = read_input(input)?;
+    cli.leaves.check_signer(&content)?;
+    match MerkleTree::from_dump(&content) {
+        Ok(merkle_tree) => {
+            cli.leaves.verify_tree(&merkle_tree)?;
+            timings.mark("read");
+            Ok(merkle_tree)
+        }
+        Err(e) if is_dump(&content) => Err(e),
+        Err(_) => {
+            let leaves = cli.leaves.parse_all(input, content)?;
+            timings.mark("read");
+            build_tree(cli, &leaves, timings)
+        }
+    }
+}
+
+/// Loads a serialized tree, or builds one from a file of leaves if it is not a tree.
+/// `--require-signer` applies to both.
+fn load_tree_or_leaves(path: &Path, leaves: &LeafOptions) -> Result<MerkleTree, MerkleTreeError> {
+    let content = read_input(path)?;
+    leaves.check_signer(&content)?;
+    match MerkleTree::from_dump(&content) {
+        Ok(tree) => {
+            leaves.verify_tree(&tree)?;
+            Ok(tree)
+        }
+        Err(e) if is_dump(&content) => Err(e),
+        Err(_) => leaves.builder().build(&leaves.parse_all(path, content)?),
+    }
+}
+
+/// Returns whether `content` is recognizably a tree dump rather than a file of leaves, so
+/// a dump that fails to load is reported instead of being read as leaves.
+fn is_dump(content: &[u8]) -> bool {
+    if Envelope::detect(content) || Compression::detect(content).is_some() {
+        return true;
+    }
+    match DumpFormat::detect(content) {
+        Some(DumpFormat::Compact | DumpFormat::Bincode | DumpFormat::Cbor) => true,
+        Some(DumpFormat::Json) => serde_json::from_slice::<serde::de::IgnoredAny>(content).is_ok(),
+        None => false,
+    }
+}
+
+impl LeafOptions {
+    /// Returns a tree builder honoring `--sort-leaves`, `--dedupe`, `--hash`,
+    /// `--sorted-pairs`, `--duplicate-odd` and `--domain-separation`.
+    fn builder(&self) -> MerkleTreeBuilder {
+        let odd_nodes = if self.duplicate_odd {
+            OddNodePolicy::Duplicate
+        } else {
+            OddNodePolicy::Promote
+        };
+        MerkleTree::builder()
+            .sort_leaves(self.sort_leaves)
+            .dedupe(self.dedupe)
+            .hash_algorithm(self.hash.unwrap_or_default())
+            .sorted_pairs(self.sorted_pairs)
+            .odd_nodes(odd_nodes)
+            .domain_separation(self.domain_separation)
+            .strict(self.strict)
+            .allow_64_byte_leaves(self.allow_64_byte_leaves)
+    }
+
+    /// Verifies `proof` against `root_hash`, strictly with `--strict`.
+    fn verify_proof(&self, proof: &MerkleProof, root_hash: &B256) -> Result<bool, MerkleTreeError> {
+        if self.strict {
+            proof.verify_strict(root_hash)
+        } else {
+            proof.verify(root_hash)
+        }
+    }
+
+    /// Deserializes a tree dump, checking its signer with `--require-signer` and all of its
+    /// hashes with `--verify`.
+    fn load_tree(&self, content: &[u8]) -> Result<MerkleTree, MerkleTreeError> {
+        let merkle_tree = MerkleTree::from_dump_with(content, &self.envelope_policy())?;
+        self.verify_tree(&merkle_tree)?;
+        Ok(merkle_tree)
+    }
+
+    /// Deserializes a proof dump, checking its signer with `--require-signer`.
+    fn load_proof(&self, content: &[u8]) -> Result<MerkleProof, MerkleTreeError> {
+        MerkleProof::from_dump_with(content, &self.envelope_policy())
+    }
+
+    /// Returns what read trees and proofs must be sealed with: a signature by the
+    /// `--require-signer` address, if given.
+    fn envelope_policy(&self) -> EnvelopePolicy {
+        match self.require_signer {
+            Some(signer) => EnvelopePolicy::signed_by(signer),
+            None => EnvelopePolicy::default(),
+        }
+    }
+
+    /// Checks that a read input is signed by the `--require-signer` address, if given.
+    fn check_signer(&self, content: &[u8]) -> Result<(), MerkleTreeError> {
+        if self.require_signer.is_some() {
+            envelope::open_with(content, &self.envelope_policy())?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether written trees and proofs are wrapped in an envelope.
+    fn seals(&self) -> bool {
+        self.checksum || self.sign_key.is_some() || self.sign_keystore.is_some()
+    }
+
+    /// Wraps a written tree or proof in an envelope with `--checksum` or a signing key.
+    fn seal(&self, bytes: Vec<u8>) -> Result<Vec<u8>, MerkleTreeError> {
+        if !self.seals() {
+            return Ok(bytes);
+        }
+        if self.sign_key.is_none() && self.sign_keystore.is_none() {
+            return Ok(Envelope::checksummed(bytes).to_bytes());
+        }
+
+        // Keystores are slow to decrypt, so the key is loaded once per run
+        let signer = match self.signer.get() {
+            Some(signer) => signer,
+            None => {
+                let signer = load_signer(
+                    self.sign_key.as_deref(),
+                    self.sign_keystore.as_deref(),
+                    self.sign_password_file.as_deref(),
+                )?;
+                self.signer.get_or_init(|| signer)
+            }
+        };
+        Ok(Envelope::signed(bytes, signer)?.to_bytes())
+    }
+
+    /// Recomputes every hash of a loaded tree with `--verify`.
+    fn verify_tree(&self, merkle_tree: &MerkleTree) -> Result<(), MerkleTreeError> {
+        if self.verify {
+            merkle_tree.verify_integrity()?;
+            if self.strict {
+                merkle_tree.check_unambiguous()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks a tree's shape against its leaf list, and that it is unambiguous with
+    /// `--strict`.
+    fn check_structure(&self, merkle_tree: &MerkleTree) -> Result<(), MerkleTreeError> {
+        merkle_tree.verify_structure()?;
+        if self.strict {
+            merkle_tree.check_unambiguous()?;
+        }
+        Ok(())
+    }
+
+    /// Checks that a loaded tree or proof was hashed with the `--hash` algorithm, if given.
+    fn check_hash(&self, recorded: HashAlgorithm) -> Result<(), MerkleTreeError> {
+        match self.hash {
+            Some(hash) if hash != recorded => Err(MerkleTreeError::AlgorithmMismatch {
+                expected: hash,
+                got: recorded,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Turns one input entry into leaf data.
+    fn parse(&self, entry: &str) -> Result<Vec<u8>, MerkleTreeError> {
+        match &self.leaf_encoding {
+            Some(encoding) => {
+                let values: Vec<&str> = entry.split(',').map(str::trim).collect();
+                encoding.encode(&values)
+            }
+            None => self.leaf_format.parse(entry),
+        }
+    }
+
+    /// Turns line `line_number` (0-based) of `input` into leaf data, reporting where the
+    /// entry is if it is malformed.
+    fn parse_line(
+        &self,
+        input: &Path,
+        line_number: usize,
+        line: &str,
+    ) -> Result<Vec<u8>, MerkleTreeError> {
+        self.parse(line).map_err(|e| {
+            let value = line.trim();
+            e.at(input_position(input, line_number, line, value), value)
+        })
+    }
+
+    /// Reads leaves from a file, or stdin when `input` is `-`.
+    fn read(&self, input: &Path) -> Result<Vec<Vec<u8>>, MerkleTreeError> {
+        self.parse_all(input, read_input(input)?)
+    }
+
+    /// Parses the leaves in the contents of `input`.
+    fn parse_all(&self, input: &Path, content: Vec<u8>) -> Result<Vec<Vec<u8>>, MerkleTreeError> {
+        #[cfg(feature = "parquet")]
+        if let InputFormat::Parquet = self.input_format {
+            return self.read_parquet(content);
+        }
+
+        let content = String::from_utf8(content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        match self.input_format {
+            InputFormat::Lines => content
+                .lines()
+                .enumerate()
+                .map(|(line_number, line)| self.parse_line(input, line_number, line))
+                .collect(),
+            InputFormat::Json => {
+                let entries: Vec<serde_json::Value> = serde_json::from_str(&content)?;
+                entries
+                    .iter()
+                    .enumerate()
+                    .map(|(i, entry)| self.parse(self.json_entry(i, entry)?))
+                    .collect()
+            }
+            #[cfg(feature = "parquet")]
+            InputFormat::Parquet => unreachable!("parquet input is read as bytes"),
+        }
+    }
+
+    /// Reads leaves from the `--column` of a Parquet file.
+    ///
+    /// Binary columns are used as leaf data directly; other values are rendered as strings
+    /// and parsed with `--leaf-format`.
+    #[cfg(feature = "parquet")]
+    fn read_parquet(&self, content: Vec<u8>) -> Result<Vec<Vec<u8>>, MerkleTreeError> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::Field;
+
+        let parquet_error = |e: parquet::errors::ParquetError| {
+            MerkleTreeError::FormatError(format!("invalid Parquet input: {}", e))
+        };
+
+        let column = self.column.as_deref().ok_or_else(|| {
+            MerkleTreeError::FormatError("--column is required for Parquet input".to_string())
+        })?;
+        let reader =
+            SerializedFileReader::new(bytes::Bytes::from(content)).map_err(parquet_error)?;
+
+        let mut leaves = Vec::new();
+        for row in reader.get_row_iter(None).map_err(parquet_error)? {
+            let row = row.map_err(parquet_error)?;
+            let (_, field) = row
+                .get_column_iter()
+                .find(|(name, _)| name.as_str() == column)
+                .ok_or_else(|| {
+                    MerkleTreeError::FormatError(format!("no column named '{}'", column))
+                })?;
+            let leaf = match field {
+                Field::Bytes(bytes) => bytes.data().to_vec(),
+                Field::Str(s) => self.parse(s)?,
+                other => self.parse(&other.to_string())?,
+            };
+            leaves.push(leaf);
+        }
+        Ok(leaves)
+    }
+
+    /// Returns the string holding the leaf in a JSON array entry.
+    fn json_entry<'a>(
+        &self,
+        index: usize,
+        entry: &'a serde_json::Value,
+    ) -> Result<&'a str, MerkleTreeError> {
+        let value = match &self.json_field {
+            Some(field) => entry.get(field).ok_or_else(|| {
+                MerkleTreeError::FormatError(format!("entry {} has no field '{}'", index, field))
+            })?,
+            None => entry,
+        };
+        value
+            .as_str()
+            .ok_or_else(|| MerkleTreeError::FormatError(format!("entry {} is not a string", index)))
+    }
+}
+
+/// Reads `address,amount` rows.
+///
+/// Addresses are compared after parsing, so a recipient listed twice in different letter
+/// cases is rejected like any other duplicate.
+fn read_airdrop_csv(input: &Path) -> Result<Vec<(Address, U256)>, MerkleTreeError> {
+    let content = String::from_utf8(read_input(input)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut seen = HashMap::new();
+    let mut recipients = Vec::new();
+    for (line_number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |value: &str, reason: String| {
+            let position = input_position(input, line_number, line, value);
+            MerkleTreeError::FormatError(reason).at(position, value.trim())
+        };
+
+        let (address_field, amount_field) = line
+            .split_once(',')
+            .ok_or_else(|| invalid(line, "expected address,amount".to_string()))?;
+        let address = match Address::from_str(address_field.trim()) {
+            Ok(address) => address,
+            // Skip a header row
+            Err(_) if line_number == 0 => continue,
+            Err(e) => return Err(invalid(address_field, format!("invalid address: {}", e))),
+        };
+        let amount = U256::from_str(amount_field.trim())
+            .map_err(|e| invalid(amount_field, format!("invalid amount: {}", e)))?;
+
+        if let Some(first_line) = seen.insert(address, line_number + 1) {
+            return Err(invalid(
+                address_field,
+                format!(
+                    "duplicate address {}, already listed on line {}",
+                    address.to_checksum(None),
+                    first_line
+                ),
+            ));
+        }
+        recipients.push((address, amount));
+    }
+    Ok(recipients)
+}
+
+fn write_output(
+    output: &Path,
+    json: String,
+    compression: Option<Compression>,
+) -> Result<(), MerkleTreeError> {
+    let mut writer = create_output(output)?;
+    match compression {
+        Some(compression) => writer.write_all(&compress(json.as_bytes(), compression)?)?,
+        None => writer.write_all(json.as_bytes())?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "merkle_tree_cli_{}_{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Runs the command line `args`, returning its exit code.
+    fn run_args(args: &[&str]) -> Result<ExitCode, MerkleTreeError> {
+        let cli = Cli::try_parse_from(std::iter::once("merkle_tree").chain(args.iter().copied()))
+            .unwrap();
+        let reporter = Reporter {
+            format: cli.format,
+            quiet: true,
+        };
+        let mut timings = Timings::new();
+        run(&cli, &reporter, &mut timings)
+    }
+
+    #[test]
+    fn hash_dir_accepts_its_64_byte_leaves() {
+        let dir = TempDir::new("hash_dir");
+        fs::write(dir.0.join("a.txt"), "a").unwrap();
+        fs::create_dir(dir.0.join("sub")).unwrap();
+        fs::write(dir.0.join("sub/b.txt"), "b").unwrap();
+
+        let mut files = Vec::new();
+        collect_files(&dir.0, String::new(), &mut files).unwrap();
+        files.sort();
+        assert_eq!(files, ["a.txt", "sub/b.txt"]);
+
+        let builder = MerkleTree::builder();
+        let (leaf_hashes, entries) =
+            hash_files(&dir.0, &files, &builder, HashFormat::default()).unwrap();
+        assert_eq!(entries.len(), 2);
+        let algorithm = builder.algorithm();
+        let expected =
+            algorithm.hash(&[algorithm.hash(b"sub/b.txt").0, algorithm.hash(b"b").0].concat());
+        assert_eq!(leaf_hashes[1], expected);
+
+        let output = dir.0.join("tree.json");
+        let input = dir.0.to_str().unwrap();
+        let flag_sets: [&[&str]; 3] = [&[], &["--domain-separation"], &["--duplicate-odd"]];
+        for flags in flag_sets {
+            let mut args = vec!["hash-dir", "--input", input];
+            args.extend_from_slice(flags);
+            args.extend(["--output", output.to_str().unwrap()]);
+            run_args(&args).unwrap();
+            let tree = MerkleTree::from_json(&fs::read_to_string(&output).unwrap()).unwrap();
+            assert_eq!(tree.leaf_count(), 2);
+        }
+    }
+
+    #[test]
+    fn hash_file_accepts_64_byte_chunks() {
+        let dir = TempDir::new("hash_file");
+        let path = dir.0.join("data.bin");
+        // Two full 100-byte chunks and a last chunk of exactly 64 bytes
+        let data: Vec<u8> = (0..264u32).map(|i| i as u8).collect();
+        fs::write(&path, &data).unwrap();
+
+        for chunk_size in [64, 100] {
+            let builder = MerkleTree::builder();
+            let hashes = hash_chunks(&path, chunk_size, &builder).unwrap();
+            let expected: Vec<B256> = data
+                .chunks(chunk_size as usize)
+                .map(|chunk| builder.hash_leaf(chunk))
+                .collect();
+            assert_eq!(hashes, expected);
+
+            let output = dir.0.join("tree.json");
+            run_args(&[
+                "hash-file",
+                "--input",
+                path.to_str().unwrap(),
+                "--chunk-size",
+                &chunk_size.to_string(),
+                "--output",
+                output.to_str().unwrap(),
+            ])
+            .unwrap();
+            let tree = MerkleTree::from_json(&fs::read_to_string(&output).unwrap()).unwrap();
+            assert_eq!(tree.leaf_count(), expected.len());
+        }
+    }
+
+    #[test]
+    fn airdrop_normalizes_addresses() {
+        let dir = TempDir::new("airdrop");
+        let input = dir.0.join("recipients.csv");
+        let output = dir.0.join("claims.json");
+        fs::write(
+            &input,
+            "address,amount\n\
+             0xfb6916095ca1df60bb79ce92ce3ea74c37c5d359,100\n\
+             0x52908400098527886E0F7030069857D2E4169EE7,200\n",
+        )
+        .unwrap();
+
+        run_args(&[
+            "airdrop",
+            "--input",
+            input.to_str().unwrap(),
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .unwrap();
+        let claims: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+        let claims = claims["claims"].as_object().unwrap();
+        assert_eq!(claims.len(), 2);
+        assert_eq!(
+            claims["0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359"]["amount"],
+            "100"
+        );
+        assert_eq!(
+            claims["0x52908400098527886E0F7030069857D2E4169EE7"]["amount"],
+            "200"
+        );
+    }
+
+    #[test]
+    fn airdrop_rejects_duplicate_recipients() {
+        let dir = TempDir::new("airdrop_duplicates");
+        let input = dir.0.join("recipients.csv");
+        fs::write(
+            &input,
+            "0xfb6916095ca1df60bb79ce92ce3ea74c37c5d359,100\n\
+             0x52908400098527886E0F7030069857D2E4169EE7,200\n\
+             0xFB6916095CA1DF60BB79CE92CE3EA74C37C5D359,300\n",
+        )
+        .unwrap();
+
+        match read_airdrop_csv(&input) {
+            Err(MerkleTreeError::ParseError {
+                position, source, ..
+            }) => {
+                assert_eq!(position.line, 3);
+                assert!(source.to_string().contains("already listed on line 1"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
 }
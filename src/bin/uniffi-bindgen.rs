@@ -0,0 +1,7 @@
+// src/bin/uniffi-bindgen.rs
+
+//! Generates Swift and Kotlin bindings for the `uniffi` feature.
+
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
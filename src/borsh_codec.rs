@@ -0,0 +1,167 @@
+//! Borsh encoding of proofs and tree snapshots.
+//!
+//! Hashes are written as raw 32-byte arrays and proof steps as a `u8` side tag (`0` left,
+//! `1` right) followed by the sibling hash. A node is its hash followed by `0` for a leaf or
+//! `1` and both children for an internal node; a tree snapshot is its root node followed by
+//! the ordered leaf hashes.
+
+use crate::error::MerkleTreeError;
+use crate::hasher::{HashAlgorithm, OddNodePolicy};
+use crate::merkle_node::{DeserializeLimits, MerkleNode};
+use crate::merkle_tree::MerkleTree;
+use crate::proof::{MerkleProof, ProofStep};
+
+use alloy_primitives::B256;
+use borsh::{BorshDeserialize, BorshSerialize};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+fn write_hash<W: Write>(hash: &B256, writer: &mut W) -> io::Result<()> {
+    hash.0.serialize(writer)
+}
+
+fn read_hash<R: Read>(reader: &mut R) -> io::Result<B256> {
+    <[u8; 32]>::deserialize_reader(reader).map(B256::from)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl BorshSerialize for ProofStep {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let (side, sibling_hash) = match self {
+            ProofStep::Left(sibling_hash) => (0u8, sibling_hash),
+            ProofStep::Right(sibling_hash) => (1u8, sibling_hash),
+        };
+        side.serialize(writer)?;
+        write_hash(sibling_hash, writer)
+    }
+}
+
+impl BorshDeserialize for ProofStep {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let side = u8::deserialize_reader(reader)?;
+        let sibling_hash = read_hash(reader)?;
+        match side {
+            0 => Ok(ProofStep::Left(sibling_hash)),
+            1 => Ok(ProofStep::Right(sibling_hash)),
+            _ => Err(invalid_data(format!("invalid proof step side {}", side))),
+        }
+    }
+}
+
+impl BorshSerialize for MerkleProof {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_hash(&self.leaf_hash, writer)?;
+        self.proof_steps.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for MerkleProof {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        Ok(MerkleProof {
+            leaf_hash: read_hash(reader)?,
+            proof_steps: Vec::<ProofStep>::deserialize_reader(reader)?,
+            hash_algorithm: HashAlgorithm::Keccak256,
+            sorted_pairs: false,
+            domain_separation: false,
+        })
+    }
+}
+
+impl BorshSerialize for MerkleNode {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_hash(&self.hash, writer)?;
+        match (&self.left, &self.right) {
+            (Some(left), Some(right)) => {
+                1u8.serialize(writer)?;
+                left.serialize(writer)?;
+                right.serialize(writer)
+            }
+            _ => 0u8.serialize(writer),
+        }
+    }
+}
+
+/// Reads a node, refusing to nest deeper than `max_depth` levels below it.
+fn read_node<R: Read>(reader: &mut R, max_depth: usize) -> io::Result<MerkleNode> {
+    let hash = read_hash(reader)?;
+    match u8::deserialize_reader(reader)? {
+        0 => Ok(MerkleNode {
+            hash,
+            left: None,
+            right: None,
+        }),
+        1 => {
+            let max_depth = max_depth
+                .checked_sub(1)
+                .ok_or_else(|| invalid_data("tree exceeds the maximum depth".to_string()))?;
+            Ok(MerkleNode {
+                hash,
+                left: Some(Box::new(read_node(reader, max_depth)?)),
+                right: Some(Box::new(read_node(reader, max_depth)?)),
+            })
+        }
+        tag => Err(invalid_data(format!("invalid node tag {}", tag))),
+    }
+}
+
+impl BorshDeserialize for MerkleNode {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        read_node(reader, DeserializeLimits::default().max_depth)
+    }
+}
+
+impl BorshSerialize for MerkleTree {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.root.serialize(writer)?;
+        let leaf_hashes: Vec<[u8; 32]> = self.leaf_hashes.iter().map(|hash| hash.0).collect();
+        leaf_hashes.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for MerkleTree {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let root = MerkleNode::deserialize_reader(reader)?;
+        let leaf_hashes = Vec::<[u8; 32]>::deserialize_reader(reader)?;
+        Ok(MerkleTree {
+            root,
+            leaves: HashMap::new(),
+            leaf_hashes: leaf_hashes.into_iter().map(B256::from).collect(),
+            hash_algorithm: HashAlgorithm::Keccak256,
+            sorted_pairs: false,
+            domain_separation: false,
+            odd_nodes: OddNodePolicy::Promote,
+            limits: DeserializeLimits::default(),
+            allow_64_byte_leaves: false,
+            strict: false,
+        })
+    }
+}
+
+impl MerkleProof {
+    /// Encodes the proof with Borsh.
+    pub fn to_borsh(&self) -> Result<Vec<u8>, MerkleTreeError> {
+        Ok(borsh::to_vec(self)?)
+    }
+
+    /// Decodes a proof from Borsh, rejecting trailing bytes.
+    pub fn from_borsh(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        Ok(borsh::from_slice(bytes)?)
+    }
+}
+
+impl MerkleTree {
+    /// Encodes a snapshot of the tree (nodes and leaf hashes) with Borsh.
+    ///
+    /// Raw leaf data is not included.
+    pub fn to_borsh(&self) -> Result<Vec<u8>, MerkleTreeError> {
+        Ok(borsh::to_vec(self)?)
+    }
+
+    /// Decodes a tree snapshot from Borsh, rejecting trailing bytes.
+    pub fn from_borsh(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        Ok(borsh::from_slice(bytes)?)
+    }
+}
@@ -0,0 +1,415 @@
+use crate::error::MerkleTreeError;
+use crate::hasher::{HashAlgorithm, OddNodePolicy, TreeHasher};
+use crate::heap_tree::HeapMerkleTree;
+use crate::merkle_node::DeserializeLimits;
+use crate::merkle_tree::MerkleTree;
+
+use alloy_primitives::B256;
+use std::fmt;
+use std::sync::Arc;
+
+/// A step reached while building a tree, passed to the callback set with
+/// [`MerkleTreeBuilder::on_progress`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BuildProgress {
+    /// `hashed` of the `total` leaves have been hashed.
+    LeavesHashed { hashed: usize, total: usize },
+    /// Level `level` of `depth` has been built, counting up from the leaves at level 0.
+    LevelBuilt { level: usize, depth: usize },
+}
+
+/// Callback receiving [`BuildProgress`] updates.
+#[derive(Clone)]
+struct ProgressCallback(Arc<dyn Fn(BuildProgress) + Send + Sync>);
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// Configures how a [`MerkleTree`] is built.
+///
+/// # Leaves that hash like internal nodes
+///
+/// By default leaves and internal nodes are hashed alike, so a leaf whose data is two child
+/// hashes side by side hashes like their parent. When leaf data may come from an attacker,
+/// build with one of these safe configurations:
+///
+/// - [`domain_separation`](MerkleTreeBuilder::domain_separation), which prefixes leaves and
+///   nodes differently before hashing, as RFC 6962 does;
+/// - leaves hashed once more before building, as OpenZeppelin's standard tree does, or a
+///   fixed-width leaf encoding other than 64 bytes, keeping 64-byte leaves rejected.
+///
+/// Without domain separation, 64-byte leaves are rejected unless
+/// [`allow_64_byte_leaves`](MerkleTreeBuilder::allow_64_byte_leaves) is set.
+#[derive(Clone, Debug)]
+pub struct MerkleTreeBuilder {
+    retain_leaf_data: bool,
+    sort_leaves: bool,
+    dedupe: bool,
+    hash_algorithm: HashAlgorithm,
+    sorted_pairs: bool,
+    odd_nodes: OddNodePolicy,
+    domain_separation: bool,
+    strict: bool,
+    allow_64_byte_leaves: bool,
+    max_depth: usize,
+    max_leaves: usize,
+    max_leaf_size: usize,
+    progress: Option<ProgressCallback>,
+}
+
+impl Default for MerkleTreeBuilder {
+    fn default() -> Self {
+        MerkleTreeBuilder {
+            retain_leaf_data: true,
+            sort_leaves: false,
+            dedupe: false,
+            hash_algorithm: HashAlgorithm::Keccak256,
+            sorted_pairs: false,
+            odd_nodes: OddNodePolicy::Promote,
+            domain_separation: false,
+            strict: false,
+            allow_64_byte_leaves: false,
+            max_depth: usize::MAX,
+            max_leaves: usize::MAX,
+            max_leaf_size: usize::MAX,
+            progress: None,
+        }
+    }
+}
+
+impl MerkleTreeBuilder {
+    /// Creates a builder with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the raw leaf data is kept in the tree's `leaves` map.
+    ///
+    /// Defaults to `true`. Disabling it halves memory use for large leaves; proofs can
+    /// still be generated by index or by data.
+    pub fn retain_leaf_data(mut self, retain: bool) -> Self {
+        self.retain_leaf_data = retain;
+        self
+    }
+
+    /// Returns whether raw leaf data will be kept.
+    pub fn retains_leaf_data(&self) -> bool {
+        self.retain_leaf_data
+    }
+
+    /// Sets whether leaves are sorted by hash before the tree is built.
+    ///
+    /// Defaults to `false`. Sorting makes the root independent of input order, matching
+    /// merkletreejs's `sortLeaves` option.
+    pub fn sort_leaves(mut self, sort: bool) -> Self {
+        self.sort_leaves = sort;
+        self
+    }
+
+    /// Returns whether leaves will be sorted by hash.
+    pub fn sorts_leaves(&self) -> bool {
+        self.sort_leaves
+    }
+
+    /// Sets whether leaves with the same hash are kept only once.
+    ///
+    /// Defaults to `false`. The first occurrence of each leaf is kept.
+    pub fn dedupe(mut self, dedupe: bool) -> Self {
+        self.dedupe = dedupe;
+        self
+    }
+
+    /// Returns whether duplicate leaves will be removed.
+    pub fn dedupes(&self) -> bool {
+        self.dedupe
+    }
+
+    /// Sets the hash function used for leaves and internal nodes.
+    ///
+    /// Defaults to keccak256. The choice is stored in the tree and in its proofs.
+    pub fn hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = hash_algorithm;
+        self
+    }
+
+    /// Returns the hash function the tree will be built with.
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// Sets whether each pair of child hashes is sorted before hashing.
+    ///
+    /// Defaults to `false`. Sorted pairs let proofs omit sibling positions, matching
+    /// OpenZeppelin's `MerkleProof` library.
+    pub fn sorted_pairs(mut self, sorted: bool) -> Self {
+        self.sorted_pairs = sorted;
+        self
+    }
+
+    /// Returns whether pairs will be sorted before hashing.
+    pub fn sorts_pairs(&self) -> bool {
+        self.sorted_pairs
+    }
+
+    /// Sets what happens to the last node of a level with an odd number of nodes.
+    ///
+    /// Defaults to [`OddNodePolicy::Promote`]. Use [`OddNodePolicy::Duplicate`] to match
+    /// Bitcoin's transaction Merkle trees.
+    pub fn odd_nodes(mut self, policy: OddNodePolicy) -> Self {
+        self.odd_nodes = policy;
+        self
+    }
+
+    /// Returns the policy for odd nodes.
+    pub fn odd_node_policy(&self) -> OddNodePolicy {
+        self.odd_nodes
+    }
+
+    /// Sets whether leaves and internal nodes are prefixed before hashing.
+    ///
+    /// Defaults to `false`. Enabled, leaf data is hashed after a [`LEAF_PREFIX`] byte and
+    /// pairs of child hashes after a [`NODE_PREFIX`] byte, as in RFC 6962, so no leaf can
+    /// hash like an internal node and 64-byte leaves are accepted. The choice is stored in
+    /// the tree and in its proofs.
+    ///
+    /// [`LEAF_PREFIX`]: crate::hasher::LEAF_PREFIX
+    /// [`NODE_PREFIX`]: crate::hasher::NODE_PREFIX
+    pub fn domain_separation(mut self, domain_separation: bool) -> Self {
+        self.domain_separation = domain_separation;
+        self
+    }
+
+    /// Returns whether leaves and nodes will be prefixed before hashing.
+    pub fn separates_domains(&self) -> bool {
+        self.domain_separation
+    }
+
+    /// Rejects trees with an ambiguous shape, whose root another leaf list also produces.
+    ///
+    /// Only trees built with [`OddNodePolicy::Duplicate`] can be ambiguous; see
+    /// [`MerkleTree::check_unambiguous`]. Built trees keep this setting, so appending,
+    /// updating or removing leaves fails, leaving the tree unchanged, if it would make the
+    /// tree ambiguous.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Returns whether ambiguous trees are rejected.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Sets whether leaves of exactly 64 bytes are accepted without domain separation.
+    ///
+    /// Defaults to `false`. Without [`domain_separation`](Self::domain_separation), leaves
+    /// and internal nodes are hashed alike, so a 64-byte leaf equal to two child hashes side
+    /// by side has the hash of their parent, and a proof of that "leaf" from the parent's
+    /// position verifies against the root. Only allow them when leaf data cannot be chosen
+    /// by an attacker; otherwise enable domain separation or use one of the other
+    /// [safe configurations](MerkleTreeBuilder#leaves-that-hash-like-internal-nodes).
+    ///
+    /// Trees built with domain separation accept 64-byte leaves whatever this says. Built
+    /// trees keep this setting, so [`MerkleTree::append`] and [`MerkleTree::update`] check
+    /// it too.
+    pub fn allow_64_byte_leaves(mut self, allow: bool) -> Self {
+        self.allow_64_byte_leaves = allow;
+        self
+    }
+
+    /// Returns whether leaves of exactly 64 bytes are accepted.
+    pub fn allows_64_byte_leaves(&self) -> bool {
+        self.allow_64_byte_leaves
+    }
+
+    /// Rejects trees with more than `max_depth` levels below the root. Unlimited by default.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Rejects more than `max_leaves` leaves. Unlimited by default.
+    ///
+    /// Checked before any leaf is hashed, or as each leaf arrives when reading streams.
+    pub fn max_leaves(mut self, max_leaves: usize) -> Self {
+        self.max_leaves = max_leaves;
+        self
+    }
+
+    /// Rejects leaves longer than `max_leaf_size` bytes. Unlimited by default.
+    pub fn max_leaf_size(mut self, max_leaf_size: usize) -> Self {
+        self.max_leaf_size = max_leaf_size;
+        self
+    }
+
+    /// Returns the limits on depth, leaf count and leaf size, as [`DeserializeLimits`]
+    /// applying the same bounds to loaded trees. The node count is not limited.
+    ///
+    /// Built trees keep these limits, so appending to them is bounded too.
+    pub fn limits(&self) -> DeserializeLimits {
+        DeserializeLimits {
+            max_depth: self.max_depth,
+            max_nodes: usize::MAX,
+            max_leaves: self.max_leaves,
+            max_leaf_size: self.max_leaf_size,
+        }
+    }
+
+    /// Sets a callback notified as leaves are hashed and levels are built.
+    ///
+    /// Leaves are reported in batches, so the callback may do I/O such as redrawing a
+    /// progress bar without slowing the build down.
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(BuildProgress) + Send + Sync + 'static,
+    {
+        self.progress = Some(ProgressCallback(Arc::new(callback)));
+        self
+    }
+
+    /// Passes `progress` to the callback, if one is set.
+    pub(crate) fn report(&self, progress: BuildProgress) {
+        if let Some(ProgressCallback(callback)) = &self.progress {
+            callback(progress);
+        }
+    }
+
+    /// Returns how the configured tree hashes leaves and joins nodes into their parents.
+    pub(crate) fn tree_hasher(&self) -> TreeHasher {
+        TreeHasher {
+            hash_algorithm: self.hash_algorithm,
+            domain_separation: self.domain_separation,
+            sorted_pairs: self.sorted_pairs,
+            odd_nodes: self.odd_nodes,
+        }
+    }
+
+    /// Hashes raw leaf data as the configured tree hashes its leaves, for building from
+    /// leaf hashes computed separately.
+    pub fn hash_leaf(&self, data: &[u8]) -> B256 {
+        self.tree_hasher().hash_leaf(data)
+    }
+
+    /// Builds a Merkle Tree from a list of data items.
+    pub fn build(&self, data: &[Vec<u8>]) -> Result<MerkleTree, MerkleTreeError> {
+        self.check_leaf_count(data.len())?;
+        for (index, leaf) in data.iter().enumerate() {
+            self.check_leaf(index, leaf)?;
+        }
+        self.check(MerkleTree::build_with(self, data)?)
+    }
+
+    /// Builds a Merkle Tree from already hashed leaves, in order.
+    ///
+    /// Leaf options such as sorting and deduplication are not applied.
+    pub fn build_from_leaf_hashes(
+        &self,
+        leaf_hashes: Vec<B256>,
+    ) -> Result<MerkleTree, MerkleTreeError> {
+        self.check_leaf_count(leaf_hashes.len())?;
+        self.check(MerkleTree::from_leaf_hashes_with(self, leaf_hashes)?)
+    }
+
+    /// Builds a [`HeapMerkleTree`] from a list of data items, hashed and joined as the
+    /// configured tree would.
+    ///
+    /// Leaf options such as sorting and deduplication are not applied.
+    pub fn build_heap(&self, data: &[Vec<u8>]) -> Result<HeapMerkleTree, MerkleTreeError> {
+        self.check_leaf_count(data.len())?;
+        for (index, leaf) in data.iter().enumerate() {
+            self.check_leaf(index, leaf)?;
+        }
+        let hasher = self.tree_hasher();
+        let leaf_hashes = data.iter().map(|leaf| hasher.hash_leaf(leaf)).collect();
+        HeapMerkleTree::from_leaf_hashes_with(leaf_hashes, hasher)
+    }
+
+    /// Rejects `leaf_count` leaves if they exceed the leaf count or depth limit.
+    pub(crate) fn check_leaf_count(&self, leaf_count: usize) -> Result<(), MerkleTreeError> {
+        self.limits().check_leaf_count(leaf_count)
+    }
+
+    /// Rejects the leaf at `index` if it exceeds the size or leaf count limit, or if it is
+    /// 64 bytes long in a tree without domain separation and such leaves are not allowed.
+    ///
+    /// [`build`](Self::build) checks every leaf; call this when hashing leaves with
+    /// [`hash_leaf`](Self::hash_leaf) to build from their hashes.
+    pub fn check_leaf(&self, index: usize, leaf: &[u8]) -> Result<(), MerkleTreeError> {
+        if index >= self.max_leaves {
+            return Err(MerkleTreeError::TooManyLeaves {
+                count: index + 1,
+                max: self.max_leaves,
+            });
+        }
+        self.limits().check_leaf_size(index, leaf)?;
+        check_leaf_length(
+            index,
+            leaf,
+            self.domain_separation || self.allow_64_byte_leaves,
+        )
+    }
+
+    /// Rejects `tree` if the builder is strict and the tree is ambiguous.
+    fn check(&self, tree: MerkleTree) -> Result<MerkleTree, MerkleTreeError> {
+        if self.strict {
+            tree.check_unambiguous()?;
+        }
+        Ok(tree)
+    }
+}
+
+/// Rejects the leaf at `index` if it is 64 bytes long, like a pair of child hashes, unless
+/// `allow_64_bytes` is set because the tree separates domains or accepts such leaves.
+pub(crate) fn check_leaf_length(
+    index: usize,
+    leaf: &[u8],
+    allow_64_bytes: bool,
+) -> Result<(), MerkleTreeError> {
+    if leaf.len() == 64 && !allow_64_bytes {
+        return Err(MerkleTreeError::AmbiguousLeaf { index });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A leaf as long as a pair of child hashes.
+    const PAIR_LEAF: [u8; 64] = [7; 64];
+
+    fn data() -> Vec<Vec<u8>> {
+        vec![b"a".to_vec(), PAIR_LEAF.to_vec(), b"c".to_vec()]
+    }
+
+    #[test]
+    fn rejects_64_byte_leaves() {
+        let result = MerkleTreeBuilder::new().build(&data());
+        assert!(matches!(
+            result,
+            Err(MerkleTreeError::AmbiguousLeaf { index: 1 })
+        ));
+
+        for len in [63, 65] {
+            MerkleTreeBuilder::new()
+                .build(&[b"a".to_vec(), vec![7; len]])
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn accepts_64_byte_leaves_when_allowed() {
+        let builders = [
+            MerkleTreeBuilder::new().domain_separation(true),
+            MerkleTreeBuilder::new().allow_64_byte_leaves(true),
+        ];
+        for builder in builders {
+            let tree = builder.build(&data()).unwrap();
+            let proof = tree.generate_proof(&PAIR_LEAF).unwrap();
+            assert!(proof.verify(tree.root_hash()).unwrap());
+        }
+    }
+}
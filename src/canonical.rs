@@ -0,0 +1,95 @@
+//! Canonical JSON serialization.
+//!
+//! Canonical output uses lowercase `0x`-prefixed hex, emits object keys in sorted order,
+//! and contains no whitespace, so equal trees and proofs serialize to identical bytes that
+//! can be hashed, signed, or diffed. Keys are inserted already sorted, so the output does not
+//! depend on whether serde_json's `preserve_order` feature is enabled. The regular
+//! deserializers accept canonical output.
+
+use crate::error::MerkleTreeError;
+use crate::merkle_node::MerkleNode;
+use crate::merkle_tree::MerkleTree;
+use crate::proof::{MerkleProof, ProofStep};
+use crate::schema::SCHEMA_VERSION;
+
+use alloy_primitives::hex::encode;
+use alloy_primitives::B256;
+use serde_json::{Map, Value};
+
+fn hex_value(hash: &B256) -> Value {
+    Value::String(format!("0x{}", encode(hash)))
+}
+
+fn node_value(node: &MerkleNode) -> Value {
+    let child_value = |child: &Option<Box<MerkleNode>>| match child {
+        Some(child) => node_value(child),
+        None => Value::Null,
+    };
+
+    let mut map = Map::new();
+    map.insert("hash".to_string(), hex_value(&node.hash));
+    map.insert("left".to_string(), child_value(&node.left));
+    map.insert("right".to_string(), child_value(&node.right));
+    Value::Object(map)
+}
+
+fn step_value(step: &ProofStep) -> Value {
+    let (side, sibling_hash) = match step {
+        ProofStep::Left(sibling_hash) => ("Left", sibling_hash),
+        ProofStep::Right(sibling_hash) => ("Right", sibling_hash),
+    };
+
+    let mut map = Map::new();
+    map.insert(side.to_string(), hex_value(sibling_hash));
+    Value::Object(map)
+}
+
+impl MerkleTree {
+    /// Serializes the Merkle Tree to canonical JSON.
+    pub fn to_canonical_json(&self) -> Result<String, MerkleTreeError> {
+        let mut map = Map::new();
+        map.insert(
+            "domain_separation".to_string(),
+            Value::from(self.domain_separation),
+        );
+        map.insert(
+            "hash_algorithm".to_string(),
+            Value::from(self.hash_algorithm.name()),
+        );
+        map.insert(
+            "leaf_hashes".to_string(),
+            Value::Array(self.leaf_hashes.iter().map(hex_value).collect()),
+        );
+        map.insert(
+            "odd_nodes".to_string(),
+            Value::from(self.odd_nodes.to_string()),
+        );
+        map.insert("root".to_string(), node_value(&self.root));
+        map.insert("schema_version".to_string(), Value::from(SCHEMA_VERSION));
+        map.insert("sorted_pairs".to_string(), Value::from(self.sorted_pairs));
+        serde_json::to_string(&Value::Object(map)).map_err(MerkleTreeError::SerdeError)
+    }
+}
+
+impl MerkleProof {
+    /// Serializes the proof to canonical JSON.
+    pub fn to_canonical_json(&self) -> Result<String, MerkleTreeError> {
+        let mut map = Map::new();
+        map.insert(
+            "domain_separation".to_string(),
+            Value::from(self.domain_separation),
+        );
+        map.insert(
+            "hash_algorithm".to_string(),
+            Value::from(self.hash_algorithm.name()),
+        );
+        map.insert("leaf_hash".to_string(), hex_value(&self.leaf_hash));
+        map.insert(
+            "proof_steps".to_string(),
+            Value::Array(self.proof_steps.iter().map(step_value).collect()),
+        );
+        map.insert("schema_version".to_string(), Value::from(SCHEMA_VERSION));
+        map.insert("sorted_pairs".to_string(), Value::from(self.sorted_pairs));
+        serde_json::to_string(&Value::Object(map)).map_err(MerkleTreeError::SerdeError)
+    }
+}
@@ -0,0 +1,160 @@
+//! Trees over the contents of Ethereum blocks, fetched over JSON-RPC with an alloy
+//! [`Provider`].
+//!
+//! Leaves are the data each item is committed to on chain, so the tree's leaf hashes are
+//! the familiar ones: transaction hashes hashed once more with keccak256 (as the CLI's
+//! `fetch-block` does), and the keccak256 of each receipt's EIP-2718 encoding and each
+//! withdrawal's RLP encoding.
+
+use crate::error::MerkleTreeError;
+use crate::merkle_tree::MerkleTree;
+
+use alloy_primitives::B256;
+use alloy_provider::Provider;
+use alloy_rlp::{Encodable, Header};
+use alloy_rpc_types_eth::{Block, BlockId, BlockTransactionsKind, TransactionReceipt};
+use alloy_transport::Transport;
+
+/// Builds a tree over the transaction hashes of a block, in block order.
+///
+/// Uses the default tree options; for others, pass [`block_transactions`] to a
+/// [`MerkleTreeBuilder`](crate::MerkleTreeBuilder).
+pub async fn build_from_block<P, T>(
+    provider: &P,
+    block_id: BlockId,
+) -> Result<MerkleTree, MerkleTreeError>
+where
+    P: Provider<T>,
+    T: Transport + Clone,
+{
+    let hashes = block_transactions(provider, block_id).await?;
+    let leaves: Vec<Vec<u8>> = hashes.iter().map(|hash| hash.to_vec()).collect();
+    MerkleTree::new(&leaves)
+}
+
+/// Fetches the transaction hashes of a block, in block order.
+pub async fn block_transactions<P, T>(
+    provider: &P,
+    block_id: BlockId,
+) -> Result<Vec<B256>, MerkleTreeError>
+where
+    P: Provider<T>,
+    T: Transport + Clone,
+{
+    let block = fetch_block(provider, block_id).await?;
+    Ok(block
+        .transactions
+        .hashes()
+        .map(|hash| B256::from_slice(hash.as_slice()))
+        .collect())
+}
+
+/// Fetches the receipts of a block, in block order, as their EIP-2718 encodings.
+pub async fn block_receipts<P, T>(
+    provider: &P,
+    block_id: BlockId,
+) -> Result<Vec<Vec<u8>>, MerkleTreeError>
+where
+    P: Provider<T>,
+    T: Transport + Clone,
+{
+    provider
+        .get_block_receipts(block_id)
+        .await
+        .map_err(|e| MerkleTreeError::RpcError(e.to_string()))?
+        .ok_or_else(|| MerkleTreeError::RpcError(format!("block {} not found", block_id)))?
+        .iter()
+        .map(encode_receipt)
+        .collect()
+}
+
+/// Fetches the withdrawals of a block, in block order, as their RLP encodings. Blocks
+/// before Shanghai have none.
+pub async fn block_withdrawals<P, T>(
+    provider: &P,
+    block_id: BlockId,
+) -> Result<Vec<Vec<u8>>, MerkleTreeError>
+where
+    P: Provider<T>,
+    T: Transport + Clone,
+{
+    let block = fetch_block(provider, block_id).await?;
+    Ok(match &block.withdrawals {
+        Some(withdrawals) => withdrawals.iter().map(alloy_rlp::encode).collect(),
+        None => Vec::new(),
+    })
+}
+
+/// Builds a tree over the receipts of a block.
+pub async fn build_from_block_receipts<P, T>(
+    provider: &P,
+    block_id: BlockId,
+) -> Result<MerkleTree, MerkleTreeError>
+where
+    P: Provider<T>,
+    T: Transport + Clone,
+{
+    MerkleTree::new(&block_receipts(provider, block_id).await?)
+}
+
+/// Builds a tree over the withdrawals of a block.
+pub async fn build_from_block_withdrawals<P, T>(
+    provider: &P,
+    block_id: BlockId,
+) -> Result<MerkleTree, MerkleTreeError>
+where
+    P: Provider<T>,
+    T: Transport + Clone,
+{
+    MerkleTree::new(&block_withdrawals(provider, block_id).await?)
+}
+
+/// Fetches a block with its transaction hashes.
+async fn fetch_block<P, T>(provider: &P, block_id: BlockId) -> Result<Block, MerkleTreeError>
+where
+    P: Provider<T>,
+    T: Transport + Clone,
+{
+    provider
+        .get_block(block_id, BlockTransactionsKind::Hashes)
+        .await
+        .map_err(|e| MerkleTreeError::RpcError(e.to_string()))?
+        .ok_or_else(|| MerkleTreeError::RpcError(format!("block {} not found", block_id)))
+}
+
+/// Encodes a receipt as in the block's receipts trie: the transaction type byte, unless
+/// legacy, followed by the RLP list of status, cumulative gas used, logs bloom and logs.
+pub(crate) fn encode_receipt(receipt: &TransactionReceipt) -> Result<Vec<u8>, MerkleTreeError> {
+    let envelope = &receipt.inner;
+    let with_bloom = envelope.as_receipt_with_bloom().ok_or_else(|| {
+        MerkleTreeError::RpcError(format!(
+            "unsupported receipt type {}",
+            envelope.tx_type() as u8
+        ))
+    })?;
+    let logs: Vec<_> = with_bloom
+        .receipt
+        .logs
+        .iter()
+        .map(|log| &log.inner)
+        .collect();
+
+    let header = Header {
+        list: true,
+        payload_length: with_bloom.receipt.status.length()
+            + with_bloom.receipt.cumulative_gas_used.length()
+            + with_bloom.logs_bloom.length()
+            + logs.length(),
+    };
+    let mut encoded = Vec::with_capacity(1 + header.length() + header.payload_length);
+    let tx_type = envelope.tx_type() as u8;
+    if tx_type != 0 {
+        encoded.push(tx_type);
+    }
+    header.encode(&mut encoded);
+    with_bloom.receipt.status.encode(&mut encoded);
+    with_bloom.receipt.cumulative_gas_used.encode(&mut encoded);
+    with_bloom.logs_bloom.encode(&mut encoded);
+    logs.encode(&mut encoded);
+    Ok(encoded)
+}
@@ -0,0 +1,139 @@
+//! Transparent compression for serialized trees and proofs.
+//!
+//! Compressed payloads are recognized by their magic bytes, so readers accept zstd, gzip,
+//! and uncompressed input alike. Compressing and decompressing need the `compression`
+//! feature; without it, compressed input is rejected.
+
+use crate::error::MerkleTreeError;
+use crate::merkle_tree::MerkleTree;
+use crate::proof::MerkleProof;
+use crate::schema;
+
+#[cfg(feature = "compression")]
+use flate2::read::GzDecoder;
+#[cfg(feature = "compression")]
+use flate2::write::GzEncoder;
+use std::fmt;
+#[cfg(feature = "compression")]
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Compression level passed to zstd.
+#[cfg(feature = "compression")]
+const ZSTD_LEVEL: i32 = 3;
+
+/// A supported compression algorithm.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Compression {
+    Zstd,
+    Gzip,
+}
+
+impl Compression {
+    /// Detects the compression used for `bytes` from its magic bytes.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&ZSTD_MAGIC) {
+            Some(Compression::Zstd)
+        } else if bytes.starts_with(&GZIP_MAGIC) {
+            Some(Compression::Gzip)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::Zstd => write!(f, "zstd"),
+            Compression::Gzip => write!(f, "gzip"),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            other => Err(format!(
+                "unknown compression '{}', expected zstd or gzip",
+                other
+            )),
+        }
+    }
+}
+
+/// The error for a compression used without the `compression` feature.
+#[cfg(not(feature = "compression"))]
+fn disabled(compression: Compression) -> MerkleTreeError {
+    MerkleTreeError::FormatError(format!(
+        "{} requires the `compression` feature",
+        compression
+    ))
+}
+
+/// Compresses `data` with the given algorithm.
+#[cfg(not(feature = "compression"))]
+pub fn compress(_data: &[u8], compression: Compression) -> Result<Vec<u8>, MerkleTreeError> {
+    Err(disabled(compression))
+}
+
+/// Compresses `data` with the given algorithm.
+#[cfg(feature = "compression")]
+pub fn compress(data: &[u8], compression: Compression) -> Result<Vec<u8>, MerkleTreeError> {
+    match compression {
+        Compression::Zstd => Ok(zstd::encode_all(data, ZSTD_LEVEL)?),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+    }
+}
+
+/// Decompresses `bytes`, returning them unchanged if they are not compressed.
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>, MerkleTreeError> {
+    match Compression::detect(bytes) {
+        #[cfg(feature = "compression")]
+        Some(Compression::Zstd) => Ok(zstd::decode_all(bytes)?),
+        #[cfg(feature = "compression")]
+        Some(Compression::Gzip) => {
+            let mut data = Vec::new();
+            GzDecoder::new(bytes).read_to_end(&mut data)?;
+            Ok(data)
+        }
+        #[cfg(not(feature = "compression"))]
+        Some(compression) => Err(disabled(compression)),
+        None => Ok(bytes.to_vec()),
+    }
+}
+
+impl MerkleTree {
+    /// Serializes the Merkle Tree to compressed JSON.
+    pub fn to_json_compressed(&self, compression: Compression) -> Result<Vec<u8>, MerkleTreeError> {
+        compress(self.to_json()?.as_bytes(), compression)
+    }
+
+    /// Deserializes the Merkle Tree from JSON that may be zstd- or gzip-compressed.
+    pub fn from_json_compressed(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        Self::from_json_value(serde_json::from_slice(&decompress(bytes)?)?)
+    }
+}
+
+impl MerkleProof {
+    /// Serializes the proof to compressed JSON.
+    pub fn to_json_compressed(&self, compression: Compression) -> Result<Vec<u8>, MerkleTreeError> {
+        compress(self.to_json()?.as_bytes(), compression)
+    }
+
+    /// Deserializes a proof from JSON that may be zstd- or gzip-compressed.
+    pub fn from_json_compressed(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        schema::from_slice(&decompress(bytes)?)
+    }
+}
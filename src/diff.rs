@@ -0,0 +1,73 @@
+//! Comparing the leaves of two trees.
+
+use crate::merkle_tree::MerkleTree;
+
+use alloy_primitives::B256;
+use std::collections::HashMap;
+
+/// The differences between the leaves of two trees.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct TreeDiff {
+    /// Whether both trees have the same root.
+    pub roots_match: bool,
+
+    /// Indices, within both trees, whose leaf hashes differ.
+    pub changed: Vec<usize>,
+
+    /// Leaf hashes in the second tree that are not in the first, in second-tree order.
+    pub added: Vec<B256>,
+
+    /// Leaf hashes in the first tree that are not in the second, in first-tree order.
+    pub removed: Vec<B256>,
+}
+
+impl TreeDiff {
+    /// Returns `true` if the trees have identical leaves.
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl MerkleTree {
+    /// Compares this tree's leaves with `other`'s.
+    ///
+    /// `changed` compares leaves position by position, which shows where two leaf lists
+    /// diverge. `added` and `removed` compare them as multisets, so reordered leaves
+    /// appear in `changed` only.
+    pub fn diff(&self, other: &MerkleTree) -> TreeDiff {
+        let changed = self
+            .leaf_hashes
+            .iter()
+            .zip(&other.leaf_hashes)
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(index, _)| index)
+            .collect();
+
+        TreeDiff {
+            roots_match: self.root_hash() == other.root_hash(),
+            changed,
+            added: multiset_difference(&other.leaf_hashes, &self.leaf_hashes),
+            removed: multiset_difference(&self.leaf_hashes, &other.leaf_hashes),
+        }
+    }
+}
+
+/// Returns the hashes of `a` left over after removing one occurrence per hash in `b`.
+fn multiset_difference(a: &[B256], b: &[B256]) -> Vec<B256> {
+    let mut counts: HashMap<&B256, usize> = HashMap::new();
+    for hash in b {
+        *counts.entry(hash).or_default() += 1;
+    }
+
+    a.iter()
+        .filter(|hash| match counts.get_mut(hash) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .copied()
+        .collect()
+}
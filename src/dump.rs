@@ -0,0 +1,321 @@
+//! Tree and proof dumps in any supported encoding.
+//!
+//! Encodings are told apart by their first bytes, after any [`crate::envelope`] is checked
+//! and any zstd or gzip compression is removed, so loaders accept whatever a writer chose:
+//!
+//! - compact trees start with the `MRKL` magic bytes (see [`crate::file_format`]);
+//! - bincode dumps start with the [`BINCODE_MAGIC`] bytes `MRKB`, followed by the bincode
+//!   payload, since bincode itself has no recognizable header;
+//! - JSON starts with `{`, after optional whitespace;
+//! - CBOR starts with a map header, `0xa0` to `0xbf`.
+//!
+//! Anything else is rejected.
+
+use crate::compression::decompress;
+use crate::envelope::{self, EnvelopePolicy};
+use crate::error::MerkleTreeError;
+use crate::file_format::MAGIC;
+use crate::merkle_node::DeserializeLimits;
+use crate::merkle_tree::MerkleTree;
+use crate::proof::MerkleProof;
+use crate::schema;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Magic bytes starting a bincode dump.
+pub const BINCODE_MAGIC: [u8; 4] = *b"MRKB";
+
+/// An encoding for tree and proof dumps.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DumpFormat {
+    /// Versioned JSON, as written by [`MerkleTree::to_json`].
+    #[default]
+    Json,
+    /// The compact level-by-level format of [`crate::file_format`]. Trees only.
+    Compact,
+    /// bincode after the [`BINCODE_MAGIC`] bytes, with the `bincode` feature.
+    Bincode,
+    /// CBOR, with the `cbor` feature.
+    Cbor,
+}
+
+impl DumpFormat {
+    /// Detects the encoding of uncompressed `bytes` from its first bytes, or returns `None`
+    /// if they start like none of them.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&MAGIC) {
+            return Some(DumpFormat::Compact);
+        }
+        if bytes.starts_with(&BINCODE_MAGIC) {
+            return Some(DumpFormat::Bincode);
+        }
+        match bytes.iter().find(|byte| !byte.is_ascii_whitespace()) {
+            Some(b'{') => Some(DumpFormat::Json),
+            Some(0xa0..=0xbf) => Some(DumpFormat::Cbor),
+            _ => None,
+        }
+    }
+}
+
+/// Detects the encoding of uncompressed `bytes`, failing if it is not recognized.
+fn detect(bytes: &[u8]) -> Result<DumpFormat, MerkleTreeError> {
+    DumpFormat::detect(bytes)
+        .ok_or_else(|| MerkleTreeError::FormatError("unrecognized dump format".to_string()))
+}
+
+/// Prefixes a bincode payload with [`BINCODE_MAGIC`].
+#[cfg(feature = "bincode")]
+fn with_bincode_magic(payload: Vec<u8>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(BINCODE_MAGIC.len() + payload.len());
+    bytes.extend_from_slice(&BINCODE_MAGIC);
+    bytes.extend_from_slice(&payload);
+    bytes
+}
+
+impl fmt::Display for DumpFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DumpFormat::Json => write!(f, "json"),
+            DumpFormat::Compact => write!(f, "compact"),
+            DumpFormat::Bincode => write!(f, "bincode"),
+            DumpFormat::Cbor => write!(f, "cbor"),
+        }
+    }
+}
+
+impl FromStr for DumpFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(DumpFormat::Json),
+            "compact" => Ok(DumpFormat::Compact),
+            "bincode" => Ok(DumpFormat::Bincode),
+            "cbor" => Ok(DumpFormat::Cbor),
+            other => Err(format!(
+                "unknown dump format '{}', expected json, compact, bincode or cbor",
+                other
+            )),
+        }
+    }
+}
+
+/// The error for an encoding whose feature is not enabled.
+fn disabled(format: DumpFormat) -> MerkleTreeError {
+    MerkleTreeError::FormatError(format!("{} dumps require the `{}` feature", format, format))
+}
+
+impl MerkleTree {
+    /// Serializes the tree in the given encoding.
+    pub fn to_dump(&self, format: DumpFormat) -> Result<Vec<u8>, MerkleTreeError> {
+        match format {
+            DumpFormat::Json => Ok(self.to_json()?.into_bytes()),
+            DumpFormat::Compact => {
+                let mut bytes = Vec::new();
+                self.write_compact(&mut bytes)?;
+                Ok(bytes)
+            }
+            #[cfg(feature = "bincode")]
+            DumpFormat::Bincode => Ok(with_bincode_magic(self.to_bytes()?)),
+            #[cfg(feature = "cbor")]
+            DumpFormat::Cbor => self.to_cbor(),
+            #[allow(unreachable_patterns)]
+            format => Err(disabled(format)),
+        }
+    }
+
+    /// Deserializes a tree in any supported encoding, optionally zstd- or gzip-compressed
+    /// and enveloped.
+    pub fn from_dump(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        Self::from_dump_with(bytes, &EnvelopePolicy::default())
+    }
+
+    /// Deserializes a tree like [`MerkleTree::from_dump`], rejecting input that is not
+    /// sealed as `policy` requires.
+    pub fn from_dump_with(bytes: &[u8], policy: &EnvelopePolicy) -> Result<Self, MerkleTreeError> {
+        let bytes = decompress(&envelope::open_with(bytes, policy)?.payload)?;
+        match detect(&bytes)? {
+            DumpFormat::Json => Self::from_json_slice(&bytes, DeserializeLimits::default()),
+            DumpFormat::Compact => Self::read_compact(&mut bytes.as_slice()),
+            #[cfg(feature = "bincode")]
+            DumpFormat::Bincode => Self::from_bytes(&bytes[BINCODE_MAGIC.len()..]),
+            #[cfg(feature = "cbor")]
+            DumpFormat::Cbor => Self::from_cbor(&bytes),
+            #[allow(unreachable_patterns)]
+            format => Err(disabled(format)),
+        }
+    }
+
+    /// Serializes the tree to CBOR.
+    #[cfg(feature = "cbor")]
+    pub fn to_cbor(&self) -> Result<Vec<u8>, MerkleTreeError> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(self, &mut bytes)
+            .map_err(|e| MerkleTreeError::CborError(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    /// Deserializes the tree from CBOR produced by [`MerkleTree::to_cbor`].
+    #[cfg(feature = "cbor")]
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        Self::from_cbor_with_limits(bytes, DeserializeLimits::default())
+    }
+
+    /// Deserializes the tree from CBOR like [`MerkleTree::from_cbor`], rejecting trees that
+    /// exceed `limits` or whose shape does not match their leaf list.
+    #[cfg(feature = "cbor")]
+    pub fn from_cbor_with_limits(
+        bytes: &[u8],
+        limits: DeserializeLimits,
+    ) -> Result<Self, MerkleTreeError> {
+        // ciborium cannot deserialize through a seed, so the tree is read under the default
+        // limits and its size checked against `limits` afterwards
+        let mut tree: Self =
+            ciborium::from_reader(bytes).map_err(|e| MerkleTreeError::CborError(e.to_string()))?;
+        tree.limits = limits;
+        tree.verify_structure()?;
+        limits.check_leaf_count(tree.leaf_count())?;
+        if tree.node_count() > limits.max_nodes {
+            return Err(MerkleTreeError::TooManyNodes {
+                count: tree.node_count(),
+                max: limits.max_nodes,
+            });
+        }
+        Ok(tree)
+    }
+}
+
+impl MerkleProof {
+    /// Serializes the proof in the given encoding. Proofs have no compact encoding.
+    pub fn to_dump(&self, format: DumpFormat) -> Result<Vec<u8>, MerkleTreeError> {
+        match format {
+            DumpFormat::Json => Ok(self.to_json()?.into_bytes()),
+            #[cfg(feature = "bincode")]
+            DumpFormat::Bincode => bincode::serialize(self)
+                .map(with_bincode_magic)
+                .map_err(MerkleTreeError::BincodeError),
+            #[cfg(feature = "cbor")]
+            DumpFormat::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(self, &mut bytes)
+                    .map_err(|e| MerkleTreeError::CborError(e.to_string()))?;
+                Ok(bytes)
+            }
+            DumpFormat::Compact => Err(MerkleTreeError::FormatError(
+                "proofs have no compact encoding".to_string(),
+            )),
+            #[allow(unreachable_patterns)]
+            format => Err(disabled(format)),
+        }
+    }
+
+    /// Deserializes a proof in any supported encoding, optionally zstd- or gzip-compressed
+    /// and enveloped.
+    pub fn from_dump(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        Self::from_dump_with(bytes, &EnvelopePolicy::default())
+    }
+
+    /// Deserializes a proof like [`MerkleProof::from_dump`], rejecting input that is not
+    /// sealed as `policy` requires.
+    pub fn from_dump_with(bytes: &[u8], policy: &EnvelopePolicy) -> Result<Self, MerkleTreeError> {
+        let bytes = decompress(&envelope::open_with(bytes, policy)?.payload)?;
+        match detect(&bytes)? {
+            DumpFormat::Json => schema::from_slice(&bytes),
+            #[cfg(feature = "bincode")]
+            DumpFormat::Bincode => bincode::deserialize(&bytes[BINCODE_MAGIC.len()..])
+                .map_err(MerkleTreeError::BincodeError),
+            #[cfg(feature = "cbor")]
+            DumpFormat::Cbor => ciborium::from_reader(bytes.as_slice())
+                .map_err(|e| MerkleTreeError::CborError(e.to_string())),
+            DumpFormat::Compact => Err(MerkleTreeError::FormatError(
+                "proofs have no compact encoding".to_string(),
+            )),
+            #[allow(unreachable_patterns)]
+            format => Err(disabled(format)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::MerkleTreeBuilder;
+    use crate::hasher::OddNodePolicy;
+
+    use alloy_primitives::keccak256;
+
+    /// Pseudo-random leaves, so every seed gives a tree with an unrelated root.
+    fn leaves(seed: usize, count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| keccak256(format!("{seed} {i}")).to_vec())
+            .collect()
+    }
+
+    /// Every encoding enabled in this build.
+    fn formats() -> Vec<DumpFormat> {
+        let mut formats = vec![DumpFormat::Json, DumpFormat::Compact];
+        if cfg!(feature = "bincode") {
+            formats.push(DumpFormat::Bincode);
+        }
+        if cfg!(feature = "cbor") {
+            formats.push(DumpFormat::Cbor);
+        }
+        formats
+    }
+
+    #[test]
+    fn trees_round_trip() {
+        for seed in 0..64 {
+            let builder = MerkleTreeBuilder::new()
+                .sorted_pairs(seed % 2 == 1)
+                .odd_nodes(if seed % 4 < 2 {
+                    OddNodePolicy::Promote
+                } else {
+                    OddNodePolicy::Duplicate
+                });
+            let tree = builder.build(&leaves(seed, 1 + seed % 13)).unwrap();
+            for format in formats() {
+                let bytes = tree.to_dump(format).unwrap();
+                assert_eq!(DumpFormat::detect(&bytes), Some(format), "{seed} {format}");
+                let decoded = MerkleTree::from_dump(&bytes).unwrap();
+                assert_eq!(decoded.root_hash(), tree.root_hash(), "{seed} {format}");
+                assert_eq!(decoded.levels().unwrap(), tree.levels().unwrap());
+                assert_eq!(decoded.sorted_pairs, tree.sorted_pairs);
+                assert_eq!(decoded.odd_nodes, tree.odd_nodes);
+            }
+        }
+    }
+
+    #[test]
+    fn proofs_round_trip() {
+        for seed in 0..64 {
+            let tree = MerkleTreeBuilder::new()
+                .build(&leaves(seed, 1 + seed % 13))
+                .unwrap();
+            let proof = tree
+                .generate_proof_by_index(seed % tree.leaf_count())
+                .unwrap();
+            for format in formats() {
+                if format == DumpFormat::Compact {
+                    assert!(proof.to_dump(format).is_err());
+                    continue;
+                }
+                let bytes = proof.to_dump(format).unwrap();
+                assert_eq!(DumpFormat::detect(&bytes), Some(format), "{seed} {format}");
+                let decoded = MerkleProof::from_dump(&bytes).unwrap();
+                assert_eq!(decoded, proof, "{seed} {format}");
+                assert!(decoded.verify(tree.root_hash()).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unrecognized_bytes() {
+        for bytes in [&b""[..], b"leaf", b"\x00\x01\x02\x03", b"[1, 2]"] {
+            assert_eq!(DumpFormat::detect(bytes), None);
+            assert!(MerkleTree::from_dump(bytes).is_err());
+            assert!(MerkleProof::from_dump(bytes).is_err());
+        }
+    }
+}
@@ -0,0 +1,493 @@
+//! Checksummed and signed envelopes for tree and proof files.
+//!
+//! An envelope wraps a dump in any encoding, compressed or not, so that artifacts passed
+//! between teams are tamper-evident. Layout (integers are little-endian):
+//!
+//! | Offset   | Size | Field                                        |
+//! |----------|------|----------------------------------------------|
+//! | 0        | 4    | Magic bytes `MRKE`                           |
+//! | 4        | 1    | Envelope version                             |
+//! | 5        | 1    | Flags (see below)                            |
+//! | 6        | 8    | Payload length `n`                           |
+//! | 14       | `n`  | Payload                                      |
+//! | 14 + n   | 32   | SHA-256 of the payload, with flag bit `0`    |
+//! | 46 + n   | 20   | Signer address, with flag bit `1`            |
+//! | 66 + n   | 65   | `r \|\| s \|\| v` signature, with flag bit `1` |
+//!
+//! Flag bit `0` marks envelopes carrying a checksum and flag bit `1` envelopes carrying a
+//! signature, which requires a checksum. Other bits must be `0`. The signature is an
+//! EIP-191 `personal_sign` over the 32 checksum bytes.
+//!
+//! [`MerkleTree::from_dump`](crate::merkle_tree::MerkleTree::from_dump) and
+//! [`MerkleProof::from_dump`](crate::proof::MerkleProof::from_dump) open envelopes
+//! transparently, rejecting files whose checksum or signature does not check out, but also
+//! accept files that are not enveloped at all. Where a checksum or a particular signer is
+//! required, load with an [`EnvelopePolicy`] through
+//! [`MerkleTree::from_dump_with`](crate::merkle_tree::MerkleTree::from_dump_with),
+//! [`MerkleProof::from_dump_with`](crate::proof::MerkleProof::from_dump_with) or
+//! [`open_with`], which also returns the verified signer.
+
+use crate::error::MerkleTreeError;
+use crate::hasher::HashAlgorithm;
+use crate::verify::hashes_equal;
+
+use alloy_primitives::{Address, B256};
+// Later alloy-primitives 0.8 releases deprecate the signature type alloy-signer 0.3 returns
+#[cfg(feature = "signer")]
+#[allow(deprecated)]
+use alloy_signer::Signature;
+#[cfg(feature = "signer")]
+use alloy_signer::{Signer, SignerSync};
+use std::borrow::Cow;
+
+/// Magic bytes identifying an envelope.
+pub const ENVELOPE_MAGIC: [u8; 4] = *b"MRKE";
+
+/// Current envelope version.
+pub const ENVELOPE_VERSION: u8 = 1;
+
+/// Flag for envelopes carrying a checksum of the payload.
+pub const FLAG_CHECKSUM: u8 = 1;
+
+/// Flag for envelopes carrying a signature over the checksum.
+pub const FLAG_SIGNATURE: u8 = 1 << 1;
+
+const HEADER_SIZE: usize = 14;
+const CHECKSUM_SIZE: usize = 32;
+const ADDRESS_SIZE: usize = 20;
+const SIGNATURE_SIZE: usize = 65;
+
+fn format_error(message: impl Into<String>) -> MerkleTreeError {
+    MerkleTreeError::FormatError(message.into())
+}
+
+/// A signature over an envelope's checksum.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct EnvelopeSignature {
+    /// Address of the account that signed the checksum.
+    pub signer: Address,
+
+    /// The 65-byte `r || s || v` signature.
+    pub signature: Vec<u8>,
+}
+
+/// What an input must be sealed with to be opened by [`open_with`].
+///
+/// The default policy accepts input that is not enveloped, as [`open`] does.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct EnvelopePolicy {
+    /// Rejects input that is not enveloped with a checksum.
+    pub require_checksum: bool,
+
+    /// Rejects input that is not signed by this address. Implies a checksum.
+    pub expected_signer: Option<Address>,
+}
+
+impl EnvelopePolicy {
+    /// Requires input signed by `signer`.
+    pub fn signed_by(signer: Address) -> Self {
+        EnvelopePolicy {
+            require_checksum: true,
+            expected_signer: Some(signer),
+        }
+    }
+
+    /// Checks what `envelope`, or its absence, records against the policy. Signatures are
+    /// not verified here.
+    fn check(&self, envelope: Option<&Envelope>) -> Result<(), MerkleTreeError> {
+        if let Some(expected) = self.expected_signer {
+            match envelope.and_then(Envelope::signer) {
+                Some(got) if got == expected => {}
+                Some(got) => return Err(MerkleTreeError::SignerMismatch { expected, got }),
+                None => return Err(MerkleTreeError::NotSigned { expected }),
+            }
+        }
+        if self.require_checksum && envelope.and_then(|e| e.checksum).is_none() {
+            return Err(MerkleTreeError::MissingChecksum);
+        }
+        Ok(())
+    }
+}
+
+/// A payload taken out of its envelope by [`open_with`], with what the envelope vouched
+/// for.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Opened<'a> {
+    /// The unwrapped dump, or the input itself if it was not enveloped.
+    pub payload: Cow<'a, [u8]>,
+
+    /// The verified checksum of the payload, if it was checksummed.
+    pub checksum: Option<B256>,
+
+    /// The address whose signature over the checksum was verified, if it was signed.
+    pub signer: Option<Address>,
+}
+
+/// A dump wrapped with an optional checksum and signature.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Envelope {
+    /// The wrapped dump.
+    pub payload: Vec<u8>,
+
+    /// SHA-256 of the payload.
+    pub checksum: Option<B256>,
+
+    /// Signature over the checksum. Only present along with a checksum.
+    pub signature: Option<EnvelopeSignature>,
+}
+
+impl Envelope {
+    /// Wraps `payload` with its checksum.
+    pub fn checksummed(payload: Vec<u8>) -> Self {
+        let checksum = HashAlgorithm::Sha256.hash(&payload);
+        Envelope {
+            payload,
+            checksum: Some(checksum),
+            signature: None,
+        }
+    }
+
+    /// Wraps `payload` with its checksum, signed by `signer`.
+    #[cfg(feature = "signer")]
+    pub fn signed<S>(payload: Vec<u8>, signer: &S) -> Result<Self, MerkleTreeError>
+    where
+        S: Signer + SignerSync,
+    {
+        let checksum = HashAlgorithm::Sha256.hash(&payload);
+        let signature = signer
+            .sign_message_sync(checksum.as_slice())
+            .map_err(|e| MerkleTreeError::SignatureError(e.to_string()))?;
+        Ok(Envelope {
+            payload,
+            checksum: Some(checksum),
+            signature: Some(EnvelopeSignature {
+                signer: Address::from_slice(signer.address().as_slice()),
+                signature: signature.as_bytes().to_vec(),
+            }),
+        })
+    }
+
+    /// Returns the address that signed the envelope, if it is signed.
+    pub fn signer(&self) -> Option<Address> {
+        self.signature.as_ref().map(|signature| signature.signer)
+    }
+
+    /// Returns whether `bytes` start with the envelope magic bytes.
+    pub fn detect(bytes: &[u8]) -> bool {
+        bytes.starts_with(&ENVELOPE_MAGIC)
+    }
+
+    /// Encodes the envelope.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if self.checksum.is_some() {
+            flags |= FLAG_CHECKSUM;
+        }
+        if self.signature.is_some() {
+            flags |= FLAG_SIGNATURE;
+        }
+
+        let trailer_len = CHECKSUM_SIZE + ADDRESS_SIZE + SIGNATURE_SIZE;
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.payload.len() + trailer_len);
+        bytes.extend_from_slice(&ENVELOPE_MAGIC);
+        bytes.push(ENVELOPE_VERSION);
+        bytes.push(flags);
+        bytes.extend_from_slice(&(self.payload.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.payload);
+        if let Some(checksum) = &self.checksum {
+            bytes.extend_from_slice(checksum.as_slice());
+        }
+        if let Some(signature) = &self.signature {
+            bytes.extend_from_slice(signature.signer.as_slice());
+            bytes.extend_from_slice(&signature.signature);
+        }
+        bytes
+    }
+
+    /// Decodes an envelope and checks its checksum and signature.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        let envelope = Self::decode(bytes)?;
+        envelope.verify()?;
+        Ok(envelope)
+    }
+
+    /// Decodes an envelope without checking its checksum or signature.
+    fn decode(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        if bytes.len() < HEADER_SIZE || !Self::detect(bytes) {
+            return Err(format_error("not an envelope"));
+        }
+        if bytes[4] != ENVELOPE_VERSION {
+            return Err(format_error(format!(
+                "unsupported envelope version {}",
+                bytes[4]
+            )));
+        }
+        let flags = bytes[5];
+        if flags & !(FLAG_CHECKSUM | FLAG_SIGNATURE) != 0 {
+            return Err(format_error(format!(
+                "unknown envelope flags {:#04x}",
+                flags
+            )));
+        }
+        if flags & FLAG_SIGNATURE != 0 && flags & FLAG_CHECKSUM == 0 {
+            return Err(format_error("envelope is signed but has no checksum"));
+        }
+
+        let mut len = [0u8; 8];
+        len.copy_from_slice(&bytes[6..HEADER_SIZE]);
+        let payload_len = u64::from_le_bytes(len);
+        let mut trailer_len = 0;
+        if flags & FLAG_CHECKSUM != 0 {
+            trailer_len += CHECKSUM_SIZE;
+        }
+        if flags & FLAG_SIGNATURE != 0 {
+            trailer_len += ADDRESS_SIZE + SIGNATURE_SIZE;
+        }
+        let expected_len = usize::try_from(payload_len)
+            .ok()
+            .and_then(|len| len.checked_add(HEADER_SIZE + trailer_len));
+        if expected_len != Some(bytes.len()) {
+            return Err(format_error(format!(
+                "envelope of {} bytes does not fit a payload of {} bytes",
+                bytes.len(),
+                payload_len
+            )));
+        }
+
+        let (payload, trailer) = bytes[HEADER_SIZE..].split_at(payload_len as usize);
+        let (checksum, trailer) = if flags & FLAG_CHECKSUM != 0 {
+            let (checksum, rest) = trailer.split_at(CHECKSUM_SIZE);
+            (Some(B256::from_slice(checksum)), rest)
+        } else {
+            (None, trailer)
+        };
+        let signature = (flags & FLAG_SIGNATURE != 0).then(|| {
+            let (signer, signature) = trailer.split_at(ADDRESS_SIZE);
+            EnvelopeSignature {
+                signer: Address::from_slice(signer),
+                signature: signature.to_vec(),
+            }
+        });
+
+        Ok(Envelope {
+            payload: payload.to_vec(),
+            checksum,
+            signature,
+        })
+    }
+
+    /// Checks that the checksum matches the payload and that the signature over it was made
+    /// by the recorded signer.
+    ///
+    /// Verifying a signature needs the `signer` feature; without it, signed envelopes are
+    /// rejected.
+    pub fn verify(&self) -> Result<(), MerkleTreeError> {
+        let Some(checksum) = &self.checksum else {
+            return Ok(());
+        };
+        let computed = HashAlgorithm::Sha256.hash(&self.payload);
+        if !hashes_equal(checksum, &computed) {
+            return Err(MerkleTreeError::ChecksumMismatch {
+                expected: *checksum,
+                got: computed,
+            });
+        }
+
+        match &self.signature {
+            Some(signature) => check_signature(checksum, signature),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Checks that `signature` over `checksum` was made by its recorded signer.
+#[cfg(feature = "signer")]
+#[allow(deprecated)]
+fn check_signature(checksum: &B256, signature: &EnvelopeSignature) -> Result<(), MerkleTreeError> {
+    let parsed = Signature::try_from(signature.signature.as_slice())
+        .map_err(|e| MerkleTreeError::SignatureError(e.to_string()))?;
+    let recovered = parsed
+        .recover_address_from_msg(checksum.as_slice())
+        .map_err(|e| MerkleTreeError::SignatureError(e.to_string()))?;
+    if recovered.as_slice() != signature.signer.as_slice() {
+        return Err(MerkleTreeError::SignatureError(format!(
+            "envelope signature was not made by {}",
+            signature.signer
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "signer"))]
+fn check_signature(_: &B256, signature: &EnvelopeSignature) -> Result<(), MerkleTreeError> {
+    Err(MerkleTreeError::SignatureError(format!(
+        "cannot check the envelope signature by {} without the `signer` feature",
+        signature.signer
+    )))
+}
+
+/// Returns the payload of an envelope after checking it, or `bytes` unchanged if they are
+/// not enveloped.
+pub fn open(bytes: &[u8]) -> Result<Cow<'_, [u8]>, MerkleTreeError> {
+    Ok(open_with(bytes, &EnvelopePolicy::default())?.payload)
+}
+
+/// Returns the payload of an envelope with its verified checksum and signer, or `bytes`
+/// unchanged if they are not enveloped and `policy` allows it.
+///
+/// The policy is checked before the checksum and signature, so input from the wrong signer
+/// is rejected as such even where signatures cannot be verified.
+pub fn open_with<'a>(
+    bytes: &'a [u8],
+    policy: &EnvelopePolicy,
+) -> Result<Opened<'a>, MerkleTreeError> {
+    if !Envelope::detect(bytes) {
+        policy.check(None)?;
+        return Ok(Opened {
+            payload: Cow::Borrowed(bytes),
+            checksum: None,
+            signer: None,
+        });
+    }
+
+    let envelope = Envelope::decode(bytes)?;
+    policy.check(Some(&envelope))?;
+    envelope.verify()?;
+    Ok(Opened {
+        checksum: envelope.checksum,
+        signer: envelope.signer(),
+        payload: Cow::Owned(envelope.payload),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::MerkleTree;
+
+    use alloy_primitives::address;
+
+    const SIGNER: Address = address!("1111111111111111111111111111111111111111");
+    const FORGER: Address = address!("2222222222222222222222222222222222222222");
+
+    fn payload() -> Vec<u8> {
+        b"{\"root\": \"0x00\"}".to_vec()
+    }
+
+    /// An envelope claiming a signature by `signer`, as a forger re-signing a modified
+    /// payload with their own key would produce.
+    fn claimed_by(signer: Address) -> Vec<u8> {
+        let mut envelope = Envelope::checksummed(payload());
+        envelope.signature = Some(EnvelopeSignature {
+            signer,
+            signature: vec![0; SIGNATURE_SIZE],
+        });
+        envelope.to_bytes()
+    }
+
+    #[test]
+    fn opens_checksummed() {
+        let bytes = Envelope::checksummed(payload()).to_bytes();
+        let policy = EnvelopePolicy {
+            require_checksum: true,
+            expected_signer: None,
+        };
+        let opened = open_with(&bytes, &policy).unwrap();
+        assert_eq!(opened.payload, payload());
+        assert_eq!(
+            opened.checksum,
+            Some(HashAlgorithm::Sha256.hash(&payload()))
+        );
+        assert_eq!(opened.signer, None);
+    }
+
+    #[test]
+    fn rejects_stripped() {
+        let bytes = payload();
+        assert_eq!(open(&bytes).unwrap(), payload());
+
+        let policy = EnvelopePolicy {
+            require_checksum: true,
+            expected_signer: None,
+        };
+        assert!(matches!(
+            open_with(&bytes, &policy),
+            Err(MerkleTreeError::MissingChecksum)
+        ));
+        assert!(matches!(
+            open_with(&bytes, &EnvelopePolicy::signed_by(SIGNER)),
+            Err(MerkleTreeError::NotSigned { expected: SIGNER })
+        ));
+
+        // A signature removed while keeping the checksum
+        let checksummed = Envelope::checksummed(payload()).to_bytes();
+        assert!(matches!(
+            open_with(&checksummed, &EnvelopePolicy::signed_by(SIGNER)),
+            Err(MerkleTreeError::NotSigned { expected: SIGNER })
+        ));
+    }
+
+    #[test]
+    fn rejects_resigned() {
+        let bytes = claimed_by(FORGER);
+        assert!(matches!(
+            open_with(&bytes, &EnvelopePolicy::signed_by(SIGNER)),
+            Err(MerkleTreeError::SignerMismatch {
+                expected: SIGNER,
+                got: FORGER
+            })
+        ));
+
+        // Claiming the expected signer does not help without their signature
+        let bytes = claimed_by(SIGNER);
+        assert!(matches!(
+            open_with(&bytes, &EnvelopePolicy::signed_by(SIGNER)),
+            Err(MerkleTreeError::SignatureError(_))
+        ));
+        assert!(open(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_corrupted_checksum() {
+        let bytes = Envelope::checksummed(payload()).to_bytes();
+
+        let mut corrupt_payload = bytes.clone();
+        corrupt_payload[HEADER_SIZE] ^= 1;
+        let mut corrupt_checksum = bytes.clone();
+        *corrupt_checksum.last_mut().unwrap() ^= 1;
+
+        for bytes in [corrupt_payload, corrupt_checksum] {
+            assert!(matches!(
+                open(&bytes),
+                Err(MerkleTreeError::ChecksumMismatch { .. })
+            ));
+            assert!(matches!(
+                Envelope::from_bytes(&bytes),
+                Err(MerkleTreeError::ChecksumMismatch { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn loads_dumps_under_policy() {
+        let tree = MerkleTree::new(&[b"a".to_vec(), b"b".to_vec()]).unwrap();
+        let json = tree.to_json().unwrap().into_bytes();
+        let sealed = Envelope::checksummed(json.clone()).to_bytes();
+        let policy = EnvelopePolicy {
+            require_checksum: true,
+            expected_signer: None,
+        };
+
+        let loaded = MerkleTree::from_dump_with(&sealed, &policy).unwrap();
+        assert_eq!(loaded.root_hash(), tree.root_hash());
+        assert!(MerkleTree::from_dump(&json).is_ok());
+        assert!(matches!(
+            MerkleTree::from_dump_with(&json, &policy),
+            Err(MerkleTreeError::MissingChecksum)
+        ));
+        assert!(matches!(
+            MerkleTree::from_dump_with(&sealed, &EnvelopePolicy::signed_by(SIGNER)),
+            Err(MerkleTreeError::NotSigned { .. })
+        ));
+    }
+}
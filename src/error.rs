@@ -1,6 +1,25 @@
+use crate::hasher::{HashAlgorithm, TreeOption};
+use crate::merkle_tree::CorruptNode;
+use crate::multiproof::MultiProofDefect;
+#[cfg(feature = "abi")]
+use crate::openzeppelin::DumpDefect;
+use crate::serialization::shorten;
+use crate::store::StoredRoot;
+
+use alloy_primitives::hex::FromHexError;
+use alloy_primitives::{Address, B256};
+use std::fmt;
 use thiserror::Error;
 
+/// Errors returned by the crate.
+///
+/// Failed checks of the crate's own have typed variants. Variants holding a `String`
+/// carry the message of a decoder, service or library the crate calls into.
+///
+/// New variants may be added in minor releases, so matches need a wildcard arm.
 #[derive(Error, Debug)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Error), uniffi(flat_error))]
+#[non_exhaustive]
 pub enum MerkleTreeError {
     #[error("Cannot build a Merkle Tree with no data")]
     EmptyData,
@@ -8,15 +27,235 @@ pub enum MerkleTreeError {
     #[error("Serialization/Deserialization error: {0}")]
     SerdeError(#[from] serde_json::Error),
 
+    #[cfg(feature = "bincode")]
+    #[error("Binary encoding error: {0}")]
+    BincodeError(#[from] bincode::Error),
+
+    #[cfg(feature = "msgpack")]
+    #[error("MessagePack encoding error: {0}")]
+    MsgpackEncodeError(#[from] rmp_serde::encode::Error),
+
+    #[cfg(feature = "msgpack")]
+    #[error("MessagePack decoding error: {0}")]
+    MsgpackDecodeError(#[from] rmp_serde::decode::Error),
+
+    #[error("CBOR error: {0}")]
+    CborError(String),
+
+    #[cfg(feature = "rlp")]
+    #[error("RLP error: {0}")]
+    RlpError(#[from] alloy_rlp::Error),
+
+    #[error("SSZ error: {0}")]
+    SszError(String),
+
+    #[error("Protobuf error: {0}")]
+    ProtobufError(String),
+
+    #[error("ABI encoding error: {0}")]
+    AbiError(String),
+
     #[error("Hex decoding error: {0}")]
     HexDecodeError(#[from] alloy_primitives::hex::FromHexError),
 
-    #[error("Hashing error: {0}")]
-    HashError(String),
+    #[error("Unknown hash algorithm '{name}', expected keccak256, sha256 or blake3")]
+    #[non_exhaustive]
+    UnknownHashAlgorithm { name: String },
+
+    #[error("{algorithm} has no EVM precompile, so it cannot be verified on-chain")]
+    #[non_exhaustive]
+    NoEvmPrecompile { algorithm: HashAlgorithm },
+
+    #[error("Malformed multiproof: {0}")]
+    MalformedMultiProof(MultiProofDefect),
+
+    #[cfg(feature = "abi")]
+    #[error("Invalid OpenZeppelin dump: {0}")]
+    InvalidStandardDump(DumpDefect),
+
+    #[error("Invalid trie proof: {0}")]
+    InvalidTrieProof(String),
+
+    #[error("Tree shape does not match its leaf count")]
+    ShapeMismatch,
+
+    #[error("Leaf {hash} not found in the tree")]
+    #[non_exhaustive]
+    LeafNotFound { hash: B256 },
+
+    #[error("Node {index} of level {level} out of range")]
+    #[non_exhaustive]
+    NodeOutOfRange { level: usize, index: usize },
+
+    #[error("Leaf index {index} out of range for {leaf_count} leaves")]
+    #[non_exhaustive]
+    IndexOutOfRange { index: usize, leaf_count: usize },
+
+    #[error("Length mismatch: expected {expected}, got {got}")]
+    #[non_exhaustive]
+    LengthMismatch { expected: usize, got: usize },
+
+    #[error("Hash algorithm mismatch: expected {expected}, got {got}")]
+    #[non_exhaustive]
+    AlgorithmMismatch {
+        expected: HashAlgorithm,
+        got: HashAlgorithm,
+    },
+
+    #[error("Tree option mismatch: expected {expected}, got {got}")]
+    #[non_exhaustive]
+    OptionMismatch {
+        expected: TreeOption,
+        got: TreeOption,
+    },
+
+    #[error("Depth {depth} exceeds the maximum of {max}")]
+    #[non_exhaustive]
+    DepthExceeded { depth: usize, max: usize },
+
+    #[error("Root mismatch: expected {expected}, got {got}")]
+    #[non_exhaustive]
+    RootMismatch { expected: B256, got: B256 },
+
+    #[error("Leaf {index} mismatch: expected {expected}, got {got}")]
+    #[non_exhaustive]
+    LeafMismatch {
+        index: usize,
+        expected: B256,
+        got: B256,
+    },
+
+    #[error("Remote leaf {index} does not match its hash")]
+    #[non_exhaustive]
+    RemoteLeafMismatch { index: usize },
 
-    #[error("Invalid proof: {0}")]
-    InvalidProof(String),
+    #[error("Checksum mismatch: expected {expected}, got {got}")]
+    #[non_exhaustive]
+    ChecksumMismatch { expected: B256, got: B256 },
+
+    #[error("Expected {expected} from the remote replica")]
+    #[non_exhaustive]
+    UnexpectedResponse { expected: &'static str },
+
+    #[error("Schema version {version} is newer than the supported version {max}")]
+    #[non_exhaustive]
+    UnsupportedSchemaVersion { version: u32, max: u32 },
+
+    #[error("Input is not enveloped with a checksum")]
+    MissingChecksum,
+
+    #[error("Input is not signed, expected a signature by {expected}")]
+    #[non_exhaustive]
+    NotSigned { expected: Address },
+
+    #[error("Signer mismatch: expected {expected}, got {got}")]
+    #[non_exhaustive]
+    SignerMismatch { expected: Address, got: Address },
+
+    #[error("Stored tree mismatch: expected {expected}, got {got}")]
+    #[non_exhaustive]
+    StoredRootMismatch {
+        expected: StoredRoot,
+        got: StoredRoot,
+    },
+
+    #[error("Corrupt node at {0}")]
+    CorruptNode(CorruptNode),
+
+    #[error(
+        "Leaf {index} is 64 bytes long, like a pair of child hashes, so its proof could be \
+         forged from an internal node; enable domain separation, hash leaves first or allow \
+         64-byte leaves"
+    )]
+    #[non_exhaustive]
+    AmbiguousLeaf { index: usize },
+
+    #[error("Ambiguous tree: level {level} ends with two copies of {hash}")]
+    #[non_exhaustive]
+    DuplicateOddNode { level: usize, hash: B256 },
+
+    #[error("{count} leaves exceed the maximum of {max}")]
+    #[non_exhaustive]
+    TooManyLeaves { count: usize, max: usize },
+
+    #[error("{count} nodes exceed the maximum of {max}")]
+    #[non_exhaustive]
+    TooManyNodes { count: usize, max: usize },
+
+    #[error("Leaf {index} is {size} bytes long, over the maximum of {max}")]
+    #[non_exhaustive]
+    LeafTooLarge {
+        index: usize,
+        size: usize,
+        max: usize,
+    },
+
+    #[error("Invalid file format: {0}")]
+    FormatError(String),
+
+    #[error("RPC error: {0}")]
+    RpcError(String),
+
+    #[error("Signature error: {0}")]
+    SignatureError(String),
+
+    #[error("Timestamp error: {0}")]
+    TimestampError(String),
+
+    #[error("Storage error: {0}")]
+    StorageError(String),
 
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+
+    #[error("{position}: invalid value {value:?}: {source}")]
+    #[non_exhaustive]
+    ParseError {
+        position: InputPosition,
+        value: String,
+        #[source]
+        source: Box<MerkleTreeError>,
+    },
+}
+
+impl MerkleTreeError {
+    /// Wraps the error raised for `value`, which starts at `position`, into a
+    /// [`MerkleTreeError::ParseError`].
+    ///
+    /// The column is moved to the offending character of invalid hex, and values longer
+    /// than 64 characters are shortened.
+    pub fn at(self, mut position: InputPosition, value: &str) -> Self {
+        position.column += match &self {
+            Self::HexDecodeError(FromHexError::InvalidHexCharacter { index, .. }) => {
+                let prefix = if value.starts_with("0x") { 2 } else { 0 };
+                prefix + index
+            }
+            _ => 0,
+        };
+        MerkleTreeError::ParseError {
+            position,
+            value: shorten(value).into_owned(),
+            source: Box::new(self),
+        }
+    }
+}
+
+/// Where a value was read from in a text input.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct InputPosition {
+    /// The file read from, or `None` for stdin and in-memory inputs.
+    pub file: Option<String>,
+    /// Line number, starting at 1.
+    pub line: usize,
+    /// Column in characters, starting at 1.
+    pub column: usize,
+}
+
+impl fmt::Display for InputPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:{}:{}", file, self.line, self.column),
+            None => write!(f, "line {}, column {}", self.line, self.column),
+        }
+    }
 }
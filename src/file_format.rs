@@ -0,0 +1,271 @@
+//! Versioned compact binary file format for trees.
+//!
+//! Layout (integers are little-endian):
+//!
+//! | Offset | Size | Field                                   |
+//! |--------|------|-----------------------------------------|
+//! | 0      | 4    | Magic bytes `MRKL`                      |
+//! | 4      | 2    | Format version                          |
+//! | 6      | 1    | Hasher id (see [`HashAlgorithm::id`])   |
+//! | 7      | 1    | Flags (see below)                       |
+//! | 8      | 8    | Leaf count `n`                          |
+//! | 16     | ...  | Level data: 32-byte hashes, bottom-up   |
+//!
+//! Level `0` holds the `n` leaf hashes and each following level holds `ceil(len / 2)`
+//! hashes, ending with the root. A level's last node is promoted unchanged when its length
+//! is odd, and its hash is repeated in the level above.
+//!
+//! Flag bit `0` marks trees that sort each pair before hashing. Flag bit `1` marks trees
+//! that pair an odd node with a copy of itself instead of promoting it; the level above
+//! then holds the parent's hash. Flag bit `2` marks trees that prefix leaves and nodes
+//! before hashing. Other bits must be `0`.
+
+use crate::error::MerkleTreeError;
+use crate::hasher::{HashAlgorithm, OddNodePolicy, TreeHasher};
+use crate::merkle_node::{DeserializeLimits, MerkleNode};
+use crate::merkle_tree::MerkleTree;
+
+use alloy_primitives::B256;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Magic bytes identifying a compact tree file.
+pub const MAGIC: [u8; 4] = *b"MRKL";
+
+/// Current format version.
+pub const VERSION: u16 = 1;
+
+/// Flag for trees that sort each pair before hashing.
+pub const FLAG_SORTED_PAIRS: u8 = 1;
+
+/// Flag for trees that duplicate odd nodes instead of promoting them.
+pub const FLAG_DUPLICATE_ODD: u8 = 1 << 1;
+
+/// Flag for trees that prefix leaves and nodes before hashing.
+pub const FLAG_DOMAIN_SEPARATION: u8 = 1 << 2;
+
+const HEADER_SIZE: usize = 16;
+
+fn format_error(message: impl Into<String>) -> MerkleTreeError {
+    MerkleTreeError::FormatError(message.into())
+}
+
+fn read_hash<R: Read>(reader: &mut R) -> Result<B256, MerkleTreeError> {
+    let mut hash = [0u8; 32];
+    reader.read_exact(&mut hash)?;
+    Ok(B256::from(hash))
+}
+
+impl MerkleTree {
+    /// Writes the tree in the compact binary format.
+    pub fn write_compact<W: Write>(&self, writer: &mut W) -> Result<(), MerkleTreeError> {
+        let levels = self.levels()?;
+        let leaf_count = levels[0].len();
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        let mut flags = 0;
+        if self.sorted_pairs {
+            flags |= FLAG_SORTED_PAIRS;
+        }
+        if self.odd_nodes == OddNodePolicy::Duplicate {
+            flags |= FLAG_DUPLICATE_ODD;
+        }
+        if self.domain_separation {
+            flags |= FLAG_DOMAIN_SEPARATION;
+        }
+        writer.write_all(&[self.hash_algorithm.id(), flags])?;
+        writer.write_all(&(leaf_count as u64).to_le_bytes())?;
+        for hash in levels.iter().flatten() {
+            writer.write_all(hash.as_slice())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a tree written by [`MerkleTree::write_compact`], validating the header.
+    ///
+    /// Stored hashes are trusted; call [`MerkleTree::verify`] to check them.
+    pub fn read_compact<R: Read>(reader: &mut R) -> Result<Self, MerkleTreeError> {
+        let mut header = [0u8; HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+
+        if header[..4] != MAGIC {
+            return Err(format_error("missing MRKL magic bytes"));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != VERSION {
+            return Err(format_error(format!("unsupported version {}", version)));
+        }
+        let hash_algorithm = HashAlgorithm::from_id(header[6])
+            .ok_or_else(|| format_error(format!("unsupported hasher id {}", header[6])))?;
+        let flags = header[7];
+        if flags & !(FLAG_SORTED_PAIRS | FLAG_DUPLICATE_ODD | FLAG_DOMAIN_SEPARATION) != 0 {
+            return Err(format_error(format!("unsupported flags {:#04x}", flags)));
+        }
+        let hasher = TreeHasher {
+            hash_algorithm,
+            domain_separation: flags & FLAG_DOMAIN_SEPARATION != 0,
+            sorted_pairs: flags & FLAG_SORTED_PAIRS != 0,
+            odd_nodes: if flags & FLAG_DUPLICATE_ODD != 0 {
+                OddNodePolicy::Duplicate
+            } else {
+                OddNodePolicy::Promote
+            },
+        };
+        let mut leaf_count = [0u8; 8];
+        leaf_count.copy_from_slice(&header[8..]);
+        let leaf_count = usize::try_from(u64::from_le_bytes(leaf_count))
+            .map_err(|_| format_error("leaf count does not fit in memory"))?;
+        if leaf_count == 0 {
+            return Err(MerkleTreeError::EmptyData);
+        }
+
+        // Grow vectors as hashes arrive, so a bogus leaf count cannot force a huge allocation.
+        let mut nodes = Vec::new();
+        for _ in 0..leaf_count {
+            let hash = read_hash(reader)?;
+            nodes.push(MerkleNode {
+                hash,
+                left: None,
+                right: None,
+            });
+        }
+        let leaf_hashes: Vec<B256> = nodes.iter().map(|node| node.hash).collect();
+
+        while nodes.len() > 1 {
+            let mut next_level = Vec::with_capacity(nodes.len().div_ceil(2));
+            let mut level = nodes.into_iter();
+            while let Some(left) = level.next() {
+                let hash = read_hash(reader)?;
+                match level.next() {
+                    Some(right) => next_level.push(MerkleNode {
+                        hash,
+                        left: Some(Box::new(left)),
+                        right: Some(Box::new(right)),
+                    }),
+                    None if hasher.duplicates_odd() => {
+                        let copy = MerkleNode::leaf(left.hash);
+                        next_level.push(MerkleNode {
+                            hash,
+                            left: Some(Box::new(left)),
+                            right: Some(Box::new(copy)),
+                        })
+                    }
+                    None if hash == left.hash => next_level.push(left),
+                    None => return Err(format_error("promoted node hash does not match")),
+                }
+            }
+            nodes = next_level;
+        }
+
+        if reader.read(&mut [0u8; 1])? != 0 {
+            return Err(format_error("trailing data after level data"));
+        }
+
+        Ok(MerkleTree {
+            root: nodes.pop().expect("leaf count is non-zero"),
+            leaves: HashMap::new(),
+            leaf_hashes,
+            hash_algorithm,
+            sorted_pairs: hasher.sorted_pairs,
+            odd_nodes: hasher.odd_nodes,
+            domain_separation: hasher.domain_separation,
+            limits: DeserializeLimits::default(),
+            allow_64_byte_leaves: false,
+            strict: false,
+        })
+    }
+
+    /// Saves the tree to `path` in the compact binary format.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MerkleTreeError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_compact(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Loads a tree saved with [`MerkleTree::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MerkleTreeError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        Self::read_compact(&mut reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::MerkleTreeBuilder;
+
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| format!("leaf {i}").into_bytes())
+            .collect()
+    }
+
+    fn compact(tree: &MerkleTree) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        tree.write_compact(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trips_every_configuration() {
+        let builder = MerkleTreeBuilder::new().retain_leaf_data(false);
+        for builder in [
+            builder.clone(),
+            builder.clone().sorted_pairs(true),
+            builder.clone().odd_nodes(OddNodePolicy::Duplicate),
+            builder
+                .clone()
+                .domain_separation(true)
+                .hash_algorithm(HashAlgorithm::Sha256),
+        ] {
+            for leaf_count in 1..=9 {
+                let tree = builder.build(&leaves(leaf_count)).unwrap();
+                let read = MerkleTree::read_compact(&mut compact(&tree).as_slice()).unwrap();
+                assert_eq!(read, tree, "{builder:?}, {leaf_count} leaves");
+            }
+        }
+
+        let tree = builder.build(&leaves(5)).unwrap();
+        let path = std::env::temp_dir().join(format!("merkle_tree_{}.mrkl", std::process::id()));
+        tree.save(&path).unwrap();
+        let loaded = MerkleTree::load(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.unwrap().root_hash(), tree.root_hash());
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let tree = MerkleTreeBuilder::new().build(&leaves(3)).unwrap();
+        let bytes = compact(&tree);
+        let read = |bytes: &[u8]| MerkleTree::read_compact(&mut &bytes[..]);
+
+        let tampered = |offset: usize, byte: u8| {
+            let mut bytes = bytes.clone();
+            bytes[offset] = byte;
+            read(&bytes)
+        };
+        // Magic, version, hasher id, flags, and the copy of the promoted leaf
+        for (offset, byte) in [(0, b'X'), (4, 2), (6, 0xff), (7, 0x80), (144, 0)] {
+            assert!(
+                matches!(tampered(offset, byte), Err(MerkleTreeError::FormatError(_))),
+                "byte {offset}"
+            );
+        }
+        assert!(matches!(
+            read(&[&bytes[..8], &[0; 8]].concat()),
+            Err(MerkleTreeError::EmptyData)
+        ));
+
+        for len in [0, 4, HEADER_SIZE, bytes.len() - 1] {
+            assert!(read(&bytes[..len]).is_err(), "{len} bytes");
+        }
+        assert!(matches!(
+            read(&[&bytes[..], &[0]].concat()),
+            Err(MerkleTreeError::FormatError(_))
+        ));
+    }
+}
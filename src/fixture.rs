@@ -0,0 +1,212 @@
+//! Fixtures for differential tests between this crate and Solidity verifiers.
+//!
+//! A [`Fixture`] holds a tree's root and proofs of some of its leaves. It is written either
+//! as a Foundry test calling a verifier generated by
+//! [`generate_verifier`](crate::solidity::generate_verifier), or as JSON for `vm.parseJson`.
+//! Hex in both is always `0x`-prefixed, whatever the [`HashFormat`] in effect, since
+//! Solidity and Foundry require it.
+//!
+//! [`HashFormat`]: crate::serialization::HashFormat
+
+use crate::error::MerkleTreeError;
+use crate::hasher::{HashAlgorithm, TreeOption};
+use crate::merkle_tree::MerkleTree;
+use crate::proof::ProofStep;
+use crate::solidity::{proof_sides, VerifierOptions};
+
+use alloy_primitives::hex::encode_prefixed;
+use alloy_primitives::{B256, U256};
+use serde_json::json;
+use std::fmt::Write;
+
+/// A tree's root and proofs of a sample of its leaves.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Fixture {
+    pub root: B256,
+    pub leaf_count: usize,
+    pub hash_algorithm: HashAlgorithm,
+    pub domain_separation: bool,
+    pub sorted_pairs: bool,
+    pub samples: Vec<FixtureLeaf>,
+}
+
+/// A sampled leaf and its proof.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FixtureLeaf {
+    /// Position of the leaf in the tree.
+    pub index: usize,
+    /// The leaf data, if the tree still holds it.
+    pub data: Option<Vec<u8>>,
+    pub leaf_hash: B256,
+    /// Sibling hashes from the leaf up to the root.
+    pub proof: Vec<B256>,
+    /// Sides of the siblings, as taken by verifiers for unsorted pairs.
+    pub sides: U256,
+}
+
+impl Fixture {
+    /// Samples `count` leaves of `tree`, spread evenly from the first to the last, or all of
+    /// them if it has no more than `count`.
+    pub fn sample(tree: &MerkleTree, count: usize) -> Result<Self, MerkleTreeError> {
+        let leaf_count = tree.leaf_count();
+        let indices: Vec<usize> = if count >= leaf_count {
+            (0..leaf_count).collect()
+        } else if count == 1 {
+            vec![0]
+        } else {
+            (0..count)
+                .map(|i| i * (leaf_count - 1) / (count - 1))
+                .collect()
+        };
+
+        let samples = indices
+            .into_iter()
+            .map(|index| {
+                let proof = tree.generate_proof_by_index(index)?;
+                Ok(FixtureLeaf {
+                    index,
+                    data: tree.leaves.get(&proof.leaf_hash).cloned(),
+                    leaf_hash: proof.leaf_hash,
+                    proof: proof
+                        .proof_steps
+                        .iter()
+                        .map(|step| match step {
+                            ProofStep::Left(hash) | ProofStep::Right(hash) => *hash,
+                        })
+                        .collect(),
+                    sides: proof_sides(&proof)?,
+                })
+            })
+            .collect::<Result<Vec<_>, MerkleTreeError>>()?;
+
+        Ok(Fixture {
+            root: *tree.root_hash(),
+            leaf_count,
+            hash_algorithm: tree.hash_algorithm,
+            domain_separation: tree.domain_separation,
+            sorted_pairs: tree.sorted_pairs,
+            samples,
+        })
+    }
+
+    /// Serializes the fixture to a JSON string.
+    ///
+    /// Each sample's keys are in alphabetical order, so `vm.parseJson` can decode the
+    /// samples into a struct of `(bytes data, uint256 index, bytes32 leafHash, bytes32[]
+    /// proof, uint256 sides)`.
+    pub fn to_json(&self) -> Result<String, MerkleTreeError> {
+        let samples: Vec<_> = self
+            .samples
+            .iter()
+            .map(|sample| {
+                json!({
+                    "data": encode_prefixed(sample.data.as_deref().unwrap_or_default()),
+                    "index": sample.index,
+                    "leafHash": encode_prefixed(sample.leaf_hash),
+                    "proof": sample.proof.iter().map(encode_prefixed).collect::<Vec<_>>(),
+                    "sides": format!("{:#x}", sample.sides),
+                })
+            })
+            .collect();
+        serde_json::to_string_pretty(&json!({
+            "root": encode_prefixed(self.root),
+            "leafCount": self.leaf_count,
+            "hashAlgorithm": self.hash_algorithm.to_string(),
+            "domainSeparation": self.domain_separation,
+            "sortedPairs": self.sorted_pairs,
+            "samples": samples,
+        }))
+        .map_err(MerkleTreeError::SerdeError)
+    }
+
+    /// Generates a Foundry test checking every sample against the verifier generated with
+    /// `options`, imported from `verifier_path`.
+    ///
+    /// Samples whose data is known are also checked against the verifier's `leafHash` when
+    /// it takes raw bytes.
+    pub fn to_foundry_test(
+        &self,
+        options: &VerifierOptions,
+        verifier_path: &str,
+    ) -> Result<String, MerkleTreeError> {
+        if options.hash_algorithm != self.hash_algorithm {
+            return Err(MerkleTreeError::AlgorithmMismatch {
+                expected: self.hash_algorithm,
+                got: options.hash_algorithm,
+            });
+        }
+        if options.domain_separation != self.domain_separation {
+            return Err(MerkleTreeError::OptionMismatch {
+                expected: TreeOption::DomainSeparation(self.domain_separation),
+                got: TreeOption::DomainSeparation(options.domain_separation),
+            });
+        }
+        if options.sorted_pairs != self.sorted_pairs {
+            return Err(MerkleTreeError::OptionMismatch {
+                expected: TreeOption::SortedPairs(self.sorted_pairs),
+                got: TreeOption::SortedPairs(options.sorted_pairs),
+            });
+        }
+        let name = &options.name;
+        let sides = |sample: &FixtureLeaf| {
+            if self.sorted_pairs {
+                String::new()
+            } else {
+                format!("{:#x}, ", sample.sides)
+            }
+        };
+
+        let mut out = String::new();
+        let _ = writeln!(out, "// SPDX-License-Identifier: MIT");
+        let _ = writeln!(out, "pragma solidity ^0.8.20;");
+        let _ = writeln!(out);
+        let _ = writeln!(out, "import {{Test}} from \"forge-std/Test.sol\";");
+        let _ = writeln!(out, "import {{{}}} from \"{}\";", name, verifier_path);
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "/// @notice Proofs of {} of the {} leaves of a tree, generated by merkle_tree.",
+            self.samples.len(),
+            self.leaf_count
+        );
+        let _ = writeln!(out, "contract {}Test is Test {{", name);
+        let _ = writeln!(out, "    bytes32 constant ROOT = {};", self.root);
+
+        for sample in &self.samples {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "    function test_proof_{}() public {{", sample.index);
+            let _ = writeln!(
+                out,
+                "        bytes32[] memory proof = new bytes32[]({});",
+                sample.proof.len()
+            );
+            for (i, hash) in sample.proof.iter().enumerate() {
+                let _ = writeln!(out, "        proof[{}] = {};", i, hash);
+            }
+            let _ = writeln!(out, "        bytes32 leaf = {};", sample.leaf_hash);
+            if let (Some(data), None) = (&sample.data, &options.leaf_encoding) {
+                let _ = writeln!(
+                    out,
+                    "        assertEq({}.leafHash(hex\"{}\"), leaf);",
+                    name,
+                    alloy_primitives::hex::encode(data)
+                );
+            }
+            let _ = writeln!(
+                out,
+                "        assertTrue({}.verify(proof, {}ROOT, leaf));",
+                name,
+                sides(sample)
+            );
+            let _ = writeln!(
+                out,
+                "        assertFalse({}.verify(proof, {}~ROOT, leaf));",
+                name,
+                sides(sample)
+            );
+            let _ = writeln!(out, "    }}");
+        }
+        let _ = writeln!(out, "}}");
+        Ok(out)
+    }
+}
@@ -0,0 +1,272 @@
+//! Hash functions for leaves and internal nodes.
+//!
+//! Trees default to keccak256, matching Solidity. SHA-256 and BLAKE3 are available for
+//! interoperating with non-EVM systems; a tree records which one built it, and proofs carry
+//! it so they are verified with the same function.
+//!
+//! Trees built with domain separation prefix leaf data with [`LEAF_PREFIX`] and pairs of
+//! child hashes with [`NODE_PREFIX`] before hashing, as RFC 6962 does, so a leaf can never
+//! hash like an internal node.
+
+#[cfg(feature = "std")]
+use crate::error::MerkleTreeError;
+use alloc::format;
+use alloc::string::String;
+use alloy_primitives::{keccak256, B256};
+use core::fmt;
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Byte prepended to leaf data in trees built with domain separation.
+pub const LEAF_PREFIX: u8 = 0x00;
+
+/// Byte prepended to pairs of child hashes in trees built with domain separation.
+pub const NODE_PREFIX: u8 = 0x01;
+
+/// A hash function used to build a tree.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum HashAlgorithm {
+    /// Keccak-256, as used by the EVM.
+    #[default]
+    Keccak256,
+    /// SHA-256.
+    Sha256,
+    /// BLAKE3 with a 32-byte output.
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// Returns the algorithm's name, as written in dumps and accepted by [`FromStr`].
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Keccak256 => "keccak256",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// Returns the id stored in binary formats.
+    pub fn id(self) -> u8 {
+        match self {
+            HashAlgorithm::Keccak256 => 1,
+            HashAlgorithm::Sha256 => 2,
+            HashAlgorithm::Blake3 => 3,
+        }
+    }
+
+    /// Returns the algorithm with the given binary id.
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(HashAlgorithm::Keccak256),
+            2 => Some(HashAlgorithm::Sha256),
+            3 => Some(HashAlgorithm::Blake3),
+            _ => None,
+        }
+    }
+
+    /// Hashes `data`.
+    pub fn hash(self, data: &[u8]) -> B256 {
+        match self {
+            HashAlgorithm::Keccak256 => keccak256(data),
+            HashAlgorithm::Sha256 => B256::from_slice(&Sha256::digest(data)),
+            HashAlgorithm::Blake3 => B256::from(*blake3::hash(data).as_bytes()),
+        }
+    }
+
+    /// Hashes `prefix` followed by `data`, without copying `data`.
+    pub fn hash_prefixed(self, prefix: u8, data: &[u8]) -> B256 {
+        match self {
+            HashAlgorithm::Keccak256 => {
+                let mut hasher = alloy_primitives::Keccak256::new();
+                hasher.update([prefix]);
+                hasher.update(data);
+                hasher.finalize()
+            }
+            HashAlgorithm::Sha256 => B256::from_slice(
+                &Sha256::new()
+                    .chain_update([prefix])
+                    .chain_update(data)
+                    .finalize(),
+            ),
+            HashAlgorithm::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update(&[prefix]);
+                hasher.update(data);
+                B256::from(*hasher.finalize().as_bytes())
+            }
+        }
+    }
+
+    /// Hashes two child hashes into their parent hash using a stack buffer.
+    pub fn hash_pair(self, left: &B256, right: &B256) -> B256 {
+        let mut buffer = [0u8; 64];
+        buffer[..32].copy_from_slice(left.as_slice());
+        buffer[32..].copy_from_slice(right.as_slice());
+        self.hash(&buffer)
+    }
+
+    /// Hashes two child hashes in ascending order, so the result does not depend on which
+    /// one is on the left.
+    pub fn hash_sorted_pair(self, a: &B256, b: &B256) -> B256 {
+        if a <= b {
+            self.hash_pair(a, b)
+        } else {
+            self.hash_pair(b, a)
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "keccak256" | "keccak" => Ok(HashAlgorithm::Keccak256),
+            "sha256" | "sha-256" => Ok(HashAlgorithm::Sha256),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            other => Err(format!(
+                "unknown hash algorithm '{}', expected keccak256, sha256 or blake3",
+                other
+            )),
+        }
+    }
+}
+
+/// What happens to the last node of a level with an odd number of nodes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum OddNodePolicy {
+    /// Carry the node up to the next level unchanged.
+    #[default]
+    Promote,
+    /// Pair the node with a copy of itself, as Bitcoin does.
+    Duplicate,
+}
+
+impl fmt::Display for OddNodePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OddNodePolicy::Promote => write!(f, "promote"),
+            OddNodePolicy::Duplicate => write!(f, "duplicate"),
+        }
+    }
+}
+
+impl FromStr for OddNodePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "promote" => Ok(OddNodePolicy::Promote),
+            "duplicate" => Ok(OddNodePolicy::Duplicate),
+            other => Err(format!(
+                "unknown odd node policy '{}', expected promote or duplicate",
+                other
+            )),
+        }
+    }
+}
+
+/// A setting of a tree other than its hash function, with its value.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TreeOption {
+    /// Whether each pair is sorted before hashing.
+    SortedPairs(bool),
+    /// What happens to the last node of each odd level.
+    OddNodes(OddNodePolicy),
+    /// Whether leaves and nodes are prefixed before hashing.
+    DomainSeparation(bool),
+}
+
+impl fmt::Display for TreeOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeOption::SortedPairs(sorted) => write!(f, "sorted_pairs = {}", sorted),
+            TreeOption::OddNodes(policy) => write!(f, "odd_nodes = {}", policy),
+            TreeOption::DomainSeparation(prefixed) => {
+                write!(f, "domain_separation = {}", prefixed)
+            }
+        }
+    }
+}
+
+/// How a tree hashes its leaves and joins nodes into their parents: the hash function,
+/// whether leaves and nodes are prefixed, whether pairs are sorted, and what happens to odd
+/// nodes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) struct TreeHasher {
+    pub(crate) hash_algorithm: HashAlgorithm,
+    pub(crate) domain_separation: bool,
+    pub(crate) sorted_pairs: bool,
+    pub(crate) odd_nodes: OddNodePolicy,
+}
+
+impl TreeHasher {
+    /// Hashes raw leaf data into a leaf hash.
+    #[cfg(feature = "std")]
+    pub(crate) fn hash_leaf(self, data: &[u8]) -> B256 {
+        if self.domain_separation {
+            self.hash_algorithm.hash_prefixed(LEAF_PREFIX, data)
+        } else {
+            self.hash_algorithm.hash(data)
+        }
+    }
+
+    /// Hashes two child hashes into their parent hash.
+    pub(crate) fn hash_pair(self, left: &B256, right: &B256) -> B256 {
+        let (left, right) = if self.sorted_pairs && right < left {
+            (right, left)
+        } else {
+            (left, right)
+        };
+        if self.domain_separation {
+            let mut buffer = [0u8; 64];
+            buffer[..32].copy_from_slice(left.as_slice());
+            buffer[32..].copy_from_slice(right.as_slice());
+            self.hash_algorithm.hash_prefixed(NODE_PREFIX, &buffer)
+        } else {
+            self.hash_algorithm.hash_pair(left, right)
+        }
+    }
+
+    /// Returns whether the last node of an odd level is paired with a copy of itself.
+    pub(crate) fn duplicates_odd(self) -> bool {
+        self.odd_nodes == OddNodePolicy::Duplicate
+    }
+
+    /// Fails unless `got` hashes trees the same way, naming the first setting that differs.
+    #[cfg(feature = "std")]
+    pub(crate) fn check_matches(self, got: TreeHasher) -> Result<(), MerkleTreeError> {
+        if got.hash_algorithm != self.hash_algorithm {
+            return Err(MerkleTreeError::AlgorithmMismatch {
+                expected: self.hash_algorithm,
+                got: got.hash_algorithm,
+            });
+        }
+        let options = |hasher: TreeHasher| {
+            [
+                TreeOption::SortedPairs(hasher.sorted_pairs),
+                TreeOption::OddNodes(hasher.odd_nodes),
+                TreeOption::DomainSeparation(hasher.domain_separation),
+            ]
+        };
+        match options(self)
+            .into_iter()
+            .zip(options(got))
+            .find(|(a, b)| a != b)
+        {
+            Some((expected, got)) => Err(MerkleTreeError::OptionMismatch { expected, got }),
+            None => Ok(()),
+        }
+    }
+}
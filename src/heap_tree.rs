@@ -0,0 +1,380 @@
+use crate::builder::MerkleTreeBuilder;
+use crate::error::MerkleTreeError;
+use crate::hasher::TreeHasher;
+use crate::proof::{MerkleProof, ProofStep};
+use crate::verify::hashes_equal;
+
+use alloy_primitives::B256;
+
+/// A complete binary Merkle Tree stored as a single array using heap indexing.
+///
+/// The leaf level is padded with zero hashes up to the next power of two. Node `1` is the
+/// root, node `i` has children `2i` and `2i + 1`, and leaf `k` lives at `width + k`, so
+/// navigating the tree is pure arithmetic. Index `0` is unused.
+///
+/// Trees built with [`MerkleTreeBuilder::build_heap`](crate::builder::MerkleTreeBuilder::build_heap)
+/// use its hash algorithm, pair sorting and domain separation. Padding makes the odd node
+/// policy irrelevant.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HeapMerkleTree {
+    nodes: Vec<B256>,
+    leaf_count: usize,
+    hasher: TreeHasher,
+}
+
+impl HeapMerkleTree {
+    /// Builds a tree from a list of data items, hashing each one into a leaf.
+    ///
+    /// Leaves are checked as [`MerkleTreeBuilder::build`] checks them, so 64-byte leaves are
+    /// rejected; build with [`MerkleTreeBuilder::build_heap`] to allow them.
+    pub fn new(data: &[Vec<u8>]) -> Result<Self, MerkleTreeError> {
+        MerkleTreeBuilder::new().build_heap(data)
+    }
+
+    /// Builds a tree from already hashed leaves.
+    pub fn from_leaf_hashes(leaf_hashes: Vec<B256>) -> Result<Self, MerkleTreeError> {
+        Self::from_leaf_hashes_with(leaf_hashes, TreeHasher::default())
+    }
+
+    /// Builds a tree from already hashed leaves, joining nodes with `hasher`.
+    pub(crate) fn from_leaf_hashes_with(
+        leaf_hashes: Vec<B256>,
+        hasher: TreeHasher,
+    ) -> Result<Self, MerkleTreeError> {
+        if leaf_hashes.is_empty() {
+            return Err(MerkleTreeError::EmptyData);
+        }
+
+        let leaf_count = leaf_hashes.len();
+        let width = leaf_count.next_power_of_two();
+        let mut nodes = vec![B256::ZERO; 2 * width];
+        nodes[width..width + leaf_count].copy_from_slice(&leaf_hashes);
+
+        for i in (1..width).rev() {
+            nodes[i] = hasher.hash_pair(&nodes[2 * i], &nodes[2 * i + 1]);
+        }
+
+        Ok(HeapMerkleTree {
+            nodes,
+            leaf_count,
+            hasher,
+        })
+    }
+
+    /// Returns the root hash of the tree.
+    pub fn root_hash(&self) -> &B256 {
+        &self.nodes[1]
+    }
+
+    /// Returns the number of leaves, excluding padding.
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// Returns the number of leaf slots, including padding.
+    pub fn width(&self) -> usize {
+        self.nodes.len() / 2
+    }
+
+    /// Returns the number of levels below the root.
+    pub fn depth(&self) -> usize {
+        self.width().trailing_zeros() as usize
+    }
+
+    /// Returns all nodes in heap order. Index `0` is unused.
+    pub fn nodes(&self) -> &[B256] {
+        &self.nodes
+    }
+
+    /// Returns the hash of the leaf at `index`.
+    pub fn leaf_hash(&self, index: usize) -> Option<&B256> {
+        if index < self.leaf_count {
+            Some(&self.nodes[self.width() + index])
+        } else {
+            None
+        }
+    }
+
+    /// Returns the sibling hashes from the leaf at `index` up to the root.
+    pub fn branch(&self, index: usize) -> Result<Vec<B256>, MerkleTreeError> {
+        if index >= self.leaf_count {
+            return Err(MerkleTreeError::IndexOutOfRange {
+                index,
+                leaf_count: self.leaf_count,
+            });
+        }
+
+        let mut branch = Vec::with_capacity(self.depth());
+        let mut i = self.width() + index;
+        while i > 1 {
+            branch.push(self.nodes[i ^ 1]);
+            i /= 2;
+        }
+        Ok(branch)
+    }
+
+    /// Generates a Merkle Proof for the leaf at `index`.
+    pub fn generate_proof(&self, index: usize) -> Result<MerkleProof, MerkleTreeError> {
+        let branch = self.branch(index)?;
+        let mut i = self.width() + index;
+        let leaf_hash = self.nodes[i];
+
+        let proof_steps = branch
+            .into_iter()
+            .map(|sibling| {
+                let step = if i.is_multiple_of(2) {
+                    ProofStep::Right(sibling)
+                } else {
+                    ProofStep::Left(sibling)
+                };
+                i /= 2;
+                step
+            })
+            .collect();
+
+        Ok(MerkleProof {
+            leaf_hash,
+            proof_steps,
+            hash_algorithm: self.hasher.hash_algorithm,
+            sorted_pairs: self.hasher.sorted_pairs,
+            domain_separation: self.hasher.domain_separation,
+        })
+    }
+
+    /// Verifies a branch produced by [`HeapMerkleTree::branch`] against `root_hash`, for
+    /// a tree of `leaf_count` leaves.
+    ///
+    /// The bits of `index` select the side of each sibling, so no proof directions are needed.
+    /// Indices of the zero padding and branches not reaching down to the leaf level are
+    /// rejected. Nodes are joined as [`HeapMerkleTree::new`] joins them; use
+    /// [`HeapMerkleTree::verifies_branch`] for trees built with other options.
+    pub fn verify_branch(
+        leaf_hash: &B256,
+        index: usize,
+        leaf_count: usize,
+        branch: &[B256],
+        root_hash: &B256,
+    ) -> bool {
+        verify_branch_with(
+            TreeHasher::default(),
+            leaf_hash,
+            index,
+            leaf_count,
+            branch,
+            root_hash,
+        )
+    }
+
+    /// Verifies a branch against `root_hash`, joining nodes as this tree does and rejecting
+    /// indices past its leaves.
+    pub fn verifies_branch(
+        &self,
+        leaf_hash: &B256,
+        index: usize,
+        branch: &[B256],
+        root_hash: &B256,
+    ) -> bool {
+        verify_branch_with(
+            self.hasher,
+            leaf_hash,
+            index,
+            self.leaf_count,
+            branch,
+            root_hash,
+        )
+    }
+
+    /// Verifies that every internal node matches the hash of its children.
+    pub fn verify(&self) -> bool {
+        (1..self.width()).all(|i| {
+            let expected_hash = self
+                .hasher
+                .hash_pair(&self.nodes[2 * i], &self.nodes[2 * i + 1]);
+            hashes_equal(&self.nodes[i], &expected_hash)
+        })
+    }
+}
+
+/// Walks `branch` up from `leaf_hash` with `hasher`, taking sides from the bits of `index`.
+fn verify_branch_with(
+    hasher: TreeHasher,
+    leaf_hash: &B256,
+    index: usize,
+    leaf_count: usize,
+    branch: &[B256],
+    root_hash: &B256,
+) -> bool {
+    if index >= leaf_count
+        || branch.len() != leaf_count.next_power_of_two().trailing_zeros() as usize
+    {
+        return false;
+    }
+
+    let mut computed_hash = *leaf_hash;
+    let mut i = index;
+
+    for sibling in branch {
+        computed_hash = if i.is_multiple_of(2) {
+            hasher.hash_pair(&computed_hash, sibling)
+        } else {
+            hasher.hash_pair(sibling, &computed_hash)
+        };
+        i /= 2;
+    }
+
+    hashes_equal(&computed_hash, root_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::HashAlgorithm;
+    use crate::merkle_tree::MerkleTree;
+
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| format!("leaf {i}").into_bytes())
+            .collect()
+    }
+
+    /// Builders for every hash algorithm, with and without sorting and domain separation.
+    fn builders() -> Vec<MerkleTreeBuilder> {
+        let mut builders = Vec::new();
+        for algorithm in [
+            HashAlgorithm::Keccak256,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Blake3,
+        ] {
+            for sorted in [false, true] {
+                for separated in [false, true] {
+                    builders.push(
+                        MerkleTreeBuilder::new()
+                            .hash_algorithm(algorithm)
+                            .sorted_pairs(sorted)
+                            .domain_separation(separated),
+                    );
+                }
+            }
+        }
+        builders
+    }
+
+    #[test]
+    fn root_matches_merkle_tree_for_full_trees() {
+        // Without padding, the heap tree is the same tree as a MerkleTree over the leaves
+        for builder in builders() {
+            for leaf_count in [1, 2, 4, 8, 16] {
+                let heap = builder.build_heap(&leaves(leaf_count)).unwrap();
+                let tree = builder.build(&leaves(leaf_count)).unwrap();
+                assert_eq!(heap.root_hash(), tree.root_hash());
+                assert!(heap.verify());
+            }
+        }
+        let heap = HeapMerkleTree::new(&leaves(8)).unwrap();
+        assert_eq!(
+            heap.root_hash(),
+            MerkleTree::new(&leaves(8)).unwrap().root_hash()
+        );
+    }
+
+    #[test]
+    fn root_matches_merkle_tree_over_padded_leaves() {
+        for leaf_count in 1..=11 {
+            let heap = HeapMerkleTree::new(&leaves(leaf_count)).unwrap();
+            let mut leaf_hashes: Vec<B256> = (0..leaf_count)
+                .map(|index| *heap.leaf_hash(index).unwrap())
+                .collect();
+            leaf_hashes.resize(heap.width(), B256::ZERO);
+            let tree = MerkleTree::from_leaf_hashes(leaf_hashes).unwrap();
+            assert_eq!(heap.root_hash(), tree.root_hash());
+        }
+    }
+
+    #[test]
+    fn proofs_round_trip() {
+        for builder in builders() {
+            for leaf_count in 1..=11 {
+                let heap = builder.build_heap(&leaves(leaf_count)).unwrap();
+                let root = heap.root_hash();
+                for index in 0..leaf_count {
+                    let proof = heap.generate_proof(index).unwrap();
+                    assert_eq!(&proof.leaf_hash, heap.leaf_hash(index).unwrap());
+                    assert!(proof.verify(root).unwrap());
+
+                    let branch = heap.branch(index).unwrap();
+                    assert!(heap.verifies_branch(&proof.leaf_hash, index, &branch, root));
+                }
+                assert!(heap.generate_proof(leaf_count).is_err());
+            }
+        }
+
+        let heap = HeapMerkleTree::new(&leaves(5)).unwrap();
+        let branch = heap.branch(3).unwrap();
+        let leaf_hash = heap.leaf_hash(3).unwrap();
+        assert!(HeapMerkleTree::verify_branch(
+            leaf_hash,
+            3,
+            5,
+            &branch,
+            heap.root_hash()
+        ));
+        assert!(!HeapMerkleTree::verify_branch(
+            leaf_hash,
+            2,
+            5,
+            &branch,
+            heap.root_hash()
+        ));
+    }
+
+    #[test]
+    fn rejects_padding_indices() {
+        let heap = HeapMerkleTree::new(&leaves(5)).unwrap();
+        let root = heap.root_hash();
+        assert_eq!(heap.width(), 8);
+        for index in 5..8 {
+            // The branch of a padding slot, which would prove a zero leaf
+            let mut i = heap.width() + index;
+            let mut branch = Vec::new();
+            while i > 1 {
+                branch.push(heap.nodes()[i ^ 1]);
+                i /= 2;
+            }
+            assert!(!heap.verifies_branch(&B256::ZERO, index, &branch, root));
+            assert!(!HeapMerkleTree::verify_branch(
+                &B256::ZERO,
+                index,
+                5,
+                &branch,
+                root
+            ));
+            assert!(heap.branch(index).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_short_branches() {
+        // Stopping one level early would prove an internal node as a leaf
+        let heap = HeapMerkleTree::new(&leaves(4)).unwrap();
+        let node = heap.nodes()[2];
+        let branch = [heap.nodes()[3]];
+        assert!(!heap.verifies_branch(&node, 0, &branch, heap.root_hash()));
+    }
+
+    #[test]
+    fn rejects_64_byte_leaves() {
+        let data = vec![b"a".to_vec(), vec![7; 64]];
+        assert!(matches!(
+            HeapMerkleTree::new(&data),
+            Err(MerkleTreeError::AmbiguousLeaf { index: 1 })
+        ));
+        MerkleTreeBuilder::new()
+            .domain_separation(true)
+            .build_heap(&data)
+            .unwrap();
+        MerkleTreeBuilder::new()
+            .allow_64_byte_leaves(true)
+            .build_heap(&data)
+            .unwrap();
+    }
+}
@@ -0,0 +1,555 @@
+//! Trees as IPLD DAG-CBOR blocks, content-addressed by CID.
+//!
+//! Every node becomes a block: `{"hash": <32 bytes>, "left": <link>, "right": <link>}` for
+//! internal nodes and `{"data": <bytes>, "hash": <32 bytes>}` for leaves, `data` being
+//! present only if the tree holds the leaf data. Links are CIDv1 with the SHA-256 of the
+//! block, so a light client that trusts the root CID can fetch any path block by block from
+//! IPFS. Blocks are exported as a CARv1 file, which `ipfs dag import` accepts, and
+//! [`IpldExport::from_car`] and [`Block::decode`] read them back.
+
+use crate::error::MerkleTreeError;
+use crate::merkle_node::MerkleNode;
+use crate::merkle_tree::MerkleTree;
+
+use alloy_primitives::B256;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt;
+
+/// Multicodec of DAG-CBOR blocks.
+const DAG_CBOR: u8 = 0x71;
+/// Multihash code and length of SHA-256.
+const SHA2_256: u8 = 0x12;
+const SHA2_256_LENGTH: u8 = 32;
+/// CBOR tag of IPLD links.
+const CID_TAG: u64 = 42;
+/// Version of the CAR files written.
+const CAR_VERSION: u64 = 1;
+
+/// CBOR major types.
+const UNSIGNED: u8 = 0;
+const BYTES: u8 = 2;
+const TEXT: u8 = 3;
+const ARRAY: u8 = 4;
+const MAP: u8 = 5;
+const TAG: u8 = 6;
+
+/// A CIDv1 of a DAG-CBOR block hashed with SHA-256.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Cid([u8; 36]);
+
+impl Cid {
+    /// Returns the CID of the DAG-CBOR block `data`.
+    pub fn for_block(data: &[u8]) -> Self {
+        let mut bytes = [0u8; 36];
+        bytes[..4].copy_from_slice(&[0x01, DAG_CBOR, SHA2_256, SHA2_256_LENGTH]);
+        bytes[4..].copy_from_slice(&Sha256::digest(data));
+        Cid(bytes)
+    }
+
+    /// Parses the binary form of a CIDv1 of a DAG-CBOR block hashed with SHA-256.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        match <[u8; 36]>::try_from(bytes) {
+            Ok(cid) if cid[..4] == [0x01, DAG_CBOR, SHA2_256, SHA2_256_LENGTH] => Ok(Cid(cid)),
+            _ => Err(format_error(
+                "expected a CIDv1 of a DAG-CBOR block hashed with SHA-256",
+            )),
+        }
+    }
+
+    /// Returns the binary form of the CID.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the SHA-256 digest of the block.
+    fn digest(&self) -> B256 {
+        B256::from_slice(&self.0[4..])
+    }
+}
+
+/// Displays the CID as base32, the default multibase of CIDv1, e.g. `bafyrei…`.
+impl fmt::Display for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+        let mut out = String::with_capacity(1 + (self.0.len() * 8).div_ceil(5));
+        out.push('b');
+        let (mut buffer, mut bits) = (0u16, 0);
+        for byte in self.0 {
+            buffer = (buffer << 8) | u16::from(byte);
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                out.push(ALPHABET[usize::from((buffer >> bits) & 0x1f)] as char);
+            }
+        }
+        if bits > 0 {
+            out.push(ALPHABET[usize::from((buffer << (5 - bits)) & 0x1f)] as char);
+        }
+        f.write_str(&out)
+    }
+}
+
+/// An encoded block and its CID.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Block {
+    pub cid: Cid,
+    pub data: Vec<u8>,
+}
+
+/// A node decoded from its block.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct IpldNode {
+    pub hash: B256,
+    /// Links to the left and right child, or `None` for a leaf.
+    pub children: Option<(Cid, Cid)>,
+    /// Raw leaf data, if the tree held it.
+    pub data: Option<Vec<u8>>,
+}
+
+impl Block {
+    /// Decodes the node the block encodes.
+    pub fn decode(&self) -> Result<IpldNode, MerkleTreeError> {
+        let mut reader = Reader::new(&self.data);
+        let (mut hash, mut left, mut right, mut data) = (None, None, None, None);
+        for _ in 0..reader.expect(MAP)? {
+            match reader.text()? {
+                "hash" => {
+                    let bytes = reader.expect_bytes()?;
+                    hash = Some(
+                        B256::try_from(bytes)
+                            .map_err(|_| format_error("node hash is not 32 bytes"))?,
+                    );
+                }
+                "left" => left = Some(reader.link()?),
+                "right" => right = Some(reader.link()?),
+                "data" => data = Some(reader.expect_bytes()?.to_vec()),
+                key => return Err(format_error(format!("unexpected node field {:?}", key))),
+            }
+        }
+        reader.finish()?;
+
+        let hash = hash.ok_or_else(|| format_error("node has no hash"))?;
+        let children = match (left, right, &data) {
+            (Some(left), Some(right), None) => Some((left, right)),
+            (None, None, _) => None,
+            _ => {
+                return Err(format_error(
+                    "a node links both children or none, and only leaves hold data",
+                ))
+            }
+        };
+        Ok(IpldNode {
+            hash,
+            children,
+            data,
+        })
+    }
+}
+
+/// The blocks of a tree, children before their parents.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct IpldExport {
+    /// CID of the root node's block.
+    pub root: Cid,
+    /// Every distinct block, each once even if identical subtrees share it.
+    pub blocks: Vec<Block>,
+}
+
+impl IpldExport {
+    /// Serializes the blocks as a CARv1 file rooted at the root node.
+    pub fn to_car(&self) -> Vec<u8> {
+        let mut header = Vec::new();
+        write_head(MAP, 2, &mut header);
+        write_text("roots", &mut header);
+        write_head(ARRAY, 1, &mut header);
+        write_link(&self.root, &mut header);
+        write_text("version", &mut header);
+        write_head(UNSIGNED, CAR_VERSION, &mut header);
+
+        let mut car = Vec::new();
+        write_varint(header.len() as u64, &mut car);
+        car.extend_from_slice(&header);
+        for block in &self.blocks {
+            write_varint((block.cid.0.len() + block.data.len()) as u64, &mut car);
+            car.extend_from_slice(&block.cid.0);
+            car.extend_from_slice(&block.data);
+        }
+        car
+    }
+
+    /// Reads a CARv1 file written by [`IpldExport::to_car`], checking that it has a single
+    /// root and that every block matches its CID.
+    pub fn from_car(car: &[u8]) -> Result<Self, MerkleTreeError> {
+        let mut reader = Reader::new(car);
+        let header_len = reader.varint()?;
+        let mut header = Reader::new(reader.bytes(header_len)?);
+        let (mut roots, mut version) = (Vec::new(), None);
+        for _ in 0..header.expect(MAP)? {
+            match header.text()? {
+                "roots" => {
+                    for _ in 0..header.expect(ARRAY)? {
+                        roots.push(header.link()?);
+                    }
+                }
+                "version" => version = Some(header.expect(UNSIGNED)?),
+                key => {
+                    return Err(format_error(format!(
+                        "unexpected CAR header field {:?}",
+                        key
+                    )))
+                }
+            }
+        }
+        header.finish()?;
+        match version {
+            Some(CAR_VERSION) => {}
+            Some(version) => {
+                return Err(format_error(format!("unsupported CAR version {}", version)))
+            }
+            None => return Err(format_error("CAR header has no version")),
+        }
+        let [root] = roots[..] else {
+            return Err(format_error(format!(
+                "expected one CAR root, got {}",
+                roots.len()
+            )));
+        };
+
+        let mut blocks = Vec::new();
+        while !reader.data.is_empty() {
+            let len = reader.varint()?;
+            let mut section = Reader::new(reader.bytes(len)?);
+            let cid = Cid::from_bytes(section.bytes(36)?)?;
+            let block = Block {
+                cid,
+                data: section.data.to_vec(),
+            };
+            let computed = Cid::for_block(&block.data);
+            if computed != cid {
+                return Err(MerkleTreeError::ChecksumMismatch {
+                    expected: cid.digest(),
+                    got: computed.digest(),
+                });
+            }
+            blocks.push(block);
+        }
+        if !blocks.iter().any(|block| block.cid == root) {
+            return Err(format_error("CAR file does not hold its root block"));
+        }
+        Ok(IpldExport { root, blocks })
+    }
+
+    /// Imports the blocks into an IPFS node through its HTTP API at `api_url`, such as
+    /// `http://127.0.0.1:5001`, pinning the root.
+    #[cfg(feature = "ipfs")]
+    pub fn push(&self, api_url: &str) -> Result<(), MerkleTreeError> {
+        const BOUNDARY: &str = "merkle-tree-car-boundary";
+
+        let mut body = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"tree.car\"\r\n\
+             Content-Type: application/vnd.ipld.car\r\n\r\n",
+            BOUNDARY
+        )
+        .into_bytes();
+        body.extend_from_slice(&self.to_car());
+        body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+        let url = format!(
+            "{}/api/v0/dag/import?pin-roots=true",
+            api_url.trim_end_matches('/')
+        );
+        ureq::post(&url)
+            .set(
+                "Content-Type",
+                &format!("multipart/form-data; boundary={}", BOUNDARY),
+            )
+            .send_bytes(&body)
+            .map_err(|e| MerkleTreeError::StorageError(format!("IPFS import failed: {}", e)))?;
+        Ok(())
+    }
+}
+
+impl MerkleTree {
+    /// Encodes the tree as DAG-CBOR blocks.
+    pub fn to_ipld(&self) -> IpldExport {
+        let mut export = IpldExport {
+            root: Cid([0; 36]),
+            blocks: Vec::new(),
+        };
+        let mut seen = HashSet::new();
+        export.root = self.encode_block(&self.root, &mut export.blocks, &mut seen);
+        export
+    }
+
+    /// Encodes `node` and its descendants, returning the CID of its block.
+    fn encode_block(
+        &self,
+        node: &MerkleNode,
+        blocks: &mut Vec<Block>,
+        seen: &mut HashSet<Cid>,
+    ) -> Cid {
+        let mut data = Vec::new();
+        match (&node.left, &node.right) {
+            (Some(left), Some(right)) => {
+                let left = self.encode_block(left, blocks, seen);
+                let right = self.encode_block(right, blocks, seen);
+                // DAG-CBOR sorts keys by length, then bytewise
+                write_head(MAP, 3, &mut data);
+                write_text("hash", &mut data);
+                write_bytes(node.hash.as_slice(), &mut data);
+                write_text("left", &mut data);
+                write_link(&left, &mut data);
+                write_text("right", &mut data);
+                write_link(&right, &mut data);
+            }
+            _ => {
+                let leaf_data = self.leaves.get(&node.hash);
+                write_head(MAP, 1 + u64::from(leaf_data.is_some()), &mut data);
+                if let Some(leaf_data) = leaf_data {
+                    write_text("data", &mut data);
+                    write_bytes(leaf_data, &mut data);
+                }
+                write_text("hash", &mut data);
+                write_bytes(node.hash.as_slice(), &mut data);
+            }
+        }
+
+        let cid = Cid::for_block(&data);
+        if seen.insert(cid) {
+            blocks.push(Block { cid, data });
+        }
+        cid
+    }
+}
+
+/// Writes a CBOR head: the major type and the argument in its shortest form.
+fn write_head(major: u8, value: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, value as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+fn write_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    write_head(BYTES, bytes.len() as u64, out);
+    out.extend_from_slice(bytes);
+}
+
+fn write_text(text: &str, out: &mut Vec<u8>) {
+    write_head(TEXT, text.len() as u64, out);
+    out.extend_from_slice(text.as_bytes());
+}
+
+/// Writes a link: tag 42 on the CID bytes behind the identity multibase prefix.
+fn write_link(cid: &Cid, out: &mut Vec<u8>) {
+    write_head(TAG, CID_TAG, out);
+    write_head(BYTES, cid.0.len() as u64 + 1, out);
+    out.push(0x00);
+    out.extend_from_slice(&cid.0);
+}
+
+/// Writes an unsigned LEB128 varint, as CAR files frame their sections.
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn format_error(message: impl Into<String>) -> MerkleTreeError {
+    MerkleTreeError::FormatError(message.into())
+}
+
+/// A cursor over a CAR file or a DAG-CBOR block.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    fn bytes(&mut self, len: u64) -> Result<&'a [u8], MerkleTreeError> {
+        if len > self.data.len() as u64 {
+            return Err(format_error("unexpected end of IPLD data"));
+        }
+        let (bytes, rest) = self.data.split_at(len as usize);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    /// Reads an unsigned LEB128 varint.
+    fn varint(&mut self) -> Result<u64, MerkleTreeError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.bytes(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(format_error("varint is too large"))
+    }
+
+    /// Reads a CBOR head of the `major` type, returning its argument. DAG-CBOR has no
+    /// indefinite lengths.
+    fn expect(&mut self, major: u8) -> Result<u64, MerkleTreeError> {
+        let initial = self.bytes(1)?[0];
+        if initial >> 5 != major {
+            return Err(format_error(format!(
+                "expected CBOR major type {}, got {}",
+                major,
+                initial >> 5
+            )));
+        }
+        let argument = match initial & 0x1f {
+            value @ 0..=23 => return Ok(u64::from(value)),
+            24 => self.bytes(1)?,
+            25 => self.bytes(2)?,
+            26 => self.bytes(4)?,
+            27 => self.bytes(8)?,
+            _ => return Err(format_error("unsupported CBOR length")),
+        };
+        Ok(argument
+            .iter()
+            .fold(0, |value, &byte| (value << 8) | u64::from(byte)))
+    }
+
+    fn expect_bytes(&mut self) -> Result<&'a [u8], MerkleTreeError> {
+        let len = self.expect(BYTES)?;
+        self.bytes(len)
+    }
+
+    fn text(&mut self) -> Result<&'a str, MerkleTreeError> {
+        let len = self.expect(TEXT)?;
+        std::str::from_utf8(self.bytes(len)?).map_err(|_| format_error("text is not UTF-8"))
+    }
+
+    /// Reads a link written by [`write_link`].
+    fn link(&mut self) -> Result<Cid, MerkleTreeError> {
+        if self.expect(TAG)? != CID_TAG {
+            return Err(format_error("expected an IPLD link"));
+        }
+        match self.expect_bytes()? {
+            [0x00, cid @ ..] => Cid::from_bytes(cid),
+            _ => Err(format_error(
+                "link is not prefixed with the identity multibase",
+            )),
+        }
+    }
+
+    /// Fails if data is left over.
+    fn finish(&self) -> Result<(), MerkleTreeError> {
+        if self.data.is_empty() {
+            Ok(())
+        } else {
+            Err(format_error("trailing data after IPLD value"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::MerkleTreeBuilder;
+    use crate::hasher::OddNodePolicy;
+    use std::collections::HashMap;
+
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| format!("leaf {i}").into_bytes())
+            .collect()
+    }
+
+    /// Rebuilds the subtree linked by `cid` from decoded blocks.
+    fn import(nodes: &HashMap<Cid, IpldNode>, cid: &Cid) -> MerkleNode {
+        let node = &nodes[cid];
+        match node.children {
+            Some((left, right)) => MerkleNode {
+                hash: node.hash,
+                left: Some(Box::new(import(nodes, &left))),
+                right: Some(Box::new(import(nodes, &right))),
+            },
+            None => MerkleNode::leaf(node.hash),
+        }
+    }
+
+    #[test]
+    fn car_files_round_trip() {
+        for (builder, leaf_count) in [
+            (MerkleTreeBuilder::new(), 7),
+            (MerkleTreeBuilder::new().retain_leaf_data(false), 5),
+            (
+                MerkleTreeBuilder::new().odd_nodes(OddNodePolicy::Duplicate),
+                6,
+            ),
+        ] {
+            let tree = builder.build(&leaves(leaf_count)).unwrap();
+            let export = tree.to_ipld();
+            let imported = IpldExport::from_car(&export.to_car()).unwrap();
+            assert_eq!(imported, export);
+
+            let nodes: HashMap<Cid, IpldNode> = imported
+                .blocks
+                .iter()
+                .map(|block| (block.cid, block.decode().unwrap()))
+                .collect();
+            let root = import(&nodes, &imported.root);
+            assert_eq!(root, tree.root);
+            for node in nodes.values() {
+                assert_eq!(node.data.as_ref(), tree.leaves.get(&node.hash));
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_other_versions_and_tampered_blocks() {
+        let car = MerkleTree::new(&leaves(3)).unwrap().to_ipld().to_car();
+
+        // The header is short enough for a one-byte length, and ends with the version
+        let header_len = usize::from(car[0]);
+        let mut version_2 = car.clone();
+        version_2[header_len] = 0x02;
+        assert!(matches!(
+            IpldExport::from_car(&version_2),
+            Err(MerkleTreeError::FormatError(_))
+        ));
+
+        let mut tampered = car.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            IpldExport::from_car(&tampered),
+            Err(MerkleTreeError::ChecksumMismatch { .. })
+        ));
+
+        for len in [0, 1, header_len + 1, car.len() - 1] {
+            assert!(IpldExport::from_car(&car[..len]).is_err(), "{len} bytes");
+        }
+
+        // Well-formed DAG-CBOR that is not a node
+        let empty_map = Block {
+            cid: Cid::for_block(&[0xa0]),
+            data: vec![0xa0],
+        };
+        assert!(matches!(
+            empty_map.decode(),
+            Err(MerkleTreeError::FormatError(_))
+        ));
+    }
+}
@@ -0,0 +1,48 @@
+//! JSON Schema generation for serialized trees and proofs.
+
+use crate::merkle_tree::MerkleTree;
+use crate::proof::MerkleProof;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, StringValidation};
+use schemars::schema_for;
+
+/// Schema for a 32-byte hash encoded as hex, with an optional `0x` prefix.
+pub fn hash_schema(_gen: &mut SchemaGenerator) -> Schema {
+    Schema::Object(SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        string: Some(Box::new(StringValidation {
+            pattern: Some("^(0x)?[0-9a-fA-F]{64}$".to_string()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    })
+}
+
+/// Adds the `schema_version` field that JSON dumps carry alongside the type's own fields.
+fn add_schema_version(schema: &mut RootSchema) {
+    let version = Schema::Object(SchemaObject {
+        instance_type: Some(InstanceType::Integer.into()),
+        format: Some("uint32".to_string()),
+        ..Default::default()
+    });
+    schema
+        .schema
+        .object()
+        .properties
+        .insert("schema_version".to_string(), version);
+}
+
+/// Returns the JSON Schema of a serialized [`MerkleTree`].
+pub fn tree_schema() -> RootSchema {
+    let mut schema = schema_for!(MerkleTree);
+    add_schema_version(&mut schema);
+    schema
+}
+
+/// Returns the JSON Schema of a serialized [`MerkleProof`].
+pub fn proof_schema() -> RootSchema {
+    let mut schema = schema_for!(MerkleProof);
+    add_schema_version(&mut schema);
+    schema
+}
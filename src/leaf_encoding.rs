@@ -0,0 +1,107 @@
+//! Solidity-compatible leaf encodings.
+//!
+//! Claim contracts typically check `keccak256(abi.encodePacked(account, amount))` or
+//! `keccak256(abi.encode(account, amount))` against a Merkle root. A [`LeafEncoding`]
+//! produces the bytes inside that `keccak256`, so the tree's leaf hashes match on-chain.
+
+use crate::error::MerkleTreeError;
+
+use alloy_dyn_abi::{DynSolType, DynSolValue};
+use std::fmt;
+use std::str::FromStr;
+
+/// How a list of Solidity values is encoded into leaf data.
+#[derive(Clone, PartialEq, Debug)]
+pub enum LeafEncoding {
+    /// `abi.encodePacked(...)`, written `packed(address,uint256)`.
+    Packed(Vec<DynSolType>),
+    /// `abi.encode(...)`, written `abi(address,uint256)`.
+    Abi(Vec<DynSolType>),
+}
+
+impl LeafEncoding {
+    /// Returns the Solidity types of the encoded values.
+    pub fn types(&self) -> &[DynSolType] {
+        match self {
+            LeafEncoding::Packed(types) | LeafEncoding::Abi(types) => types,
+        }
+    }
+
+    /// Encodes `values`, given in Solidity literal syntax (e.g. `0xabc...`, `100`).
+    pub fn encode<S: AsRef<str>>(&self, values: &[S]) -> Result<Vec<u8>, MerkleTreeError> {
+        let values = coerce_values(self.types(), values)?;
+        Ok(match self {
+            LeafEncoding::Packed(_) => DynSolValue::Tuple(values).abi_encode_packed(),
+            LeafEncoding::Abi(_) => DynSolValue::Tuple(values).abi_encode_params(),
+        })
+    }
+}
+
+impl fmt::Display for LeafEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LeafEncoding::Packed(_) => "packed",
+            LeafEncoding::Abi(_) => "abi",
+        };
+        let types: Vec<String> = self.types().iter().map(|ty| ty.to_string()).collect();
+        write!(f, "{}({})", name, types.join(","))
+    }
+}
+
+impl FromStr for LeafEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, types) = s
+            .strip_suffix(')')
+            .and_then(|s| s.split_once('('))
+            .ok_or_else(|| {
+                format!(
+                    "invalid leaf encoding '{}', expected e.g. packed(address,uint256)",
+                    s
+                )
+            })?;
+
+        let types = DynSolType::parse(&format!("({})", types))
+            .map_err(|e| format!("invalid leaf encoding types '{}': {}", types, e))?;
+        let types = match types {
+            DynSolType::Tuple(types) if !types.is_empty() => types,
+            _ => return Err(format!("leaf encoding '{}' has no types", s)),
+        };
+
+        match name.trim() {
+            "packed" | "encodePacked" => Ok(LeafEncoding::Packed(types)),
+            "abi" | "encode" => Ok(LeafEncoding::Abi(types)),
+            other => Err(format!(
+                "unknown leaf encoding '{}', expected packed or abi",
+                other
+            )),
+        }
+    }
+}
+
+/// Parses `values` as the corresponding `types`.
+pub(crate) fn coerce_values<S: AsRef<str>>(
+    types: &[DynSolType],
+    values: &[S],
+) -> Result<Vec<DynSolValue>, MerkleTreeError> {
+    if types.len() != values.len() {
+        return Err(MerkleTreeError::AbiError(format!(
+            "expected {} values, got {}",
+            types.len(),
+            values.len()
+        )));
+    }
+
+    types
+        .iter()
+        .zip(values)
+        .map(|(ty, value)| {
+            let value = value.as_ref();
+            ty.coerce_str(value).map_err(|e| {
+                MerkleTreeError::AbiError(format!("invalid {} value '{}': {}", ty, value, e))
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,72 @@
+//! Leaf-list persistence: only the ordered leaf hashes and the tree configuration are
+//! stored, and internal nodes are rebuilt on load.
+//!
+//! The dump is far smaller than a full node dump, and since every internal hash is
+//! recomputed it cannot describe an inconsistent tree.
+
+use crate::error::MerkleTreeError;
+use crate::hasher::{HashAlgorithm, OddNodePolicy};
+use crate::merkle_tree::MerkleTree;
+use crate::schema::{self, Versioned};
+
+use alloy_primitives::B256;
+use serde::{Deserialize, Serialize};
+
+/// Serialized form of a leaf-list dump.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct LeafList {
+    /// Hash function used for leaves and internal nodes.
+    pub hash_algorithm: HashAlgorithm,
+
+    /// Leaf hashes in insertion order.
+    #[serde(with = "crate::serialization::b256_vec_hex")]
+    pub leaf_hashes: Vec<B256>,
+
+    /// Whether each pair of child hashes is sorted before hashing.
+    #[serde(default)]
+    pub sorted_pairs: bool,
+
+    /// What happens to the last node of each odd level.
+    #[serde(default)]
+    pub odd_nodes: OddNodePolicy,
+
+    /// Whether leaves and nodes are prefixed before hashing.
+    #[serde(default)]
+    pub domain_separation: bool,
+}
+
+impl LeafList {
+    /// Rebuilds the tree described by this leaf list.
+    pub fn into_tree(self) -> Result<MerkleTree, MerkleTreeError> {
+        MerkleTree::builder()
+            .hash_algorithm(self.hash_algorithm)
+            .sorted_pairs(self.sorted_pairs)
+            .odd_nodes(self.odd_nodes)
+            .domain_separation(self.domain_separation)
+            .build_from_leaf_hashes(self.leaf_hashes)
+    }
+}
+
+impl MerkleTree {
+    /// Returns the leaf-list form of the tree.
+    pub fn to_leaf_list(&self) -> LeafList {
+        LeafList {
+            hash_algorithm: self.hash_algorithm,
+            leaf_hashes: self.leaf_hashes.clone(),
+            sorted_pairs: self.sorted_pairs,
+            odd_nodes: self.odd_nodes,
+            domain_separation: self.domain_separation,
+        }
+    }
+
+    /// Serializes only the ordered leaf hashes and configuration to a JSON string.
+    pub fn to_leaf_list_json(&self) -> Result<String, MerkleTreeError> {
+        serde_json::to_string_pretty(&Versioned::new(&self.to_leaf_list()))
+            .map_err(MerkleTreeError::SerdeError)
+    }
+
+    /// Rebuilds a tree from a JSON string written by [`MerkleTree::to_leaf_list_json`].
+    pub fn from_leaf_list_json(json_str: &str) -> Result<Self, MerkleTreeError> {
+        schema::from_slice::<LeafList>(json_str.as_bytes())?.into_tree()
+    }
+}
@@ -1,8 +1,127 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "async")]
+pub mod async_reader;
+#[cfg(feature = "signer")]
+pub mod attestation;
+#[cfg(feature = "borsh")]
+pub mod borsh_codec;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod canonical;
+#[cfg(feature = "rpc")]
+pub mod chain;
+#[cfg(feature = "std")]
+pub mod compression;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod dump;
+#[cfg(feature = "std")]
+pub mod envelope;
+#[cfg(feature = "std")]
 pub mod error;
+#[cfg(feature = "std")]
+pub mod file_format;
+#[cfg(feature = "abi")]
+pub mod fixture;
+pub mod hasher;
+#[cfg(feature = "std")]
+pub mod heap_tree;
+#[cfg(feature = "std")]
+pub mod ipld;
+#[cfg(feature = "schemars")]
+pub mod json_schema;
+#[cfg(feature = "abi")]
+pub mod leaf_encoding;
+#[cfg(feature = "std")]
+pub mod leaf_list;
+#[cfg(feature = "std")]
 pub mod merkle_node;
+#[cfg(feature = "std")]
 pub mod merkle_tree;
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+#[cfg(feature = "rlp")]
+pub mod mpt;
+#[cfg(feature = "std")]
+pub mod multiproof;
+#[cfg(feature = "std")]
+pub mod mutate;
+#[cfg(feature = "std")]
+pub mod ndjson;
+#[cfg(feature = "object_store")]
+pub mod object_storage;
+#[cfg(feature = "abi")]
+pub mod openzeppelin;
+#[cfg(feature = "std")]
 pub mod proof;
-pub use error::MerkleTreeError;
-pub use merkle_tree::MerkleTree;
-pub use proof::{MerkleProof, ProofStep};
+#[cfg(feature = "std")]
+pub mod proof_cache;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+#[cfg(feature = "publisher")]
+pub mod publisher;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "rlp")]
+pub mod rlp;
+#[cfg(feature = "std")]
+pub mod rolling;
+#[cfg(feature = "signer")]
+pub use attestation::SignedRoot;
+#[cfg(feature = "std")]
+pub use builder::{BuildProgress, MerkleTreeBuilder};
+#[cfg(feature = "std")]
+pub use dump::DumpFormat;
+#[cfg(feature = "std")]
+pub use error::{InputPosition, MerkleTreeError};
+pub use hasher::{HashAlgorithm, OddNodePolicy, TreeOption};
+#[cfg(feature = "std")]
+pub use heap_tree::HeapMerkleTree;
+#[cfg(feature = "std")]
+pub use merkle_node::{DeserializeLimits, DisplayOptions};
+#[cfg(feature = "std")]
+pub use merkle_tree::{CorruptNode, Direction, MerkleTree};
+#[cfg(feature = "std")]
+pub use multiproof::{MultiProof, MultiProofDefect};
+#[cfg(feature = "std")]
+pub use proof::MerkleProof;
+#[cfg(feature = "signer")]
+pub use signing::{Eip712Domain, RootAttestation};
+pub use verify::ProofStep;
+#[cfg(feature = "std")]
+pub mod schema;
+#[cfg(feature = "std")]
 pub mod serialization;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "signer")]
+pub mod signing;
+#[cfg(feature = "abi")]
+pub mod solidity;
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub mod sql;
+#[cfg(feature = "ssz")]
+pub mod ssz;
+#[cfg(feature = "std")]
+pub mod store;
+#[cfg(feature = "std")]
+pub mod sync;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+#[cfg(feature = "opentimestamps")]
+pub mod timestamp;
+pub mod verify;
+#[cfg(feature = "std")]
+pub mod visualize;
+#[cfg(feature = "std")]
+pub mod wal;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
@@ -1,18 +1,31 @@
 use crate::error::MerkleTreeError;
-use alloy_primitives::hex::{decode, encode};
+use crate::hasher::TreeHasher;
+use crate::mutate::tree_depth;
+use crate::serialization::HexB256;
 
+use alloy_primitives::hex::{encode, encode_prefixed};
 use alloy_primitives::B256;
 
-use serde::de::Error as SerdeError;
+use serde::de::{
+    DeserializeOwned, DeserializeSeed, Error as SerdeError, IgnoredAny, MapAccess, SeqAccess,
+    Visitor,
+};
 use serde::ser::SerializeStruct;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::value::RawValue;
+use std::cell::Cell;
 use std::fmt;
 use std::io::Read;
 
 /// Represents a node in the Merkle Tree.
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct MerkleNode {
     /// The hash of this node as a B256.
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(schema_with = "crate::json_schema::hash_schema")
+    )]
     pub hash: B256,
 
     /// Left child node. `None` if this is a leaf node.
@@ -47,6 +60,32 @@ impl MerkleNode {
             right: Some(Box::new(right)),
         })
     }
+
+    /// Creates a leaf node holding an already computed hash.
+    pub(crate) fn leaf(hash: B256) -> Self {
+        MerkleNode {
+            hash,
+            left: None,
+            right: None,
+        }
+    }
+
+    /// Creates an internal node, hashing its children with `hasher`.
+    pub(crate) fn internal(left: MerkleNode, right: MerkleNode, hasher: TreeHasher) -> Self {
+        MerkleNode {
+            hash: hasher.hash_pair(&left.hash, &right.hash),
+            left: Some(Box::new(left)),
+            right: Some(Box::new(right)),
+        }
+    }
+
+    /// Creates the parent of an odd node paired with a copy of itself.
+    ///
+    /// The copy holds only the hash, so duplicating a node costs one node, not a subtree.
+    pub(crate) fn with_copy(node: MerkleNode, hasher: TreeHasher) -> Self {
+        let copy = MerkleNode::leaf(node.hash);
+        MerkleNode::internal(node, copy, hasher)
+    }
 }
 
 // Custom Serialize and Deserialize implementations
@@ -56,54 +95,567 @@ impl Serialize for MerkleNode {
     where
         S: Serializer,
     {
-        // Serialize hash as hex string
+        // Serialize hash as hex string (raw bytes for binary formats)
         let mut state = serializer.serialize_struct("MerkleNode", 3)?;
-        state.serialize_field("hash", &encode(self.hash))?;
+        state.serialize_field("hash", &HexB256(self.hash))?;
         state.serialize_field("left", &self.left)?;
         state.serialize_field("right", &self.right)?;
         state.end()
     }
 }
 
+/// Limits applied while deserializing a tree, so untrusted dumps cannot exhaust the stack
+/// or memory.
+///
+/// A tree keeps the limits it was built or loaded with in [`MerkleTree::limits`], and
+/// appending leaves checks the leaf count, depth and leaf size limits against them.
+///
+/// [`MerkleTree::limits`]: crate::MerkleTree::limits
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DeserializeLimits {
+    /// Maximum number of levels below the root.
+    pub max_depth: usize,
+
+    /// Maximum number of nodes in the tree.
+    pub max_nodes: usize,
+
+    /// Maximum number of leaves in the tree.
+    pub max_leaves: usize,
+
+    /// Maximum length of each leaf's raw data, for dumps carrying it.
+    pub max_leaf_size: usize,
+}
+
+impl Default for DeserializeLimits {
+    /// A depth of 64 covers any tree with up to `2^64` leaves; the other limits are
+    /// unbounded.
+    fn default() -> Self {
+        DeserializeLimits {
+            max_depth: 64,
+            max_nodes: usize::MAX,
+            max_leaves: usize::MAX,
+            max_leaf_size: usize::MAX,
+        }
+    }
+}
+
+impl DeserializeLimits {
+    /// Rejects `leaf_count` leaves if they exceed the leaf count or depth limit.
+    pub(crate) fn check_leaf_count(&self, leaf_count: usize) -> Result<(), MerkleTreeError> {
+        if leaf_count > self.max_leaves {
+            return Err(MerkleTreeError::TooManyLeaves {
+                count: leaf_count,
+                max: self.max_leaves,
+            });
+        }
+        let depth = tree_depth(leaf_count);
+        if depth > self.max_depth {
+            return Err(MerkleTreeError::DepthExceeded {
+                depth,
+                max: self.max_depth,
+            });
+        }
+        Ok(())
+    }
+
+    /// Rejects the leaf at `index` if it exceeds the leaf size limit.
+    pub(crate) fn check_leaf_size(&self, index: usize, leaf: &[u8]) -> Result<(), MerkleTreeError> {
+        if leaf.len() > self.max_leaf_size {
+            return Err(MerkleTreeError::LeafTooLarge {
+                index,
+                size: leaf.len(),
+                max: self.max_leaf_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl MerkleNode {
+    /// Deserializes a node, enforcing the given depth and node count limits.
+    pub fn deserialize_with_limits<'de, D>(
+        deserializer: D,
+        limits: DeserializeLimits,
+    ) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let failure = LoadFailure::default();
+        LimitedNode {
+            limits,
+            failure: &failure,
+        }
+        .deserialize(deserializer)
+    }
+}
+
 impl<'de> Deserialize<'de> for MerkleNode {
     fn deserialize<D>(deserializer: D) -> Result<MerkleNode, D::Error>
     where
         D: Deserializer<'de>,
     {
-        #[derive(Deserialize)]
-        struct MerkleNodeHelper {
-            hash: String,
-            left: Option<Box<MerkleNode>>,
-            right: Option<Box<MerkleNode>>,
+        Self::deserialize_with_limits(deserializer, DeserializeLimits::default())
+    }
+}
+
+/// The typed error behind the last check a deserializer failed, which serde otherwise
+/// reduces to its message.
+#[derive(Default)]
+pub(crate) struct LoadFailure(Cell<Option<MerkleTreeError>>);
+
+impl LoadFailure {
+    /// Records `error` and returns it as a deserializer error.
+    pub(crate) fn fail<E: SerdeError>(&self, error: MerkleTreeError) -> E {
+        let message = E::custom(&error);
+        self.0.set(Some(error));
+        message
+    }
+
+    /// Returns the recorded error, or `error` if no check failed.
+    pub(crate) fn or(self, error: impl Into<MerkleTreeError>) -> MerkleTreeError {
+        self.0.into_inner().unwrap_or_else(|| error.into())
+    }
+}
+
+/// Seed deserializing a whole subtree under the given limits.
+pub(crate) struct LimitedNode<'a> {
+    pub(crate) limits: DeserializeLimits,
+    pub(crate) failure: &'a LoadFailure,
+}
+
+impl<'de> DeserializeSeed<'de> for LimitedNode<'_> {
+    type Value = MerkleNode;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<MerkleNode, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let node_count = Cell::new(0);
+        NodeSeed {
+            depth: 0,
+            limits: self.limits,
+            node_count: &node_count,
+            failure: self.failure,
         }
+        .deserialize(deserializer)
+    }
+}
 
-        let helper = MerkleNodeHelper::deserialize(deserializer)?;
-        let hash_bytes = decode(&helper.hash).map_err(D::Error::custom)?;
-        let hash = B256::from_slice(&hash_bytes);
-        Ok(MerkleNode {
-            hash,
-            left: helper.left,
-            right: helper.right,
+/// Seed for a single node.
+///
+/// JSON is read as a raw value, which serde_json skips over without recursing, and its
+/// nodes are then walked with an explicit stack by [`read_json_node`]. Binary encodings
+/// can only be read through nested serde calls, so there recursion is bounded by
+/// `limits.max_depth`. Either way every node is counted before its children are visited.
+struct NodeSeed<'a> {
+    depth: usize,
+    limits: DeserializeLimits,
+    node_count: &'a Cell<usize>,
+    failure: &'a LoadFailure,
+}
+
+impl<'a> NodeSeed<'a> {
+    fn child(&self) -> ChildSeed<'a> {
+        ChildSeed(NodeSeed {
+            depth: self.depth + 1,
+            limits: self.limits,
+            node_count: self.node_count,
+            failure: self.failure,
         })
     }
+
+    /// Counts a node at `depth`, failing if it exceeds the depth or node count limit.
+    fn enter<E: SerdeError>(&self, depth: usize) -> Result<(), E> {
+        if depth > self.limits.max_depth {
+            return Err(self.failure.fail(MerkleTreeError::DepthExceeded {
+                depth,
+                max: self.limits.max_depth,
+            }));
+        }
+
+        let node_count = self.node_count.get() + 1;
+        if node_count > self.limits.max_nodes {
+            return Err(self.failure.fail(MerkleTreeError::TooManyNodes {
+                count: node_count,
+                max: self.limits.max_nodes,
+            }));
+        }
+        self.node_count.set(node_count);
+        Ok(())
+    }
 }
 
-impl fmt::Display for MerkleNode {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fn fmt_node(node: &MerkleNode, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
-            for _ in 0..depth {
-                write!(f, "  ")?;
+impl<'de> DeserializeSeed<'de> for NodeSeed<'_> {
+    type Value = MerkleNode;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<MerkleNode, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            let raw = Box::<RawValue>::deserialize(deserializer)?;
+            return read_json_node(raw.get(), &self).map_err(D::Error::custom);
+        }
+
+        self.enter(self.depth)?;
+        deserializer.deserialize_struct("MerkleNode", &["hash", "left", "right"], self)
+    }
+}
+
+/// A node whose members are being read by [`read_json_node`].
+#[derive(Default)]
+struct JsonFrame {
+    hash: Option<B256>,
+    left: Option<MerkleNode>,
+    right: Option<MerkleNode>,
+    /// Whether the child being read goes on the right.
+    reading_right: bool,
+    /// Whether a member has been read, so the next one follows a comma.
+    started: bool,
+}
+
+/// Reads a JSON node and its subtree with an explicit stack of the nodes being read, so
+/// nesting depth costs heap, not call stack. Member values other than children are
+/// parsed by serde_json.
+fn read_json_node(json: &str, seed: &NodeSeed) -> Result<MerkleNode, serde_json::Error> {
+    let mut cursor = JsonCursor { json, position: 0 };
+    let mut stack: Vec<JsonFrame> = Vec::new();
+
+    cursor.expect(b'{')?;
+    seed.enter(seed.depth)?;
+    stack.push(JsonFrame::default());
+
+    loop {
+        let frame = stack.last_mut().expect("a node is being read");
+        if cursor.peek() == Some(b'}') {
+            cursor.position += 1;
+            let frame = stack.pop().expect("a node is being read");
+            let node = MerkleNode {
+                hash: frame
+                    .hash
+                    .ok_or_else(|| serde_json::Error::missing_field("hash"))?,
+                left: frame.left.map(Box::new),
+                right: frame.right.map(Box::new),
+            };
+            match stack.last_mut() {
+                Some(parent) if parent.reading_right => parent.right = Some(node),
+                Some(parent) => parent.left = Some(node),
+                None => return Ok(node),
+            }
+            continue;
+        }
+
+        if frame.started {
+            cursor.expect(b',')?;
+        }
+        frame.started = true;
+        let key: String = cursor.value()?;
+        cursor.expect(b':')?;
+        match key.as_str() {
+            "hash" => {
+                let HexB256(hash) = cursor.value()?;
+                frame.hash = Some(hash);
+            }
+            "left" | "right" => {
+                frame.reading_right = key == "right";
+                match cursor.peek() {
+                    Some(b'{') => {
+                        cursor.position += 1;
+                        seed.enter(seed.depth + stack.len())?;
+                        stack.push(JsonFrame::default());
+                    }
+                    Some(b'n') => cursor.value::<()>()?,
+                    _ => {
+                        return Err(serde_json::Error::custom(
+                            "expected an optional Merkle Tree node",
+                        ))
+                    }
+                }
+            }
+            _ => {
+                cursor.value::<IgnoredAny>()?;
+            }
+        }
+    }
+}
+
+/// Position in the JSON of a node, reading structural characters itself and values
+/// with serde_json.
+struct JsonCursor<'a> {
+    json: &'a str,
+    position: usize,
+}
+
+impl JsonCursor<'_> {
+    /// Skips whitespace and returns the next byte, if any.
+    fn peek(&mut self) -> Option<u8> {
+        let rest = &self.json.as_bytes()[self.position..];
+        self.position += rest
+            .iter()
+            .take_while(|byte| byte.is_ascii_whitespace())
+            .count();
+        self.json.as_bytes().get(self.position).copied()
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), serde_json::Error> {
+        match self.peek() {
+            Some(byte) if byte == expected => {
+                self.position += 1;
+                Ok(())
             }
-            writeln!(f, "- {}", encode(node.hash))?;
-            if let Some(left) = &node.left {
-                fmt_node(left, f, depth + 1)?;
+            Some(byte) => Err(serde_json::Error::custom(format!(
+                "expected '{}' in a Merkle Tree node, found '{}'",
+                expected as char, byte as char
+            ))),
+            None => Err(serde_json::Error::custom(format!(
+                "expected '{}' in a Merkle Tree node, found the end of the node",
+                expected as char
+            ))),
+        }
+    }
+
+    /// Parses the next value with serde_json.
+    fn value<T: DeserializeOwned>(&mut self) -> Result<T, serde_json::Error> {
+        let mut values =
+            serde_json::Deserializer::from_str(&self.json[self.position..]).into_iter::<T>();
+        let value = values.next().unwrap_or_else(|| {
+            Err(serde_json::Error::custom(
+                "unexpected end of a Merkle Tree node",
+            ))
+        })?;
+        self.position += values.byte_offset();
+        Ok(value)
+    }
+}
+
+impl<'de> Visitor<'de> for NodeSeed<'_> {
+    type Value = MerkleNode;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a Merkle Tree node")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<MerkleNode, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut hash = None;
+        let mut left = None;
+        let mut right = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "hash" => {
+                    let HexB256(value) = map.next_value()?;
+                    hash = Some(value);
+                }
+                "left" => left = map.next_value_seed(self.child())?,
+                "right" => right = map.next_value_seed(self.child())?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
             }
-            if let Some(right) = &node.right {
-                fmt_node(right, f, depth + 1)?;
+        }
+
+        let hash = hash.ok_or_else(|| A::Error::missing_field("hash"))?;
+        Ok(MerkleNode {
+            hash,
+            left: left.map(Box::new),
+            right: right.map(Box::new),
+        })
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<MerkleNode, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let HexB256(hash) = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let left = seq
+            .next_element_seed(self.child())?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        let right = seq
+            .next_element_seed(self.child())?
+            .ok_or_else(|| A::Error::invalid_length(2, &self))?;
+
+        Ok(MerkleNode {
+            hash,
+            left: left.map(Box::new),
+            right: right.map(Box::new),
+        })
+    }
+}
+
+/// Seed for an optional child node, where `null` marks a missing child.
+struct ChildSeed<'a>(NodeSeed<'a>);
+
+impl<'de> DeserializeSeed<'de> for ChildSeed<'_> {
+    type Value = Option<MerkleNode>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Option<MerkleNode>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ChildSeed<'_> {
+    type Value = Option<MerkleNode>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an optional Merkle Tree node")
+    }
+
+    fn visit_none<E>(self) -> Result<Option<MerkleNode>, E>
+    where
+        E: SerdeError,
+    {
+        Ok(None)
+    }
+
+    fn visit_unit<E>(self) -> Result<Option<MerkleNode>, E>
+    where
+        E: SerdeError,
+    {
+        Ok(None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Option<MerkleNode>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.0.deserialize(deserializer).map(Some)
+    }
+}
+
+/// Options for printing a tree with [`MerkleNode::display`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct DisplayOptions {
+    /// Number of levels printed below the root. Deeper subtrees are shown as `…`.
+    pub max_depth: Option<usize>,
+
+    /// Number of hex digits printed per hash, followed by `…`.
+    pub truncate: Option<usize>,
+
+    /// Draw branches with box-drawing characters instead of indenting with spaces.
+    pub unicode: bool,
+
+    /// Print hashes with a `0x` prefix.
+    pub hex_prefix: bool,
+}
+
+/// A node printed with [`DisplayOptions`], returned by [`MerkleNode::display`].
+pub struct NodeDisplay<'a> {
+    node: &'a MerkleNode,
+    options: DisplayOptions,
+}
+
+impl MerkleNode {
+    /// Returns a value printing this subtree with the given options.
+    pub fn display(&self, options: DisplayOptions) -> NodeDisplay<'_> {
+        NodeDisplay {
+            node: self,
+            options,
+        }
+    }
+}
+
+impl NodeDisplay<'_> {
+    fn hash(&self, node: &MerkleNode) -> String {
+        let hex = if self.options.hex_prefix {
+            encode_prefixed(node.hash)
+        } else {
+            encode(node.hash)
+        };
+        match self.options.truncate {
+            Some(digits) => {
+                let prefix = if self.options.hex_prefix { 2 } else { 0 };
+                match hex.get(..prefix + digits) {
+                    Some(truncated) if truncated.len() < hex.len() => format!("{}…", truncated),
+                    _ => hex,
+                }
             }
-            Ok(())
+            None => hex,
         }
+    }
 
-        fmt_node(self, f, 0)
+    fn children(node: &MerkleNode) -> impl Iterator<Item = &MerkleNode> {
+        node.left
+            .iter()
+            .chain(node.right.iter())
+            .map(|child| &**child)
+    }
+
+    /// Writes a node and its children, indenting each level by two spaces.
+    fn fmt_indented(
+        &self,
+        node: &MerkleNode,
+        f: &mut fmt::Formatter<'_>,
+        depth: usize,
+    ) -> fmt::Result {
+        writeln!(f, "{:width$}- {}", "", self.hash(node), width = depth * 2)?;
+        if node.left.is_none() && node.right.is_none() {
+            return Ok(());
+        }
+        if self.options.max_depth == Some(depth) {
+            return writeln!(f, "{:width$}- …", "", width = (depth + 1) * 2);
+        }
+        for child in Self::children(node) {
+            self.fmt_indented(child, f, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the children of a node, prefixing each line with the branches of its
+    /// ancestors.
+    fn fmt_branches(
+        &self,
+        node: &MerkleNode,
+        f: &mut fmt::Formatter<'_>,
+        prefix: &mut String,
+        depth: usize,
+    ) -> fmt::Result {
+        if node.left.is_none() && node.right.is_none() {
+            return Ok(());
+        }
+        if self.options.max_depth == Some(depth) {
+            return writeln!(f, "{}└── …", prefix);
+        }
+
+        let count = Self::children(node).count();
+        for (i, child) in Self::children(node).enumerate() {
+            let last = i + 1 == count;
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            writeln!(f, "{}{}{}", prefix, branch, self.hash(child))?;
+
+            let len = prefix.len();
+            prefix.push_str(indent);
+            self.fmt_branches(child, f, prefix, depth + 1)?;
+            prefix.truncate(len);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for NodeDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.options.unicode {
+            writeln!(f, "{}", self.hash(self.node))?;
+            self.fmt_branches(self.node, f, &mut String::new(), 0)
+        } else {
+            self.fmt_indented(self.node, f, 0)
+        }
+    }
+}
+
+impl fmt::Display for MerkleNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display(DisplayOptions::default()).fmt(f)
     }
 }
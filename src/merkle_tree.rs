@@ -1,18 +1,39 @@
 // src/merkle_tree.rs
 
+use crate::builder::{BuildProgress, MerkleTreeBuilder};
 use crate::error::MerkleTreeError;
-use crate::merkle_node::MerkleNode;
+use crate::hasher::{HashAlgorithm, OddNodePolicy, TreeHasher};
+use crate::merkle_node::{
+    DeserializeLimits, DisplayOptions, LimitedNode, LoadFailure, MerkleNode, NodeDisplay,
+};
+use crate::mutate::tree_depth;
 use crate::proof::{MerkleProof, ProofStep};
+use crate::schema::{Versioned, SCHEMA_VERSION};
+use crate::serialization::{HashFormat, HexB256, HexBytes};
+use crate::verify::hashes_equal;
 use alloy_primitives::hex::encode;
-use alloy_primitives::keccak256;
 use alloy_primitives::B256;
 use log::{debug, info};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::de::{
+    DeserializeOwned, DeserializeSeed, Error as SerdeError, IgnoredAny, MapAccess, SeqAccess,
+    Visitor,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+
+/// Number of leaves hashed between progress reports.
+const PROGRESS_BATCH: usize = 1 << 16;
 
 /// Represents the Merkle Tree.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+///
+/// Deserializing goes through the same depth, node, leaf count and leaf size checks as
+/// [`MerkleTree::from_json`], under the default [`DeserializeLimits`].
+#[derive(Serialize, Debug, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct MerkleTree {
     /// The root node of the tree.
     pub root: MerkleNode,
@@ -20,66 +41,276 @@ pub struct MerkleTree {
     /// Map from leaf hashes to their corresponding data.
     #[serde(skip)]
     pub leaves: HashMap<B256, Vec<u8>>,
+
+    /// Leaf hashes in insertion order, used for index-based proof generation.
+    #[serde(default, with = "crate::serialization::b256_vec_hex")]
+    #[cfg_attr(feature = "schemars", schemars(with = "Vec<String>"))]
+    pub leaf_hashes: Vec<B256>,
+
+    /// Hash function used for leaves and internal nodes. Dumps without it use keccak256.
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+
+    /// Whether each pair of child hashes was sorted before hashing.
+    #[serde(default)]
+    pub sorted_pairs: bool,
+
+    /// What happened to the last node of each odd level.
+    #[serde(default)]
+    pub odd_nodes: OddNodePolicy,
+
+    /// Whether leaves and nodes were prefixed before hashing, so that no leaf hashes like
+    /// an internal node. See [`MerkleTreeBuilder::domain_separation`].
+    #[serde(default)]
+    pub domain_separation: bool,
+
+    /// Limits checked when leaves are appended: those of the builder the tree was built
+    /// with, or those it was loaded with. Not serialized, and ignored when comparing trees.
+    #[serde(skip)]
+    #[cfg_attr(feature = "schemars", schemars(skip))]
+    pub limits: DeserializeLimits,
+
+    /// Whether appended and updated leaves may be 64 bytes long without domain separation;
+    /// see [`MerkleTreeBuilder::allow_64_byte_leaves`]. Not serialized, so loaded trees
+    /// reject them, and ignored when comparing trees.
+    #[serde(skip)]
+    #[cfg_attr(feature = "schemars", schemars(skip))]
+    pub allow_64_byte_leaves: bool,
+
+    /// Whether changes that leave the tree ambiguous are rolled back and rejected; see
+    /// [`MerkleTreeBuilder::strict`]. Not serialized, and ignored when comparing trees.
+    #[serde(skip)]
+    #[cfg_attr(feature = "schemars", schemars(skip))]
+    pub strict: bool,
+}
+
+impl PartialEq for MerkleTree {
+    fn eq(&self, other: &Self) -> bool {
+        self.root == other.root
+            && self.leaves == other.leaves
+            && self.leaf_hashes == other.leaf_hashes
+            && self.hash_algorithm == other.hash_algorithm
+            && self.sorted_pairs == other.sorted_pairs
+            && self.odd_nodes == other.odd_nodes
+            && self.domain_separation == other.domain_separation
+    }
+}
+
+impl Eq for MerkleTree {}
+
+/// A tree serialized together with its raw leaf data, aligned with `leaf_hashes`.
+#[derive(Serialize)]
+struct WithLeafData<'a> {
+    #[serde(flatten)]
+    tree: &'a MerkleTree,
+    leaf_data: Vec<Option<HexBytes>>,
 }
 
 impl MerkleTree {
     /// Builds a new Merkle Tree from a list of data items.
     pub fn new(data: &[Vec<u8>]) -> Result<Self, MerkleTreeError> {
+        MerkleTreeBuilder::new().build(data)
+    }
+
+    /// Returns a builder for configuring how the tree is constructed.
+    pub fn builder() -> MerkleTreeBuilder {
+        MerkleTreeBuilder::new()
+    }
+
+    /// Builds the tree using the options set on `builder`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "build",
+            skip_all,
+            fields(
+                leaf_count = data.len(),
+                depth = tracing::field::Empty,
+                hash_algorithm = %builder.tree_hasher().hash_algorithm,
+            )
+        )
+    )]
+    pub(crate) fn build_with(
+        builder: &MerkleTreeBuilder,
+        data: &[Vec<u8>],
+    ) -> Result<Self, MerkleTreeError> {
         if data.is_empty() {
             return Err(MerkleTreeError::EmptyData);
         }
 
-        // Initialize logging
-        let _ = env_logger::builder().is_test(true).try_init();
-
         info!("Building Merkle Tree with {} leaves.", data.len());
 
+        // Hash the leaves, then order and filter them as configured
+        let hasher = builder.tree_hasher();
+        let mut leaves = Vec::with_capacity(data.len());
+        for chunk in data.chunks(PROGRESS_BATCH) {
+            hash_leaves(chunk, hasher, &mut leaves);
+            builder.report(BuildProgress::LeavesHashed {
+                hashed: leaves.len(),
+                total: data.len(),
+            });
+        }
+        if builder.dedupes() {
+            let mut seen = HashSet::new();
+            leaves.retain(|(leaf, _)| seen.insert(leaf.hash));
+        }
+        if builder.sorts_leaves() {
+            leaves.sort_by_key(|(leaf, _)| leaf.hash);
+        }
+
         // Create leaf nodes
         let mut leaf_nodes: Vec<MerkleNode> = Vec::new();
         let mut leaves_map: HashMap<B256, Vec<u8>> = HashMap::new();
+        let mut leaf_hashes: Vec<B256> = Vec::with_capacity(leaves.len());
 
-        for datum in data {
-            let leaf = MerkleNode::new_leaf(datum)?;
-            leaves_map.insert(leaf.hash.clone(), datum.clone());
+        for (leaf, datum) in leaves {
+            if builder.retains_leaf_data() {
+                leaves_map.insert(leaf.hash, datum.clone());
+            }
+            leaf_hashes.push(leaf.hash);
             leaf_nodes.push(leaf);
         }
 
         // Build the tree
-        let root = Self::build_tree_recursive(leaf_nodes)?;
+        let root = Self::build_tree_recursive(leaf_nodes, builder, 0)?;
 
-        Ok(MerkleTree {
+        let tree = MerkleTree {
             root,
             leaves: leaves_map,
+            leaf_hashes,
+            hash_algorithm: hasher.hash_algorithm,
+            sorted_pairs: hasher.sorted_pairs,
+            odd_nodes: hasher.odd_nodes,
+            domain_separation: hasher.domain_separation,
+            limits: builder.limits(),
+            allow_64_byte_leaves: builder.allows_64_byte_leaves(),
+            strict: builder.is_strict(),
+        };
+        // Deduplication may have dropped leaves, so the depth is only known now
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("depth", tree.depth());
+        Ok(tree)
+    }
+
+    /// Builds a Merkle Tree from already hashed leaves, in order.
+    ///
+    /// The tree holds no raw leaf data, but proofs can be generated by index or by data.
+    pub fn from_leaf_hashes(leaf_hashes: Vec<B256>) -> Result<Self, MerkleTreeError> {
+        MerkleTreeBuilder::new().build_from_leaf_hashes(leaf_hashes)
+    }
+
+    /// Builds a tree from already hashed leaves using the options set on `builder`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "build_from_leaf_hashes",
+            skip_all,
+            fields(
+                leaf_count = leaf_hashes.len(),
+                depth = tree_depth(leaf_hashes.len()),
+                hash_algorithm = %builder.tree_hasher().hash_algorithm,
+            )
+        )
+    )]
+    pub(crate) fn from_leaf_hashes_with(
+        builder: &MerkleTreeBuilder,
+        leaf_hashes: Vec<B256>,
+    ) -> Result<Self, MerkleTreeError> {
+        if leaf_hashes.is_empty() {
+            return Err(MerkleTreeError::EmptyData);
+        }
+
+        info!(
+            "Rebuilding Merkle Tree from {} leaf hashes.",
+            leaf_hashes.len()
+        );
+
+        let leaf_nodes = leaf_hashes
+            .iter()
+            .map(|hash| MerkleNode::leaf(*hash))
+            .collect();
+        let hasher = builder.tree_hasher();
+        let root = Self::build_tree_recursive(leaf_nodes, builder, 0)?;
+
+        Ok(MerkleTree {
+            root,
+            leaves: HashMap::new(),
+            leaf_hashes,
+            hash_algorithm: hasher.hash_algorithm,
+            sorted_pairs: hasher.sorted_pairs,
+            odd_nodes: hasher.odd_nodes,
+            domain_separation: hasher.domain_separation,
+            limits: builder.limits(),
+            allow_64_byte_leaves: builder.allows_64_byte_leaves(),
+            strict: builder.is_strict(),
         })
     }
 
-    /// Recursively builds the Merkle Tree from a list of nodes.
-    fn build_tree_recursive(mut nodes: Vec<MerkleNode>) -> Result<MerkleNode, MerkleTreeError> {
+    /// Recursively builds the Merkle Tree from a list of nodes at `level`.
+    fn build_tree_recursive(
+        mut nodes: Vec<MerkleNode>,
+        builder: &MerkleTreeBuilder,
+        level: usize,
+    ) -> Result<MerkleNode, MerkleTreeError> {
         debug!("Building tree level with {} nodes.", nodes.len());
 
         if nodes.len() == 1 {
             return Ok(nodes.pop().unwrap());
         }
 
-        let mut next_level = Vec::new();
-
-        for i in (0..nodes.len()).step_by(2) {
-            if i + 1 < nodes.len() {
-                let left = nodes[i].clone();
-                let right = nodes[i + 1].clone();
-                let parent = MerkleNode::new_internal(left, right)?;
-                next_level.push(parent);
-            } else {
+        let hasher = builder.tree_hasher();
+        let depth = level + tree_depth(nodes.len());
+        let join = |pair: &[MerkleNode]| match pair {
+            [left, right] => MerkleNode::internal(left.clone(), right.clone(), hasher),
+            [odd] if hasher.duplicates_odd() => {
+                // Odd node, pair it with a copy of itself
+                info!(
+                    "Duplicating node with hash {} due to odd count.",
+                    encode(odd.hash)
+                );
+                MerkleNode::with_copy(odd.clone(), hasher)
+            }
+            [odd] => {
                 // Odd node, promote to next level
-                next_level.push(nodes[i].clone());
                 info!(
                     "Promoting node with hash {} to next level due to odd count.",
-                    encode(nodes[i].hash)
+                    encode(odd.hash)
                 );
+                odd.clone()
             }
+            _ => unreachable!("chunks of two nodes"),
+        };
+
+        // Pairs are independent, so with the `parallel` feature they are joined on
+        // rayon's thread pool
+        #[cfg(feature = "parallel")]
+        let next_level: Vec<_> = {
+            use rayon::prelude::*;
+            nodes.par_chunks(2).map(join).collect()
+        };
+        #[cfg(not(feature = "parallel"))]
+        let next_level: Vec<_> = nodes.chunks(2).map(join).collect();
+
+        builder.report(BuildProgress::LevelBuilt {
+            level: level + 1,
+            depth,
+        });
+        Self::build_tree_recursive(next_level, builder, level + 1)
+    }
+
+    /// Returns how this tree joins nodes into their parents.
+    pub(crate) fn hasher(&self) -> TreeHasher {
+        TreeHasher {
+            hash_algorithm: self.hash_algorithm,
+            domain_separation: self.domain_separation,
+            sorted_pairs: self.sorted_pairs,
+            odd_nodes: self.odd_nodes,
         }
+    }
 
-        Self::build_tree_recursive(next_level)
+    /// Hashes raw leaf data as this tree's leaves are hashed.
+    pub fn hash_leaf(&self, data: &[u8]) -> B256 {
+        self.hasher().hash_leaf(data)
     }
 
     /// Returns the root hash of the Merkle Tree.
@@ -87,51 +318,441 @@ impl MerkleTree {
         &self.root.hash
     }
 
+    /// Returns the number of leaves in the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_hashes.len()
+    }
+
+    /// Returns the number of levels above the leaves, i.e. the length of a full proof.
+    pub fn depth(&self) -> usize {
+        tree_depth(self.leaf_count())
+    }
+
+    /// Returns the number of nodes in the tree.
+    ///
+    /// Every internal node joins two others and promoted nodes are not duplicated, so a
+    /// tree of `n` leaves has `2n - 1` nodes. With [`OddNodePolicy::Duplicate`], each odd
+    /// level adds one copy node.
+    pub fn node_count(&self) -> usize {
+        let mut count = (2 * self.leaf_count()).saturating_sub(1);
+        if self.odd_nodes == OddNodePolicy::Duplicate {
+            let mut size = self.leaf_count();
+            while size > 1 {
+                count += size % 2;
+                size = size.div_ceil(2);
+            }
+        }
+        count
+    }
+
+    /// Returns the hashes of every level, from the leaves up to the root.
+    ///
+    /// When a level has an odd length its last node is promoted unchanged, so that hash
+    /// also appears in the level above. With [`OddNodePolicy::Duplicate`] it is paired with
+    /// a copy of itself instead; copies are not listed.
+    pub fn levels(&self) -> Result<Vec<Vec<B256>>, MerkleTreeError> {
+        let leaf_count = if self.leaf_hashes.is_empty() {
+            let mut leaf_hashes = Vec::new();
+            collect_leaf_hashes(&self.root, &mut leaf_hashes);
+            leaf_hashes.len()
+        } else {
+            self.leaf_hashes.len()
+        };
+
+        let mut level_sizes = vec![leaf_count];
+        while level_sizes[level_sizes.len() - 1] > 1 {
+            level_sizes.push(level_sizes[level_sizes.len() - 1].div_ceil(2));
+        }
+        let mut levels: Vec<Vec<B256>> = level_sizes
+            .iter()
+            .map(|&size| vec![B256::ZERO; size])
+            .collect();
+        fill_levels(
+            &self.root,
+            levels.len() - 1,
+            0,
+            leaf_count,
+            self.odd_nodes,
+            &mut levels,
+        )?;
+        Ok(levels)
+    }
+
+    /// Discards the raw leaf data held by the tree.
+    ///
+    /// Leaf hashes are kept, so proofs can still be generated by index or by data. With the
+    /// `zeroize` feature the data is wiped first, as [`MerkleTree::clear_leaf_data`] does.
+    pub fn shrink(&mut self) {
+        self.clear_leaf_data();
+    }
+
+    /// Discards the raw leaf data held by the tree, overwriting it with zeros first with the
+    /// `zeroize` feature.
+    ///
+    /// Use it to scrub leaves holding personal data or secret salts once proofs no longer
+    /// need to be generated by data. Without `zeroize` the memory is only freed, not wiped.
+    /// Copies made outside the tree, such as the input the tree was built from or its
+    /// serialized dumps, are not affected.
+    pub fn clear_leaf_data(&mut self) {
+        #[cfg(feature = "zeroize")]
+        for data in self.leaves.values_mut() {
+            zeroize::Zeroize::zeroize(data);
+        }
+        self.leaves = HashMap::new();
+    }
+
+    /// Discards the raw data of the leaf `leaf_hash`, wiping it first with the `zeroize`
+    /// feature.
+    pub(crate) fn discard_leaf_data(&mut self, leaf_hash: &B256) {
+        #[cfg(feature = "zeroize")]
+        if let Some(data) = self.leaves.get_mut(leaf_hash) {
+            zeroize::Zeroize::zeroize(data);
+        }
+        self.leaves.remove(leaf_hash);
+    }
+
     /// Serializes the Merkle Tree to a JSON string.
     pub fn to_json(&self) -> Result<String, MerkleTreeError> {
-        serde_json::to_string_pretty(&self).map_err(MerkleTreeError::SerdeError)
+        self.to_json_with(HashFormat::default())
+    }
+
+    /// Serializes the Merkle Tree to a JSON string, writing hashes in `format`.
+    pub fn to_json_with(&self, format: HashFormat) -> Result<String, MerkleTreeError> {
+        serde_json::to_string_pretty(&format.encoded(Versioned::new(self)))
+            .map_err(MerkleTreeError::SerdeError)
+    }
+
+    /// Serializes the Merkle Tree to a JSON string that also carries the raw leaf data, so
+    /// the loaded tree can look leaves up by data as well as by index.
+    ///
+    /// `leaf_data` is aligned with `leaf_hashes`; leaves whose data was not retained are `null`.
+    pub fn to_json_with_leaf_data(&self) -> Result<String, MerkleTreeError> {
+        self.to_json_with_leaf_data_in(HashFormat::default())
+    }
+
+    /// Serializes the Merkle Tree like [`MerkleTree::to_json_with_leaf_data`], writing
+    /// hashes and leaf data in `format`.
+    pub fn to_json_with_leaf_data_in(&self, format: HashFormat) -> Result<String, MerkleTreeError> {
+        let leaf_data = self
+            .leaf_hashes
+            .iter()
+            .map(|hash| self.leaves.get(hash).cloned().map(HexBytes))
+            .collect();
+        let tree = WithLeafData {
+            tree: self,
+            leaf_data,
+        };
+        serde_json::to_string_pretty(&format.encoded(Versioned::new(&tree)))
+            .map_err(MerkleTreeError::SerdeError)
     }
 
-    /// Deserializes the Merkle Tree from a JSON string.
+    /// Deserializes the Merkle Tree from a JSON string, migrating older schema versions.
     pub fn from_json(json_str: &str) -> Result<Self, MerkleTreeError> {
-        serde_json::from_str(json_str).map_err(MerkleTreeError::SerdeError)
+        Self::from_json_with_limits(json_str, DeserializeLimits::default())
+    }
+
+    /// Deserializes the Merkle Tree from a parsed JSON value, migrating older schema versions.
+    ///
+    /// Dumps without `leaf_hashes` get them rebuilt from the tree, and `leaf_data`, when
+    /// present, is restored into `leaves`.
+    pub fn from_json_value(value: Value) -> Result<Self, MerkleTreeError> {
+        Self::from_json_value_with_limits(value, DeserializeLimits::default())
+    }
+
+    /// Deserializes the Merkle Tree from a parsed JSON value, rejecting trees that exceed
+    /// `limits`.
+    pub(crate) fn from_json_value_with_limits(
+        value: Value,
+        limits: DeserializeLimits,
+    ) -> Result<Self, MerkleTreeError> {
+        load_tree(limits, |seed| seed.deserialize(value))
+    }
+
+    /// Deserializes the Merkle Tree from JSON bytes under `limits`, without building a
+    /// [`Value`] first.
+    pub(crate) fn from_json_slice(
+        bytes: &[u8],
+        limits: DeserializeLimits,
+    ) -> Result<Self, MerkleTreeError> {
+        load_tree(limits, |seed| {
+            let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+            let tree = seed.deserialize(&mut deserializer)?;
+            deserializer.end()?;
+            Ok::<_, serde_json::Error>(tree)
+        })
+    }
+
+    /// Restores the leaf hashes older dumps omit and attaches serialized leaf data.
+    fn finish_loading(
+        &mut self,
+        leaf_data: Option<Vec<Option<Vec<u8>>>>,
+    ) -> Result<(), MerkleTreeError> {
+        if self.leaf_hashes.is_empty() {
+            collect_leaf_hashes(&self.root, &mut self.leaf_hashes);
+        }
+
+        if let Some(leaf_data) = leaf_data {
+            if leaf_data.len() != self.leaf_hashes.len() {
+                return Err(MerkleTreeError::LengthMismatch {
+                    expected: self.leaf_hashes.len(),
+                    got: leaf_data.len(),
+                });
+            }
+            for (index, (hash, data)) in self.leaf_hashes.iter().zip(leaf_data).enumerate() {
+                let Some(data) = data else { continue };
+                let data_hash = self.hash_leaf(&data);
+                if data_hash != *hash {
+                    return Err(MerkleTreeError::LeafMismatch {
+                        index,
+                        expected: *hash,
+                        got: data_hash,
+                    });
+                }
+                self.leaves.insert(*hash, data);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Streams the Merkle Tree as JSON into `writer` without building the whole document
+    /// in memory. Wrap files in a `BufWriter`.
+    pub fn to_writer<W: Write>(&self, writer: W) -> Result<(), MerkleTreeError> {
+        self.to_writer_with(writer, HashFormat::default())
+    }
+
+    /// Streams the Merkle Tree as JSON into `writer` like [`MerkleTree::to_writer`],
+    /// writing hashes in `format`.
+    pub fn to_writer_with<W: Write>(
+        &self,
+        mut writer: W,
+        format: HashFormat,
+    ) -> Result<(), MerkleTreeError> {
+        serde_json::to_writer_pretty(&mut writer, &format.encoded(Versioned::new(self)))?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Deserializes the Merkle Tree from a JSON stream, migrating older schema versions.
+    /// Wrap files in a `BufReader`.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, MerkleTreeError> {
+        load_tree(DeserializeLimits::default(), |seed| {
+            let mut deserializer = serde_json::Deserializer::from_reader(reader);
+            let tree = seed.deserialize(&mut deserializer)?;
+            deserializer.end()?;
+            Ok::<_, serde_json::Error>(tree)
+        })
+    }
+
+    /// Serializes the Merkle Tree to a compact binary encoding.
+    ///
+    /// Hashes are written as raw bytes, making the output several times smaller and faster
+    /// to process than [`MerkleTree::to_json`].
+    #[cfg(feature = "bincode")]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MerkleTreeError> {
+        bincode::serialize(self).map_err(MerkleTreeError::BincodeError)
+    }
+
+    /// Deserializes the Merkle Tree from bytes produced by [`MerkleTree::to_bytes`].
+    #[cfg(feature = "bincode")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        Self::from_bytes_with_limits(bytes, DeserializeLimits::default())
+    }
+
+    /// Deserializes the Merkle Tree from bytes like [`MerkleTree::from_bytes`], rejecting
+    /// trees that exceed `limits` or whose shape does not match their leaf list.
+    #[cfg(feature = "bincode")]
+    pub fn from_bytes_with_limits(
+        bytes: &[u8],
+        limits: DeserializeLimits,
+    ) -> Result<Self, MerkleTreeError> {
+        use bincode::Options;
+
+        // The options of `bincode::deserialize`
+        let tree = load_tree(limits, |seed| {
+            bincode::DefaultOptions::new()
+                .with_fixint_encoding()
+                .allow_trailing_bytes()
+                .deserialize_seed(seed, bytes)
+                .map_err(MerkleTreeError::BincodeError)
+        })?;
+        tree.verify_structure()?;
+        Ok(tree)
+    }
+
+    /// Serializes the Merkle Tree to MessagePack.
+    #[cfg(feature = "msgpack")]
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, MerkleTreeError> {
+        rmp_serde::to_vec(self).map_err(MerkleTreeError::MsgpackEncodeError)
+    }
+
+    /// Deserializes the Merkle Tree from MessagePack.
+    #[cfg(feature = "msgpack")]
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        Self::from_msgpack_with_limits(bytes, DeserializeLimits::default())
+    }
+
+    /// Deserializes the Merkle Tree from MessagePack like [`MerkleTree::from_msgpack`],
+    /// rejecting trees that exceed `limits` or whose shape does not match their leaf list.
+    #[cfg(feature = "msgpack")]
+    pub fn from_msgpack_with_limits(
+        bytes: &[u8],
+        limits: DeserializeLimits,
+    ) -> Result<Self, MerkleTreeError> {
+        let tree = load_tree(limits, |seed| {
+            seed.deserialize(&mut rmp_serde::Deserializer::from_read_ref(bytes))
+                .map_err(MerkleTreeError::MsgpackDecodeError)
+        })?;
+        tree.verify_structure()?;
+        Ok(tree)
+    }
+
+    /// Deserializes the Merkle Tree from a JSON string, rejecting trees that exceed `limits`.
+    ///
+    /// Use this instead of [`MerkleTree::from_json`] when loading untrusted dumps with
+    /// tighter limits than the defaults.
+    pub fn from_json_with_limits(
+        json_str: &str,
+        limits: DeserializeLimits,
+    ) -> Result<Self, MerkleTreeError> {
+        Self::from_json_slice(json_str.as_bytes(), limits)
+    }
+
+    /// Deserializes the Merkle Tree from a JSON string like [`MerkleTree::from_json`], then
+    /// recomputes every hash with [`MerkleTree::verify_integrity`].
+    ///
+    /// Plain loading accepts any well-formed dump as is; use this for dumps that may have
+    /// been tampered with or corrupted in storage.
+    pub fn from_json_verified(json_str: &str) -> Result<Self, MerkleTreeError> {
+        let tree = Self::from_json(json_str)?;
+        tree.verify_integrity()?;
+        Ok(tree)
+    }
+
+    /// Recomputes every internal hash and checks the tree's shape against its leaf list,
+    /// failing at the first inconsistency.
+    pub fn verify_integrity(&self) -> Result<(), MerkleTreeError> {
+        if let Some(node) = self.find_corrupt_node() {
+            return Err(MerkleTreeError::CorruptNode(node));
+        }
+        self.verify_structure()
     }
 
     /// Verifies the integrity of the Merkle Tree.
     pub fn verify(&self) -> bool {
-        Self::verify_node(&self.root)
-    }
-
-    /// Recursively verifies the hash of each node.
-    fn verify_node(node: &MerkleNode) -> bool {
-        if node.left.is_none() && node.right.is_none() {
-            // Leaf node: hash should already be correct
-            true
-        } else if let (Some(left), Some(right)) = (&node.left, &node.right) {
-            // Internal node: recompute hash and compare
-            let mut combined = Vec::new();
-            combined.extend(left.hash);
-            combined.extend(right.hash);
-            let expected_hash = keccak256(&combined);
-            if node.hash != expected_hash {
-                return false;
-            }
-            // Recursively verify children
-            Self::verify_node(left) && Self::verify_node(right)
-        } else {
-            // Invalid node state
-            false
+        self.find_corrupt_node().is_none()
+    }
+
+    /// Returns the first corrupt node in pre-order, or `None` if every hash checks out.
+    ///
+    /// The walk stops at the first failure instead of visiting the rest of the tree.
+    pub fn find_corrupt_node(&self) -> Option<CorruptNode> {
+        find_corrupt_node(&self.root, self.hasher()).map(CorruptNode::finish)
+    }
+
+    /// Checks that the tree's shape and leaf list are consistent with each other.
+    ///
+    /// Hashes are not recomputed; combine with [`MerkleTree::find_corrupt_node`] for a
+    /// full audit of a persisted tree. Ambiguous trees are well-formed and pass; reject
+    /// them with [`MerkleTree::check_unambiguous`] where they are not wanted.
+    pub fn verify_structure(&self) -> Result<(), MerkleTreeError> {
+        // Copies made for odd nodes look like leaves, so duplicating trees are read by position
+        let leaf_hashes = match self.odd_nodes {
+            OddNodePolicy::Promote => {
+                let mut leaf_hashes = Vec::with_capacity(self.leaf_hashes.len());
+                collect_leaf_hashes(&self.root, &mut leaf_hashes);
+                leaf_hashes
+            }
+            OddNodePolicy::Duplicate => self.levels()?.swap_remove(0),
+        };
+        if leaf_hashes.len() != self.leaf_hashes.len() {
+            return Err(MerkleTreeError::LengthMismatch {
+                expected: leaf_hashes.len(),
+                got: self.leaf_hashes.len(),
+            });
         }
+        if let Some(index) = (0..leaf_hashes.len()).find(|&i| leaf_hashes[i] != self.leaf_hashes[i])
+        {
+            return Err(MerkleTreeError::LeafMismatch {
+                index,
+                expected: leaf_hashes[index],
+                got: self.leaf_hashes[index],
+            });
+        }
+
+        // Fails if a node that should have been promoted was split, or vice versa.
+        self.levels()?;
+        Ok(())
+    }
+
+    /// Checks that no other leaf list produces the same root (CVE-2012-2459).
+    ///
+    /// With [`OddNodePolicy::Duplicate`], a level ending in two equal nodes hashes exactly
+    /// like the same level without its last node, whose odd last node is paired with a copy
+    /// of itself. A tree over `[a, b, c, c]` thus has the root of a tree over `[a, b, c]`,
+    /// which lets a forger add or drop trailing leaves without changing the root. Promoting
+    /// trees are never ambiguous.
+    pub fn check_unambiguous(&self) -> Result<(), MerkleTreeError> {
+        if self.odd_nodes != OddNodePolicy::Duplicate {
+            return Ok(());
+        }
+        for (level, nodes) in self.levels()?.iter().enumerate() {
+            if let [.., second_last, last] = nodes.as_slice() {
+                if nodes.len() % 2 == 0 && second_last == last {
+                    return Err(MerkleTreeError::DuplicateOddNode { level, hash: *last });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Verifies the integrity of the Merkle Tree, checking subtrees in parallel.
+    #[cfg(feature = "parallel")]
+    pub fn par_verify(&self) -> bool {
+        self.par_find_corrupt_node().is_none()
+    }
+
+    /// Parallel version of [`MerkleTree::find_corrupt_node`].
+    ///
+    /// Subtrees are checked as separate rayon tasks; the reported node is still the first
+    /// corrupt one in pre-order.
+    #[cfg(feature = "parallel")]
+    pub fn par_find_corrupt_node(&self) -> Option<CorruptNode> {
+        par_find_corrupt_node(&self.root, self.hasher()).map(CorruptNode::finish)
+    }
+
+    /// Generates a Merkle Proof for every leaf, in leaf order.
+    pub fn generate_all_proofs(&self) -> Result<Vec<MerkleProof>, MerkleTreeError> {
+        (0..self.leaf_count())
+            .map(|index| self.generate_proof_by_index(index))
+            .collect()
+    }
+
+    /// Parallel version of [`MerkleTree::generate_all_proofs`], generating proofs on
+    /// rayon's thread pool.
+    #[cfg(feature = "parallel")]
+    pub fn par_generate_all_proofs(&self) -> Result<Vec<MerkleProof>, MerkleTreeError> {
+        use rayon::prelude::*;
+        (0..self.leaf_count())
+            .into_par_iter()
+            .map(|index| self.generate_proof_by_index(index))
+            .collect()
     }
 
     /// Generates a Merkle Proof for the given data.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(leaf_count = self.leaf_count(), depth = self.depth())
+        )
+    )]
     pub fn generate_proof(&self, data: &[u8]) -> Result<MerkleProof, MerkleTreeError> {
-        let leaf_hash = keccak256(data);
+        let leaf_hash = self.hash_leaf(data);
 
-        if !self.leaves.contains_key(&leaf_hash) {
-            return Err(MerkleTreeError::InvalidProof(
-                "Data not found in the tree".to_string(),
-            ));
+        if !self.leaves.contains_key(&leaf_hash) && !self.leaf_hashes.contains(&leaf_hash) {
+            return Err(MerkleTreeError::LeafNotFound { hash: leaf_hash });
         }
 
         let mut proof_steps = Vec::new();
@@ -140,6 +761,44 @@ impl MerkleTree {
         Ok(MerkleProof {
             leaf_hash,
             proof_steps,
+            hash_algorithm: self.hash_algorithm,
+            sorted_pairs: self.sorted_pairs,
+            domain_separation: self.domain_separation,
+        })
+    }
+
+    /// Generates a Merkle Proof for the leaf at `index`.
+    ///
+    /// Works without the raw leaf data, so it remains available after [`MerkleTree::shrink`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip(self),
+            fields(leaf_count = self.leaf_count(), depth = self.depth())
+        )
+    )]
+    pub fn generate_proof_by_index(&self, index: usize) -> Result<MerkleProof, MerkleTreeError> {
+        let (leaf, proof_steps) = index_proof_steps(
+            &self.root,
+            self.leaf_hashes.len(),
+            index,
+            self.odd_nodes,
+            |node| {
+                Ok(match (&node.left, &node.right) {
+                    (Some(left), Some(right)) => Some((&**left, &**right)),
+                    _ => None,
+                })
+            },
+            |node| node.hash,
+        )?;
+
+        Ok(MerkleProof {
+            leaf_hash: leaf.hash,
+            proof_steps,
+            hash_algorithm: self.hash_algorithm,
+            sorted_pairs: self.sorted_pairs,
+            domain_separation: self.domain_separation,
         })
     }
 
@@ -157,13 +816,13 @@ impl MerkleTree {
         if let (Some(left), Some(right)) = (&node.left, &node.right) {
             // Search left subtree
             if self.build_proof(left, target_hash, proof_steps)? {
-                proof_steps.push(ProofStep::Right(right.hash.clone()));
+                proof_steps.push(ProofStep::Right(right.hash));
                 return Ok(true);
             }
 
             // Search right subtree
             if self.build_proof(right, target_hash, proof_steps)? {
-                proof_steps.push(ProofStep::Left(left.hash.clone()));
+                proof_steps.push(ProofStep::Left(left.hash));
                 return Ok(true);
             }
         }
@@ -195,8 +854,744 @@ impl MerkleTree {
     }
 }
 
+impl<'de> Deserialize<'de> for MerkleTree {
+    fn deserialize<D>(deserializer: D) -> Result<MerkleTree, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let failure = LoadFailure::default();
+        TreeSeed {
+            limits: DeserializeLimits::default(),
+            failure: &failure,
+        }
+        .deserialize(deserializer)
+    }
+}
+
+/// Fields written by [`MerkleTree`]'s `Serialize` implementation, in order.
+const TREE_FIELDS: &[&str] = &[
+    "root",
+    "leaf_hashes",
+    "hash_algorithm",
+    "sorted_pairs",
+    "odd_nodes",
+    "domain_separation",
+];
+
+/// Runs `load` with a [`TreeSeed`] under `limits`, returning the typed error of a failed
+/// limit or consistency check instead of the deserializer's message.
+fn load_tree<E: Into<MerkleTreeError>>(
+    limits: DeserializeLimits,
+    load: impl FnOnce(TreeSeed<'_>) -> Result<MerkleTree, E>,
+) -> Result<MerkleTree, MerkleTreeError> {
+    let failure = LoadFailure::default();
+    load(TreeSeed {
+        limits,
+        failure: &failure,
+    })
+    .map_err(|e| failure.or(e))
+}
+
+/// Seed deserializing a tree whose root is read under the given limits.
+#[derive(Clone, Copy)]
+struct TreeSeed<'a> {
+    limits: DeserializeLimits,
+    failure: &'a LoadFailure,
+}
+
+impl<'a> TreeSeed<'a> {
+    /// Seed for the root, read under the same limits.
+    fn root(self) -> LimitedNode<'a> {
+        LimitedNode {
+            limits: self.limits,
+            failure: self.failure,
+        }
+    }
+
+    /// Restores what the dump left out and checks the leaf count limit.
+    fn finish<E: SerdeError>(
+        self,
+        mut tree: MerkleTree,
+        leaf_data: Option<Vec<Option<Vec<u8>>>>,
+    ) -> Result<MerkleTree, E> {
+        tree.finish_loading(leaf_data)
+            .and_then(|()| self.limits.check_leaf_count(tree.leaf_count()))
+            .map_err(|e| self.failure.fail(e))?;
+        Ok(tree)
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for TreeSeed<'_> {
+    type Value = MerkleTree;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<MerkleTree, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct("MerkleTree", TREE_FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for TreeSeed<'_> {
+    type Value = MerkleTree;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a Merkle Tree")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<MerkleTree, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut root = None;
+        let mut leaf_hashes = Vec::new();
+        let mut leaf_data = None;
+        let mut hash_algorithm = HashAlgorithm::default();
+        let mut sorted_pairs = false;
+        let mut odd_nodes = OddNodePolicy::default();
+        let mut domain_separation = false;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "root" => root = Some(map.next_value_seed(self.root())?),
+                "leaf_hashes" => {
+                    let hashes: Vec<HexB256> = map.next_value_seed(LeafSeq::new(self))?;
+                    leaf_hashes = hashes.into_iter().map(|HexB256(hash)| hash).collect();
+                }
+                "leaf_data" => {
+                    let data: Vec<Option<HexBytes>> = map.next_value_seed(LeafSeq::new(self))?;
+                    leaf_data = Some(
+                        data.into_iter()
+                            .map(|data| data.map(|HexBytes(bytes)| bytes))
+                            .collect(),
+                    );
+                }
+                "hash_algorithm" => hash_algorithm = map.next_value()?,
+                "sorted_pairs" => sorted_pairs = map.next_value()?,
+                "odd_nodes" => odd_nodes = map.next_value()?,
+                "domain_separation" => domain_separation = map.next_value()?,
+                "schema_version" => {
+                    let version: u32 = map.next_value()?;
+                    if version > SCHEMA_VERSION {
+                        return Err(self
+                            .failure
+                            .fail(MerkleTreeError::UnsupportedSchemaVersion {
+                                version,
+                                max: SCHEMA_VERSION,
+                            }));
+                    }
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        let root = root.ok_or_else(|| A::Error::missing_field("root"))?;
+        let tree = MerkleTree {
+            root,
+            leaves: HashMap::new(),
+            leaf_hashes,
+            hash_algorithm,
+            sorted_pairs,
+            odd_nodes,
+            domain_separation,
+            limits: self.limits,
+            allow_64_byte_leaves: false,
+            strict: false,
+        };
+        self.finish(tree, leaf_data)
+    }
+
+    /// Reads the fields of binary encodings, which are written in order without names
+    /// and carry no leaf data.
+    fn visit_seq<A>(self, mut seq: A) -> Result<MerkleTree, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let root = seq
+            .next_element_seed(self.root())?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let leaf_hashes: Vec<HexB256> = seq
+            .next_element_seed(LeafSeq::new(self))?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        let hash_algorithm = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(2, &self))?;
+        let sorted_pairs = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(3, &self))?;
+        let odd_nodes = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(4, &self))?;
+        let domain_separation = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(5, &self))?;
+
+        let tree = MerkleTree {
+            root,
+            leaves: HashMap::new(),
+            leaf_hashes: leaf_hashes.into_iter().map(|HexB256(hash)| hash).collect(),
+            hash_algorithm,
+            sorted_pairs,
+            odd_nodes,
+            domain_separation,
+            limits: self.limits,
+            allow_64_byte_leaves: false,
+            strict: false,
+        };
+        self.finish(tree, None)
+    }
+}
+
+/// An element of `leaf_hashes` or `leaf_data`.
+trait LeafEntry: DeserializeOwned {
+    /// Length of the raw leaf data, checked against the leaf size limit.
+    fn data_len(&self) -> usize;
+}
+
+impl LeafEntry for HexB256 {
+    fn data_len(&self) -> usize {
+        0
+    }
+}
+
+impl LeafEntry for Option<HexBytes> {
+    fn data_len(&self) -> usize {
+        self.as_ref().map_or(0, |HexBytes(bytes)| bytes.len())
+    }
+}
+
+/// Seed for `leaf_hashes` or `leaf_data` that fails at the first element over the leaf
+/// or node count limit, or longer than the leaf size limit, before reading the rest.
+struct LeafSeq<'a, T> {
+    seed: TreeSeed<'a>,
+    entries: PhantomData<T>,
+}
+
+impl<'a, T> LeafSeq<'a, T> {
+    fn new(seed: TreeSeed<'a>) -> Self {
+        LeafSeq {
+            seed,
+            entries: PhantomData,
+        }
+    }
+}
+
+impl<'de, T: LeafEntry> DeserializeSeed<'de> for LeafSeq<'_, T> {
+    type Value = Vec<T>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, T: LeafEntry> Visitor<'de> for LeafSeq<'_, T> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a list of leaves")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Vec<T>, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let TreeSeed { limits, failure } = self.seed;
+        let mut entries = Vec::new();
+        while let Some(entry) = seq.next_element::<T>()? {
+            let count = entries.len() + 1;
+            if count > limits.max_nodes {
+                return Err(failure.fail(MerkleTreeError::TooManyNodes {
+                    count,
+                    max: limits.max_nodes,
+                }));
+            }
+            if count > limits.max_leaves {
+                return Err(failure.fail(MerkleTreeError::TooManyLeaves {
+                    count,
+                    max: limits.max_leaves,
+                }));
+            }
+            if entry.data_len() > limits.max_leaf_size {
+                return Err(failure.fail(MerkleTreeError::LeafTooLarge {
+                    index: entries.len(),
+                    size: entry.data_len(),
+                    max: limits.max_leaf_size,
+                }));
+            }
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+/// The child taken when descending from a node.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    Left,
+    Right,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Left => write!(f, "left"),
+            Direction::Right => write!(f, "right"),
+        }
+    }
+}
+
+/// A node whose stored hash does not match its children.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CorruptNode {
+    /// Directions taken from the root to reach the node.
+    pub path: Vec<Direction>,
+
+    /// The hash stored in the node.
+    pub hash: B256,
+
+    /// The hash recomputed from the children. `None` if the node has a single child.
+    pub expected_hash: Option<B256>,
+}
+
+impl CorruptNode {
+    fn new(node: &MerkleNode, expected_hash: Option<B256>) -> Self {
+        CorruptNode {
+            path: Vec::new(),
+            hash: node.hash,
+            expected_hash,
+        }
+    }
+
+    /// Records that the node was found below the given child. Paths are built while
+    /// unwinding, so they are stored reversed until [`CorruptNode::finish`].
+    fn under(mut self, direction: Direction) -> Self {
+        self.path.push(direction);
+        self
+    }
+
+    fn finish(mut self) -> Self {
+        self.path.reverse();
+        self
+    }
+}
+
+impl fmt::Display for CorruptNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "root")?;
+        for direction in &self.path {
+            write!(f, "/{}", direction)?;
+        }
+        match &self.expected_hash {
+            Some(expected_hash) => write!(
+                f,
+                ": stored hash {}, expected {}",
+                encode(self.hash),
+                encode(expected_hash)
+            ),
+            None => write!(f, ": node {} has a single child", encode(self.hash)),
+        }
+    }
+}
+
+/// Checks a node's hash against its children, returning the children to descend into.
+fn check_node(
+    node: &MerkleNode,
+    hasher: TreeHasher,
+) -> Result<Option<(&MerkleNode, &MerkleNode)>, CorruptNode> {
+    match (&node.left, &node.right) {
+        (None, None) => Ok(None),
+        (Some(left), Some(right)) => {
+            let expected_hash = hasher.hash_pair(&left.hash, &right.hash);
+            if hashes_equal(&node.hash, &expected_hash) {
+                Ok(Some((&**left, &**right)))
+            } else {
+                Err(CorruptNode::new(node, Some(expected_hash)))
+            }
+        }
+        _ => Err(CorruptNode::new(node, None)),
+    }
+}
+
+fn find_corrupt_node(node: &MerkleNode, hasher: TreeHasher) -> Option<CorruptNode> {
+    let (left, right) = match check_node(node, hasher) {
+        Ok(Some(children)) => children,
+        Ok(None) => return None,
+        Err(corrupt) => return Some(corrupt),
+    };
+
+    find_corrupt_node(left, hasher)
+        .map(|corrupt| corrupt.under(Direction::Left))
+        .or_else(|| find_corrupt_node(right, hasher).map(|corrupt| corrupt.under(Direction::Right)))
+}
+
+/// Hashes a batch of leaves onto `leaves`, on rayon's thread pool with the `parallel`
+/// feature.
+fn hash_leaves<'a>(
+    batch: &'a [Vec<u8>],
+    hasher: TreeHasher,
+    leaves: &mut Vec<(MerkleNode, &'a Vec<u8>)>,
+) {
+    let hash_leaf = |datum: &'a Vec<u8>| (MerkleNode::leaf(hasher.hash_leaf(datum)), datum);
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        leaves.par_extend(batch.par_iter().map(hash_leaf));
+    }
+    #[cfg(not(feature = "parallel"))]
+    leaves.extend(batch.iter().map(hash_leaf));
+}
+
+#[cfg(feature = "parallel")]
+fn par_find_corrupt_node(node: &MerkleNode, hasher: TreeHasher) -> Option<CorruptNode> {
+    let (left, right) = match check_node(node, hasher) {
+        Ok(Some(children)) => children,
+        Ok(None) => return None,
+        Err(corrupt) => return Some(corrupt),
+    };
+
+    let (left_result, right_result) = rayon::join(
+        || par_find_corrupt_node(left, hasher),
+        || par_find_corrupt_node(right, hasher),
+    );
+    left_result
+        .map(|corrupt| corrupt.under(Direction::Left))
+        .or_else(|| right_result.map(|corrupt| corrupt.under(Direction::Right)))
+}
+
+/// Records the hash of `node`, which sits at `index` on `level`, and of everything below it.
+///
+/// A node whose leaf range fits in its left half was promoted, so it also fills the slot
+/// below it instead of splitting into its children. Under [`OddNodePolicy::Duplicate`] it
+/// joins its left child with a copy, and only the left child is recorded.
+fn fill_levels(
+    node: &MerkleNode,
+    level: usize,
+    index: usize,
+    leaf_count: usize,
+    odd_nodes: OddNodePolicy,
+    levels: &mut [Vec<B256>],
+) -> Result<(), MerkleTreeError> {
+    levels[level][index] = node.hash;
+    if level == 0 {
+        return match (&node.left, &node.right) {
+            (None, None) => Ok(()),
+            _ => Err(shape_mismatch()),
+        };
+    }
+
+    let mid = (index << level) + (1 << (level - 1));
+    if leaf_count <= mid {
+        let node = match odd_nodes {
+            OddNodePolicy::Promote => node,
+            OddNodePolicy::Duplicate => duplicated_child(node).ok_or_else(shape_mismatch)?,
+        };
+        return fill_levels(node, level - 1, 2 * index, leaf_count, odd_nodes, levels);
+    }
+
+    match (&node.left, &node.right) {
+        (Some(left), Some(right)) => {
+            fill_levels(left, level - 1, 2 * index, leaf_count, odd_nodes, levels)?;
+            fill_levels(
+                right,
+                level - 1,
+                2 * index + 1,
+                leaf_count,
+                odd_nodes,
+                levels,
+            )
+        }
+        _ => Err(shape_mismatch()),
+    }
+}
+
+/// Returns the left child of a node that joins it with a copy of itself.
+pub(crate) fn duplicated_child(node: &MerkleNode) -> Option<&MerkleNode> {
+    match (&node.left, &node.right) {
+        (Some(left), Some(copy))
+            if copy.hash == left.hash && copy.left.is_none() && copy.right.is_none() =>
+        {
+            Some(left)
+        }
+        _ => None,
+    }
+}
+
+pub(crate) fn shape_mismatch() -> MerkleTreeError {
+    MerkleTreeError::ShapeMismatch
+}
+
+/// Appends the hashes of the leaves below `node`, left to right.
+pub(crate) fn collect_leaf_hashes(node: &MerkleNode, leaf_hashes: &mut Vec<B256>) {
+    match (&node.left, &node.right) {
+        (Some(left), Some(right)) => {
+            collect_leaf_hashes(left, leaf_hashes);
+            collect_leaf_hashes(right, leaf_hashes);
+        }
+        _ => leaf_hashes.push(node.hash),
+    }
+}
+
+/// Walks down from `root` to leaf `index`, returning the leaf and its proof steps.
+///
+/// `split` resolves a node into its children and `hash_of` returns a node's hash, so the
+/// walk works over in-memory nodes as well as nodes fetched from a store. Node `j` at
+/// level `k` covers leaves `[j * 2^k, (j + 1) * 2^k)`; a node whose range fits entirely in
+/// its left half was promoted unchanged, so the walk descends without moving, or, under
+/// [`OddNodePolicy::Duplicate`], joins its left child with a copy that becomes a step.
+pub(crate) fn index_proof_steps<N, S, H>(
+    root: N,
+    leaf_count: usize,
+    index: usize,
+    odd_nodes: OddNodePolicy,
+    mut split: S,
+    hash_of: H,
+) -> Result<(N, Vec<ProofStep>), MerkleTreeError>
+where
+    S: FnMut(&N) -> Result<Option<(N, N)>, MerkleTreeError>,
+    H: Fn(&N) -> B256,
+{
+    if index >= leaf_count {
+        return Err(MerkleTreeError::IndexOutOfRange { index, leaf_count });
+    }
+
+    let mut level = leaf_count.next_power_of_two().trailing_zeros();
+    let (mut lo, mut hi) = (0, leaf_count);
+    let mut node = root;
+    let mut proof_steps = Vec::new();
+
+    while level > 0 {
+        level -= 1;
+        let mid = lo + (1 << level);
+        if hi <= mid && odd_nodes == OddNodePolicy::Promote {
+            continue;
+        }
+
+        let (left, right) = split(&node)?.ok_or_else(shape_mismatch)?;
+
+        if hi <= mid {
+            proof_steps.push(ProofStep::Right(hash_of(&right)));
+            node = left;
+        } else if index < mid {
+            proof_steps.push(ProofStep::Right(hash_of(&right)));
+            node = left;
+            hi = mid;
+        } else {
+            proof_steps.push(ProofStep::Left(hash_of(&left)));
+            node = right;
+            lo = mid;
+        }
+    }
+
+    proof_steps.reverse();
+    Ok((node, proof_steps))
+}
+
 impl fmt::Display for MerkleTree {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.root.fmt(f)
     }
 }
+
+/// Wipes the raw leaf data when the tree is dropped.
+#[cfg(feature = "zeroize")]
+impl Drop for MerkleTree {
+    fn drop(&mut self) {
+        self.clear_leaf_data();
+    }
+}
+
+impl MerkleTree {
+    /// Returns a value printing the tree with the given options, for trees too large to
+    /// print in full with [`Display`](fmt::Display).
+    pub fn display(&self, options: DisplayOptions) -> NodeDisplay<'_> {
+        self.root.display(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| format!("leaf {i}").into_bytes())
+            .collect()
+    }
+
+    fn tree(leaf_count: usize) -> MerkleTree {
+        MerkleTree::builder()
+            .retain_leaf_data(false)
+            .build(&leaves(leaf_count))
+            .unwrap()
+    }
+
+    /// A well-formed root of the given depth, whose nodes all hash to zero.
+    #[cfg(any(feature = "bincode", feature = "msgpack", feature = "cbor"))]
+    fn deep_root(depth: usize) -> MerkleNode {
+        let leaf = || MerkleNode {
+            hash: B256::ZERO,
+            left: None,
+            right: None,
+        };
+        let mut node = leaf();
+        for _ in 0..depth {
+            node = MerkleNode {
+                hash: B256::ZERO,
+                left: Some(Box::new(node)),
+                right: Some(Box::new(leaf())),
+            };
+        }
+        node
+    }
+
+    /// Checks a binary encoding's round trip, limits and handling of malicious input.
+    #[cfg(any(feature = "bincode", feature = "msgpack", feature = "cbor"))]
+    fn check_binary_loader<E: fmt::Debug>(
+        encode: impl Fn(&MerkleTree) -> Result<Vec<u8>, E>,
+        decode: impl Fn(&[u8], DeserializeLimits) -> Result<MerkleTree, MerkleTreeError>,
+    ) {
+        let limits = DeserializeLimits::default();
+        for leaf_count in 1..=9 {
+            let tree = tree(leaf_count);
+            let decoded = decode(&encode(&tree).unwrap(), limits).unwrap();
+            assert_eq!(decoded, tree, "{leaf_count}");
+        }
+
+        let bytes = encode(&tree(9)).unwrap();
+        for max_leaves in [1, 8] {
+            let limits = DeserializeLimits {
+                max_leaves,
+                ..limits
+            };
+            assert!(
+                matches!(
+                    decode(&bytes, limits),
+                    Err(MerkleTreeError::TooManyLeaves { max, .. }) if max == max_leaves
+                ),
+                "{max_leaves} leaves"
+            );
+        }
+        let shallow = DeserializeLimits {
+            max_depth: 3,
+            ..limits
+        };
+        assert!(matches!(
+            decode(&bytes, shallow),
+            Err(MerkleTreeError::DepthExceeded { depth: 4, max: 3 })
+        ));
+        let small = DeserializeLimits {
+            max_nodes: 10,
+            ..limits
+        };
+        assert!(matches!(
+            decode(&bytes, small),
+            Err(MerkleTreeError::TooManyNodes { max: 10, .. })
+        ));
+
+        // Truncated input
+        for len in [0, 1, bytes.len() / 2, bytes.len() - 1] {
+            assert!(decode(&bytes[..len], limits).is_err(), "{len} bytes");
+        }
+
+        // A leaf list that does not match the tree
+        let mut tampered = tree(9);
+        tampered.leaf_hashes.swap(0, 1);
+        assert!(matches!(
+            decode(&encode(&tampered).unwrap(), limits),
+            Err(MerkleTreeError::LeafMismatch { index: 0, .. })
+        ));
+        tampered.leaf_hashes.pop();
+        assert!(matches!(
+            decode(&encode(&tampered).unwrap(), limits),
+            Err(MerkleTreeError::LengthMismatch {
+                expected: 9,
+                got: 8
+            })
+        ));
+
+        // A root nested deeper than the default depth limit
+        let mut deep = tree(1);
+        deep.root = deep_root(100);
+        assert!(decode(&encode(&deep).unwrap(), limits).is_err());
+    }
+
+    #[test]
+    fn json_loader_reports_typed_errors() {
+        let json = tree(9).to_json().unwrap();
+        let limits = DeserializeLimits::default();
+        let load = |limits| MerkleTree::from_json_with_limits(&json, limits);
+        assert!(matches!(
+            load(DeserializeLimits {
+                max_leaves: 8,
+                ..limits
+            }),
+            Err(MerkleTreeError::TooManyLeaves { count: 9, max: 8 })
+        ));
+        assert!(matches!(
+            load(DeserializeLimits {
+                max_depth: 3,
+                ..limits
+            }),
+            Err(MerkleTreeError::DepthExceeded { depth: 4, max: 3 })
+        ));
+        assert!(matches!(
+            load(DeserializeLimits {
+                max_nodes: 10,
+                ..limits
+            }),
+            Err(MerkleTreeError::TooManyNodes { count: 11, max: 10 })
+        ));
+
+        let mut value: Value = serde_json::from_str(&json).unwrap();
+        value["schema_version"] = Value::from(SCHEMA_VERSION + 1);
+        let newer = value.to_string();
+        assert!(matches!(
+            MerkleTree::from_json(&newer),
+            Err(MerkleTreeError::UnsupportedSchemaVersion { version, max })
+                if version == SCHEMA_VERSION + 1 && max == SCHEMA_VERSION
+        ));
+        assert!(matches!(
+            MerkleTree::from_reader(newer.as_bytes()),
+            Err(MerkleTreeError::UnsupportedSchemaVersion { .. })
+        ));
+
+        let tree = MerkleTree::new(&leaves(3)).unwrap();
+        let mut value: Value =
+            serde_json::from_str(&tree.to_json_with_leaf_data().unwrap()).unwrap();
+        value["leaf_data"][1] = Value::from("0x00");
+        match MerkleTree::from_json_value(value) {
+            Err(MerkleTreeError::LeafMismatch {
+                index,
+                expected,
+                got,
+            }) => {
+                assert_eq!(index, 1);
+                assert_eq!(expected, tree.leaf_hashes[1]);
+                assert_eq!(got, tree.hash_leaf(&[0]));
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_loader() {
+        check_binary_loader(MerkleTree::to_bytes, MerkleTree::from_bytes_with_limits);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_loader() {
+        check_binary_loader(MerkleTree::to_msgpack, MerkleTree::from_msgpack_with_limits);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_loader() {
+        check_binary_loader(MerkleTree::to_cbor, MerkleTree::from_cbor_with_limits);
+    }
+}
@@ -0,0 +1,126 @@
+//! UniFFI bindings for Swift and Kotlin apps, so proofs can be built and verified
+//! on-device.
+//!
+//! Build the library with `cargo rustc --lib --release --features uniffi --crate-type
+//! staticlib` for Swift (or `cdylib` for Kotlin), then generate the bindings with
+//! `cargo run --features uniffi --bin uniffi-bindgen generate --library <lib> --language
+//! swift` (or `kotlin`). Hashes are passed as hex strings, `0x` optional, and proofs as the
+//! JSON written by [`MerkleProof::to_json`](crate::MerkleProof::to_json).
+
+use crate::error::MerkleTreeError;
+use crate::hasher::HashAlgorithm;
+use crate::serialization::parse_b256;
+
+use alloy_primitives::hex::encode_prefixed;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+/// A Merkle Tree over a list of leaves.
+#[derive(uniffi::Object)]
+pub struct MerkleTree(RwLock<crate::MerkleTree>);
+
+#[uniffi::export]
+impl MerkleTree {
+    /// Builds a tree over `leaves`, hashed with `hash_algorithm` (keccak256, sha256 or
+    /// blake3).
+    #[uniffi::constructor]
+    pub fn new(
+        leaves: Vec<Vec<u8>>,
+        hash_algorithm: String,
+        sorted_pairs: bool,
+    ) -> Result<Arc<Self>, MerkleTreeError> {
+        let hash_algorithm = HashAlgorithm::from_str(&hash_algorithm).map_err(|_| {
+            MerkleTreeError::UnknownHashAlgorithm {
+                name: hash_algorithm.clone(),
+            }
+        })?;
+        let tree = crate::MerkleTree::builder()
+            .hash_algorithm(hash_algorithm)
+            .sorted_pairs(sorted_pairs)
+            .build(&leaves)?;
+        Ok(Arc::new(MerkleTree(RwLock::new(tree))))
+    }
+
+    /// Loads a tree from its JSON dump.
+    #[uniffi::constructor]
+    pub fn from_json(json: String) -> Result<Arc<Self>, MerkleTreeError> {
+        let tree = crate::MerkleTree::from_json(&json)?;
+        Ok(Arc::new(MerkleTree(RwLock::new(tree))))
+    }
+
+    /// Serializes the tree to JSON.
+    pub fn to_json(&self) -> Result<String, MerkleTreeError> {
+        self.tree().to_json()
+    }
+
+    /// The root hash.
+    pub fn root(&self) -> String {
+        encode_prefixed(self.tree().root_hash())
+    }
+
+    /// Number of leaves.
+    pub fn leaf_count(&self) -> u64 {
+        self.tree().leaf_count() as u64
+    }
+
+    /// Generates the proof of a leaf.
+    pub fn proof(&self, leaf: Vec<u8>) -> Result<Arc<MerkleProof>, MerkleTreeError> {
+        Ok(Arc::new(MerkleProof(self.tree().generate_proof(&leaf)?)))
+    }
+
+    /// Generates the proof of the leaf at `index`.
+    pub fn proof_by_index(&self, index: u64) -> Result<Arc<MerkleProof>, MerkleTreeError> {
+        let proof = self.tree().generate_proof_by_index(index as usize)?;
+        Ok(Arc::new(MerkleProof(proof)))
+    }
+
+    /// Appends leaves, updating the root.
+    pub fn append(&self, leaves: Vec<Vec<u8>>) -> Result<(), MerkleTreeError> {
+        self.0
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .append(&leaves)
+    }
+}
+
+impl MerkleTree {
+    fn tree(&self) -> std::sync::RwLockReadGuard<'_, crate::MerkleTree> {
+        self.0
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A proof that a leaf belongs to a tree.
+#[derive(uniffi::Object)]
+pub struct MerkleProof(crate::MerkleProof);
+
+#[uniffi::export]
+impl MerkleProof {
+    /// Parses a proof from JSON, migrating older schema versions.
+    #[uniffi::constructor]
+    pub fn from_json(json: String) -> Result<Arc<Self>, MerkleTreeError> {
+        Ok(Arc::new(MerkleProof(crate::MerkleProof::from_json(&json)?)))
+    }
+
+    /// Serializes the proof to JSON.
+    pub fn to_json(&self) -> Result<String, MerkleTreeError> {
+        self.0.to_json()
+    }
+
+    /// The hash of the proven leaf.
+    pub fn leaf_hash(&self) -> String {
+        encode_prefixed(self.0.leaf_hash)
+    }
+
+    /// Verifies the proof against a root hash.
+    pub fn verify(&self, root: String) -> Result<bool, MerkleTreeError> {
+        self.0.verify(&parse_b256(&root)?)
+    }
+}
+
+/// Verifies a proof, given as JSON, against a root hash.
+#[uniffi::export]
+pub fn verify_proof(proof_json: String, root: String) -> Result<bool, MerkleTreeError> {
+    crate::MerkleProof::from_json(&proof_json)?.verify(&parse_b256(&root)?)
+}
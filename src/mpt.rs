@@ -0,0 +1,824 @@
+//! Ethereum Merkle Patricia Tries: roots of the tries committed to in block headers, and
+//! proofs such as the ones returned by `eth_getProof`.
+//!
+//! Unlike [`MerkleTree`](crate::MerkleTree), a trie is keyed: the transactions, receipts
+//! and withdrawals tries map `rlp(index)` to each item's encoding, so their roots can only
+//! be reproduced here.
+//!
+//! A proof lists the RLP-encoded nodes on the path from the root to a key, each one
+//! referenced from its parent by keccak256 hash, or inline when its encoding is shorter
+//! than 32 bytes. Keys of the state and storage tries are the keccak256 of the address
+//! and slot. A proof can also show that a key is absent, by ending at a node where the
+//! key's path diverges.
+
+use crate::error::MerkleTreeError;
+
+use alloy_primitives::{b256, keccak256, Address, Bloom, Log, B256, U256};
+use alloy_rlp::{length_of_length, BufMut, Decodable, Encodable, Header};
+use std::collections::BTreeMap;
+
+/// Root of an empty trie, `keccak256(rlp(""))`.
+pub const EMPTY_ROOT_HASH: B256 =
+    b256!("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+
+/// An account as stored in the state trie.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TrieAccount {
+    pub nonce: u64,
+    pub balance: U256,
+    pub storage_root: B256,
+    pub code_hash: B256,
+}
+
+impl Decodable for TrieAccount {
+    fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+        let header = Header::decode(buf)?;
+        if !header.list {
+            return Err(alloy_rlp::Error::UnexpectedString);
+        }
+        let started = buf.len();
+        let account = TrieAccount {
+            nonce: u64::decode(buf)?,
+            balance: U256::decode(buf)?,
+            storage_root: B256::decode(buf)?,
+            code_hash: B256::decode(buf)?,
+        };
+        let consumed = started - buf.len();
+        if consumed != header.payload_length {
+            return Err(alloy_rlp::Error::ListLengthMismatch {
+                expected: header.payload_length,
+                got: consumed,
+            });
+        }
+        Ok(account)
+    }
+}
+
+/// A validator withdrawal, as listed in a post-Shanghai block (EIP-4895).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Withdrawal {
+    pub index: u64,
+    pub validator_index: u64,
+    pub address: Address,
+    /// Amount in gwei.
+    pub amount: u64,
+}
+
+impl Withdrawal {
+    fn rlp_payload_length(&self) -> usize {
+        self.index.length()
+            + self.validator_index.length()
+            + self.address.length()
+            + self.amount.length()
+    }
+}
+
+impl Encodable for Withdrawal {
+    fn encode(&self, out: &mut dyn BufMut) {
+        Header {
+            list: true,
+            payload_length: self.rlp_payload_length(),
+        }
+        .encode(out);
+        self.index.encode(out);
+        self.validator_index.encode(out);
+        self.address.encode(out);
+        self.amount.encode(out);
+    }
+
+    fn length(&self) -> usize {
+        let payload_length = self.rlp_payload_length();
+        payload_length + length_of_length(payload_length)
+    }
+}
+
+/// How a node refers to a child.
+enum NodeRef<'a> {
+    Hash(B256),
+    Inline(&'a [u8]),
+}
+
+fn invalid(message: impl Into<String>) -> MerkleTreeError {
+    MerkleTreeError::InvalidTrieProof(message.into())
+}
+
+/// Splits a node into the raw RLP encodings of its items.
+fn node_items(node: &[u8]) -> Result<Vec<&[u8]>, MerkleTreeError> {
+    let mut buf = node;
+    let header = Header::decode(&mut buf)?;
+    if !header.list || buf.len() != header.payload_length {
+        return Err(invalid("trie node is not an RLP list"));
+    }
+    let mut items = Vec::with_capacity(17);
+    while !buf.is_empty() {
+        let start = buf;
+        let item = Header::decode(&mut buf)?;
+        if buf.len() < item.payload_length {
+            return Err(alloy_rlp::Error::InputTooShort.into());
+        }
+        buf = &buf[item.payload_length..];
+        items.push(&start[..start.len() - buf.len()]);
+    }
+    Ok(items)
+}
+
+/// Returns the payload of an item that must be a string.
+fn item_bytes(mut item: &[u8]) -> Result<&[u8], MerkleTreeError> {
+    Ok(Header::decode_bytes(&mut item, false)?)
+}
+
+/// Returns the child an item refers to, or `None` for an empty slot.
+fn child_ref(item: &[u8]) -> Result<Option<NodeRef<'_>>, MerkleTreeError> {
+    if item
+        .first()
+        .is_some_and(|&byte| byte >= alloy_rlp::EMPTY_LIST_CODE)
+    {
+        return Ok(Some(NodeRef::Inline(item)));
+    }
+    let bytes = item_bytes(item)?;
+    match bytes.len() {
+        0 => Ok(None),
+        32 => Ok(Some(NodeRef::Hash(B256::from_slice(bytes)))),
+        len => Err(invalid(format!("child reference of {} bytes", len))),
+    }
+}
+
+/// Splits a key into nibbles, high nibble first.
+pub(crate) fn nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+/// Decodes the hex-prefix encoded path of a leaf or extension node, returning its
+/// nibbles and whether the node is a leaf.
+fn decode_path(encoded: &[u8]) -> Result<(Vec<u8>, bool), MerkleTreeError> {
+    let (&first, rest) = encoded
+        .split_first()
+        .ok_or_else(|| invalid("empty node path"))?;
+    let flag = first >> 4;
+    if flag > 3 {
+        return Err(invalid(format!("invalid node path flag {}", flag)));
+    }
+    let mut path = Vec::with_capacity(rest.len() * 2 + 1);
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    path.extend(nibbles(rest));
+    Ok((path, flag & 2 == 2))
+}
+
+/// Hex-prefix encodes a node path, flagging leaves and odd lengths in the first nibble.
+fn encode_path(path: &[u8], is_leaf: bool) -> Vec<u8> {
+    let flag = (path.len() % 2) as u8 + if is_leaf { 2 } else { 0 };
+    let mut encoded = Vec::with_capacity(path.len() / 2 + 1);
+    let rest = match path.split_first() {
+        Some((&first, rest)) if path.len() % 2 == 1 => {
+            encoded.push((flag << 4) | first);
+            rest
+        }
+        _ => {
+            encoded.push(flag << 4);
+            path
+        }
+    };
+    encoded.extend(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    encoded
+}
+
+/// Wraps encoded items in an RLP list header.
+fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload_length = items.iter().map(Vec::len).sum();
+    let mut encoded = Vec::with_capacity(payload_length + 9);
+    Header {
+        list: true,
+        payload_length,
+    }
+    .encode(&mut encoded);
+    for item in items {
+        encoded.extend_from_slice(item);
+    }
+    encoded
+}
+
+/// Returns how a parent refers to an encoded node: inline if shorter than 32 bytes,
+/// otherwise by hash.
+fn node_ref(node: Vec<u8>) -> Vec<u8> {
+    if node.len() < 32 {
+        node
+    } else {
+        alloy_rlp::encode(keccak256(&node))
+    }
+}
+
+/// Encodes the node holding `entries`, sorted by key nibbles, which all share their
+/// first `depth` nibbles.
+///
+/// With a `target` key whose path leads through this node, also pushes the nodes a proof
+/// for it needs onto `proof`, deepest first: the root, and the ones referenced by hash.
+fn encode_node(
+    entries: &[(&[u8], &[u8])],
+    depth: usize,
+    target: Option<&[u8]>,
+    proof: &mut Vec<Vec<u8>>,
+) -> Vec<u8> {
+    let node = encode_node_items(entries, depth, target, proof);
+    if target.is_some() && (depth == 0 || node.len() >= 32) {
+        proof.push(node.clone());
+    }
+    node
+}
+
+fn encode_node_items(
+    entries: &[(&[u8], &[u8])],
+    depth: usize,
+    target: Option<&[u8]>,
+    proof: &mut Vec<Vec<u8>>,
+) -> Vec<u8> {
+    if let [(key, value)] = entries {
+        return encode_list(&[
+            alloy_rlp::encode(encode_path(&key[depth..], true).as_slice()),
+            alloy_rlp::encode(*value),
+        ]);
+    }
+
+    // Keys are sorted, so the first and last share the prefix common to all of them
+    let first = &entries[0].0[depth..];
+    let last = &entries[entries.len() - 1].0[depth..];
+    let shared = first.iter().zip(last).take_while(|(a, b)| a == b).count();
+    if shared > 0 {
+        let path = &first[..shared];
+        let target = target.filter(|target| target[depth..].starts_with(path));
+        return encode_list(&[
+            alloy_rlp::encode(encode_path(path, false).as_slice()),
+            node_ref(encode_node(entries, depth + shared, target, proof)),
+        ]);
+    }
+
+    // A key ending here sorts first and holds the branch's own value
+    let (value, mut rest) = match entries.split_first() {
+        Some(((key, value), rest)) if key.len() == depth => (*value, rest),
+        _ => (&[][..], entries),
+    };
+    let mut items = Vec::with_capacity(17);
+    for nibble in 0..16 {
+        let count = rest
+            .iter()
+            .take_while(|(key, _)| key[depth] == nibble)
+            .count();
+        let (children, remaining) = rest.split_at(count);
+        let target = target.filter(|target| target.get(depth) == Some(&nibble));
+        items.push(if children.is_empty() {
+            alloy_rlp::encode(&[][..])
+        } else {
+            node_ref(encode_node(children, depth + 1, target, proof))
+        });
+        rest = remaining;
+    }
+    items.push(alloy_rlp::encode(value));
+    encode_list(&items)
+}
+
+/// Sorts `entries` by key nibbles. Later entries replace earlier ones with the same key,
+/// and empty values are left out, as the trie does not store them.
+fn sorted_entries<K, V>(entries: impl IntoIterator<Item = (K, V)>) -> BTreeMap<Vec<u8>, V>
+where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    let mut entries: BTreeMap<Vec<u8>, V> = entries
+        .into_iter()
+        .map(|(key, value)| (nibbles(key.as_ref()), value))
+        .collect();
+    entries.retain(|_, value| !value.as_ref().is_empty());
+    entries
+}
+
+/// Encodes the root node of a trie, pushing the proof of `target` onto `proof`, or returns
+/// `None` for an empty trie.
+fn encode_root<V: AsRef<[u8]>>(
+    entries: &BTreeMap<Vec<u8>, V>,
+    target: Option<&[u8]>,
+    proof: &mut Vec<Vec<u8>>,
+) -> Option<Vec<u8>> {
+    if entries.is_empty() {
+        return None;
+    }
+    let entries: Vec<(&[u8], &[u8])> = entries
+        .iter()
+        .map(|(key, value)| (key.as_slice(), value.as_ref()))
+        .collect();
+    Some(encode_node(&entries, 0, target, proof))
+}
+
+/// Computes the root of the trie holding `entries`. Later entries replace earlier ones
+/// with the same key, and empty values are left out, as the trie does not store them.
+pub fn trie_root<K, V>(entries: impl IntoIterator<Item = (K, V)>) -> B256
+where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    encode_root(&sorted_entries(entries), None, &mut Vec::new()).map_or(EMPTY_ROOT_HASH, keccak256)
+}
+
+/// Generates the proof for `key` in the trie holding `entries`, verifiable with
+/// [`verify_proof`]. If `key` is absent, the proof shows that it is.
+pub fn trie_proof<K, V>(entries: impl IntoIterator<Item = (K, V)>, key: &[u8]) -> Vec<Vec<u8>>
+where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    let target = nibbles(key);
+    let mut proof = Vec::new();
+    encode_root(&sorted_entries(entries), Some(&target), &mut proof);
+    proof.reverse();
+    proof
+}
+
+/// Computes the root of a trie mapping `rlp(index)` to each value, as for the
+/// transactions, receipts and withdrawals of a block.
+pub fn ordered_trie_root<V: AsRef<[u8]>>(values: &[V]) -> B256 {
+    trie_root(
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (alloy_rlp::encode(index), value)),
+    )
+}
+
+/// Computes a block's `transactionsRoot` from its transactions, in block order, as their
+/// EIP-2718 encodings (plain RLP for legacy transactions), as returned by
+/// `eth_getRawTransactionByBlockNumberAndIndex`.
+pub fn transactions_root<T: AsRef<[u8]>>(transactions: &[T]) -> B256 {
+    ordered_trie_root(transactions)
+}
+
+/// Computes a block's `withdrawalsRoot` from its withdrawals, in block order. Blocks
+/// before Shanghai have no withdrawals root.
+pub fn withdrawals_root(withdrawals: &[Withdrawal]) -> B256 {
+    let encoded: Vec<Vec<u8>> = withdrawals.iter().map(alloy_rlp::encode).collect();
+    ordered_trie_root(&encoded)
+}
+
+/// Generates the proof for the value at `index` in an ordered trie, keyed by
+/// `rlp(index)`.
+pub fn ordered_trie_proof<V: AsRef<[u8]>>(values: &[V], index: usize) -> Vec<Vec<u8>> {
+    trie_proof(
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (alloy_rlp::encode(index), value)),
+        &alloy_rlp::encode(index),
+    )
+}
+
+/// Computes a block's `receiptsRoot` from its receipts, in block order, as their EIP-2718
+/// encodings.
+pub fn receipts_root<T: AsRef<[u8]>>(receipts: &[T]) -> B256 {
+    ordered_trie_root(receipts)
+}
+
+/// Generates the proof that the receipt at `index` is in the receipts trie of its block.
+pub fn receipt_proof<T: AsRef<[u8]>>(
+    receipts: &[T],
+    index: usize,
+) -> Result<Vec<Vec<u8>>, MerkleTreeError> {
+    if index >= receipts.len() {
+        return Err(MerkleTreeError::IndexOutOfRange {
+            index,
+            leaf_count: receipts.len(),
+        });
+    }
+    Ok(ordered_trie_proof(receipts, index))
+}
+
+/// Verifies that a receipt is at `index` in the receipts trie with root `receipts_root`,
+/// and returns the logs it emitted.
+pub fn verify_receipt_proof<N: AsRef<[u8]>>(
+    receipts_root: B256,
+    index: usize,
+    receipt: &[u8],
+    proof: &[N],
+) -> Result<Vec<Log>, MerkleTreeError> {
+    match verify_proof(receipts_root, &alloy_rlp::encode(index), proof)? {
+        Some(value) if value == receipt => receipt_logs(receipt),
+        Some(_) => Err(invalid(format!(
+            "receipt {} differs from the proven one",
+            index
+        ))),
+        None => Err(invalid(format!("receipt {} is not in the trie", index))),
+    }
+}
+
+/// Decodes the logs of an EIP-2718 encoded receipt.
+pub fn receipt_logs(receipt: &[u8]) -> Result<Vec<Log>, MerkleTreeError> {
+    // Typed receipts start with their type byte, legacy ones with an RLP list
+    let mut buf = match receipt.split_first() {
+        Some((&tx_type, rest)) if tx_type < alloy_rlp::EMPTY_LIST_CODE => rest,
+        _ => receipt,
+    };
+    let header = Header::decode(&mut buf)?;
+    if !header.list || buf.len() != header.payload_length {
+        return Err(invalid("receipt is not an RLP list"));
+    }
+    Header::decode_bytes(&mut buf, false)?; // status or post-state root
+    u128::decode(&mut buf)?; // cumulative gas used
+    Bloom::decode(&mut buf)?;
+    Ok(alloy_rlp::decode_exact(buf)?)
+}
+
+/// Verifies a proof for `key` against `root` and returns the value stored at the key, or
+/// `None` if the proof shows the key is absent.
+///
+/// `key` is the trie key itself; for the state and storage tries, hash the address or
+/// slot first, or use [`verify_account_proof`] and [`verify_storage_proof`].
+pub fn verify_proof<N: AsRef<[u8]>>(
+    root: B256,
+    key: &[u8],
+    proof: &[N],
+) -> Result<Option<Vec<u8>>, MerkleTreeError> {
+    let key = nibbles(key);
+    let mut nodes = proof.iter().map(|node| node.as_ref());
+    let mut position = 0;
+    let mut next = NodeRef::Hash(root);
+
+    let value = loop {
+        let node = match next {
+            NodeRef::Hash(hash) => match nodes.next() {
+                Some(node) if keccak256(node) == hash => node,
+                Some(_) => {
+                    return Err(invalid(format!("proof node does not match hash {}", hash)));
+                }
+                None if hash == EMPTY_ROOT_HASH => break None,
+                None => return Err(invalid("proof ends before the key's leaf")),
+            },
+            NodeRef::Inline(node) => node,
+        };
+
+        let items = node_items(node)?;
+        let child = match items.len() {
+            17 => {
+                let Some(&nibble) = key.get(position) else {
+                    let value = item_bytes(items[16])?;
+                    break (!value.is_empty()).then(|| value.to_vec());
+                };
+                position += 1;
+                items[nibble as usize]
+            }
+            2 => {
+                let (path, is_leaf) = decode_path(item_bytes(items[0])?)?;
+                let rest = &key[position..];
+                if is_leaf {
+                    break (rest == path.as_slice())
+                        .then(|| item_bytes(items[1]).map(<[u8]>::to_vec))
+                        .transpose()?;
+                }
+                if !rest.starts_with(&path) {
+                    break None;
+                }
+                position += path.len();
+                items[1]
+            }
+            len => return Err(invalid(format!("trie node with {} items", len))),
+        };
+        match child_ref(child)? {
+            Some(child) => next = child,
+            None => break None,
+        }
+    };
+
+    if nodes.next().is_some() {
+        return Err(invalid("proof has unused nodes"));
+    }
+    Ok(value)
+}
+
+/// Verifies an account proof against a state root and returns the account, or `None` if
+/// the proof shows it does not exist.
+pub fn verify_account_proof<N: AsRef<[u8]>>(
+    state_root: B256,
+    address: Address,
+    proof: &[N],
+) -> Result<Option<TrieAccount>, MerkleTreeError> {
+    verify_proof(state_root, keccak256(address).as_slice(), proof)?
+        .map(|value| alloy_rlp::decode_exact(value).map_err(MerkleTreeError::RlpError))
+        .transpose()
+}
+
+/// Verifies a storage proof against an account's storage root and returns the slot's
+/// value, which is zero for slots that were never written.
+pub fn verify_storage_proof<N: AsRef<[u8]>>(
+    storage_root: B256,
+    slot: B256,
+    proof: &[N],
+) -> Result<U256, MerkleTreeError> {
+    match verify_proof(storage_root, keccak256(slot).as_slice(), proof)? {
+        Some(value) => Ok(alloy_rlp::decode_exact(value)?),
+        None => Ok(U256::ZERO),
+    }
+}
+
+/// The contents of an `eth_getProof` response, checked against a state root.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct VerifiedAccount {
+    pub address: Address,
+    /// The account, or `None` if it does not exist.
+    pub account: Option<TrieAccount>,
+    /// The requested storage slots and their values.
+    pub storage: Vec<(B256, U256)>,
+}
+
+/// Verifies an `eth_getProof` response against a state root, such as a block header's
+/// `stateRoot`.
+///
+/// Fails if any proof is invalid, or if the account fields or storage values in the
+/// response differ from the ones the proofs commit to.
+#[cfg(feature = "rpc")]
+pub fn verify_eth_get_proof(
+    state_root: B256,
+    response: &alloy_rpc_types_eth::EIP1186AccountProofResponse,
+) -> Result<VerifiedAccount, MerkleTreeError> {
+    // The RPC types use alloy-primitives 0.8, so hashes and addresses go through bytes
+    let address = Address::from_slice(response.address.as_slice());
+    let account = verify_account_proof(state_root, address, &response.account_proof)?;
+    let storage_root = match &account {
+        Some(account) => {
+            if account.nonce != response.nonce
+                || account.balance != response.balance
+                || account.storage_root.as_slice() != response.storage_hash.as_slice()
+                || account.code_hash.as_slice() != response.code_hash.as_slice()
+            {
+                return Err(invalid(format!(
+                    "response fields of {} differ from the proven account",
+                    address
+                )));
+            }
+            account.storage_root
+        }
+        None => {
+            if response.nonce != 0 || response.balance != U256::ZERO {
+                return Err(invalid(format!(
+                    "{} does not exist but the response has a balance or nonce",
+                    address
+                )));
+            }
+            EMPTY_ROOT_HASH
+        }
+    };
+
+    let storage = response
+        .storage_proof
+        .iter()
+        .map(|storage_proof| {
+            let slot = B256::from_slice(storage_proof.key.0.as_slice());
+            let value = verify_storage_proof(storage_root, slot, &storage_proof.proof)?;
+            if value != storage_proof.value {
+                return Err(invalid(format!(
+                    "response value of slot {} differs from the proven value {}",
+                    slot, value
+                )));
+            }
+            Ok((slot, value))
+        })
+        .collect::<Result<_, MerkleTreeError>>()?;
+
+    Ok(VerifiedAccount {
+        address,
+        account,
+        storage,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, hex};
+    use serde_json::Value;
+
+    /// Mainnet `eth_getProof` response for the Uniswap V3 position manager, which has code
+    /// and storage but no balance.
+    const ACCOUNT_PROOF: &str =
+        include_str!("../testdata/eth_get_proof_0xc36442b4a4522e871399cd717abdd847ab11fe88.json");
+
+    fn hex_lines(lines: &str) -> Vec<Vec<u8>> {
+        lines
+            .lines()
+            .map(|line| hex::decode(line).unwrap())
+            .collect()
+    }
+
+    fn account_proof(response: &Value) -> Vec<Vec<u8>> {
+        response["accountProof"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|node| hex::decode(node.as_str().unwrap()).unwrap())
+            .collect()
+    }
+
+    fn quantity(value: &Value) -> u64 {
+        u64::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+    }
+
+    #[test]
+    fn empty_trie_root() {
+        assert_eq!(trie_root(Vec::<(Vec<u8>, Vec<u8>)>::new()), EMPTY_ROOT_HASH);
+        assert_eq!(transactions_root::<Vec<u8>>(&[]), EMPTY_ROOT_HASH);
+    }
+
+    #[test]
+    fn known_trie_roots() {
+        // From the Ethereum trie test suite
+        let entries = [
+            ("doe", "reindeer"),
+            ("dog", "puppy"),
+            ("dogglesworth", "cat"),
+        ];
+        assert_eq!(
+            trie_root(entries),
+            b256!("8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3")
+        );
+        assert_eq!(
+            ordered_trie_root(&["doe", "reindeer"]),
+            b256!("e766d5d51b89dc39d981b41bda63248d7abce4f0225eefd023792a540bcffee3")
+        );
+    }
+
+    #[test]
+    fn mainnet_block_roots() {
+        // Block 14764013
+        let transactions = hex_lines(include_str!("../testdata/block_14764013_transactions.txt"));
+        assert_eq!(
+            transactions_root(&transactions),
+            b256!("18a2978fc62cd1a23e90de920af68c0c3af3330327927cda4c005faccefb5ce7")
+        );
+        let receipts = hex_lines(include_str!("../testdata/block_14764013_receipts.txt"));
+        assert_eq!(
+            receipts_root(&receipts),
+            b256!("168a3827607627e781941dc777737fc4b6beb69a8b139240b881992b35b854ea")
+        );
+
+        // Block 17139055
+        let withdrawals: Value =
+            serde_json::from_str(include_str!("../testdata/block_17139055_withdrawals.json"))
+                .unwrap();
+        let withdrawals: Vec<Withdrawal> = withdrawals
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|withdrawal| Withdrawal {
+                index: quantity(&withdrawal["index"]),
+                validator_index: quantity(&withdrawal["validatorIndex"]),
+                address: withdrawal["address"].as_str().unwrap().parse().unwrap(),
+                amount: quantity(&withdrawal["amount"]),
+            })
+            .collect();
+        assert_eq!(
+            withdrawals_root(&withdrawals),
+            b256!("413f0935d01b220feb4c062960d0a859d1f58448af55dd1434ed9c98a91ee1db")
+        );
+    }
+
+    #[test]
+    fn mainnet_receipt_proof() {
+        let receipts = hex_lines(include_str!("../testdata/block_14764013_receipts.txt"));
+        let root = receipts_root(&receipts);
+        for index in [0, 6, 18] {
+            let proof = receipt_proof(&receipts, index).unwrap();
+            let logs = verify_receipt_proof(root, index, &receipts[index], &proof).unwrap();
+            assert_eq!(logs, receipt_logs(&receipts[index]).unwrap());
+            assert!(verify_receipt_proof(root, index + 1, &receipts[index], &proof).is_err());
+        }
+    }
+
+    #[test]
+    fn mainnet_account_proof() {
+        let response: Value = serde_json::from_str(ACCOUNT_PROOF).unwrap();
+        let proof = account_proof(&response);
+        // The response does not name its block; the first node is the state root's
+        let state_root = keccak256(&proof[0]);
+
+        let account = verify_account_proof(
+            state_root,
+            address!("c36442b4a4522e871399cd717abdd847ab11fe88"),
+            &proof,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(account.nonce, 1);
+        assert_eq!(account.balance, U256::ZERO);
+        assert_eq!(
+            account.storage_root,
+            b256!("79fe22fe88fc4b45db10ce94d975e02e8a42b57dc190f8ae15e321f72bbc08ea")
+        );
+        assert_eq!(
+            account.code_hash,
+            b256!("692e658b31cbe3407682854806658d315d61a58c7e4933a2f91d383dc00736c6")
+        );
+
+        let mut tampered = proof.clone();
+        tampered[3][40] ^= 1;
+        assert!(verify_account_proof(
+            state_root,
+            address!("c36442b4a4522e871399cd717abdd847ab11fe88"),
+            &tampered,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn mainnet_absent_account_proof() {
+        let response: Value = serde_json::from_str(ACCOUNT_PROOF).unwrap();
+        let proof = account_proof(&response);
+        let state_root = keccak256(&proof[0]);
+
+        // This address hashes to 0x3a55e67f…, sharing the position manager's first seven
+        // nibbles; the eighth leads to an empty slot of the branch above its leaf
+        let absent = address!("0000000000000000000000000000000011da0fb6");
+        assert_eq!(
+            verify_account_proof(state_root, absent, &proof[..8]).unwrap(),
+            None
+        );
+        // Nodes past the divergence are not part of the proof
+        assert!(verify_account_proof(state_root, absent, &proof).is_err());
+
+        // Every slot of an account that does not exist is zero
+        assert_eq!(
+            verify_storage_proof(EMPTY_ROOT_HASH, B256::ZERO, &Vec::<Vec<u8>>::new()).unwrap(),
+            U256::ZERO
+        );
+    }
+
+    #[test]
+    fn storage_proof() {
+        let slots: Vec<(B256, U256)> = (0u64..40)
+            .map(|slot| (B256::from(U256::from(slot)), U256::from(slot * 1000 + 1)))
+            .collect();
+        let entries: Vec<(B256, Vec<u8>)> = slots
+            .iter()
+            .map(|(slot, value)| (keccak256(slot), alloy_rlp::encode(value)))
+            .collect();
+        let storage_root = trie_root(entries.iter().map(|(key, value)| (key, value)));
+
+        for (slot, value) in &slots {
+            let proof = trie_proof(
+                entries.iter().map(|(key, value)| (key, value)),
+                keccak256(slot).as_slice(),
+            );
+            assert_eq!(
+                verify_storage_proof(storage_root, *slot, &proof).unwrap(),
+                *value
+            );
+        }
+
+        let unwritten = B256::from(U256::from(1000));
+        let proof = trie_proof(
+            entries.iter().map(|(key, value)| (key, value)),
+            keccak256(unwritten).as_slice(),
+        );
+        assert_eq!(
+            verify_storage_proof(storage_root, unwritten, &proof).unwrap(),
+            U256::ZERO
+        );
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn mainnet_eth_get_proof() {
+        let response: alloy_rpc_types_eth::EIP1186AccountProofResponse =
+            serde_json::from_str(ACCOUNT_PROOF).unwrap();
+        let state_root = keccak256(&response.account_proof[0]);
+
+        let verified = verify_eth_get_proof(state_root, &response).unwrap();
+        assert_eq!(
+            verified.address,
+            address!("c36442b4a4522e871399cd717abdd847ab11fe88")
+        );
+        assert_eq!(verified.account.unwrap().nonce, 1);
+        assert!(verified.storage.is_empty());
+
+        let mut inflated = response.clone();
+        inflated.nonce += 1;
+        assert!(verify_eth_get_proof(state_root, &inflated).is_err());
+
+        // The same nodes, cut where the address of `mainnet_absent_account_proof` diverges
+        let absent: alloy_rpc_types_eth::EIP1186AccountProofResponse =
+            serde_json::from_value(serde_json::json!({
+                "address": "0x0000000000000000000000000000000011da0fb6",
+                "accountProof": &response.account_proof[..8],
+                "balance": "0x0",
+                "codeHash": B256::ZERO,
+                "nonce": "0x0",
+                "storageHash": B256::ZERO,
+                "storageProof": [{ "key": B256::ZERO, "value": "0x0", "proof": [] }],
+            }))
+            .unwrap();
+        let verified = verify_eth_get_proof(state_root, &absent).unwrap();
+        assert_eq!(verified.account, None);
+        assert_eq!(verified.storage, vec![(B256::ZERO, U256::ZERO)]);
+
+        let mut funded = absent.clone();
+        funded.balance = U256::from(1);
+        assert!(verify_eth_get_proof(state_root, &funded).is_err());
+    }
+}
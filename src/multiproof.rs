@@ -0,0 +1,545 @@
+//! Proofs for several leaves at once.
+//!
+//! A [`MultiProof`] lists the leaves it proves and only the sibling hashes that cannot be
+//! computed from them, so hashes shared by the proven leaves are included once. Sibling
+//! hashes are listed level by level from the leaves up, left to right within a level.
+//!
+//! With the `abi` feature, proofs of trees with sorted keccak256 pairs convert to
+//! OpenZeppelin's `MerkleProof.multiProofVerify` encoding with `MultiProof::to_openzeppelin`.
+
+use crate::error::MerkleTreeError;
+use crate::hasher::{HashAlgorithm, OddNodePolicy, TreeHasher};
+use crate::merkle_tree::MerkleTree;
+#[cfg(feature = "abi")]
+use crate::openzeppelin::StandardMultiProof;
+use crate::schema::{self, Versioned};
+use crate::serialization::HashFormat;
+use crate::verify::hashes_equal;
+
+use alloy_primitives::B256;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A proof that several leaves belong to a tree.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct MultiProof {
+    /// Number of leaves in the tree, which fixes its shape.
+    pub leaf_count: usize,
+
+    /// Positions of the proven leaves, in ascending order.
+    pub indices: Vec<usize>,
+
+    /// Hashes of the proven leaves, aligned with `indices`.
+    #[serde(with = "crate::serialization::b256_vec_hex")]
+    #[cfg_attr(feature = "schemars", schemars(with = "Vec<String>"))]
+    pub leaf_hashes: Vec<B256>,
+
+    /// Sibling hashes not computable from the proven leaves, in the order they are used.
+    #[serde(with = "crate::serialization::b256_vec_hex")]
+    #[cfg_attr(feature = "schemars", schemars(with = "Vec<String>"))]
+    pub proof_hashes: Vec<B256>,
+
+    /// Hash function of the tree the proof was taken from.
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+
+    /// Whether the tree sorted each pair before hashing.
+    #[serde(default)]
+    pub sorted_pairs: bool,
+
+    /// What happened to the last node of each odd level.
+    #[serde(default)]
+    pub odd_nodes: OddNodePolicy,
+
+    /// Whether the tree prefixed leaves and nodes before hashing.
+    #[serde(default)]
+    pub domain_separation: bool,
+}
+
+/// Why a multiproof is malformed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum MultiProofDefect {
+    /// The proof proves no leaves.
+    NoIndices,
+    /// The indices are not strictly ascending.
+    UnsortedIndices,
+    /// The proof has fewer hashes than computing the root needs.
+    MissingHashes,
+    /// Some hashes of the proof were not needed to compute the root.
+    UnusedHashes,
+    /// The flags of an OpenZeppelin multiproof use more leaves or hashes than it has.
+    MalformedFlags,
+}
+
+impl fmt::Display for MultiProofDefect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MultiProofDefect::NoIndices => write!(f, "no indices"),
+            MultiProofDefect::UnsortedIndices => write!(f, "indices are not in ascending order"),
+            MultiProofDefect::MissingHashes => write!(f, "missing hashes"),
+            MultiProofDefect::UnusedHashes => write!(f, "unused hashes"),
+            MultiProofDefect::MalformedFlags => write!(f, "flags are malformed"),
+        }
+    }
+}
+
+impl MerkleTree {
+    /// Generates a proof for every item of `data`. Repeated items are proven once.
+    pub fn generate_multiproof<T: AsRef<[u8]>>(
+        &self,
+        data: &[T],
+    ) -> Result<MultiProof, MerkleTreeError> {
+        let levels = self.levels()?;
+        let mut positions = HashMap::new();
+        for (index, hash) in levels[0].iter().enumerate() {
+            positions.entry(*hash).or_insert(index);
+        }
+
+        let indices = data
+            .iter()
+            .map(|item| {
+                let hash = self.hash_leaf(item.as_ref());
+                positions
+                    .get(&hash)
+                    .copied()
+                    .ok_or(MerkleTreeError::LeafNotFound { hash })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.multiproof_from_levels(&levels, indices)
+    }
+
+    /// Generates a proof for the leaves at `indices`. Repeated indices are proven once.
+    pub fn generate_multiproof_by_indices(
+        &self,
+        indices: &[usize],
+    ) -> Result<MultiProof, MerkleTreeError> {
+        self.multiproof_from_levels(&self.levels()?, indices.to_vec())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "generate_multiproof",
+            level = "debug",
+            skip_all,
+            fields(
+                leaf_count = levels[0].len(),
+                depth = levels.len() - 1,
+                targets = indices.len(),
+            )
+        )
+    )]
+    fn multiproof_from_levels(
+        &self,
+        levels: &[Vec<B256>],
+        mut indices: Vec<usize>,
+    ) -> Result<MultiProof, MerkleTreeError> {
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.is_empty() {
+            return Err(MerkleTreeError::EmptyData);
+        }
+        let leaf_count = levels[0].len();
+        if let Some(&index) = indices.iter().find(|&&index| index >= leaf_count) {
+            return Err(MerkleTreeError::IndexOutOfRange { index, leaf_count });
+        }
+
+        let leaf_hashes = indices.iter().map(|&index| levels[0][index]).collect();
+        let mut proof_hashes = Vec::new();
+        let mut known = indices.clone();
+        for level in &levels[..levels.len() - 1] {
+            let mut parents = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let sibling = known[i] ^ 1;
+                if known.get(i + 1) == Some(&sibling) {
+                    i += 1;
+                } else if sibling < level.len() {
+                    proof_hashes.push(level[sibling]);
+                }
+                parents.push(known[i] / 2);
+                i += 1;
+            }
+            known = parents;
+        }
+
+        Ok(MultiProof {
+            leaf_count,
+            indices,
+            leaf_hashes,
+            proof_hashes,
+            hash_algorithm: self.hash_algorithm,
+            sorted_pairs: self.sorted_pairs,
+            odd_nodes: self.odd_nodes,
+            domain_separation: self.domain_separation,
+        })
+    }
+}
+
+impl MultiProof {
+    /// Serializes the proof to a JSON string.
+    pub fn to_json(&self) -> Result<String, MerkleTreeError> {
+        self.to_json_with(HashFormat::default())
+    }
+
+    /// Serializes the proof to a JSON string, writing hashes in `format`.
+    pub fn to_json_with(&self, format: HashFormat) -> Result<String, MerkleTreeError> {
+        serde_json::to_string_pretty(&format.encoded(Versioned::new(self)))
+            .map_err(MerkleTreeError::SerdeError)
+    }
+
+    /// Deserializes a proof from a JSON string.
+    pub fn from_json(json_str: &str) -> Result<Self, MerkleTreeError> {
+        schema::from_slice(json_str.as_bytes())
+    }
+
+    /// Computes the root implied by the proven leaves and the proof hashes.
+    ///
+    /// Fails if the indices are not ascending and in range, or if the proof has too few or
+    /// too many hashes.
+    pub fn root(&self) -> Result<B256, MerkleTreeError> {
+        self.check_indices()?;
+        let hasher = TreeHasher {
+            hash_algorithm: self.hash_algorithm,
+            domain_separation: self.domain_separation,
+            sorted_pairs: self.sorted_pairs,
+            odd_nodes: self.odd_nodes,
+        };
+
+        let mut known: Vec<(usize, B256)> = self
+            .indices
+            .iter()
+            .copied()
+            .zip(self.leaf_hashes.iter().copied())
+            .collect();
+        let mut proof_hashes = self.proof_hashes.iter();
+        let mut level_len = self.leaf_count;
+        while level_len > 1 {
+            let mut parents = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let (position, hash) = known[i];
+                let sibling = position ^ 1;
+                let parent = match known.get(i + 1) {
+                    Some(&(next, next_hash)) if next == sibling => {
+                        i += 1;
+                        hasher.hash_pair(&hash, &next_hash)
+                    }
+                    _ if sibling < level_len => {
+                        let sibling_hash =
+                            proof_hashes
+                                .next()
+                                .ok_or(MerkleTreeError::MalformedMultiProof(
+                                    MultiProofDefect::MissingHashes,
+                                ))?;
+                        if position % 2 == 0 {
+                            hasher.hash_pair(&hash, sibling_hash)
+                        } else {
+                            hasher.hash_pair(sibling_hash, &hash)
+                        }
+                    }
+                    _ if hasher.duplicates_odd() => hasher.hash_pair(&hash, &hash),
+                    _ => hash,
+                };
+                parents.push((position / 2, parent));
+                i += 1;
+            }
+            known = parents;
+            level_len = level_len.div_ceil(2);
+        }
+
+        if proof_hashes.next().is_some() {
+            return Err(MerkleTreeError::MalformedMultiProof(
+                MultiProofDefect::UnusedHashes,
+            ));
+        }
+        Ok(known[0].1)
+    }
+
+    /// Verifies the proof against a given root hash.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "verify_multiproof",
+            level = "debug",
+            skip_all,
+            fields(
+                leaf_count = self.leaf_count,
+                targets = self.indices.len(),
+                proof_hashes = self.proof_hashes.len(),
+                valid = tracing::field::Empty,
+            )
+        )
+    )]
+    pub fn verify(&self, root_hash: &B256) -> Result<bool, MerkleTreeError> {
+        let valid = hashes_equal(&self.root()?, root_hash);
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("valid", valid);
+        Ok(valid)
+    }
+
+    /// Converts the proof to OpenZeppelin's encoding, with one flag per hash telling
+    /// whether its second input is a known node (`true`) or the next proof hash (`false`).
+    ///
+    /// Requires sorted keccak256 pairs. Fails when a proven node is the odd node of a level,
+    /// which the encoding cannot express.
+    #[cfg(feature = "abi")]
+    pub fn to_openzeppelin(&self) -> Result<StandardMultiProof, MerkleTreeError> {
+        self.check_indices()?;
+        if self.hash_algorithm != HashAlgorithm::Keccak256 {
+            return Err(MerkleTreeError::AlgorithmMismatch {
+                expected: HashAlgorithm::Keccak256,
+                got: self.hash_algorithm,
+            });
+        }
+        if !self.sorted_pairs {
+            return Err(MerkleTreeError::FormatError(
+                "OpenZeppelin multiproofs require sorted pairs".to_string(),
+            ));
+        }
+        if self.domain_separation {
+            return Err(MerkleTreeError::FormatError(
+                "OpenZeppelin multiproofs do not prefix leaves and nodes".to_string(),
+            ));
+        }
+
+        let mut proof_flags = Vec::new();
+        let mut known = self.indices.clone();
+        let mut level_len = self.leaf_count;
+        let mut level = 0;
+        while level_len > 1 {
+            let mut parents = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let sibling = known[i] ^ 1;
+                if known.get(i + 1) == Some(&sibling) {
+                    proof_flags.push(true);
+                    i += 1;
+                } else if sibling < level_len {
+                    proof_flags.push(false);
+                } else {
+                    return Err(MerkleTreeError::FormatError(format!(
+                        "node {} of level {} is an odd node, which OpenZeppelin multiproofs \
+                         cannot express",
+                        known[i], level
+                    )));
+                }
+                parents.push(known[i] / 2);
+                i += 1;
+            }
+            known = parents;
+            level_len = level_len.div_ceil(2);
+            level += 1;
+        }
+
+        Ok(StandardMultiProof {
+            leaves: self.leaf_hashes.clone(),
+            proof: self.proof_hashes.clone(),
+            proof_flags,
+        })
+    }
+
+    fn check_indices(&self) -> Result<(), MerkleTreeError> {
+        if self.indices.is_empty() {
+            return Err(MerkleTreeError::MalformedMultiProof(
+                MultiProofDefect::NoIndices,
+            ));
+        }
+        if self.indices.len() != self.leaf_hashes.len() {
+            return Err(MerkleTreeError::LengthMismatch {
+                expected: self.indices.len(),
+                got: self.leaf_hashes.len(),
+            });
+        }
+        if self.indices.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(MerkleTreeError::MalformedMultiProof(
+                MultiProofDefect::UnsortedIndices,
+            ));
+        }
+        if self.indices[self.indices.len() - 1] >= self.leaf_count {
+            return Err(MerkleTreeError::IndexOutOfRange {
+                index: self.indices[self.indices.len() - 1],
+                leaf_count: self.leaf_count,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::MerkleTreeBuilder;
+
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| format!("leaf {i}").into_bytes())
+            .collect()
+    }
+
+    /// Every set of one to three indices of a tree of `leaf_count` leaves.
+    fn subsets(leaf_count: usize) -> Vec<Vec<usize>> {
+        let mut subsets = Vec::new();
+        for a in 0..leaf_count {
+            subsets.push(vec![a]);
+            for b in a + 1..leaf_count {
+                subsets.push(vec![a, b]);
+                for c in b + 1..leaf_count {
+                    subsets.push(vec![a, b, c]);
+                }
+            }
+        }
+        subsets
+    }
+
+    #[test]
+    fn round_trips() {
+        for policy in [OddNodePolicy::Promote, OddNodePolicy::Duplicate] {
+            for sorted in [false, true] {
+                let builder = MerkleTreeBuilder::new()
+                    .sorted_pairs(sorted)
+                    .odd_nodes(policy);
+                for leaf_count in 1..=9 {
+                    let tree = builder.build(&leaves(leaf_count)).unwrap();
+                    let root = *tree.root_hash();
+                    for indices in subsets(leaf_count) {
+                        let proof = tree.generate_multiproof_by_indices(&indices).unwrap();
+                        assert_eq!(proof.root().unwrap(), root, "{leaf_count} {indices:?}");
+                        assert!(proof.verify(&root).unwrap());
+
+                        let decoded = MultiProof::from_json(&proof.to_json().unwrap()).unwrap();
+                        assert_eq!(decoded, proof);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        let tree = MerkleTreeBuilder::new().build(&leaves(7)).unwrap();
+        let root = *tree.root_hash();
+        let proof = tree.generate_multiproof_by_indices(&[1, 4]).unwrap();
+
+        let mut wrong_leaf = proof.clone();
+        wrong_leaf.leaf_hashes[0] = B256::repeat_byte(1);
+        assert!(!wrong_leaf.verify(&root).unwrap());
+
+        let mut short = proof.clone();
+        short.proof_hashes.pop();
+        assert!(short.root().is_err());
+
+        let mut long = proof.clone();
+        long.proof_hashes.push(B256::ZERO);
+        assert!(long.root().is_err());
+
+        let mut unordered = proof;
+        unordered.indices.reverse();
+        unordered.leaf_hashes.reverse();
+        assert!(unordered.root().is_err());
+    }
+
+    #[cfg(feature = "abi")]
+    mod openzeppelin {
+        use super::*;
+        use crate::openzeppelin::StandardMerkleTree;
+        use alloy_primitives::b256;
+        use serde_json::json;
+
+        /// The example from the `@openzeppelin/merkle-tree` README and the root it prints.
+        fn readme_tree() -> StandardMerkleTree {
+            let values = vec![
+                vec![
+                    json!("0x1111111111111111111111111111111111111111"),
+                    json!("5000000000000000000"),
+                ],
+                vec![
+                    json!("0x2222222222222222222222222222222222222222"),
+                    json!("2500000000000000000"),
+                ],
+            ];
+            StandardMerkleTree::of(values, &["address".to_string(), "uint256".to_string()]).unwrap()
+        }
+
+        #[test]
+        fn readme_root() {
+            assert_eq!(
+                *readme_tree().root(),
+                b256!("d4dee0beab2d53f2cc83e567171bd2820e49898130a22622b10ead383e90bd77")
+            );
+        }
+
+        /// Builds a sorted keccak256 tree over the leaves of an OpenZeppelin tree, in the
+        /// order they sit in its array. For a power-of-two leaf count both trees have the
+        /// same shape, so proofs of one are proofs of the other.
+        fn mirror(standard: &StandardMerkleTree, leaf_count: usize) -> MerkleTree {
+            let dump = standard.dump();
+            let leaf_hashes = dump.tree[dump.tree.len() - leaf_count..].to_vec();
+            MerkleTreeBuilder::new()
+                .sorted_pairs(true)
+                .build_from_leaf_hashes(leaf_hashes)
+                .unwrap()
+        }
+
+        #[test]
+        fn readme_multiproof() {
+            let standard = readme_tree();
+            let tree = mirror(&standard, 2);
+            assert_eq!(tree.root_hash(), standard.root());
+
+            let proof = tree.generate_multiproof_by_indices(&[0, 1]).unwrap();
+            let oz = proof.to_openzeppelin().unwrap();
+            assert!(oz.proof.is_empty());
+            assert_eq!(oz.proof_flags, vec![true]);
+            assert!(oz.verify(standard.root()).unwrap());
+        }
+
+        #[test]
+        fn matches_standard_tree() {
+            let values = (0..8u64)
+                .map(|i| {
+                    vec![
+                        json!(format!("0x{:040x}", i + 1)),
+                        json!(((i + 1) * 1_000_000_000).to_string()),
+                    ]
+                })
+                .collect();
+            let standard =
+                StandardMerkleTree::of(values, &["address".to_string(), "uint256".to_string()])
+                    .unwrap();
+            let tree = mirror(&standard, 8);
+            assert_eq!(tree.root_hash(), standard.root());
+
+            for indices in subsets(8) {
+                let oz = tree
+                    .generate_multiproof_by_indices(&indices)
+                    .unwrap()
+                    .to_openzeppelin()
+                    .unwrap();
+                assert_eq!(oz.proof_flags.len(), oz.leaves.len() + oz.proof.len() - 1);
+                assert!(oz.verify(standard.root()).unwrap(), "{indices:?}");
+
+                let mut wrong = oz.clone();
+                wrong.leaves[0] = B256::repeat_byte(1);
+                assert!(!wrong.verify(standard.root()).unwrap_or(false));
+            }
+        }
+
+        #[test]
+        fn rejects_odd_nodes() {
+            let tree = MerkleTreeBuilder::new()
+                .sorted_pairs(true)
+                .build(&leaves(5))
+                .unwrap();
+            let proof = tree.generate_multiproof_by_indices(&[4]).unwrap();
+            assert!(proof.to_openzeppelin().is_err());
+
+            let unsorted = MerkleTreeBuilder::new().build(&leaves(4)).unwrap();
+            let proof = unsorted.generate_multiproof_by_indices(&[0]).unwrap();
+            assert!(proof.to_openzeppelin().is_err());
+        }
+    }
+}
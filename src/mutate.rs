@@ -0,0 +1,588 @@
+//! In-place changes to a built tree.
+//!
+//! Node `j` at level `k` covers leaves `[j * 2^k, (j + 1) * 2^k)`. A node whose range lies
+//! entirely within the unchanged leaves is identical before and after the change, so only
+//! the nodes along the changed edge are rehashed.
+//!
+//! A node whose range fits in its left half covers an odd node: it is either that node,
+//! promoted unchanged, or under [`OddNodePolicy::Duplicate`] its join with a copy of itself.
+
+use crate::builder::check_leaf_length;
+use crate::error::MerkleTreeError;
+use crate::hasher::{OddNodePolicy, TreeHasher};
+use crate::merkle_node::MerkleNode;
+use crate::merkle_tree::{duplicated_child, shape_mismatch, MerkleTree};
+
+use alloy_primitives::B256;
+use log::info;
+use std::collections::HashMap;
+use std::mem;
+
+impl MerkleTree {
+    /// Appends leaves built from `data`, keeping their raw data in the tree.
+    ///
+    /// Subtrees over the existing leaves are reused, so appending `m` leaves to a tree of
+    /// `n` costs `O(m + log n)` hashes instead of a full rebuild. Fails, leaving the tree
+    /// unchanged, if the leaves would exceed the tree's [`limits`](MerkleTree::limits) or
+    /// one is 64 bytes long where [`allow_64_byte_leaves`](MerkleTree::allow_64_byte_leaves)
+    /// forbids it.
+    pub fn append(&mut self, data: &[Vec<u8>]) -> Result<(), MerkleTreeError> {
+        let old_count = self.leaf_count();
+        for (offset, datum) in data.iter().enumerate() {
+            self.check_leaf(old_count + offset, datum)?;
+        }
+        let leaf_hashes: Vec<B256> = data.iter().map(|datum| self.hash_leaf(datum)).collect();
+        self.append_leaf_hashes(leaf_hashes.clone())?;
+        for (leaf_hash, datum) in leaf_hashes.into_iter().zip(data) {
+            self.leaves.insert(leaf_hash, datum.clone());
+        }
+        Ok(())
+    }
+
+    /// Appends already hashed leaves, in order.
+    pub fn append_leaf_hashes(&mut self, leaf_hashes: Vec<B256>) -> Result<(), MerkleTreeError> {
+        if leaf_hashes.is_empty() {
+            return Err(MerkleTreeError::EmptyData);
+        }
+
+        let old_count = self.leaf_count();
+        self.limits
+            .check_leaf_count(old_count.saturating_add(leaf_hashes.len()))?;
+        info!(
+            "Appending {} leaves to Merkle Tree with {} leaves.",
+            leaf_hashes.len(),
+            old_count
+        );
+
+        // Check the shape before taking the tree apart, so errors leave it unchanged
+        check_edge(
+            &self.root,
+            tree_depth(old_count),
+            0,
+            old_count,
+            self.odd_nodes,
+        )?;
+        let mut complete = HashMap::new();
+        take_complete_subtrees(
+            mem::replace(&mut self.root, MerkleNode::empty()),
+            tree_depth(old_count),
+            0,
+            old_count,
+            old_count,
+            self.odd_nodes,
+            &mut complete,
+        );
+
+        self.leaf_hashes.extend(leaf_hashes);
+        self.root = build_node(
+            tree_depth(self.leaf_hashes.len()),
+            0,
+            &self.leaf_hashes,
+            &mut complete,
+            self.hasher(),
+        );
+
+        if let Err(e) = self.check_strict() {
+            self.truncate(old_count)?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Removes every leaf after the first `leaf_count`, undoing an append.
+    ///
+    /// Like appending, this reuses the subtrees over the kept leaves, rehashing only the
+    /// `O(log n)` nodes along the new right edge.
+    pub(crate) fn truncate(&mut self, leaf_count: usize) -> Result<(), MerkleTreeError> {
+        let old_count = self.leaf_count();
+        if leaf_count == 0 {
+            return Err(MerkleTreeError::EmptyData);
+        }
+        if leaf_count >= old_count {
+            return Ok(());
+        }
+
+        check_edge(
+            &self.root,
+            tree_depth(old_count),
+            0,
+            old_count,
+            self.odd_nodes,
+        )?;
+        let mut complete = HashMap::new();
+        take_complete_subtrees(
+            mem::replace(&mut self.root, MerkleNode::empty()),
+            tree_depth(old_count),
+            0,
+            old_count,
+            leaf_count,
+            self.odd_nodes,
+            &mut complete,
+        );
+
+        let removed = self.leaf_hashes.split_off(leaf_count);
+        self.root = build_node(
+            tree_depth(leaf_count),
+            0,
+            &self.leaf_hashes,
+            &mut complete,
+            self.hasher(),
+        );
+        for leaf_hash in removed {
+            if !self.leaf_hashes.contains(&leaf_hash) {
+                self.discard_leaf_data(&leaf_hash);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the leaf at `index` with one built from `data`, keeping its raw data.
+    ///
+    /// Only the `O(log n)` nodes on the path to the leaf are rehashed. The new leaf is
+    /// checked as [`append`](MerkleTree::append) checks its leaves.
+    pub fn update(&mut self, index: usize, data: &[u8]) -> Result<(), MerkleTreeError> {
+        self.check_leaf(index, data)?;
+        let leaf_hash = self.hash_leaf(data);
+        self.update_leaf_hash(index, leaf_hash)?;
+        self.leaves.insert(leaf_hash, data.to_vec());
+        Ok(())
+    }
+
+    /// Replaces the hash of the leaf at `index`.
+    pub fn update_leaf_hash(
+        &mut self,
+        index: usize,
+        leaf_hash: B256,
+    ) -> Result<(), MerkleTreeError> {
+        let leaf_count = self.leaf_count();
+        if index >= leaf_count {
+            return Err(MerkleTreeError::IndexOutOfRange { index, leaf_count });
+        }
+
+        let hasher = self.hasher();
+        set_leaf(
+            &mut self.root,
+            tree_depth(leaf_count),
+            0,
+            leaf_count,
+            index,
+            leaf_hash,
+            hasher,
+        )?;
+        let old_hash = mem::replace(&mut self.leaf_hashes[index], leaf_hash);
+        if let Err(e) = self.check_strict() {
+            set_leaf(
+                &mut self.root,
+                tree_depth(leaf_count),
+                0,
+                leaf_count,
+                index,
+                old_hash,
+                hasher,
+            )?;
+            self.leaf_hashes[index] = old_hash;
+            return Err(e);
+        }
+
+        // Drop the replaced leaf's data unless another leaf shares its hash
+        if !self.leaf_hashes.contains(&old_hash) {
+            self.discard_leaf_data(&old_hash);
+        }
+        Ok(())
+    }
+
+    /// Removes the leaf at `index`, returning its hash.
+    ///
+    /// Every later leaf moves down one place, changing the nodes above it, so the tree is
+    /// rebuilt from the remaining leaf hashes. The last leaf of a tree cannot be removed.
+    pub fn remove(&mut self, index: usize) -> Result<B256, MerkleTreeError> {
+        let leaf_count = self.leaf_count();
+        if index >= leaf_count {
+            return Err(MerkleTreeError::IndexOutOfRange { index, leaf_count });
+        }
+        if leaf_count == 1 {
+            return Err(MerkleTreeError::EmptyData);
+        }
+
+        info!(
+            "Removing leaf {} from Merkle Tree with {} leaves.",
+            index, leaf_count
+        );
+        let leaf_hash = self.leaf_hashes.remove(index);
+        let new_root = build_node(
+            tree_depth(self.leaf_hashes.len()),
+            0,
+            &self.leaf_hashes,
+            &mut HashMap::new(),
+            self.hasher(),
+        );
+        let old_root = mem::replace(&mut self.root, new_root);
+        if let Err(e) = self.check_strict() {
+            self.root = old_root;
+            self.leaf_hashes.insert(index, leaf_hash);
+            return Err(e);
+        }
+
+        if !self.leaf_hashes.contains(&leaf_hash) {
+            self.discard_leaf_data(&leaf_hash);
+        }
+        Ok(leaf_hash)
+    }
+
+    /// Rejects the changed tree if it is strict and ambiguous, for the caller to roll back.
+    fn check_strict(&self) -> Result<(), MerkleTreeError> {
+        if self.strict {
+            self.check_unambiguous()?;
+        }
+        Ok(())
+    }
+
+    /// Rejects leaf data for `index` that exceeds the size limit, or is 64 bytes long in a
+    /// tree that neither separates domains nor allows such leaves.
+    fn check_leaf(&self, index: usize, leaf: &[u8]) -> Result<(), MerkleTreeError> {
+        self.limits.check_leaf_size(index, leaf)?;
+        check_leaf_length(
+            index,
+            leaf,
+            self.domain_separation || self.allow_64_byte_leaves,
+        )
+    }
+}
+
+impl MerkleNode {
+    /// A placeholder node, used while a tree's root is temporarily taken apart.
+    fn empty() -> Self {
+        MerkleNode::leaf(B256::ZERO)
+    }
+}
+
+/// Returns the level of the root in a tree of `leaf_count` leaves.
+pub(crate) fn tree_depth(leaf_count: usize) -> usize {
+    leaf_count.next_power_of_two().trailing_zeros() as usize
+}
+
+/// Checks that the nodes along the incomplete right edge below `node` have the shape
+/// implied by `leaf_count`.
+fn check_edge(
+    node: &MerkleNode,
+    level: usize,
+    index: usize,
+    leaf_count: usize,
+    odd_nodes: OddNodePolicy,
+) -> Result<(), MerkleTreeError> {
+    if (index + 1) << level <= leaf_count {
+        return Ok(());
+    }
+
+    // A node whose range fits in its left half was promoted unchanged or duplicated
+    let mid = (index << level) + (1 << (level - 1));
+    if leaf_count <= mid {
+        let node = match odd_nodes {
+            OddNodePolicy::Promote => node,
+            OddNodePolicy::Duplicate => duplicated_child(node).ok_or_else(shape_mismatch)?,
+        };
+        return check_edge(node, level - 1, 2 * index, leaf_count, odd_nodes);
+    }
+
+    match (&node.left, &node.right) {
+        (Some(_), Some(right)) => {
+            check_edge(right, level - 1, 2 * index + 1, leaf_count, odd_nodes)
+        }
+        _ => Err(shape_mismatch()),
+    }
+}
+
+/// Splits the tree of `leaf_count` leaves below `node`, which sits at `index` on `level`,
+/// into the maximal complete subtrees over its first `keep` leaves, keyed by position.
+/// Copies of duplicated nodes are dropped. The shape must have passed [`check_edge`].
+fn take_complete_subtrees(
+    node: MerkleNode,
+    level: usize,
+    index: usize,
+    leaf_count: usize,
+    keep: usize,
+    odd_nodes: OddNodePolicy,
+    complete: &mut HashMap<(usize, usize), MerkleNode>,
+) {
+    if (index + 1) << level <= keep {
+        complete.insert((level, index), node);
+        return;
+    }
+    if index << level >= keep {
+        return;
+    }
+
+    let mid = (index << level) + (1 << (level - 1));
+    if leaf_count <= mid {
+        // A duplicated node's left child is the odd node itself
+        let node = match odd_nodes {
+            OddNodePolicy::Promote => node,
+            OddNodePolicy::Duplicate => match node.left {
+                Some(left) => *left,
+                None => return,
+            },
+        };
+        return take_complete_subtrees(
+            node,
+            level - 1,
+            2 * index,
+            leaf_count,
+            keep,
+            odd_nodes,
+            complete,
+        );
+    }
+
+    if let (Some(left), Some(right)) = (node.left, node.right) {
+        take_complete_subtrees(
+            *left,
+            level - 1,
+            2 * index,
+            leaf_count,
+            keep,
+            odd_nodes,
+            complete,
+        );
+        take_complete_subtrees(
+            *right,
+            level - 1,
+            2 * index + 1,
+            leaf_count,
+            keep,
+            odd_nodes,
+            complete,
+        );
+    }
+}
+
+/// Sets the hash of leaf `leaf_index` below `node`, which sits at `index` on `level`, and
+/// rehashes the nodes above it.
+fn set_leaf(
+    node: &mut MerkleNode,
+    level: usize,
+    index: usize,
+    leaf_count: usize,
+    leaf_index: usize,
+    leaf_hash: B256,
+    hasher: TreeHasher,
+) -> Result<(), MerkleTreeError> {
+    if level == 0 {
+        node.hash = leaf_hash;
+        return Ok(());
+    }
+
+    // A node whose range fits in its left half was promoted unchanged or duplicated
+    let mid = (index << level) + (1 << (level - 1));
+    if leaf_count <= mid && !hasher.duplicates_odd() {
+        return set_leaf(
+            node,
+            level - 1,
+            2 * index,
+            leaf_count,
+            leaf_index,
+            leaf_hash,
+            hasher,
+        );
+    }
+
+    let (Some(left), Some(right)) = (&mut node.left, &mut node.right) else {
+        return Err(shape_mismatch());
+    };
+    if leaf_index < mid {
+        set_leaf(
+            left,
+            level - 1,
+            2 * index,
+            leaf_count,
+            leaf_index,
+            leaf_hash,
+            hasher,
+        )?;
+        if leaf_count <= mid {
+            // Refresh the copy of the duplicated node
+            right.hash = left.hash;
+        }
+    } else {
+        set_leaf(
+            right,
+            level - 1,
+            2 * index + 1,
+            leaf_count,
+            leaf_index,
+            leaf_hash,
+            hasher,
+        )?;
+    }
+    node.hash = hasher.hash_pair(&left.hash, &right.hash);
+    Ok(())
+}
+
+/// Builds the node at `index` on `level` over `leaf_hashes`, reusing `complete` subtrees.
+fn build_node(
+    level: usize,
+    index: usize,
+    leaf_hashes: &[B256],
+    complete: &mut HashMap<(usize, usize), MerkleNode>,
+    hasher: TreeHasher,
+) -> MerkleNode {
+    if let Some(node) = complete.remove(&(level, index)) {
+        return node;
+    }
+    if level == 0 {
+        return MerkleNode::leaf(leaf_hashes[index]);
+    }
+
+    let mid = (index << level) + (1 << (level - 1));
+    if leaf_hashes.len() <= mid {
+        let node = build_node(level - 1, 2 * index, leaf_hashes, complete, hasher);
+        return if hasher.duplicates_odd() {
+            MerkleNode::with_copy(node, hasher)
+        } else {
+            node
+        };
+    }
+
+    let left = build_node(level - 1, 2 * index, leaf_hashes, complete, hasher);
+    let right = build_node(level - 1, 2 * index + 1, leaf_hashes, complete, hasher);
+    MerkleNode::internal(left, right, hasher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::MerkleTreeBuilder;
+
+    fn leaf(name: &str) -> Vec<u8> {
+        name.as_bytes().to_vec()
+    }
+
+    fn leaves(names: &[&str]) -> Vec<Vec<u8>> {
+        names.iter().map(|name| leaf(name)).collect()
+    }
+
+    fn strict_tree(names: &[&str]) -> MerkleTree {
+        MerkleTreeBuilder::new()
+            .odd_nodes(OddNodePolicy::Duplicate)
+            .strict(true)
+            .build(&leaves(names))
+            .unwrap()
+    }
+
+    /// Checks that a rejected change left `tree` exactly as `before`.
+    fn assert_unchanged(tree: &MerkleTree, before: &MerkleTree) {
+        assert_eq!(tree.root_hash(), before.root_hash());
+        assert_eq!(tree.leaf_count(), before.leaf_count());
+        assert_eq!(tree.leaf_hashes, before.leaf_hashes);
+        assert_eq!(tree.leaves, before.leaves);
+        assert_eq!(tree, before);
+        tree.verify_integrity().unwrap();
+    }
+
+    #[test]
+    fn strict_rejects_ambiguous_append() {
+        let mut tree = strict_tree(&["a", "b", "c"]);
+        let before = tree.clone();
+        let result = tree.append(&[leaf("c")]);
+        assert!(matches!(
+            result,
+            Err(MerkleTreeError::DuplicateOddNode { .. })
+        ));
+        assert_unchanged(&tree, &before);
+
+        tree.append(&[leaf("d")]).unwrap();
+        assert_eq!(tree.leaf_count(), 4);
+    }
+
+    #[test]
+    fn strict_rejects_ambiguous_update() {
+        let mut tree = strict_tree(&["a", "b", "c", "d"]);
+        let before = tree.clone();
+        let result = tree.update(3, b"c");
+        assert!(matches!(
+            result,
+            Err(MerkleTreeError::DuplicateOddNode { .. })
+        ));
+        assert_unchanged(&tree, &before);
+
+        tree.update(3, b"e").unwrap();
+        assert_ne!(tree.root_hash(), before.root_hash());
+    }
+
+    #[test]
+    fn strict_rejects_ambiguous_remove() {
+        let mut tree = strict_tree(&["a", "b", "c", "d", "c"]);
+        let before = tree.clone();
+        let result = tree.remove(3);
+        assert!(matches!(
+            result,
+            Err(MerkleTreeError::DuplicateOddNode { .. })
+        ));
+        assert_unchanged(&tree, &before);
+
+        tree.remove(4).unwrap();
+        assert_eq!(tree.leaf_count(), 4);
+    }
+
+    #[test]
+    fn lenient_trees_accept_ambiguous_changes() {
+        let mut tree = MerkleTreeBuilder::new()
+            .odd_nodes(OddNodePolicy::Duplicate)
+            .build(&leaves(&["a", "b", "c"]))
+            .unwrap();
+        let root = *tree.root_hash();
+        tree.append(&[leaf("c")]).unwrap();
+        assert_eq!(*tree.root_hash(), root);
+        assert!(tree.check_unambiguous().is_err());
+    }
+
+    /// A leaf as long as a pair of child hashes.
+    const PAIR_LEAF: [u8; 64] = [7; 64];
+
+    #[test]
+    fn mutations_reject_64_byte_leaves() {
+        let mut tree = MerkleTreeBuilder::new()
+            .build(&leaves(&["a", "b", "c"]))
+            .unwrap();
+        let before = tree.clone();
+
+        let result = tree.append(&[leaf("d"), PAIR_LEAF.to_vec()]);
+        assert!(matches!(result, Err(MerkleTreeError::AmbiguousLeaf { .. })));
+        assert_unchanged(&tree, &before);
+
+        let result = tree.update(1, &PAIR_LEAF);
+        assert!(matches!(result, Err(MerkleTreeError::AmbiguousLeaf { .. })));
+        assert_unchanged(&tree, &before);
+    }
+
+    #[test]
+    fn mutations_accept_64_byte_leaves_when_allowed() {
+        let builders = [
+            MerkleTreeBuilder::new().domain_separation(true),
+            MerkleTreeBuilder::new().allow_64_byte_leaves(true),
+        ];
+        for builder in builders {
+            let mut tree = builder.build(&leaves(&["a", "b", "c"])).unwrap();
+            tree.append(&[PAIR_LEAF.to_vec()]).unwrap();
+            tree.update(0, &PAIR_LEAF).unwrap();
+            assert_eq!(tree.leaf_hashes[0], tree.leaf_hashes[3]);
+            tree.verify_integrity().unwrap();
+        }
+    }
+
+    #[test]
+    fn loaded_trees_keep_domain_separation_but_not_the_allowance() {
+        let separated = MerkleTreeBuilder::new()
+            .domain_separation(true)
+            .build(&leaves(&["a", "b"]))
+            .unwrap();
+        let mut loaded = MerkleTree::from_json(&separated.to_json().unwrap()).unwrap();
+        loaded.append(&[PAIR_LEAF.to_vec()]).unwrap();
+
+        let allowing = MerkleTreeBuilder::new()
+            .allow_64_byte_leaves(true)
+            .build(&leaves(&["a", "b"]))
+            .unwrap();
+        let mut loaded = MerkleTree::from_json(&allowing.to_json().unwrap()).unwrap();
+        assert!(loaded.append(&[PAIR_LEAF.to_vec()]).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+//! Newline-delimited JSON export, one record per line.
+//!
+//! The first line is a header record:
+//!
+//! ```text
+//! {"type":"header","schema_version":6,"hash_algorithm":"keccak256","sorted_pairs":false,"odd_nodes":"promote","domain_separation":false,"leaf_count":5,"levels":4,"root":"…"}
+//! ```
+//!
+//! followed by one node record per node and level, bottom-up and left to right:
+//!
+//! ```text
+//! {"type":"node","level":0,"index":0,"hash":"…"}
+//! ```
+//!
+//! Promoted nodes appear once per level they occupy; copies of duplicated odd nodes are not
+//! listed. Tools like `jq` or Spark can process the export line by line without loading a
+//! single monolithic document.
+
+use crate::error::MerkleTreeError;
+use crate::hasher::{HashAlgorithm, OddNodePolicy};
+use crate::merkle_tree::MerkleTree;
+use crate::schema::SCHEMA_VERSION;
+use crate::serialization::HexB256;
+
+use serde::Serialize;
+use std::io::Write;
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Record {
+    Header {
+        schema_version: u32,
+        hash_algorithm: HashAlgorithm,
+        sorted_pairs: bool,
+        odd_nodes: OddNodePolicy,
+        domain_separation: bool,
+        leaf_count: usize,
+        levels: usize,
+        root: HexB256,
+    },
+    Node {
+        level: usize,
+        index: usize,
+        hash: HexB256,
+    },
+}
+
+fn write_record<W: Write>(writer: &mut W, record: &Record) -> Result<(), MerkleTreeError> {
+    serde_json::to_writer(&mut *writer, record)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+impl MerkleTree {
+    /// Streams the tree as newline-delimited JSON records into `writer`.
+    pub fn to_ndjson<W: Write>(&self, mut writer: W) -> Result<(), MerkleTreeError> {
+        let levels = self.levels()?;
+
+        write_record(
+            &mut writer,
+            &Record::Header {
+                schema_version: SCHEMA_VERSION,
+                hash_algorithm: self.hash_algorithm,
+                sorted_pairs: self.sorted_pairs,
+                odd_nodes: self.odd_nodes,
+                domain_separation: self.domain_separation,
+                leaf_count: levels[0].len(),
+                levels: levels.len(),
+                root: HexB256(*self.root_hash()),
+            },
+        )?;
+
+        for (level, hashes) in levels.iter().enumerate() {
+            for (index, hash) in hashes.iter().enumerate() {
+                write_record(
+                    &mut writer,
+                    &Record::Node {
+                        level,
+                        index,
+                        hash: HexB256(*hash),
+                    },
+                )?;
+            }
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+//! Trees and proofs saved to, and loaded from, object storage such as S3 or GCS with the
+//! [`object_store`] crate.
+//!
+//! Proof bundles use the layout of the CLI's `proof-all --layout dir`: one `<index>.json`
+//! proof per leaf under a prefix, next to a `root.json` naming the root and leaf count, so
+//! claim frontends can fetch a single proof straight from the bucket.
+
+use crate::dump::DumpFormat;
+use crate::error::MerkleTreeError;
+use crate::merkle_tree::MerkleTree;
+use crate::proof::MerkleProof;
+
+use alloy_primitives::hex::encode_prefixed;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use object_store::path::Path;
+use object_store::{ObjectStore, PutPayload};
+
+/// Number of proofs uploaded at once by [`save_proofs`].
+const UPLOAD_CONCURRENCY: usize = 32;
+
+/// Opens the store holding `url`, such as `s3://bucket/claims/tree.json`, and returns it
+/// with the path of `url` within it.
+///
+/// Credentials and regions are read from the environment, e.g. `AWS_ACCESS_KEY_ID` or
+/// `GOOGLE_SERVICE_ACCOUNT`, as the store's builder does.
+pub fn open_url(url: &str) -> Result<(Box<dyn ObjectStore>, Path), MerkleTreeError> {
+    let parsed: url::Url = url
+        .parse()
+        .map_err(|e| MerkleTreeError::StorageError(format!("invalid URL '{}': {}", url, e)))?;
+    // Configuration keys are the lowercase names of the environment variables
+    let options = std::env::vars().map(|(key, value)| (key.to_ascii_lowercase(), value));
+    object_store::parse_url_opts(&parsed, options).map_err(storage_error)
+}
+
+/// Saves `tree` at `location`, encoded as `format`.
+pub async fn save_tree(
+    store: &dyn ObjectStore,
+    location: &Path,
+    tree: &MerkleTree,
+    format: DumpFormat,
+) -> Result<(), MerkleTreeError> {
+    put(store, location, tree.to_dump(format)?).await
+}
+
+/// Loads the tree at `location`, in any format [`MerkleTree::from_dump`] detects.
+pub async fn load_tree(
+    store: &dyn ObjectStore,
+    location: &Path,
+) -> Result<MerkleTree, MerkleTreeError> {
+    MerkleTree::from_dump(&get(store, location).await?)
+}
+
+/// Saves a proof of every leaf of `tree` under `prefix`, and returns the number of proofs
+/// saved.
+///
+/// `root.json` is written last, so readers that find it can rely on every proof being
+/// there.
+pub async fn save_proofs(
+    store: &dyn ObjectStore,
+    prefix: &Path,
+    tree: &MerkleTree,
+) -> Result<usize, MerkleTreeError> {
+    let proofs = tree.generate_all_proofs()?;
+    stream::iter(proofs.iter().enumerate())
+        .map(|(index, proof)| async move {
+            let location = prefix.child(format!("{}.json", index));
+            put(store, &location, proof.to_json()?.into_bytes()).await
+        })
+        .buffer_unordered(UPLOAD_CONCURRENCY)
+        .try_collect::<()>()
+        .await?;
+
+    let manifest = serde_json::to_vec_pretty(&serde_json::json!({
+        "root": encode_prefixed(tree.root_hash()),
+        "leaf_count": tree.leaf_count(),
+    }))?;
+    put(store, &prefix.child("root.json"), manifest).await?;
+    Ok(proofs.len())
+}
+
+/// Loads the proof of the leaf at `index` from a bundle saved under `prefix`.
+pub async fn load_proof(
+    store: &dyn ObjectStore,
+    prefix: &Path,
+    index: usize,
+) -> Result<MerkleProof, MerkleTreeError> {
+    let location = prefix.child(format!("{}.json", index));
+    MerkleProof::from_dump(&get(store, &location).await?)
+}
+
+async fn put(
+    store: &dyn ObjectStore,
+    location: &Path,
+    bytes: Vec<u8>,
+) -> Result<(), MerkleTreeError> {
+    store
+        .put(location, PutPayload::from(bytes))
+        .await
+        .map_err(storage_error)?;
+    Ok(())
+}
+
+async fn get(store: &dyn ObjectStore, location: &Path) -> Result<Vec<u8>, MerkleTreeError> {
+    let result = store.get(location).await.map_err(storage_error)?;
+    Ok(result.bytes().await.map_err(storage_error)?.to_vec())
+}
+
+fn storage_error(e: object_store::Error) -> MerkleTreeError {
+    MerkleTreeError::StorageError(e.to_string())
+}
@@ -0,0 +1,528 @@
+//! Compatibility with the `standard-v1` dump format of `@openzeppelin/merkle-tree`.
+//!
+//! OpenZeppelin trees differ from [`MerkleTree`](crate::MerkleTree):
+//!
+//! - leaves are `keccak256(keccak256(abi.encode(values)))`, using the dump's `leafEncoding`;
+//! - pairs are sorted before hashing, so proofs carry no directions;
+//! - nodes are stored in a flat array with the root at `0` and the children of `i` at
+//!   `2i + 1` and `2i + 2`, leaves (sorted by hash) filling the end of the array.
+
+use crate::error::MerkleTreeError;
+use crate::hasher::HashAlgorithm;
+use crate::leaf_encoding::coerce_values;
+use crate::multiproof::MultiProofDefect;
+use crate::proof::{MerkleProof, ProofStep};
+use crate::verify::hashes_equal;
+
+use alloy_dyn_abi::{DynSolType, DynSolValue};
+use alloy_primitives::hex::encode;
+use alloy_primitives::{keccak256, B256};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// The only dump format version this module reads and writes.
+pub const STANDARD_V1: &str = "standard-v1";
+
+/// A serialized `@openzeppelin/merkle-tree` `StandardMerkleTree`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StandardMerkleTreeDump {
+    pub format: String,
+    #[serde(with = "prefixed_b256_vec")]
+    pub tree: Vec<B256>,
+    pub values: Vec<StandardValue>,
+    pub leaf_encoding: Vec<String>,
+}
+
+/// Why a `standard-v1` dump was rejected by [`StandardMerkleTree::load`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum DumpDefect {
+    /// The dump's `format` is not [`STANDARD_V1`].
+    UnknownFormat(String),
+    /// A value's `treeIndex` points outside the leaves at the end of the tree array.
+    NotALeaf { tree_index: usize },
+    /// The leaf at `tree_index` is not the hash of its value.
+    LeafMismatch { tree_index: usize },
+    /// The node at `tree_index` is not the hash of its children.
+    NodeMismatch { tree_index: usize },
+}
+
+impl fmt::Display for DumpDefect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DumpDefect::UnknownFormat(format) => write!(f, "unknown format '{}'", format),
+            DumpDefect::NotALeaf { tree_index } => {
+                write!(f, "tree index {} is not a leaf", tree_index)
+            }
+            DumpDefect::LeafMismatch { tree_index } => {
+                write!(
+                    f,
+                    "leaf at tree index {} does not match its value",
+                    tree_index
+                )
+            }
+            DumpDefect::NodeMismatch { tree_index } => {
+                write!(
+                    f,
+                    "node at tree index {} does not match its children",
+                    tree_index
+                )
+            }
+        }
+    }
+}
+
+/// A leaf value and the position of its hash in the tree array.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StandardValue {
+    pub value: Vec<Value>,
+    pub tree_index: usize,
+}
+
+/// A multiproof in the encoding of OpenZeppelin's `MerkleProof.multiProofVerify`.
+///
+/// Each flag produces one hash from the next known node and either the node after it
+/// (`true`) or the next `proof` hash (`false`). Known nodes are the `leaves`, in order,
+/// followed by the hashes produced so far.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StandardMultiProof {
+    #[serde(with = "prefixed_b256_vec")]
+    pub leaves: Vec<B256>,
+    #[serde(with = "prefixed_b256_vec")]
+    pub proof: Vec<B256>,
+    pub proof_flags: Vec<bool>,
+}
+
+impl StandardMultiProof {
+    /// Computes the root, as `MerkleProof.processMultiProof` does.
+    pub fn process(&self) -> Result<B256, MerkleTreeError> {
+        let total_hashes = self.proof_flags.len();
+        if self.leaves.len() + self.proof.len() != total_hashes + 1 {
+            return Err(MerkleTreeError::LengthMismatch {
+                expected: total_hashes + 1,
+                got: self.leaves.len() + self.proof.len(),
+            });
+        }
+
+        let malformed = || MerkleTreeError::MalformedMultiProof(MultiProofDefect::MalformedFlags);
+        let mut hashes: Vec<B256> = Vec::with_capacity(total_hashes);
+        let (mut leaf_pos, mut hash_pos, mut proof_pos) = (0, 0, 0);
+        for &flag in &self.proof_flags {
+            let mut operands = [B256::ZERO; 2];
+            for (operand, from_known) in operands.iter_mut().zip([true, flag]) {
+                *operand = if !from_known {
+                    proof_pos += 1;
+                    *self.proof.get(proof_pos - 1).ok_or_else(malformed)?
+                } else if leaf_pos < self.leaves.len() {
+                    leaf_pos += 1;
+                    self.leaves[leaf_pos - 1]
+                } else {
+                    hash_pos += 1;
+                    *hashes.get(hash_pos - 1).ok_or_else(malformed)?
+                };
+            }
+            hashes.push(hash_sorted_pair(&operands[0], &operands[1]));
+        }
+
+        match (hashes.last(), self.leaves.first()) {
+            (Some(root), _) if proof_pos == self.proof.len() => Ok(*root),
+            (Some(_), _) => Err(MerkleTreeError::MalformedMultiProof(
+                MultiProofDefect::UnusedHashes,
+            )),
+            (None, Some(leaf)) => Ok(*leaf),
+            (None, None) => Ok(self.proof[0]),
+        }
+    }
+
+    /// Verifies the multiproof against `root`.
+    pub fn verify(&self, root: &B256) -> Result<bool, MerkleTreeError> {
+        Ok(hashes_equal(&self.process()?, root))
+    }
+}
+
+/// `0x`-prefixed hex hashes, as written by OpenZeppelin.
+mod prefixed_b256_vec {
+    use crate::serialization::HexB256;
+
+    use super::*;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S>(hashes: &[B256], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(hashes.iter().map(|hash| format!("0x{}", encode(hash))))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<B256>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let hashes = Vec::<HexB256>::deserialize(deserializer)?;
+        Ok(hashes.into_iter().map(|HexB256(hash)| hash).collect())
+    }
+}
+
+/// Hashes a pair in sorted order.
+pub fn hash_sorted_pair(a: &B256, b: &B256) -> B256 {
+    HashAlgorithm::Keccak256.hash_sorted_pair(a, b)
+}
+
+/// Renders a JSON value in the string syntax accepted by [`DynSolType::coerce_str`].
+fn value_to_abi_str(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => format!(
+            "[{}]",
+            items
+                .iter()
+                .map(value_to_abi_str)
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        other => other.to_string(),
+    }
+}
+
+/// An OpenZeppelin-compatible tree that can be loaded, dumped, and used to serve proofs.
+#[derive(Clone, PartialEq, Debug)]
+pub struct StandardMerkleTree {
+    tree: Vec<B256>,
+    values: Vec<StandardValue>,
+    leaf_encoding: Vec<String>,
+    leaf_types: Vec<DynSolType>,
+}
+
+impl StandardMerkleTree {
+    /// Builds a tree over `values`, ABI-encoding each one with `leaf_encoding`.
+    ///
+    /// Leaves are sorted by hash, matching OpenZeppelin's default `sortLeaves: true`.
+    pub fn of(values: Vec<Vec<Value>>, leaf_encoding: &[String]) -> Result<Self, MerkleTreeError> {
+        if values.is_empty() {
+            return Err(MerkleTreeError::EmptyData);
+        }
+
+        let leaf_types = parse_leaf_encoding(leaf_encoding)?;
+        let mut hashed = values
+            .iter()
+            .enumerate()
+            .map(|(value_index, value)| Ok((leaf_hash(&leaf_types, value)?, value_index)))
+            .collect::<Result<Vec<_>, MerkleTreeError>>()?;
+        hashed.sort();
+
+        let tree_len = 2 * hashed.len() - 1;
+        let mut tree = vec![B256::ZERO; tree_len];
+        let mut values: Vec<StandardValue> = values
+            .into_iter()
+            .map(|value| StandardValue {
+                value,
+                tree_index: 0,
+            })
+            .collect();
+
+        for (leaf_index, (hash, value_index)) in hashed.iter().enumerate() {
+            let tree_index = tree_len - 1 - leaf_index;
+            tree[tree_index] = *hash;
+            values[*value_index].tree_index = tree_index;
+        }
+        for i in (0..tree_len - hashed.len()).rev() {
+            tree[i] = hash_sorted_pair(&tree[2 * i + 1], &tree[2 * i + 2]);
+        }
+
+        Ok(StandardMerkleTree {
+            tree,
+            values,
+            leaf_encoding: leaf_encoding.to_vec(),
+            leaf_types,
+        })
+    }
+
+    /// Loads a dump, checking every leaf against its value and every internal node against
+    /// its children.
+    pub fn load(dump: StandardMerkleTreeDump) -> Result<Self, MerkleTreeError> {
+        if dump.format != STANDARD_V1 {
+            return Err(MerkleTreeError::InvalidStandardDump(
+                DumpDefect::UnknownFormat(dump.format),
+            ));
+        }
+        if dump.values.is_empty() {
+            return Err(MerkleTreeError::EmptyData);
+        }
+        let tree_len = dump.values.len().saturating_mul(2) - 1;
+        if dump.tree.len() != tree_len {
+            return Err(MerkleTreeError::LengthMismatch {
+                expected: tree_len,
+                got: dump.tree.len(),
+            });
+        }
+
+        let leaf_types = parse_leaf_encoding(&dump.leaf_encoding)?;
+        let first_leaf = dump.tree.len() - dump.values.len();
+        for value in &dump.values {
+            let tree_index = value.tree_index;
+            if tree_index < first_leaf || tree_index >= dump.tree.len() {
+                return Err(MerkleTreeError::InvalidStandardDump(DumpDefect::NotALeaf {
+                    tree_index,
+                }));
+            }
+            if leaf_hash(&leaf_types, &value.value)? != dump.tree[tree_index] {
+                return Err(MerkleTreeError::InvalidStandardDump(
+                    DumpDefect::LeafMismatch { tree_index },
+                ));
+            }
+        }
+        for tree_index in 0..first_leaf {
+            let children = (
+                &dump.tree[2 * tree_index + 1],
+                &dump.tree[2 * tree_index + 2],
+            );
+            if dump.tree[tree_index] != hash_sorted_pair(children.0, children.1) {
+                return Err(MerkleTreeError::InvalidStandardDump(
+                    DumpDefect::NodeMismatch { tree_index },
+                ));
+            }
+        }
+
+        Ok(StandardMerkleTree {
+            tree: dump.tree,
+            values: dump.values,
+            leaf_encoding: dump.leaf_encoding,
+            leaf_types,
+        })
+    }
+
+    /// Parses and loads a JSON dump.
+    pub fn from_json(json_str: &str) -> Result<Self, MerkleTreeError> {
+        Self::load(serde_json::from_str(json_str)?)
+    }
+
+    /// Returns the dump of this tree.
+    pub fn dump(&self) -> StandardMerkleTreeDump {
+        StandardMerkleTreeDump {
+            format: STANDARD_V1.to_string(),
+            tree: self.tree.clone(),
+            values: self.values.clone(),
+            leaf_encoding: self.leaf_encoding.clone(),
+        }
+    }
+
+    /// Serializes the dump of this tree to a JSON string.
+    pub fn to_json(&self) -> Result<String, MerkleTreeError> {
+        serde_json::to_string_pretty(&self.dump()).map_err(MerkleTreeError::SerdeError)
+    }
+
+    /// Returns the root hash.
+    pub fn root(&self) -> &B256 {
+        &self.tree[0]
+    }
+
+    /// Returns the leaf values in their original order.
+    pub fn values(&self) -> &[StandardValue] {
+        &self.values
+    }
+
+    /// Hashes a value the way this tree hashes its leaves.
+    pub fn leaf_hash(&self, value: &[Value]) -> Result<B256, MerkleTreeError> {
+        leaf_hash(&self.leaf_types, value)
+    }
+
+    /// Returns the sibling hashes for the value at `index`, in OpenZeppelin's proof format.
+    pub fn get_proof(&self, index: usize) -> Result<Vec<B256>, MerkleTreeError> {
+        let value = self
+            .values
+            .get(index)
+            .ok_or(MerkleTreeError::IndexOutOfRange {
+                index,
+                leaf_count: self.values.len(),
+            })?;
+
+        let mut proof = Vec::new();
+        let mut i = value.tree_index;
+        while i > 0 {
+            let sibling = if i % 2 == 1 { i + 1 } else { i - 1 };
+            proof.push(self.tree[sibling]);
+            i = (i - 1) / 2;
+        }
+        Ok(proof)
+    }
+
+    /// Returns the proof for the value at `index` as a [`MerkleProof`].
+    ///
+    /// Sorted-pair hashing is expressed by putting the smaller hash on the left, so the
+    /// proof verifies with [`MerkleProof::verify`].
+    pub fn get_merkle_proof(&self, index: usize) -> Result<MerkleProof, MerkleTreeError> {
+        let siblings = self.get_proof(index)?;
+        let leaf_hash = self.tree[self.values[index].tree_index];
+
+        let mut computed_hash = leaf_hash;
+        let proof_steps = siblings
+            .into_iter()
+            .map(|sibling| {
+                let step = if sibling < computed_hash {
+                    ProofStep::Left(sibling)
+                } else {
+                    ProofStep::Right(sibling)
+                };
+                computed_hash = hash_sorted_pair(&computed_hash, &sibling);
+                step
+            })
+            .collect();
+
+        Ok(MerkleProof {
+            leaf_hash,
+            proof_steps,
+            hash_algorithm: HashAlgorithm::Keccak256,
+            sorted_pairs: false,
+            domain_separation: false,
+        })
+    }
+
+    /// Verifies an OpenZeppelin-style proof for `leaf_hash` against `root`.
+    pub fn verify_proof(root: &B256, leaf_hash: &B256, proof: &[B256]) -> bool {
+        let computed_hash = proof
+            .iter()
+            .fold(*leaf_hash, |hash, sibling| hash_sorted_pair(&hash, sibling));
+        hashes_equal(&computed_hash, root)
+    }
+}
+
+fn parse_leaf_encoding(leaf_encoding: &[String]) -> Result<Vec<DynSolType>, MerkleTreeError> {
+    leaf_encoding
+        .iter()
+        .map(|ty| {
+            DynSolType::parse(ty)
+                .map_err(|e| MerkleTreeError::AbiError(format!("invalid type '{}': {}", ty, e)))
+        })
+        .collect()
+}
+
+/// Computes `keccak256(keccak256(abi.encode(value)))`.
+fn leaf_hash(leaf_types: &[DynSolType], value: &[Value]) -> Result<B256, MerkleTreeError> {
+    let values: Vec<String> = value.iter().map(value_to_abi_str).collect();
+    let values = coerce_values(leaf_types, &values)?;
+
+    let encoded = DynSolValue::Tuple(values).abi_encode_params();
+    Ok(keccak256(keccak256(encoded)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The dump written by the `@openzeppelin/merkle-tree` README example,
+    /// `JSON.stringify(tree.dump())`.
+    const README_DUMP: &str = include_str!("../testdata/openzeppelin_standard_v1_tree.json");
+
+    /// The root, `getProof` of each value and a multiproof of both values of that tree.
+    const README_PROOFS: &str = include_str!("../testdata/openzeppelin_standard_v1_proofs.json");
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Proofs {
+        root: crate::serialization::HexB256,
+        proofs: Vec<Vec<crate::serialization::HexB256>>,
+        multi_proof: StandardMultiProof,
+    }
+
+    fn proofs() -> Proofs {
+        serde_json::from_str(README_PROOFS).unwrap()
+    }
+
+    fn dump() -> StandardMerkleTreeDump {
+        serde_json::from_str(README_DUMP).unwrap()
+    }
+
+    #[test]
+    fn loads_and_dumps_openzeppelin_trees() {
+        let tree = StandardMerkleTree::from_json(README_DUMP).unwrap();
+        assert_eq!(*tree.root(), proofs().root.0);
+        assert_eq!(serde_json::to_string(&tree.dump()).unwrap(), README_DUMP);
+
+        let values = tree.values().iter().map(|v| v.value.clone()).collect();
+        let rebuilt = StandardMerkleTree::of(values, &dump().leaf_encoding).unwrap();
+        assert_eq!(rebuilt, tree);
+    }
+
+    #[test]
+    fn proofs_match_openzeppelin() {
+        let tree = StandardMerkleTree::from_json(README_DUMP).unwrap();
+        let expected = proofs();
+        for (index, proof) in expected.proofs.iter().enumerate() {
+            let proof: Vec<B256> = proof.iter().map(|hash| hash.0).collect();
+            assert_eq!(tree.get_proof(index).unwrap(), proof);
+
+            let leaf_hash = tree.leaf_hash(&tree.values()[index].value).unwrap();
+            assert!(StandardMerkleTree::verify_proof(
+                tree.root(),
+                &leaf_hash,
+                &proof
+            ));
+            assert!(tree
+                .get_merkle_proof(index)
+                .unwrap()
+                .verify(tree.root())
+                .unwrap());
+        }
+        assert!(matches!(
+            tree.get_proof(2),
+            Err(MerkleTreeError::IndexOutOfRange { index: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn multiproofs_match_openzeppelin() {
+        let expected = proofs();
+        assert_eq!(expected.multi_proof.process().unwrap(), expected.root.0);
+
+        let mut wrong = expected.multi_proof;
+        wrong.proof_flags = vec![false];
+        assert!(matches!(
+            wrong.process(),
+            Err(MerkleTreeError::MalformedMultiProof(
+                MultiProofDefect::MalformedFlags
+            ))
+        ));
+    }
+
+    #[test]
+    fn rejects_inconsistent_dumps() {
+        let defect = |dump: StandardMerkleTreeDump| match StandardMerkleTree::load(dump) {
+            Err(MerkleTreeError::InvalidStandardDump(defect)) => defect,
+            other => panic!("unexpected {:?}", other),
+        };
+
+        let mut unknown = dump();
+        unknown.format = "standard-v2".to_string();
+        assert_eq!(
+            defect(unknown),
+            DumpDefect::UnknownFormat("standard-v2".to_string())
+        );
+
+        let mut not_a_leaf = dump();
+        not_a_leaf.values[0].tree_index = 0;
+        assert_eq!(defect(not_a_leaf), DumpDefect::NotALeaf { tree_index: 0 });
+
+        let mut swapped = dump();
+        swapped.values[0].tree_index = 2;
+        assert_eq!(defect(swapped), DumpDefect::LeafMismatch { tree_index: 2 });
+
+        let mut tampered = dump();
+        tampered.tree[0] = B256::repeat_byte(1);
+        assert_eq!(defect(tampered), DumpDefect::NodeMismatch { tree_index: 0 });
+
+        let mut truncated = dump();
+        truncated.tree.pop();
+        assert!(matches!(
+            StandardMerkleTree::load(truncated),
+            Err(MerkleTreeError::LengthMismatch {
+                expected: 3,
+                got: 2,
+                ..
+            })
+        ));
+    }
+}
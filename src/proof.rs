@@ -1,61 +1,277 @@
 use crate::error::MerkleTreeError;
+use crate::hasher::HashAlgorithm;
+use crate::schema::{self, Versioned};
+use crate::serialization::HashFormat;
+pub use crate::verify::ProofStep;
+use crate::verify::{verify_proof, verify_proof_strict};
 
 use alloy_primitives::B256;
-use alloy_signer::k256::sha2::Digest;
-use alloy_signer::k256::sha2::Sha256;
 use serde::{Deserialize, Serialize};
 
-/// Represents a single step in the Merkle Proof.
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
-pub enum ProofStep {
-    #[serde(
-        serialize_with = "crate::serialization::b256_hex::serialize",
-        deserialize_with = "crate::serialization::b256_hex::deserialize"
-    )]
-    Left(B256), // Sibling hash is on the left
-    #[serde(
-        serialize_with = "crate::serialization::b256_hex::serialize",
-        deserialize_with = "crate::serialization::b256_hex::deserialize"
-    )]
-    Right(B256), // Sibling hash is on the right
-}
-
 /// Represents a Merkle Proof for a specific leaf.
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct MerkleProof {
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(schema_with = "crate::json_schema::hash_schema")
+    )]
     #[serde(
         serialize_with = "crate::serialization::b256_hex::serialize",
         deserialize_with = "crate::serialization::b256_hex::deserialize"
     )]
     pub leaf_hash: B256,
     pub proof_steps: Vec<ProofStep>,
+
+    /// Hash function of the tree the proof was taken from. Dumps without it use keccak256.
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+
+    /// Whether the tree sorted each pair before hashing, in which case the sides of the
+    /// steps do not matter.
+    #[serde(default)]
+    pub sorted_pairs: bool,
+
+    /// Whether the tree prefixed leaves and nodes before hashing. `leaf_hash` is then the
+    /// prefixed hash of the leaf data.
+    #[serde(default)]
+    pub domain_separation: bool,
 }
 
 impl MerkleProof {
+    /// Serializes the proof to a JSON string.
+    pub fn to_json(&self) -> Result<String, MerkleTreeError> {
+        self.to_json_with(HashFormat::default())
+    }
+
+    /// Serializes the proof to a JSON string, writing hashes in `format`.
+    pub fn to_json_with(&self, format: HashFormat) -> Result<String, MerkleTreeError> {
+        serde_json::to_string_pretty(&format.encoded(Versioned::new(self)))
+            .map_err(MerkleTreeError::SerdeError)
+    }
+
+    /// Deserializes a proof from a JSON string, migrating older schema versions.
+    pub fn from_json(json_str: &str) -> Result<Self, MerkleTreeError> {
+        schema::from_slice(json_str.as_bytes())
+    }
+
+    /// Serializes the proof to MessagePack.
+    #[cfg(feature = "msgpack")]
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, MerkleTreeError> {
+        rmp_serde::to_vec(self).map_err(MerkleTreeError::MsgpackEncodeError)
+    }
+
+    /// Deserializes a proof from MessagePack.
+    #[cfg(feature = "msgpack")]
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        rmp_serde::from_slice(bytes).map_err(MerkleTreeError::MsgpackDecodeError)
+    }
+
     /// Verifies the Merkle Proof against a given root hash.
+    ///
+    /// Pairs are hashed with the proof's `hash_algorithm` through a fixed 64-byte stack
+    /// buffer, so verification performs no heap allocations.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "verify_proof",
+            level = "debug",
+            skip_all,
+            fields(
+                steps = self.proof_steps.len(),
+                hash_algorithm = %self.hash_algorithm,
+                valid = tracing::field::Empty,
+            )
+        )
+    )]
     pub fn verify(&self, root_hash: &B256) -> Result<bool, MerkleTreeError> {
-        let mut computed_hash = self.leaf_hash;
-
-        for step in &self.proof_steps {
-            let combined = match step {
-                ProofStep::Left(sibling_hash) => {
-                    let mut combined = Vec::new();
-                    combined.extend_from_slice(&sibling_hash.0);
-                    combined.extend_from_slice(&computed_hash.0);
-                    combined
-                }
-                ProofStep::Right(sibling_hash) => {
-                    let mut combined = Vec::new();
-                    combined.extend_from_slice(&computed_hash.0);
-                    combined.extend_from_slice(&sibling_hash.0);
-                    combined
+        let valid = verify_proof(
+            self.hash_algorithm,
+            self.domain_separation,
+            self.sorted_pairs,
+            self.leaf_hash,
+            &self.proof_steps,
+            root_hash,
+        );
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("valid", valid);
+        Ok(valid)
+    }
+
+    /// Verifies the proof like [`MerkleProof::verify`], but also rejects proofs that pair a
+    /// node with a copy of itself on its left, as proofs of forged leaves do (CVE-2012-2459).
+    /// See [`verify_proof_strict`].
+    pub fn verify_strict(&self, root_hash: &B256) -> Result<bool, MerkleTreeError> {
+        Ok(verify_proof_strict(
+            self.hash_algorithm,
+            self.domain_separation,
+            self.sorted_pairs,
+            self.leaf_hash,
+            &self.proof_steps,
+            root_hash,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::MerkleTreeBuilder;
+    use crate::hasher::OddNodePolicy;
+
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| format!("leaf {i}").into_bytes())
+            .collect()
+    }
+
+    /// Every combination of odd node policy and pair sorting.
+    fn options() -> Vec<(OddNodePolicy, bool)> {
+        let mut options = Vec::new();
+        for policy in [OddNodePolicy::Promote, OddNodePolicy::Duplicate] {
+            for sorted in [false, true] {
+                options.push((policy, sorted));
+            }
+        }
+        options
+    }
+
+    fn builder((policy, sorted): (OddNodePolicy, bool)) -> MerkleTreeBuilder {
+        MerkleTreeBuilder::new()
+            .sorted_pairs(sorted)
+            .odd_nodes(policy)
+    }
+
+    /// Flips the last bit of `hash`.
+    fn tamper(hash: &mut B256) {
+        hash.0[31] ^= 1;
+    }
+
+    #[test]
+    fn honest_proofs_verify_strictly() {
+        for (policy, sorted) in options() {
+            // Sorted trees that duplicate odd nodes pair them with a copy, which strict
+            // verification rejects, so only trees without odd nodes are checked there.
+            let sorted_duplicates = sorted && policy == OddNodePolicy::Duplicate;
+            for leaf_count in (1..=9usize).filter(|n| !sorted_duplicates || n.is_power_of_two()) {
+                let tree = builder((policy, sorted))
+                    .build(&leaves(leaf_count))
+                    .unwrap();
+                for index in 0..leaf_count {
+                    let proof = tree.generate_proof_by_index(index).unwrap();
+                    assert!(proof.verify(tree.root_hash()).unwrap());
+                    assert!(proof.verify_strict(tree.root_hash()).unwrap());
                 }
-            };
+            }
+        }
+    }
+
+    #[test]
+    fn strict_rejects_forged_duplicate_leaf() {
+        // Duplicating the odd last leaf makes [a, b, c] and [a, b, c, c] share a root.
+        let builder = MerkleTreeBuilder::new().odd_nodes(OddNodePolicy::Duplicate);
+        let data = leaves(3);
+        let tree = builder.build(&data).unwrap();
+        let mut forged_data = data.clone();
+        forged_data.push(data[2].clone());
+        let forged = builder.build(&forged_data).unwrap();
+        assert_eq!(forged.root_hash(), tree.root_hash());
 
-            let digest = Sha256::digest(&combined);
-            computed_hash = B256::from_slice(&digest);
+        let proof = forged.generate_proof_by_index(3).unwrap();
+        assert_eq!(proof.proof_steps[0], ProofStep::Left(proof.leaf_hash));
+        assert!(proof.verify(tree.root_hash()).unwrap());
+        assert!(!proof.verify_strict(tree.root_hash()).unwrap());
+
+        // The honest proof of the odd leaf pairs it with its copy on the right.
+        let honest = tree.generate_proof_by_index(2).unwrap();
+        assert_eq!(honest.proof_steps[0], ProofStep::Right(honest.leaf_hash));
+        assert!(honest.verify_strict(tree.root_hash()).unwrap());
+    }
+
+    #[test]
+    fn strict_rejects_copies_on_either_side_of_sorted_pairs() {
+        let tree = MerkleTreeBuilder::new()
+            .sorted_pairs(true)
+            .odd_nodes(OddNodePolicy::Duplicate)
+            .build(&leaves(3))
+            .unwrap();
+        let proof = tree.generate_proof_by_index(2).unwrap();
+        assert!(proof.verify(tree.root_hash()).unwrap());
+        assert!(!proof.verify_strict(tree.root_hash()).unwrap());
+
+        let mut swapped = proof.clone();
+        swapped.proof_steps[0] = ProofStep::Left(proof.leaf_hash);
+        assert!(swapped.verify(tree.root_hash()).unwrap());
+        assert!(!swapped.verify_strict(tree.root_hash()).unwrap());
+    }
+
+    #[test]
+    fn rejects_proofs_of_the_wrong_length() {
+        for (policy, sorted) in options() {
+            let tree = builder((policy, sorted)).build(&leaves(8)).unwrap();
+            let root = tree.root_hash();
+            let proof = tree.generate_proof_by_index(5).unwrap();
+
+            let mut short = proof.clone();
+            short.proof_steps.pop();
+            let mut long = proof.clone();
+            long.proof_steps.push(ProofStep::Right(proof.leaf_hash));
+            let mut empty = proof.clone();
+            empty.proof_steps.clear();
+
+            for bad in [short, long, empty] {
+                assert!(!bad.verify(root).unwrap(), "{:?}", bad.proof_steps);
+                assert!(!bad.verify_strict(root).unwrap(), "{:?}", bad.proof_steps);
+            }
         }
+    }
+
+    #[test]
+    fn rejects_tampered_proofs() {
+        for (policy, sorted) in options() {
+            let tree = builder((policy, sorted)).build(&leaves(8)).unwrap();
+            let root = tree.root_hash();
+            let proof = tree.generate_proof_by_index(5).unwrap();
+            let mut bad = Vec::new();
 
-        Ok(&computed_hash == root_hash)
+            let mut leaf = proof.clone();
+            tamper(&mut leaf.leaf_hash);
+            bad.push(leaf);
+            for i in 0..proof.proof_steps.len() {
+                let mut sibling = proof.clone();
+                match &mut sibling.proof_steps[i] {
+                    ProofStep::Left(hash) | ProofStep::Right(hash) => tamper(hash),
+                }
+                bad.push(sibling);
+                // The sides of the steps only matter when pairs are not sorted.
+                if !sorted {
+                    let mut side = proof.clone();
+                    side.proof_steps[i] = match proof.proof_steps[i] {
+                        ProofStep::Left(hash) => ProofStep::Right(hash),
+                        ProofStep::Right(hash) => ProofStep::Left(hash),
+                    };
+                    bad.push(side);
+                }
+            }
+            let mut reordered = proof.clone();
+            reordered.proof_steps.swap(0, 1);
+            bad.push(reordered);
+            let mut other_algorithm = proof.clone();
+            other_algorithm.hash_algorithm = HashAlgorithm::Sha256;
+            bad.push(other_algorithm);
+            let mut separated = proof.clone();
+            separated.domain_separation = true;
+            bad.push(separated);
+
+            for bad in bad {
+                assert!(!bad.verify(root).unwrap(), "{bad:?}");
+                assert!(!bad.verify_strict(root).unwrap(), "{bad:?}");
+            }
+
+            let mut wrong_root = *root;
+            tamper(&mut wrong_root);
+            assert!(!proof.verify(&wrong_root).unwrap());
+            assert!(!proof.verify_strict(&wrong_root).unwrap());
+        }
     }
 }
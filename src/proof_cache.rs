@@ -0,0 +1,176 @@
+//! A least-recently-used cache of generated proofs, for serving the same leaves repeatedly.
+
+use crate::error::MerkleTreeError;
+use crate::proof::MerkleProof;
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Caches up to a fixed number of proofs, keyed by leaf index and tree version.
+///
+/// The version identifies the state of the tree the proofs were generated from, e.g. a
+/// counter bumped on every append. Caching a proof of a newer version drops every proof of
+/// older ones, since they no longer lead to the current root.
+#[derive(Clone, Debug)]
+pub struct ProofCache {
+    capacity: usize,
+    version: u64,
+    /// Proofs by leaf index, with the tick they were last used at.
+    entries: HashMap<usize, (MerkleProof, u64)>,
+    /// Leaf indices by the tick they were last used at, least recent first.
+    recency: BTreeMap<u64, usize>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl ProofCache {
+    /// Creates a cache holding up to `capacity` proofs.
+    pub fn new(capacity: usize) -> Self {
+        ProofCache {
+            capacity,
+            version: 0,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the proof of the leaf at `index` cached for `version`, if any.
+    pub fn get(&mut self, version: u64, index: usize) -> Option<MerkleProof> {
+        if version != self.version {
+            self.misses += 1;
+            return None;
+        }
+        self.tick += 1;
+        match self.entries.get_mut(&index) {
+            Some((proof, last_used)) => {
+                self.recency.remove(last_used);
+                self.recency.insert(self.tick, index);
+                *last_used = self.tick;
+                self.hits += 1;
+                Some(proof.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Caches the proof of the leaf at `index` for `version`, evicting the least recently
+    /// used proof if the cache is full. Proofs of versions older than the newest one cached
+    /// are ignored.
+    pub fn insert(&mut self, version: u64, index: usize, proof: MerkleProof) {
+        if version < self.version || self.capacity == 0 {
+            return;
+        }
+        if version > self.version {
+            self.clear();
+            self.version = version;
+        }
+
+        self.tick += 1;
+        if let Some((_, last_used)) = self.entries.insert(index, (proof, self.tick)) {
+            self.recency.remove(&last_used);
+        }
+        self.recency.insert(self.tick, index);
+        while self.entries.len() > self.capacity {
+            let Some((_, evicted)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&evicted);
+        }
+    }
+
+    /// Returns the cached proof of the leaf at `index`, or generates it with `generate`
+    /// and caches it.
+    pub fn get_or_insert_with<F>(
+        &mut self,
+        version: u64,
+        index: usize,
+        generate: F,
+    ) -> Result<MerkleProof, MerkleTreeError>
+    where
+        F: FnOnce() -> Result<MerkleProof, MerkleTreeError>,
+    {
+        if let Some(proof) = self.get(version, index) {
+            return Ok(proof);
+        }
+        let proof = generate()?;
+        self.insert(version, index, proof.clone());
+        Ok(proof)
+    }
+
+    /// Drops every cached proof.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    /// Returns the number of cached proofs.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of lookups that found a proof and the number that did not.
+    pub fn stats(&self) -> (u64, u64) {
+        (self.hits, self.misses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::MerkleTree;
+
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| format!("leaf {i}").into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn serves_fresh_proofs_after_mutations() {
+        let mutations: [fn(&mut MerkleTree); 4] = [
+            |tree| tree.append(&[b"new".to_vec()]).unwrap(),
+            |tree| tree.update(2, b"new").unwrap(),
+            |tree| tree.remove(0).map(|_| ()).unwrap(),
+            |tree| tree.truncate(3).unwrap(),
+        ];
+        for (step, mutate) in mutations.iter().enumerate() {
+            let mut tree = MerkleTree::new(&leaves(5)).unwrap();
+            let mut cache = ProofCache::new(4);
+            let index = 2;
+
+            let stale = cache
+                .get_or_insert_with(0, index, || tree.generate_proof_by_index(index))
+                .unwrap();
+            assert!(stale.verify(tree.root_hash()).unwrap());
+
+            mutate(&mut tree);
+            assert!(!stale.verify(tree.root_hash()).unwrap(), "mutation {step}");
+            assert!(cache.get(1, index).is_none());
+
+            let fresh = cache
+                .get_or_insert_with(1, index, || tree.generate_proof_by_index(index))
+                .unwrap();
+            assert!(fresh.verify(tree.root_hash()).unwrap(), "mutation {step}");
+            assert_eq!(cache.len(), 1);
+            assert_eq!(cache.get(1, index), Some(fresh));
+
+            // Proofs of the old version are no longer cached
+            cache.insert(0, index, stale);
+            assert!(cache.get(0, index).is_none());
+        }
+    }
+}
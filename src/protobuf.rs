@@ -0,0 +1,295 @@
+//! Protobuf types for trees and proofs, matching `proto/merkle_tree.proto`.
+//!
+//! The message types are written out with prost derives rather than generated at build time,
+//! so building the crate does not require `protoc`. Keep them in sync with the schema.
+
+use crate::error::MerkleTreeError;
+use crate::hasher;
+use crate::merkle_node;
+use crate::merkle_tree;
+use crate::proof;
+
+use alloy_primitives::B256;
+use prost::Message;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Side {
+    Left = 0,
+    Right = 1,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum HashAlgorithm {
+    Keccak256 = 0,
+    Sha256 = 1,
+    Blake3 = 2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum OddNodePolicy {
+    Promote = 0,
+    Duplicate = 1,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ProofStep {
+    #[prost(enumeration = "Side", tag = "1")]
+    pub side: i32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub sibling_hash: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MerkleProof {
+    #[prost(bytes = "vec", tag = "1")]
+    pub leaf_hash: Vec<u8>,
+    #[prost(message, repeated, tag = "2")]
+    pub proof_steps: Vec<ProofStep>,
+    #[prost(enumeration = "HashAlgorithm", tag = "3")]
+    pub hash_algorithm: i32,
+    #[prost(bool, tag = "4")]
+    pub sorted_pairs: bool,
+    #[prost(bool, tag = "5")]
+    pub domain_separation: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MerkleNode {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hash: Vec<u8>,
+    #[prost(message, optional, boxed, tag = "2")]
+    pub left: Option<Box<MerkleNode>>,
+    #[prost(message, optional, boxed, tag = "3")]
+    pub right: Option<Box<MerkleNode>>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MerkleTree {
+    #[prost(message, optional, tag = "1")]
+    pub root: Option<MerkleNode>,
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub leaf_hashes: Vec<Vec<u8>>,
+    #[prost(enumeration = "HashAlgorithm", tag = "3")]
+    pub hash_algorithm: i32,
+    #[prost(bool, tag = "4")]
+    pub sorted_pairs: bool,
+    #[prost(enumeration = "OddNodePolicy", tag = "5")]
+    pub odd_nodes: i32,
+    #[prost(bool, tag = "6")]
+    pub domain_separation: bool,
+}
+
+fn hash_from_bytes(bytes: &[u8]) -> Result<B256, MerkleTreeError> {
+    if bytes.len() != 32 {
+        return Err(MerkleTreeError::ProtobufError(format!(
+            "expected a 32-byte hash, got {} bytes",
+            bytes.len()
+        )));
+    }
+    Ok(B256::from_slice(bytes))
+}
+
+impl From<hasher::HashAlgorithm> for HashAlgorithm {
+    fn from(hash_algorithm: hasher::HashAlgorithm) -> Self {
+        match hash_algorithm {
+            hasher::HashAlgorithm::Keccak256 => HashAlgorithm::Keccak256,
+            hasher::HashAlgorithm::Sha256 => HashAlgorithm::Sha256,
+            hasher::HashAlgorithm::Blake3 => HashAlgorithm::Blake3,
+        }
+    }
+}
+
+fn hash_algorithm_from_i32(value: i32) -> Result<hasher::HashAlgorithm, MerkleTreeError> {
+    match HashAlgorithm::try_from(value) {
+        Ok(HashAlgorithm::Keccak256) => Ok(hasher::HashAlgorithm::Keccak256),
+        Ok(HashAlgorithm::Sha256) => Ok(hasher::HashAlgorithm::Sha256),
+        Ok(HashAlgorithm::Blake3) => Ok(hasher::HashAlgorithm::Blake3),
+        Err(_) => Err(MerkleTreeError::ProtobufError(format!(
+            "invalid hash algorithm {}",
+            value
+        ))),
+    }
+}
+
+impl From<hasher::OddNodePolicy> for OddNodePolicy {
+    fn from(odd_nodes: hasher::OddNodePolicy) -> Self {
+        match odd_nodes {
+            hasher::OddNodePolicy::Promote => OddNodePolicy::Promote,
+            hasher::OddNodePolicy::Duplicate => OddNodePolicy::Duplicate,
+        }
+    }
+}
+
+fn odd_node_policy_from_i32(value: i32) -> Result<hasher::OddNodePolicy, MerkleTreeError> {
+    match OddNodePolicy::try_from(value) {
+        Ok(OddNodePolicy::Promote) => Ok(hasher::OddNodePolicy::Promote),
+        Ok(OddNodePolicy::Duplicate) => Ok(hasher::OddNodePolicy::Duplicate),
+        Err(_) => Err(MerkleTreeError::ProtobufError(format!(
+            "invalid odd node policy {}",
+            value
+        ))),
+    }
+}
+
+impl From<&proof::ProofStep> for ProofStep {
+    fn from(step: &proof::ProofStep) -> Self {
+        let (side, sibling_hash) = match step {
+            proof::ProofStep::Left(sibling_hash) => (Side::Left, sibling_hash),
+            proof::ProofStep::Right(sibling_hash) => (Side::Right, sibling_hash),
+        };
+        ProofStep {
+            side: side as i32,
+            sibling_hash: sibling_hash.to_vec(),
+        }
+    }
+}
+
+impl TryFrom<ProofStep> for proof::ProofStep {
+    type Error = MerkleTreeError;
+
+    fn try_from(step: ProofStep) -> Result<Self, MerkleTreeError> {
+        let sibling_hash = hash_from_bytes(&step.sibling_hash)?;
+        match Side::try_from(step.side) {
+            Ok(Side::Left) => Ok(proof::ProofStep::Left(sibling_hash)),
+            Ok(Side::Right) => Ok(proof::ProofStep::Right(sibling_hash)),
+            Err(_) => Err(MerkleTreeError::ProtobufError(format!(
+                "invalid proof step side {}",
+                step.side
+            ))),
+        }
+    }
+}
+
+impl From<&proof::MerkleProof> for MerkleProof {
+    fn from(proof: &proof::MerkleProof) -> Self {
+        MerkleProof {
+            leaf_hash: proof.leaf_hash.to_vec(),
+            proof_steps: proof.proof_steps.iter().map(ProofStep::from).collect(),
+            hash_algorithm: HashAlgorithm::from(proof.hash_algorithm) as i32,
+            sorted_pairs: proof.sorted_pairs,
+            domain_separation: proof.domain_separation,
+        }
+    }
+}
+
+impl TryFrom<MerkleProof> for proof::MerkleProof {
+    type Error = MerkleTreeError;
+
+    fn try_from(proof: MerkleProof) -> Result<Self, MerkleTreeError> {
+        Ok(proof::MerkleProof {
+            leaf_hash: hash_from_bytes(&proof.leaf_hash)?,
+            proof_steps: proof
+                .proof_steps
+                .into_iter()
+                .map(proof::ProofStep::try_from)
+                .collect::<Result<_, _>>()?,
+            hash_algorithm: hash_algorithm_from_i32(proof.hash_algorithm)?,
+            sorted_pairs: proof.sorted_pairs,
+            domain_separation: proof.domain_separation,
+        })
+    }
+}
+
+impl From<&merkle_node::MerkleNode> for MerkleNode {
+    fn from(node: &merkle_node::MerkleNode) -> Self {
+        MerkleNode {
+            hash: node.hash.to_vec(),
+            left: node
+                .left
+                .as_ref()
+                .map(|left| Box::new(MerkleNode::from(&**left))),
+            right: node
+                .right
+                .as_ref()
+                .map(|right| Box::new(MerkleNode::from(&**right))),
+        }
+    }
+}
+
+impl TryFrom<MerkleNode> for merkle_node::MerkleNode {
+    type Error = MerkleTreeError;
+
+    fn try_from(node: MerkleNode) -> Result<Self, MerkleTreeError> {
+        let convert_child = |child: Option<Box<MerkleNode>>| {
+            child
+                .map(|child| merkle_node::MerkleNode::try_from(*child).map(Box::new))
+                .transpose()
+        };
+        Ok(merkle_node::MerkleNode {
+            hash: hash_from_bytes(&node.hash)?,
+            left: convert_child(node.left)?,
+            right: convert_child(node.right)?,
+        })
+    }
+}
+
+impl From<&merkle_tree::MerkleTree> for MerkleTree {
+    fn from(tree: &merkle_tree::MerkleTree) -> Self {
+        MerkleTree {
+            root: Some(MerkleNode::from(&tree.root)),
+            leaf_hashes: tree.leaf_hashes.iter().map(|hash| hash.to_vec()).collect(),
+            hash_algorithm: HashAlgorithm::from(tree.hash_algorithm) as i32,
+            sorted_pairs: tree.sorted_pairs,
+            odd_nodes: OddNodePolicy::from(tree.odd_nodes) as i32,
+            domain_separation: tree.domain_separation,
+        }
+    }
+}
+
+impl TryFrom<MerkleTree> for merkle_tree::MerkleTree {
+    type Error = MerkleTreeError;
+
+    fn try_from(tree: MerkleTree) -> Result<Self, MerkleTreeError> {
+        let root = tree
+            .root
+            .ok_or_else(|| MerkleTreeError::ProtobufError("missing root node".to_string()))?;
+        Ok(merkle_tree::MerkleTree {
+            root: merkle_node::MerkleNode::try_from(root)?,
+            leaves: HashMap::new(),
+            leaf_hashes: tree
+                .leaf_hashes
+                .iter()
+                .map(|hash| hash_from_bytes(hash))
+                .collect::<Result<_, _>>()?,
+            hash_algorithm: hash_algorithm_from_i32(tree.hash_algorithm)?,
+            sorted_pairs: tree.sorted_pairs,
+            odd_nodes: odd_node_policy_from_i32(tree.odd_nodes)?,
+            domain_separation: tree.domain_separation,
+            limits: merkle_node::DeserializeLimits::default(),
+            allow_64_byte_leaves: false,
+            strict: false,
+        })
+    }
+}
+
+impl proof::MerkleProof {
+    /// Encodes the proof as a protobuf `MerkleProof` message.
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        MerkleProof::from(self).encode_to_vec()
+    }
+
+    /// Decodes a proof from a protobuf `MerkleProof` message.
+    pub fn from_protobuf(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        let message = MerkleProof::decode(bytes)
+            .map_err(|e| MerkleTreeError::ProtobufError(e.to_string()))?;
+        proof::MerkleProof::try_from(message)
+    }
+}
+
+impl merkle_tree::MerkleTree {
+    /// Encodes the tree as a protobuf `MerkleTree` message.
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        MerkleTree::from(self).encode_to_vec()
+    }
+
+    /// Decodes a tree from a protobuf `MerkleTree` message.
+    pub fn from_protobuf(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        let message =
+            MerkleTree::decode(bytes).map_err(|e| MerkleTreeError::ProtobufError(e.to_string()))?;
+        merkle_tree::MerkleTree::try_from(message)
+    }
+}
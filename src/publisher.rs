@@ -0,0 +1,154 @@
+//! Publishing a tree's root to a contract, e.g. at the end of an allowlist deployment
+//! pipeline.
+//!
+//! The root is set by calling a function taking `(bytes32)` or `(bytes32,uint256)`, the
+//! second argument being the number of leaves. In dry-run mode the call is only estimated,
+//! which fails if it would revert, and no transaction is sent.
+
+use crate::error::MerkleTreeError;
+use crate::merkle_tree::MerkleTree;
+
+use alloy_network::{EthereumWallet, TxSigner};
+use alloy_primitives::{keccak256, Address, B256, U256};
+use alloy_provider::{Provider, ProviderBuilder};
+use alloy_rpc_types_eth::TransactionRequest;
+// Transaction signers in alloy 0.3 produce alloy-primitives 0.8's `Signature`, which later
+// 0.8 releases deprecate
+#[allow(deprecated)]
+use alloy_signer::Signature;
+
+/// The function called when none is set, as in OpenZeppelin-style allowlist contracts.
+pub const DEFAULT_FUNCTION: &str = "setMerkleRoot(bytes32)";
+
+/// Sends transactions setting a contract's root.
+#[derive(Clone, Debug)]
+pub struct Publisher<S> {
+    rpc_url: String,
+    contract: Address,
+    function: String,
+    signer: S,
+    dry_run: bool,
+}
+
+/// The outcome of [`Publisher::publish`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Publication {
+    /// The published root.
+    pub root: B256,
+    /// Calldata of the transaction.
+    pub calldata: Vec<u8>,
+    /// Gas the node estimated for the transaction.
+    pub gas_estimate: u128,
+    /// Hash of the mined transaction, or `None` in dry-run mode.
+    pub transaction_hash: Option<B256>,
+}
+
+#[allow(deprecated)]
+impl<S> Publisher<S>
+where
+    S: TxSigner<Signature> + Clone + Send + Sync + 'static,
+{
+    /// Creates a publisher sending transactions signed by `signer` to `contract` through
+    /// the JSON-RPC endpoint at `rpc_url`.
+    pub fn new(rpc_url: impl Into<String>, contract: Address, signer: S) -> Self {
+        Publisher {
+            rpc_url: rpc_url.into(),
+            contract,
+            function: DEFAULT_FUNCTION.to_string(),
+            signer,
+            dry_run: false,
+        }
+    }
+
+    /// Sets the signature of the function that sets the root, such as
+    /// `setRoot(bytes32,uint256)`.
+    pub fn function(mut self, signature: impl Into<String>) -> Self {
+        self.function = signature.into();
+        self
+    }
+
+    /// Only estimates the transaction instead of sending it.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Returns the calldata setting the root of `tree`.
+    pub fn calldata(&self, tree: &MerkleTree) -> Result<Vec<u8>, MerkleTreeError> {
+        let params = self
+            .function
+            .split_once('(')
+            .and_then(|(_, params)| params.strip_suffix(')'))
+            .ok_or_else(|| {
+                MerkleTreeError::FormatError(format!(
+                    "invalid function signature '{}'",
+                    self.function
+                ))
+            })?;
+
+        let mut calldata = keccak256(self.function.as_bytes())[..4].to_vec();
+        calldata.extend_from_slice(tree.root_hash().as_slice());
+        match params {
+            "bytes32" => {}
+            "bytes32,uint256" => {
+                calldata.extend_from_slice(&U256::from(tree.leaf_count()).to_be_bytes::<32>())
+            }
+            _ => {
+                return Err(MerkleTreeError::FormatError(format!(
+                    "function must take (bytes32) or (bytes32,uint256), got '{}'",
+                    self.function
+                )))
+            }
+        }
+        Ok(calldata)
+    }
+
+    /// Sets the contract's root to the root of `tree` and waits for the transaction to be
+    /// mined, failing if it reverts.
+    pub async fn publish(&self, tree: &MerkleTree) -> Result<Publication, MerkleTreeError> {
+        let calldata = self.calldata(tree)?;
+        let url = self.rpc_url.parse().map_err(|e| {
+            MerkleTreeError::RpcError(format!("invalid RPC URL '{}': {}", self.rpc_url, e))
+        })?;
+        let provider = ProviderBuilder::new()
+            .with_recommended_fillers()
+            .wallet(EthereumWallet::from(self.signer.clone()))
+            .on_http(url);
+
+        // alloy's own primitives may be a different version from the crate's
+        let request = TransactionRequest::default()
+            .from(self.signer.address())
+            .to(self.contract.0 .0.into())
+            .input(calldata.clone().into());
+        let gas_estimate = provider
+            .estimate_gas(&request)
+            .await
+            .map_err(|e| MerkleTreeError::RpcError(e.to_string()))?;
+
+        let transaction_hash = if self.dry_run {
+            None
+        } else {
+            let receipt = provider
+                .send_transaction(request)
+                .await
+                .map_err(|e| MerkleTreeError::RpcError(e.to_string()))?
+                .get_receipt()
+                .await
+                .map_err(|e| MerkleTreeError::RpcError(e.to_string()))?;
+            if !receipt.status() {
+                return Err(MerkleTreeError::RpcError(format!(
+                    "transaction {} reverted",
+                    receipt.transaction_hash
+                )));
+            }
+            Some(B256::from_slice(receipt.transaction_hash.as_slice()))
+        };
+
+        Ok(Publication {
+            root: *tree.root_hash(),
+            calldata,
+            gas_estimate,
+            transaction_hash,
+        })
+    }
+}
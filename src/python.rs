@@ -0,0 +1,174 @@
+//! Python bindings, published as the `merkle_tree_py` module with `maturin build`, which
+//! builds the library as a `cdylib` itself.
+//!
+//! Leaves are `bytes`, and hashes are returned as `0x`-prefixed hex strings. Proofs are
+//! exchanged as the JSON written by [`MerkleProof::to_json`], so they verify the same way in
+//! Python, the CLI, and on a server. Errors raise `merkle_tree_py.MerkleTreeError`.
+
+use crate::hasher::{HashAlgorithm, OddNodePolicy};
+use crate::merkle_tree::MerkleTree;
+use crate::proof::MerkleProof;
+use crate::serialization::parse_b256;
+
+use alloy_primitives::hex::encode_prefixed;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use std::str::FromStr;
+
+create_exception!(merkle_tree_py, MerkleTreeError, PyException);
+
+impl From<crate::MerkleTreeError> for PyErr {
+    fn from(error: crate::MerkleTreeError) -> Self {
+        MerkleTreeError::new_err(error.to_string())
+    }
+}
+
+/// A Merkle Tree over a list of leaves.
+#[pyclass(name = "MerkleTree", module = "merkle_tree_py")]
+pub struct PyMerkleTree(MerkleTree);
+
+#[pymethods]
+impl PyMerkleTree {
+    #[new]
+    #[pyo3(signature = (
+        leaves,
+        hash_algorithm = "keccak256",
+        sorted_pairs = false,
+        duplicate_odd = false,
+        sort_leaves = false,
+        dedupe = false,
+        domain_separation = false,
+    ))]
+    fn new(
+        leaves: Vec<Vec<u8>>,
+        hash_algorithm: &str,
+        sorted_pairs: bool,
+        duplicate_odd: bool,
+        sort_leaves: bool,
+        dedupe: bool,
+        domain_separation: bool,
+    ) -> PyResult<Self> {
+        let hash_algorithm =
+            HashAlgorithm::from_str(hash_algorithm).map_err(PyValueError::new_err)?;
+        let odd_nodes = if duplicate_odd {
+            OddNodePolicy::Duplicate
+        } else {
+            OddNodePolicy::Promote
+        };
+        let tree = MerkleTree::builder()
+            .hash_algorithm(hash_algorithm)
+            .sorted_pairs(sorted_pairs)
+            .odd_nodes(odd_nodes)
+            .sort_leaves(sort_leaves)
+            .dedupe(dedupe)
+            .domain_separation(domain_separation)
+            .build(&leaves)?;
+        Ok(PyMerkleTree(tree))
+    }
+
+    /// Loads a tree from its JSON dump.
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        Ok(PyMerkleTree(MerkleTree::from_json(json)?))
+    }
+
+    /// Serializes the tree to JSON.
+    fn to_json(&self) -> PyResult<String> {
+        Ok(self.0.to_json()?)
+    }
+
+    /// The root hash.
+    #[getter]
+    fn root(&self) -> String {
+        encode_prefixed(self.0.root_hash())
+    }
+
+    /// Number of leaves.
+    #[getter]
+    fn leaf_count(&self) -> usize {
+        self.0.leaf_count()
+    }
+
+    /// Generates the proof of a leaf.
+    fn proof(&self, leaf: Vec<u8>) -> PyResult<PyMerkleProof> {
+        Ok(PyMerkleProof(self.0.generate_proof(&leaf)?))
+    }
+
+    /// Generates the proof of the leaf at `index`.
+    fn proof_by_index(&self, index: usize) -> PyResult<PyMerkleProof> {
+        Ok(PyMerkleProof(self.0.generate_proof_by_index(index)?))
+    }
+
+    /// Appends leaves, updating the root.
+    fn append(&mut self, leaves: Vec<Vec<u8>>) -> PyResult<()> {
+        Ok(self.0.append(&leaves)?)
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.leaf_count()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "MerkleTree(root={}, leaf_count={})",
+            encode_prefixed(self.0.root_hash()),
+            self.0.leaf_count()
+        )
+    }
+}
+
+/// A proof that a leaf belongs to a tree.
+#[pyclass(name = "MerkleProof", module = "merkle_tree_py")]
+pub struct PyMerkleProof(MerkleProof);
+
+#[pymethods]
+impl PyMerkleProof {
+    /// Parses a proof from JSON, migrating older schema versions.
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        Ok(PyMerkleProof(MerkleProof::from_json(json)?))
+    }
+
+    /// Serializes the proof to JSON.
+    fn to_json(&self) -> PyResult<String> {
+        Ok(self.0.to_json()?)
+    }
+
+    /// The hash of the proven leaf.
+    #[getter]
+    fn leaf_hash(&self) -> String {
+        encode_prefixed(self.0.leaf_hash)
+    }
+
+    /// Verifies the proof against a root hash.
+    fn verify(&self, root: &str) -> PyResult<bool> {
+        Ok(self.0.verify(&parse_b256(root)?)?)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "MerkleProof(leaf_hash={}, steps={})",
+            encode_prefixed(self.0.leaf_hash),
+            self.0.proof_steps.len()
+        )
+    }
+}
+
+/// Verifies a proof, given as JSON, against a root hash.
+#[pyfunction]
+fn verify_proof(proof_json: &str, root: &str) -> PyResult<bool> {
+    Ok(MerkleProof::from_json(proof_json)?.verify(&parse_b256(root)?)?)
+}
+
+#[pymodule]
+fn merkle_tree_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyMerkleTree>()?;
+    m.add_class::<PyMerkleProof>()?;
+    m.add_function(wrap_pyfunction!(verify_proof, m)?)?;
+    m.add(
+        "MerkleTreeError",
+        m.py().get_type_bound::<MerkleTreeError>(),
+    )?;
+    Ok(())
+}
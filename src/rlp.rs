@@ -0,0 +1,208 @@
+//! RLP encoding of proofs and stored nodes, for embedding in Ethereum-adjacent protocols.
+//!
+//! - [`ProofStep`] is the list `[side, sibling]`, where `side` is `0` for left and `1` for right.
+//! - [`MerkleProof`] is the list `[leaf_hash, [step, ...]]`.
+//! - [`StoredNode`] is the list `[hash, left, right]`; a missing child is the empty string.
+
+use crate::error::MerkleTreeError;
+use crate::hasher::HashAlgorithm;
+use crate::proof::{MerkleProof, ProofStep};
+use crate::store::StoredNode;
+
+use alloy_primitives::B256;
+use alloy_rlp::{length_of_length, BufMut, Decodable, Encodable, Header};
+
+const LEFT: u8 = 0;
+const RIGHT: u8 = 1;
+
+/// Decodes a list header and returns the payload length.
+fn decode_list_header(buf: &mut &[u8]) -> alloy_rlp::Result<usize> {
+    let header = Header::decode(buf)?;
+    if !header.list {
+        return Err(alloy_rlp::Error::UnexpectedString);
+    }
+    if buf.len() < header.payload_length {
+        return Err(alloy_rlp::Error::InputTooShort);
+    }
+    Ok(header.payload_length)
+}
+
+/// Checks that decoding consumed exactly the list payload.
+fn check_consumed(
+    payload_length: usize,
+    started: usize,
+    remaining: usize,
+) -> alloy_rlp::Result<()> {
+    let consumed = started - remaining;
+    if consumed != payload_length {
+        return Err(alloy_rlp::Error::ListLengthMismatch {
+            expected: payload_length,
+            got: consumed,
+        });
+    }
+    Ok(())
+}
+
+fn optional_hash_bytes(hash: &Option<B256>) -> &[u8] {
+    hash.as_ref().map_or(&[][..], |hash| hash.as_slice())
+}
+
+fn decode_optional_hash(buf: &mut &[u8]) -> alloy_rlp::Result<Option<B256>> {
+    let bytes = Header::decode_bytes(buf, false)?;
+    match bytes.len() {
+        0 => Ok(None),
+        32 => Ok(Some(B256::from_slice(bytes))),
+        _ => Err(alloy_rlp::Error::UnexpectedLength),
+    }
+}
+
+impl ProofStep {
+    fn side_and_hash(&self) -> (u8, &B256) {
+        match self {
+            ProofStep::Left(sibling_hash) => (LEFT, sibling_hash),
+            ProofStep::Right(sibling_hash) => (RIGHT, sibling_hash),
+        }
+    }
+
+    fn rlp_payload_length(&self) -> usize {
+        let (side, sibling_hash) = self.side_and_hash();
+        side.length() + sibling_hash.length()
+    }
+}
+
+impl Encodable for ProofStep {
+    fn encode(&self, out: &mut dyn BufMut) {
+        let (side, sibling_hash) = self.side_and_hash();
+        Header {
+            list: true,
+            payload_length: self.rlp_payload_length(),
+        }
+        .encode(out);
+        side.encode(out);
+        sibling_hash.encode(out);
+    }
+
+    fn length(&self) -> usize {
+        let payload_length = self.rlp_payload_length();
+        payload_length + length_of_length(payload_length)
+    }
+}
+
+impl Decodable for ProofStep {
+    fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+        let payload_length = decode_list_header(buf)?;
+        let started = buf.len();
+
+        let side = u8::decode(buf)?;
+        let sibling_hash = B256::decode(buf)?;
+        check_consumed(payload_length, started, buf.len())?;
+
+        match side {
+            LEFT => Ok(ProofStep::Left(sibling_hash)),
+            RIGHT => Ok(ProofStep::Right(sibling_hash)),
+            _ => Err(alloy_rlp::Error::Custom("invalid proof step side")),
+        }
+    }
+}
+
+impl MerkleProof {
+    fn rlp_payload_length(&self) -> usize {
+        self.leaf_hash.length() + self.proof_steps.length()
+    }
+
+    /// Encodes the proof as RLP.
+    pub fn to_rlp(&self) -> Vec<u8> {
+        alloy_rlp::encode(self)
+    }
+
+    /// Decodes a proof from RLP, rejecting trailing bytes.
+    pub fn from_rlp(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        alloy_rlp::decode_exact(bytes).map_err(MerkleTreeError::RlpError)
+    }
+}
+
+impl Encodable for MerkleProof {
+    fn encode(&self, out: &mut dyn BufMut) {
+        Header {
+            list: true,
+            payload_length: self.rlp_payload_length(),
+        }
+        .encode(out);
+        self.leaf_hash.encode(out);
+        self.proof_steps.encode(out);
+    }
+
+    fn length(&self) -> usize {
+        let payload_length = self.rlp_payload_length();
+        payload_length + length_of_length(payload_length)
+    }
+}
+
+impl Decodable for MerkleProof {
+    fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+        let payload_length = decode_list_header(buf)?;
+        let started = buf.len();
+
+        let leaf_hash = B256::decode(buf)?;
+        let proof_steps = Vec::<ProofStep>::decode(buf)?;
+        check_consumed(payload_length, started, buf.len())?;
+
+        Ok(MerkleProof {
+            leaf_hash,
+            proof_steps,
+            hash_algorithm: HashAlgorithm::Keccak256,
+            sorted_pairs: false,
+            domain_separation: false,
+        })
+    }
+}
+
+impl StoredNode {
+    fn rlp_payload_length(&self) -> usize {
+        self.hash.length()
+            + optional_hash_bytes(&self.left).length()
+            + optional_hash_bytes(&self.right).length()
+    }
+
+    /// Encodes the node as RLP.
+    pub fn to_rlp(&self) -> Vec<u8> {
+        alloy_rlp::encode(self)
+    }
+
+    /// Decodes a node from RLP, rejecting trailing bytes.
+    pub fn from_rlp(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        alloy_rlp::decode_exact(bytes).map_err(MerkleTreeError::RlpError)
+    }
+}
+
+impl Encodable for StoredNode {
+    fn encode(&self, out: &mut dyn BufMut) {
+        Header {
+            list: true,
+            payload_length: self.rlp_payload_length(),
+        }
+        .encode(out);
+        self.hash.encode(out);
+        optional_hash_bytes(&self.left).encode(out);
+        optional_hash_bytes(&self.right).encode(out);
+    }
+
+    fn length(&self) -> usize {
+        let payload_length = self.rlp_payload_length();
+        payload_length + length_of_length(payload_length)
+    }
+}
+
+impl Decodable for StoredNode {
+    fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+        let payload_length = decode_list_header(buf)?;
+        let started = buf.len();
+
+        let hash = B256::decode(buf)?;
+        let left = decode_optional_hash(buf)?;
+        let right = decode_optional_hash(buf)?;
+        check_consumed(payload_length, started, buf.len())?;
+
+        Ok(StoredNode { hash, left, right })
+    }
+}
@@ -0,0 +1,357 @@
+//! A tree maintained over a stream of leaves, with periodic root checkpoints.
+//!
+//! Leaves are buffered as they arrive and appended in arrival order whenever a checkpoint
+//! is due, after a number of leaves or a period of time. Appends reuse the existing
+//! subtrees, so each checkpoint costs `O(m + log n)` hashes for `m` new leaves.
+
+use crate::builder::MerkleTreeBuilder;
+use crate::error::MerkleTreeError;
+use crate::merkle_tree::MerkleTree;
+
+use alloy_primitives::B256;
+use serde::Serialize;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// When a [`RollingTree`] emits checkpoints. With neither limit set, it checkpoints only
+/// when flushed or when its stream ends.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct CheckpointPolicy {
+    /// Checkpoint once this many leaves have arrived since the last checkpoint.
+    pub every_leaves: Option<usize>,
+    /// Checkpoint once this much time has passed since the last checkpoint, if any leaves
+    /// arrived meanwhile.
+    pub interval: Option<Duration>,
+}
+
+/// The root of a [`RollingTree`] after a batch of leaves was appended.
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct Checkpoint {
+    /// Number of checkpoints so far, starting at 1.
+    pub sequence: u64,
+    #[serde(with = "crate::serialization::b256_hex")]
+    pub root: B256,
+    pub leaf_count: usize,
+}
+
+/// A tree that grows as leaves arrive.
+pub struct RollingTree {
+    builder: MerkleTreeBuilder,
+    policy: CheckpointPolicy,
+    tree: Option<MerkleTree>,
+    pending: Vec<Vec<u8>>,
+    last_checkpoint: Instant,
+    sequence: u64,
+}
+
+impl RollingTree {
+    /// Creates an empty tree built with the options of `builder`.
+    ///
+    /// Leaves are appended in arrival order, so builders that sort or dedupe leaves are
+    /// rejected.
+    pub fn new(
+        builder: MerkleTreeBuilder,
+        policy: CheckpointPolicy,
+    ) -> Result<Self, MerkleTreeError> {
+        if builder.sorts_leaves() || builder.dedupes() {
+            return Err(MerkleTreeError::FormatError(
+                "a rolling tree cannot sort or dedupe leaves".to_string(),
+            ));
+        }
+        Ok(RollingTree {
+            builder,
+            policy,
+            tree: None,
+            pending: Vec::new(),
+            last_checkpoint: Instant::now(),
+            sequence: 0,
+        })
+    }
+
+    /// Resumes from a tree built earlier, e.g. loaded from the last checkpoint's dump.
+    pub fn resume(
+        tree: MerkleTree,
+        builder: MerkleTreeBuilder,
+        policy: CheckpointPolicy,
+    ) -> Result<Self, MerkleTreeError> {
+        let mut rolling = Self::new(builder, policy)?;
+        rolling.tree = Some(tree);
+        Ok(rolling)
+    }
+
+    /// Returns the tree as of the last checkpoint, or `None` before the first.
+    pub fn tree(&self) -> Option<&MerkleTree> {
+        self.tree.as_ref()
+    }
+
+    /// Returns the number of leaves waiting for the next checkpoint.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Adds a leaf, returning a checkpoint if one is now due.
+    pub fn push(&mut self, leaf: Vec<u8>) -> Result<Option<Checkpoint>, MerkleTreeError> {
+        self.pending.push(leaf);
+        let full = self
+            .policy
+            .every_leaves
+            .is_some_and(|every| self.pending.len() >= every);
+        if full || self.interval_elapsed() {
+            return self.flush();
+        }
+        Ok(None)
+    }
+
+    /// Appends the pending leaves and returns the new checkpoint, or `None` if there were
+    /// none.
+    pub fn flush(&mut self) -> Result<Option<Checkpoint>, MerkleTreeError> {
+        self.last_checkpoint = Instant::now();
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+
+        let leaves = std::mem::take(&mut self.pending);
+        match &mut self.tree {
+            Some(tree) if self.builder.retains_leaf_data() => tree.append(&leaves)?,
+            Some(tree) => {
+                let hashes = leaves.iter().map(|leaf| tree.hash_leaf(leaf)).collect();
+                tree.append_leaf_hashes(hashes)?
+            }
+            None => self.tree = Some(self.builder.build(&leaves)?),
+        }
+
+        self.sequence += 1;
+        let tree = self.tree.as_ref().expect("tree was just built");
+        Ok(Some(Checkpoint {
+            sequence: self.sequence,
+            root: *tree.root_hash(),
+            leaf_count: tree.leaf_count(),
+        }))
+    }
+
+    /// Consumes leaves from `leaves` until every sender is dropped, calling `on_checkpoint`
+    /// with each checkpoint and the tree it is for. The pending leaves are flushed at the
+    /// end.
+    pub fn run<F>(
+        &mut self,
+        leaves: &Receiver<Vec<u8>>,
+        mut on_checkpoint: F,
+    ) -> Result<(), MerkleTreeError>
+    where
+        F: FnMut(&Checkpoint, &MerkleTree) -> Result<(), MerkleTreeError>,
+    {
+        loop {
+            let received = match self.policy.interval {
+                Some(interval) => {
+                    let left = interval.saturating_sub(self.last_checkpoint.elapsed());
+                    leaves.recv_timeout(left)
+                }
+                None => leaves.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let (checkpoint, done) = match received {
+                Ok(leaf) => (self.push(leaf)?, false),
+                Err(RecvTimeoutError::Timeout) => (self.flush()?, false),
+                Err(RecvTimeoutError::Disconnected) => (self.flush()?, true),
+            };
+            if let (Some(checkpoint), Some(tree)) = (checkpoint, &self.tree) {
+                on_checkpoint(&checkpoint, tree)?;
+            }
+            if done {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Signs the root of the latest checkpoint, or returns `None` before the first.
+    #[cfg(feature = "signer")]
+    pub fn sign_checkpoint<S>(
+        &self,
+        signer: &S,
+    ) -> Result<Option<crate::SignedRoot>, MerkleTreeError>
+    where
+        S: alloy_signer::Signer + alloy_signer::SignerSync,
+    {
+        self.tree
+            .as_ref()
+            .map(|tree| crate::SignedRoot::sign(tree, signer))
+            .transpose()
+    }
+
+    fn interval_elapsed(&self) -> bool {
+        self.policy
+            .interval
+            .is_some_and(|interval| self.last_checkpoint.elapsed() >= interval)
+    }
+}
+
+/// Consumes `topic` from Kafka as `group_id`, sending each message's payload as a leaf.
+///
+/// Offsets are committed automatically, so leaves received but not yet checkpointed when
+/// the process stops are lost; resume from the last checkpoint's tree and replay the topic
+/// from there if every message must be included.
+#[cfg(feature = "kafka")]
+pub fn kafka_leaves(
+    brokers: &str,
+    group_id: &str,
+    topic: &str,
+) -> Result<Receiver<Vec<u8>>, MerkleTreeError> {
+    use rdkafka::config::ClientConfig;
+    use rdkafka::consumer::{BaseConsumer, Consumer};
+    use rdkafka::Message;
+
+    let kafka_error = |e: rdkafka::error::KafkaError| MerkleTreeError::StorageError(e.to_string());
+    let consumer: BaseConsumer = ClientConfig::new()
+        .set("bootstrap.servers", brokers)
+        .set("group.id", group_id)
+        .set("enable.auto.commit", "true")
+        .create()
+        .map_err(kafka_error)?;
+    consumer.subscribe(&[topic]).map_err(kafka_error)?;
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for message in consumer.iter() {
+            match message {
+                Ok(message) => {
+                    let payload = message.payload().unwrap_or_default().to_vec();
+                    if sender.send(payload).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => log::warn!("Kafka error: {}", e),
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::{HashAlgorithm, OddNodePolicy};
+
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| format!("leaf {i}").into_bytes())
+            .collect()
+    }
+
+    fn every(leaves: usize) -> CheckpointPolicy {
+        CheckpointPolicy {
+            every_leaves: Some(leaves),
+            interval: None,
+        }
+    }
+
+    /// Checks that each checkpoint has the root of a tree built from the same prefix.
+    fn check_checkpoints(
+        builder: &MerkleTreeBuilder,
+        leaves: &[Vec<u8>],
+        checkpoints: &[Checkpoint],
+    ) {
+        for (i, checkpoint) in checkpoints.iter().enumerate() {
+            assert_eq!(checkpoint.sequence, i as u64 + 1);
+            let tree = builder
+                .clone()
+                .build(&leaves[..checkpoint.leaf_count])
+                .unwrap();
+            assert_eq!(
+                checkpoint.root,
+                *tree.root_hash(),
+                "{} leaves",
+                checkpoint.leaf_count
+            );
+        }
+    }
+
+    #[test]
+    fn checkpoints_match_trees_of_the_same_prefix() {
+        let leaves = leaves(10);
+        for builder in [
+            MerkleTreeBuilder::new(),
+            MerkleTreeBuilder::new().retain_leaf_data(false),
+            MerkleTreeBuilder::new().sorted_pairs(true),
+            MerkleTreeBuilder::new().odd_nodes(OddNodePolicy::Duplicate),
+            MerkleTreeBuilder::new()
+                .domain_separation(true)
+                .hash_algorithm(HashAlgorithm::Sha256),
+        ] {
+            let mut rolling = RollingTree::new(builder.clone(), every(3)).unwrap();
+            let mut checkpoints = Vec::new();
+            for leaf in &leaves {
+                checkpoints.extend(rolling.push(leaf.clone()).unwrap());
+            }
+            assert_eq!(rolling.pending(), 1);
+            checkpoints.extend(rolling.flush().unwrap());
+            assert!(rolling.flush().unwrap().is_none());
+
+            let counts: Vec<usize> = checkpoints.iter().map(|c| c.leaf_count).collect();
+            assert_eq!(counts, [3, 6, 9, 10]);
+            check_checkpoints(&builder, &leaves, &checkpoints);
+        }
+    }
+
+    #[test]
+    fn runs_until_the_stream_ends() {
+        let leaves = leaves(10);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        for leaf in &leaves {
+            sender.send(leaf.clone()).unwrap();
+        }
+        drop(sender);
+
+        let builder = MerkleTreeBuilder::new();
+        let mut rolling = RollingTree::new(builder.clone(), every(4)).unwrap();
+        let mut checkpoints = Vec::new();
+        rolling
+            .run(&receiver, |checkpoint, tree| {
+                assert_eq!(checkpoint.root, *tree.root_hash());
+                checkpoints.push(checkpoint.clone());
+                Ok(())
+            })
+            .unwrap();
+        let counts: Vec<usize> = checkpoints.iter().map(|c| c.leaf_count).collect();
+        assert_eq!(counts, [4, 8, 10]);
+        check_checkpoints(&builder, &leaves, &checkpoints);
+    }
+
+    #[test]
+    fn resumes_from_a_tree() {
+        let leaves = leaves(7);
+        let builder = MerkleTreeBuilder::new();
+        let tree = builder.clone().build(&leaves[..5]).unwrap();
+        let mut rolling = RollingTree::resume(tree, builder.clone(), every(2)).unwrap();
+        rolling.push(leaves[5].clone()).unwrap();
+        let checkpoint = rolling.push(leaves[6].clone()).unwrap().unwrap();
+        assert_eq!(checkpoint.leaf_count, 7);
+        assert_eq!(
+            checkpoint.root,
+            *builder.build(&leaves).unwrap().root_hash()
+        );
+    }
+
+    #[test]
+    fn rejects_reordering_builders() {
+        let policy = CheckpointPolicy::default();
+        assert!(RollingTree::new(MerkleTreeBuilder::new().sort_leaves(true), policy).is_err());
+        assert!(RollingTree::new(MerkleTreeBuilder::new().dedupe(true), policy).is_err());
+    }
+
+    // Local keys come from alloy-signer-local, which only the CLI depends on
+    #[cfg(feature = "cli")]
+    #[test]
+    fn signs_checkpoints() {
+        let signer = alloy_signer_local::PrivateKeySigner::from_bytes(&[1; 32].into()).unwrap();
+        let mut rolling = RollingTree::new(MerkleTreeBuilder::new(), every(2)).unwrap();
+        assert!(rolling.sign_checkpoint(&signer).unwrap().is_none());
+
+        for leaf in leaves(3) {
+            rolling.push(leaf).unwrap();
+        }
+        let signed = rolling.sign_checkpoint(&signer).unwrap().unwrap();
+        let tree = rolling.tree().unwrap();
+        assert_eq!(signed.leaf_count, 2);
+        assert!(signed.covers(tree));
+        assert!(signed.verify().unwrap());
+    }
+}
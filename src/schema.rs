@@ -0,0 +1,94 @@
+//! Schema versioning for JSON dumps of trees and proofs.
+//!
+//! Every dump carries a top-level `schema_version` field. Dumps written before versioning
+//! was introduced have no such field and are treated as version 1. Loaders run [`migrate`]
+//! before deserializing, so older artifacts keep loading as the structures evolve.
+
+use crate::error::MerkleTreeError;
+
+use serde::de::{DeserializeOwned, Error as _, Unexpected};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Schema version written by this crate.
+pub const SCHEMA_VERSION: u32 = 6;
+
+/// Version assumed for dumps without a `schema_version` field.
+pub const LEGACY_SCHEMA_VERSION: u32 = 1;
+
+/// Wraps a value so it serializes with a leading `schema_version` field.
+#[derive(Serialize)]
+pub(crate) struct Versioned<'a, T: Serialize> {
+    schema_version: u32,
+    #[serde(flatten)]
+    inner: &'a T,
+}
+
+impl<'a, T: Serialize> Versioned<'a, T> {
+    pub(crate) fn new(inner: &'a T) -> Self {
+        Versioned {
+            schema_version: SCHEMA_VERSION,
+            inner,
+        }
+    }
+}
+
+/// Returns the schema version of a dump.
+pub fn detect_version(value: &Value) -> Result<u32, MerkleTreeError> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| serde_json::Error::invalid_type(unexpected(value), &"a JSON object"))?;
+
+    match object.get("schema_version") {
+        None => Ok(LEGACY_SCHEMA_VERSION),
+        Some(version) => Ok(u32::deserialize(version)?),
+    }
+}
+
+/// Describes a JSON value in type errors.
+fn unexpected(value: &Value) -> Unexpected<'_> {
+    match value {
+        Value::Null => Unexpected::Unit,
+        Value::Bool(b) => Unexpected::Bool(*b),
+        Value::Number(_) => Unexpected::Other("number"),
+        Value::String(s) => Unexpected::Str(s),
+        Value::Array(_) => Unexpected::Seq,
+        Value::Object(_) => Unexpected::Map,
+    }
+}
+
+/// Upgrades a dump to [`SCHEMA_VERSION`] in place, returning the version it had.
+pub fn migrate(value: &mut Value) -> Result<u32, MerkleTreeError> {
+    let version = detect_version(value)?;
+    if version > SCHEMA_VERSION {
+        return Err(MerkleTreeError::UnsupportedSchemaVersion {
+            version,
+            max: SCHEMA_VERSION,
+        });
+    }
+
+    let object = value.as_object_mut().expect("checked by detect_version");
+
+    // Version 1 -> 2: the `schema_version` field was added; the layout is otherwise unchanged.
+    // Version 2 -> 3: trees gained `leaf_hashes` and optional `leaf_data`. Both may be absent,
+    // and tree loaders rebuild `leaf_hashes` from the nodes.
+    // Version 3 -> 4: trees and proofs gained `hash_algorithm`; dumps without it use keccak256.
+    // Version 4 -> 5: trees gained `sorted_pairs` and `odd_nodes`, and proofs `sorted_pairs`;
+    // dumps without them use unsorted pairs and promote odd nodes.
+    // Version 5 -> 6: trees, proofs, multiproofs and leaf lists gained `domain_separation`;
+    // dumps without it hash leaves and nodes unprefixed.
+    object.insert("schema_version".to_string(), Value::from(SCHEMA_VERSION));
+
+    Ok(version)
+}
+
+/// Migrates a parsed dump and deserializes it.
+pub(crate) fn from_value<T: DeserializeOwned>(mut value: Value) -> Result<T, MerkleTreeError> {
+    migrate(&mut value)?;
+    serde_json::from_value(value).map_err(MerkleTreeError::SerdeError)
+}
+
+/// Parses, migrates, and deserializes a JSON dump.
+pub(crate) fn from_slice<T: DeserializeOwned>(json: &[u8]) -> Result<T, MerkleTreeError> {
+    from_value(serde_json::from_slice(json)?)
+}
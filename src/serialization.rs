@@ -1,8 +1,502 @@
-use alloy_primitives::hex::{decode, encode};
+use crate::error::MerkleTreeError;
+
+use alloy_primitives::hex::{decode, encode, encode_prefixed};
 use alloy_primitives::TxHash;
 use alloy_primitives::B256;
-use serde::{self, Deserialize, Deserializer, Serializer};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::de::Unexpected;
+use serde::ser::{
+    self, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant,
+};
+use serde::{self, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// The text encoding used for hashes and bytes in human-readable formats.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum HashEncoding {
+    /// Hex, optionally `0x`-prefixed (see [`HashFormat::hex_prefix`]).
+    #[default]
+    Hex,
+    /// Standard padded base64, as used by RFC 6962 JSON APIs.
+    Base64,
+}
+
+impl fmt::Display for HashEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashEncoding::Hex => write!(f, "hex"),
+            HashEncoding::Base64 => write!(f, "base64"),
+        }
+    }
+}
+
+impl FromStr for HashEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hex" => Ok(HashEncoding::Hex),
+            "base64" => Ok(HashEncoding::Base64),
+            other => Err(format!(
+                "unknown hash encoding '{}', expected hex or base64",
+                other
+            )),
+        }
+    }
+}
+
+/// How hashes and bytes are written in human-readable formats.
+///
+/// The default, unprefixed hex, matches earlier releases. Other formats apply to what is
+/// serialized through [`HashFormat::encoded`], or a `_with` method such as
+/// [`MerkleTree::to_json_with`](crate::MerkleTree::to_json_with). Input
+/// accepts hashes in either encoding, which their length tells apart; other bytes are
+/// written in base64 behind a `base64:` prefix, so that reading them need not guess.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct HashFormat {
+    /// The encoding of hashes and bytes.
+    pub encoding: HashEncoding,
+
+    /// Whether hex is written with a `0x` prefix. Ignored for base64.
+    pub hex_prefix: bool,
+}
+
+impl HashFormat {
+    /// Wraps `value` so that serializing it uses this format.
+    pub fn encoded<T: Serialize>(self, value: T) -> Encoded<T> {
+        Encoded {
+            value,
+            format: self,
+        }
+    }
+
+    /// Encodes bytes in this format.
+    pub fn encode<T: AsRef<[u8]>>(self, bytes: T) -> String {
+        match self.encoding {
+            HashEncoding::Hex if self.hex_prefix => encode_prefixed(bytes),
+            HashEncoding::Hex => encode(bytes),
+            HashEncoding::Base64 => BASE64.encode(bytes),
+        }
+    }
+
+    /// Encodes arbitrary bytes in this format, prefixing base64 with `base64:`.
+    ///
+    /// Unlike 32-byte hashes, bytes of any length may be valid in both encodings, such as
+    /// `"abcd"`, so base64 is marked for [`decode_bytes`] to read back.
+    pub fn encode_data<T: AsRef<[u8]>>(self, bytes: T) -> String {
+        match self.encoding {
+            HashEncoding::Hex => self.encode(bytes),
+            HashEncoding::Base64 => format!("{}{}", BASE64_PREFIX, BASE64.encode(bytes)),
+        }
+    }
+}
+
+/// Marks base64 in bytes written by [`HashFormat::encode_data`].
+const BASE64_PREFIX: &str = "base64:";
+
+/// A value serialized with a given [`HashFormat`], returned by [`HashFormat::encoded`].
+#[derive(Clone, Copy, Debug)]
+pub struct Encoded<T> {
+    value: T,
+    format: HashFormat,
+}
+
+impl<T: Serialize> Serialize for Encoded<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // The default format is what the value writes by itself
+        if self.format == HashFormat::default() {
+            return self.value.serialize(serializer);
+        }
+        self.value.serialize(FormatSerializer {
+            inner: serializer,
+            format: self.format,
+        })
+    }
+}
+
+/// Names the newtype structs that [`b256_hex`] and [`bytes_hex`] put their default hex
+/// in. [`FormatSerializer`] re-encodes them; other serializers see through the newtype.
+const HASH_TOKEN: &str = "$merkle_tree::private::Hash";
+const BYTES_TOKEN: &str = "$merkle_tree::private::Bytes";
+
+/// Passes everything on to `inner`, re-encoding hashes and bytes in `format`. Nested
+/// values are wrapped in [`Encoded`] to do the same.
+struct FormatSerializer<S> {
+    inner: S,
+    format: HashFormat,
+}
+
+impl<S: Serializer> FormatSerializer<S> {
+    /// Re-encodes the default hex written by [`b256_hex`] or [`bytes_hex`].
+    fn reencode<T: ?Sized + Serialize>(self, name: &str, value: &T) -> Result<S::Ok, S::Error> {
+        let bytes = match serde_json::to_value(value) {
+            Ok(Value::String(hex)) => decode(hex).map_err(ser::Error::custom)?,
+            _ => return Err(ser::Error::custom("expected hex")),
+        };
+        let text = match name {
+            HASH_TOKEN => self.format.encode(bytes),
+            _ => self.format.encode_data(bytes),
+        };
+        self.inner.serialize_str(&text)
+    }
+}
+
+/// Forwards a primitive to the inner serializer.
+macro_rules! forward {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method(self, v: $ty) -> Result<S::Ok, S::Error> {
+                self.inner.$method(v)
+            }
+        )*
+    };
+}
+
+impl<S: Serializer> Serializer for FormatSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = Compound<S::SerializeSeq>;
+    type SerializeTuple = Compound<S::SerializeTuple>;
+    type SerializeTupleStruct = Compound<S::SerializeTupleStruct>;
+    type SerializeTupleVariant = Compound<S::SerializeTupleVariant>;
+    type SerializeMap = Compound<S::SerializeMap>;
+    type SerializeStruct = Compound<S::SerializeStruct>;
+    type SerializeStructVariant = Compound<S::SerializeStructVariant>;
+
+    forward!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_unit_struct(&'static str),
+    );
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_none()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_some(&self.format.encoded(value))
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner
+            .serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        match name {
+            HASH_TOKEN | BYTES_TOKEN => self.reencode(name, value),
+            _ => self
+                .inner
+                .serialize_newtype_struct(name, &self.format.encoded(value)),
+        }
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_newtype_variant(
+            name,
+            variant_index,
+            variant,
+            &self.format.encoded(value),
+        )
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        Ok(Compound::new(self.inner.serialize_seq(len)?, self.format))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        Ok(Compound::new(self.inner.serialize_tuple(len)?, self.format))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        let inner = self.inner.serialize_tuple_struct(name, len)?;
+        Ok(Compound::new(inner, self.format))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        let inner = self
+            .inner
+            .serialize_tuple_variant(name, variant_index, variant, len)?;
+        Ok(Compound::new(inner, self.format))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        Ok(Compound::new(self.inner.serialize_map(len)?, self.format))
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        Ok(Compound::new(
+            self.inner.serialize_struct(name, len)?,
+            self.format,
+        ))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        let inner = self
+            .inner
+            .serialize_struct_variant(name, variant_index, variant, len)?;
+        Ok(Compound::new(inner, self.format))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// A sequence, map or struct of a [`FormatSerializer`], wrapping its elements in
+/// [`Encoded`].
+struct Compound<C> {
+    inner: C,
+    format: HashFormat,
+}
+
+impl<C> Compound<C> {
+    fn new(inner: C, format: HashFormat) -> Self {
+        Compound { inner, format }
+    }
+}
+
+impl<C: SerializeSeq> SerializeSeq for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.inner.serialize_element(&self.format.encoded(value))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeTuple> SerializeTuple for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.inner.serialize_element(&self.format.encoded(value))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeTupleStruct> SerializeTupleStruct for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.inner.serialize_field(&self.format.encoded(value))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeTupleVariant> SerializeTupleVariant for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.inner.serialize_field(&self.format.encoded(value))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeMap> SerializeMap for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), C::Error> {
+        self.inner.serialize_key(&self.format.encoded(key))
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.inner.serialize_value(&self.format.encoded(value))
+    }
+
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), C::Error>
+    where
+        K: ?Sized + Serialize,
+        V: ?Sized + Serialize,
+    {
+        self.inner
+            .serialize_entry(&self.format.encoded(key), &self.format.encoded(value))
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeStruct> SerializeStruct for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), C::Error> {
+        self.inner.serialize_field(key, &self.format.encoded(value))
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), C::Error> {
+        self.inner.skip_field(key)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeStructVariant> SerializeStructVariant for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), C::Error> {
+        self.inner.serialize_field(key, &self.format.encoded(value))
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), C::Error> {
+        self.inner.skip_field(key)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+/// Decodes bytes written by [`HashFormat::encode_data`]: base64 after a `base64:` prefix,
+/// and hex, with or without a `0x` prefix, otherwise.
+pub fn decode_bytes(s: &str) -> Result<Vec<u8>, MerkleTreeError> {
+    match s.strip_prefix(BASE64_PREFIX) {
+        Some(base64) => BASE64
+            .decode(base64)
+            .map_err(|e| MerkleTreeError::FormatError(format!("invalid base64: {}", e))),
+        None => Ok(decode(s)?),
+    }
+}
+
+/// Parses a 32-byte hash from hex (with or without a `0x` prefix) or base64.
+///
+/// A 32-byte hash is 64 hex characters or 44 base64 characters, so the two encodings
+/// cannot be confused.
+pub fn parse_b256(s: &str) -> Result<B256, MerkleTreeError> {
+    let s = s.trim();
+    let bytes = match decode(s) {
+        Ok(bytes) => bytes,
+        Err(e) => BASE64
+            .decode(s.strip_prefix(BASE64_PREFIX).unwrap_or(s))
+            .map_err(|_| MerkleTreeError::from(e))?,
+    };
+    if bytes.len() != 32 {
+        return Err(MerkleTreeError::LengthMismatch {
+            expected: 32,
+            got: bytes.len(),
+        });
+    }
+    Ok(B256::from_slice(&bytes))
+}
+
+/// Shortens `value` to its first 64 characters, for quoting it in error messages.
+pub(crate) fn shorten(value: &str) -> Cow<'_, str> {
+    match value.char_indices().nth(64) {
+        Some((end, _)) => Cow::Owned(format!("{}…", &value[..end])),
+        None => Cow::Borrowed(value),
+    }
+}
+
+/// Returns the deserialization error for a string that is not a hash.
+fn invalid_hash<E: serde::de::Error>(s: &str) -> E {
+    E::invalid_value(
+        Unexpected::Str(&shorten(s)),
+        &"a 32-byte hash in hex or base64",
+    )
+}
 
+/// Newtype (de)serializing a hash with [`b256_hex`], for use in hand-written impls.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct HexB256(#[serde(with = "b256_hex")] pub(crate) B256);
+
+/// Newtype (de)serializing raw bytes with [`bytes_hex`].
+#[derive(Serialize, Deserialize)]
+pub(crate) struct HexBytes(#[serde(with = "bytes_hex")] pub(crate) Vec<u8>);
+
+/// Hex strings for human-readable formats, raw 32-byte arrays for binary ones.
+///
+/// Parsing accepts hex with or without a `0x` prefix, or base64; output is unprefixed hex,
+/// or follows the [`HashFormat`] of an enclosing [`Encoded`].
 pub mod b256_hex {
     use super::*;
 
@@ -10,22 +504,21 @@ pub mod b256_hex {
     where
         S: Serializer,
     {
-        let hex_str = encode(bytes);
-        serializer.serialize_str(&hex_str)
+        if !serializer.is_human_readable() {
+            return bytes.0.serialize(serializer);
+        }
+        serializer.serialize_newtype_struct(HASH_TOKEN, &encode(bytes))
     }
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<B256, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        let bytes = decode(&s).map_err(serde::de::Error::custom)?;
-        if bytes.len() != 32 {
-            return Err(serde::de::Error::custom("Invalid length for B256"));
+        if !deserializer.is_human_readable() {
+            return <[u8; 32]>::deserialize(deserializer).map(B256::from);
         }
-        let mut array = [0u8; 32];
-        array.copy_from_slice(&bytes);
-        Ok(B256::from_slice(&array))
+        let s = String::deserialize(deserializer)?;
+        parse_b256(&s).map_err(|_| invalid_hash(&s))
     }
 }
 
@@ -36,21 +529,195 @@ pub mod txhash_hex {
     where
         S: Serializer,
     {
-        let hex_str = encode(tx_hash);
-        serializer.serialize_str(&hex_str)
+        if !serializer.is_human_readable() {
+            return tx_hash.0.serialize(serializer);
+        }
+        serializer.serialize_newtype_struct(HASH_TOKEN, &encode(tx_hash))
     }
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<TxHash, D::Error>
     where
         D: Deserializer<'de>,
     {
+        if !deserializer.is_human_readable() {
+            return <[u8; 32]>::deserialize(deserializer).map(TxHash::from);
+        }
         let s = String::deserialize(deserializer)?;
-        let bytes = decode(&s).map_err(serde::de::Error::custom)?;
-        if bytes.len() != 32 {
-            return Err(serde::de::Error::custom("Invalid length for TxHash"));
+        parse_b256(&s).map_err(|_| invalid_hash(&s))
+    }
+}
+
+pub mod option_b256_hex {
+    use super::*;
+
+    pub fn serialize<S>(bytes: &Option<B256>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        bytes.map(HexB256).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<B256>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let wrapper = Option::<HexB256>::deserialize(deserializer)?;
+        Ok(wrapper.map(|HexB256(bytes)| bytes))
+    }
+}
+
+/// A list of hashes, each encoded like [`b256_hex`].
+pub mod b256_vec_hex {
+    use super::*;
+
+    pub fn serialize<S>(hashes: &[B256], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(hashes.iter().map(|hash| HexB256(*hash)))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<B256>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let hashes = Vec::<HexB256>::deserialize(deserializer)?;
+        Ok(hashes.into_iter().map(|HexB256(hash)| hash).collect())
+    }
+}
+
+/// Arbitrary bytes as a hex (or, per the [`HashFormat`] of an enclosing [`Encoded`],
+/// `base64:`-prefixed base64) string for human-readable formats, raw bytes for binary ones.
+pub mod bytes_hex {
+    use super::*;
+
+    pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_bytes(bytes);
+        }
+        serializer.serialize_newtype_struct(BYTES_TOKEN, &encode(bytes))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if !deserializer.is_human_readable() {
+            return Vec::<u8>::deserialize(deserializer);
         }
-        let mut array = [0u8; 32];
-        array.copy_from_slice(&bytes);
-        Ok(TxHash::from_slice(&array))
+        let s = String::deserialize(deserializer)?;
+        decode_bytes(&s).map_err(|_| {
+            serde::de::Error::invalid_value(
+                Unexpected::Str(&shorten(&s)),
+                &"hex, or base64 after a `base64:` prefix",
+            )
+        })
+    }
+}
+
+/// Optional bytes, encoded like [`bytes_hex`] when present.
+pub mod option_bytes_hex {
+    use super::*;
+
+    pub fn serialize<S>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        bytes.clone().map(HexBytes).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let wrapper = Option::<HexBytes>::deserialize(deserializer)?;
+        Ok(wrapper.map(|HexBytes(bytes)| bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MerkleProof, MerkleTree};
+    use serde_json::json;
+
+    const BASE64_FORMAT: HashFormat = HashFormat {
+        encoding: HashEncoding::Base64,
+        hex_prefix: false,
+    };
+
+    /// Leaf data whose base64, `abcdef12`, is also valid hex for other bytes.
+    fn ambiguous_data() -> Vec<u8> {
+        let data = BASE64.decode("abcdef12").unwrap();
+        assert_eq!(decode("abcdef12").unwrap().len(), 4);
+        data
+    }
+
+    /// A hash whose base64 holds only hex digits before its padding.
+    fn ambiguous_hash() -> B256 {
+        let text = format!("{}0=", &"abcdef0123456789".repeat(3)[..42]);
+        let hash = B256::from_slice(&BASE64.decode(&text).unwrap());
+        assert_eq!(BASE64_FORMAT.encode(hash), text);
+        hash
+    }
+
+    #[test]
+    fn base64_data_that_is_also_hex_round_trips() {
+        let data = ambiguous_data();
+        let text = BASE64_FORMAT.encode_data(&data);
+        assert_eq!(text, "base64:abcdef12");
+        assert_eq!(decode_bytes(&text).unwrap(), data);
+        // Without the prefix the same text reads as hex.
+        assert_ne!(decode_bytes("abcdef12").unwrap(), data);
+    }
+
+    #[test]
+    fn base64_hashes_that_are_also_hex_round_trip() {
+        let hash = ambiguous_hash();
+        assert_eq!(parse_b256(&BASE64_FORMAT.encode(hash)).unwrap(), hash);
+        // 64 hex digits are also valid base64, for 48 bytes, and still read as hex.
+        assert_eq!(parse_b256(&encode(hash)).unwrap(), hash);
+        assert_eq!(parse_b256(&encode_prefixed(hash)).unwrap(), hash);
+    }
+
+    #[test]
+    fn trees_round_trip_in_base64() {
+        let hash = ambiguous_hash();
+        let data = vec![ambiguous_data(), hash.to_vec(), b"leaf".to_vec()];
+        let tree = MerkleTree::new(&data).unwrap();
+        let json = tree.to_json_with_leaf_data_in(BASE64_FORMAT).unwrap();
+        assert!(json.contains("\"base64:abcdef12\""));
+        assert_eq!(MerkleTree::from_json(&json).unwrap(), tree);
+    }
+
+    #[test]
+    fn formats_apply_to_encoded_values_only() {
+        let tree = MerkleTree::new(&[b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]).unwrap();
+        let proof = tree.generate_proof_by_index(2).unwrap();
+        let prefixed = HashFormat {
+            encoding: HashEncoding::Hex,
+            hex_prefix: true,
+        };
+
+        let value = json!({
+            "encoded": [prefixed.encoded(Some(&proof))],
+            "plain": &proof,
+        });
+        assert_eq!(
+            value["encoded"][0]["leaf_hash"],
+            encode_prefixed(proof.leaf_hash)
+        );
+        assert_eq!(value["plain"]["leaf_hash"], encode(proof.leaf_hash));
+
+        let json = proof.to_json_with(BASE64_FORMAT).unwrap();
+        assert!(json.contains(&BASE64.encode(proof.leaf_hash)));
+        assert_eq!(MerkleProof::from_json(&json).unwrap(), proof);
+        assert_eq!(
+            proof.to_json_with(HashFormat::default()).unwrap(),
+            proof.to_json().unwrap()
+        );
     }
 }
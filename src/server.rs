@@ -0,0 +1,562 @@
+//! HTTP endpoints serving a tree's root and proofs, for mounting into an axum application.
+//!
+//! [`router`] serves a fixed tree. [`Server`] also configures how leaves in requests are
+//! parsed and whether `POST /leaves` may append to the tree:
+//!
+//! - `GET /root` returns the root and leaf count;
+//! - `GET /proof/{leaf}` and `GET /proof/index/{index}` return a leaf's proof;
+//! - `POST /verify` with `{"proof": ..., "root": ...}` checks a proof, `root` defaulting to
+//!   the served root;
+//! - `POST /leaves` with `{"leaves": [...]}` appends leaves, when enabled, within the
+//!   tree's [`limits`](MerkleTree::limits);
+//! - `POST /rpc` answers JSON-RPC 2.0 calls, singly or in batches;
+//! - `GET /ws` opens a WebSocket receiving a [`RootUpdate`] on connecting and after each
+//!   append.
+//!
+//! Errors are answered with `{"status": "error", "error": ...}`, except on `/rpc`.
+//!
+//! The JSON-RPC methods take positional parameters:
+//!
+//! - `merkle_getRoot()` returns `{"root": ..., "leaf_count": ...}`;
+//! - `merkle_getProof(leaf)` returns `{"root": ..., "proof": ...}` for a leaf given as a
+//!   string, or for the leaf at an index given as a number;
+//! - `merkle_verifyProof(proof, root?)` returns `{"root": ..., "valid": ...}`, `root`
+//!   defaulting to the served root.
+
+use crate::error::MerkleTreeError;
+use crate::merkle_tree::MerkleTree;
+use crate::proof::MerkleProof;
+use crate::proof_cache::ProofCache;
+use crate::serialization::{parse_b256, HashFormat};
+
+use alloy_primitives::hex::decode;
+use alloy_primitives::B256;
+use axum::body::Bytes;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock as StdRwLock};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::RwLock;
+
+/// Turns a leaf given in a request into leaf data.
+type LeafParser = Box<dyn Fn(&str) -> Result<Vec<u8>, MerkleTreeError> + Send + Sync>;
+
+/// Called with the tree after each append, while it is still locked.
+type AppendHook = Box<dyn Fn(&MerkleTree) -> Result<(), MerkleTreeError> + Send + Sync>;
+
+/// JSON-RPC 2.0 error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+/// Root updates buffered per WebSocket subscriber. Subscribers falling further behind
+/// skip the oldest updates.
+const UPDATE_BUFFER: usize = 64;
+
+/// The state of the served tree, pushed to WebSocket subscribers.
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct RootUpdate {
+    /// The root hash, encoded like other hashes in responses.
+    pub root: String,
+
+    /// Number of leaves in the tree.
+    pub leaf_count: usize,
+
+    /// Number of appends served so far.
+    pub version: u64,
+}
+
+impl RootUpdate {
+    fn new(tree: &MerkleTree, version: u64, format: HashFormat) -> Self {
+        RootUpdate {
+            root: format.encode(tree.root_hash()),
+            leaf_count: tree.leaf_count(),
+            version,
+        }
+    }
+}
+
+/// Returns a router serving `tree`, with leaves given as hex and appends disabled.
+pub fn router(tree: MerkleTree) -> Router {
+    Server::new(tree).router()
+}
+
+/// Configures the endpoints serving a tree.
+pub struct Server {
+    tree: Arc<RwLock<MerkleTree>>,
+    parse_leaf: LeafParser,
+    on_append: Option<AppendHook>,
+    updates: broadcast::Sender<RootUpdate>,
+    proof_cache: Option<usize>,
+    hash_format: HashFormat,
+}
+
+/// State shared by the handlers.
+struct Shared {
+    tree: Arc<RwLock<MerkleTree>>,
+    parse_leaf: LeafParser,
+    on_append: Option<AppendHook>,
+    updates: broadcast::Sender<RootUpdate>,
+    version: AtomicU64,
+    proofs: Option<CachedProofs>,
+    hash_format: HashFormat,
+}
+
+/// Proofs cached across requests, with what is needed to look them up by leaf data.
+struct CachedProofs {
+    cache: Mutex<ProofCache>,
+    positions: StdRwLock<LeafPositions>,
+}
+
+/// Index of the first leaf with each hash, covering the first `indexed` leaves of the tree.
+#[derive(Default)]
+struct LeafPositions {
+    indexed: usize,
+    first: HashMap<B256, usize>,
+}
+
+impl CachedProofs {
+    fn new(capacity: usize) -> Self {
+        CachedProofs {
+            cache: Mutex::new(ProofCache::new(capacity)),
+            positions: StdRwLock::new(LeafPositions::default()),
+        }
+    }
+
+    /// Returns the index of the first leaf of `tree` hashing to `leaf_hash`, first indexing
+    /// any leaves appended since the last lookup.
+    fn position(&self, tree: &MerkleTree, leaf_hash: &B256) -> Option<usize> {
+        {
+            let positions = self
+                .positions
+                .read()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if positions.indexed == tree.leaf_hashes.len() {
+                return positions.first.get(leaf_hash).copied();
+            }
+        }
+        let mut positions = self
+            .positions
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let start = positions.indexed;
+        for (index, hash) in tree.leaf_hashes.iter().enumerate().skip(start) {
+            positions.first.entry(*hash).or_insert(index);
+        }
+        positions.indexed = positions.indexed.max(tree.leaf_hashes.len());
+        positions.first.get(leaf_hash).copied()
+    }
+
+    fn cache(&self) -> MutexGuard<'_, ProofCache> {
+        self.cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Server {
+    /// Serves `tree`, with leaves given as hex and appends disabled.
+    pub fn new(tree: MerkleTree) -> Self {
+        Server {
+            tree: Arc::new(RwLock::new(tree)),
+            parse_leaf: Box::new(|leaf| Ok(decode(leaf.trim())?)),
+            on_append: None,
+            updates: broadcast::channel(UPDATE_BUFFER).0,
+            proof_cache: None,
+            hash_format: HashFormat::default(),
+        }
+    }
+
+    /// Sets how leaves in request paths and bodies are turned into leaf data.
+    pub fn parse_leaves_with<F>(mut self, parse_leaf: F) -> Self
+    where
+        F: Fn(&str) -> Result<Vec<u8>, MerkleTreeError> + Send + Sync + 'static,
+    {
+        self.parse_leaf = Box::new(parse_leaf);
+        self
+    }
+
+    /// Enables `POST /leaves`. `on_append` runs after each append, while the tree is still
+    /// locked, so it can persist the tree; its error is returned to the client and the
+    /// append is discarded.
+    pub fn allow_append<F>(mut self, on_append: F) -> Self
+    where
+        F: Fn(&MerkleTree) -> Result<(), MerkleTreeError> + Send + Sync + 'static,
+    {
+        self.on_append = Some(Box::new(on_append));
+        self
+    }
+
+    /// Caches up to `capacity` generated proofs, keeping the most recently requested ones.
+    ///
+    /// Cached proofs are dropped after each append. A tree updated outside of requests,
+    /// through [`Server::tree`], must not be served with a cache.
+    pub fn cache_proofs(mut self, capacity: usize) -> Self {
+        self.proof_cache = Some(capacity);
+        self
+    }
+
+    /// Sets how hashes and bytes are encoded in responses.
+    pub fn hash_format(mut self, format: HashFormat) -> Self {
+        self.hash_format = format;
+        self
+    }
+
+    /// Returns the served tree, for reading or updating it outside of requests.
+    pub fn tree(&self) -> Arc<RwLock<MerkleTree>> {
+        self.tree.clone()
+    }
+
+    /// Returns a receiver of the updates pushed to WebSocket subscribers after each append.
+    pub fn subscribe(&self) -> broadcast::Receiver<RootUpdate> {
+        self.updates.subscribe()
+    }
+
+    /// Returns the router serving the endpoints.
+    pub fn router(self) -> Router {
+        let mut router = Router::new()
+            .route("/root", get(root))
+            .route("/proof/:leaf", get(proof))
+            .route("/proof/index/:index", get(proof_by_index))
+            .route("/verify", post(verify))
+            .route("/rpc", post(rpc))
+            .route("/ws", get(subscribe));
+        if self.on_append.is_some() {
+            router = router.route("/leaves", post(append));
+        }
+        router.with_state(Arc::new(Shared {
+            tree: self.tree,
+            parse_leaf: self.parse_leaf,
+            on_append: self.on_append,
+            updates: self.updates,
+            version: AtomicU64::new(0),
+            proofs: self.proof_cache.map(CachedProofs::new),
+            hash_format: self.hash_format,
+        }))
+    }
+}
+
+/// Body of `POST /verify`.
+#[derive(Deserialize)]
+struct VerifyRequest {
+    proof: MerkleProof,
+    root: Option<String>,
+}
+
+/// Body of `POST /leaves`.
+#[derive(Deserialize)]
+struct AppendRequest {
+    leaves: Vec<String>,
+}
+
+/// A failed request, answered with a JSON error body.
+struct ApiError(MerkleTreeError);
+
+impl From<MerkleTreeError> for ApiError {
+    fn from(error: MerkleTreeError) -> Self {
+        ApiError(error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self.0 {
+            MerkleTreeError::NodeOutOfRange { .. }
+            | MerkleTreeError::LeafNotFound { .. }
+            | MerkleTreeError::IndexOutOfRange { .. } => StatusCode::NOT_FOUND,
+            MerkleTreeError::IoError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        let body = json!({ "status": "error", "error": self.0.to_string() });
+        (status, Json(body)).into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+/// Returns the proof of the leaf at `index`, from the cache if one is configured.
+///
+/// Proofs are generated without holding the cache lock, so concurrent requests for other
+/// leaves are not held up.
+fn proof_at(
+    state: &Shared,
+    tree: &MerkleTree,
+    index: usize,
+) -> Result<MerkleProof, MerkleTreeError> {
+    let Some(proofs) = &state.proofs else {
+        return tree.generate_proof_by_index(index);
+    };
+    let version = state.version.load(Ordering::SeqCst);
+    if let Some(proof) = proofs.cache().get(version, index) {
+        return Ok(proof);
+    }
+    let proof = tree.generate_proof_by_index(index)?;
+    proofs.cache().insert(version, index, proof.clone());
+    Ok(proof)
+}
+
+/// Returns the proof of the leaf holding `data`, from the cache if one is configured.
+fn proof_of(
+    state: &Shared,
+    tree: &MerkleTree,
+    data: &[u8],
+) -> Result<MerkleProof, MerkleTreeError> {
+    let Some(proofs) = &state.proofs else {
+        return tree.generate_proof(data);
+    };
+    match proofs.position(tree, &tree.hash_leaf(data)) {
+        Some(index) => proof_at(state, tree, index),
+        None => tree.generate_proof(data),
+    }
+}
+
+async fn root(State(state): State<Arc<Shared>>) -> Json<Value> {
+    let tree = state.tree.read().await;
+    Json(json!({
+        "root": state.hash_format.encode(tree.root_hash()),
+        "leaf_count": tree.leaf_count(),
+    }))
+}
+
+async fn proof(
+    State(state): State<Arc<Shared>>,
+    Path(leaf): Path<String>,
+) -> ApiResult<Json<Value>> {
+    let data = (state.parse_leaf)(&leaf)?;
+    let tree = state.tree.read().await;
+    let proof = proof_of(&state, &tree, &data)?;
+    Ok(Json(
+        json!({ "root": state.hash_format.encode(tree.root_hash()), "proof": state.hash_format.encoded(&proof) }),
+    ))
+}
+
+async fn proof_by_index(
+    State(state): State<Arc<Shared>>,
+    Path(index): Path<usize>,
+) -> ApiResult<Json<Value>> {
+    let tree = state.tree.read().await;
+    let proof = proof_at(&state, &tree, index)?;
+    Ok(Json(
+        json!({ "root": state.hash_format.encode(tree.root_hash()), "proof": state.hash_format.encoded(&proof) }),
+    ))
+}
+
+async fn verify(
+    State(state): State<Arc<Shared>>,
+    Json(request): Json<VerifyRequest>,
+) -> ApiResult<Json<Value>> {
+    let root_hash = match &request.root {
+        Some(root) => parse_b256(root)?,
+        None => *state.tree.read().await.root_hash(),
+    };
+    let valid = request.proof.verify(&root_hash)?;
+    Ok(Json(
+        json!({ "root": state.hash_format.encode(root_hash), "valid": valid }),
+    ))
+}
+
+/// Appends leaves and runs the append hook, holding the lock so hooks run in order.
+///
+/// The leaves are appended in place and truncated away again if the hook fails, so a
+/// failed append or hook leaves the tree and cached proofs as they were.
+async fn append(
+    State(state): State<Arc<Shared>>,
+    Json(request): Json<AppendRequest>,
+) -> ApiResult<Json<Value>> {
+    let data = request
+        .leaves
+        .iter()
+        .map(|leaf| (state.parse_leaf)(leaf))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut tree = state.tree.write().await;
+    let old_count = tree.leaf_count();
+    tree.append(&data)?;
+    if let Some(on_append) = &state.on_append {
+        if let Err(e) = on_append(&tree) {
+            tree.truncate(old_count)?;
+            return Err(e.into());
+        }
+    }
+
+    // Sending only fails when nobody is subscribed
+    let version = state.version.fetch_add(1, Ordering::SeqCst) + 1;
+    let _ = state
+        .updates
+        .send(RootUpdate::new(&tree, version, state.hash_format));
+    Ok(Json(json!({
+        "status": "ok",
+        "root": state.hash_format.encode(tree.root_hash()),
+        "leaf_count": tree.leaf_count(),
+    })))
+}
+
+async fn subscribe(State(state): State<Arc<Shared>>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| push_updates(state, socket))
+}
+
+/// Sends the current root, then every update, until the subscriber disconnects.
+async fn push_updates(state: Arc<Shared>, mut socket: WebSocket) {
+    // Subscribe before reading the tree, so no append falls between the two
+    let mut updates = state.updates.subscribe();
+    let current = {
+        let tree = state.tree.read().await;
+        RootUpdate::new(
+            &tree,
+            state.version.load(Ordering::SeqCst),
+            state.hash_format,
+        )
+    };
+
+    let mut update = current;
+    loop {
+        let Ok(text) = serde_json::to_string(&update) else {
+            return;
+        };
+        if socket.send(Message::Text(text)).await.is_err() {
+            return;
+        }
+        update = loop {
+            match updates.recv().await {
+                Ok(update) => break update,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            }
+        };
+    }
+}
+
+/// Answers a JSON-RPC request or batch. Notifications, which have no `id`, get no answer.
+async fn rpc(State(state): State<Arc<Shared>>, body: Bytes) -> Response {
+    let request: Value = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(e) => return Json(rpc_error(Value::Null, PARSE_ERROR, e.to_string())).into_response(),
+    };
+
+    let response = match request {
+        Value::Array(calls) if !calls.is_empty() => {
+            let mut responses = Vec::new();
+            for call in calls {
+                responses.extend(rpc_call(&state, call).await);
+            }
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        call => rpc_call(&state, call).await,
+    };
+    match response {
+        Some(response) => Json(response).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+/// Answers a single JSON-RPC call, or returns `None` for a notification.
+async fn rpc_call(state: &Shared, call: Value) -> Option<Value> {
+    let Some(call) = call.as_object() else {
+        return Some(rpc_error(
+            Value::Null,
+            INVALID_REQUEST,
+            "expected a request object",
+        ));
+    };
+    let id = call.get("id").cloned();
+    let method = match call.get("method") {
+        Some(Value::String(method)) if call.get("jsonrpc") == Some(&json!("2.0")) => method,
+        _ => {
+            let message = "expected `jsonrpc: \"2.0\"` and a method";
+            return Some(rpc_error(
+                id.unwrap_or(Value::Null),
+                INVALID_REQUEST,
+                message,
+            ));
+        }
+    };
+    let params = match call.get("params") {
+        None => Vec::new(),
+        Some(Value::Array(params)) => params.clone(),
+        Some(_) => {
+            let message = "expected positional parameters";
+            return Some(rpc_error(
+                id.unwrap_or(Value::Null),
+                INVALID_PARAMS,
+                message,
+            ));
+        }
+    };
+
+    let result = rpc_dispatch(state, method, params).await;
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err((code, message)) => rpc_error(id, code, message),
+    })
+}
+
+async fn rpc_dispatch(
+    state: &Shared,
+    method: &str,
+    params: Vec<Value>,
+) -> Result<Value, (i64, String)> {
+    let invalid_params = |e: MerkleTreeError| (INVALID_PARAMS, e.to_string());
+    let server_error = |e: MerkleTreeError| (SERVER_ERROR, e.to_string());
+
+    match (method, params.as_slice()) {
+        ("merkle_getRoot", []) => {
+            let tree = state.tree.read().await;
+            Ok(json!({
+                "root": state.hash_format.encode(tree.root_hash()),
+                "leaf_count": tree.leaf_count(),
+            }))
+        }
+        ("merkle_getProof", [leaf]) => {
+            let tree = state.tree.read().await;
+            let proof = match leaf {
+                Value::String(leaf) => {
+                    let data = (state.parse_leaf)(leaf).map_err(invalid_params)?;
+                    proof_of(state, &tree, &data)
+                }
+                Value::Number(index) => match index.as_u64() {
+                    Some(index) => proof_at(state, &tree, index as usize),
+                    None => return Err((INVALID_PARAMS, format!("invalid index {}", index))),
+                },
+                _ => return Err((INVALID_PARAMS, "expected a leaf or an index".to_string())),
+            }
+            .map_err(server_error)?;
+            Ok(
+                json!({ "root": state.hash_format.encode(tree.root_hash()), "proof": state.hash_format.encoded(&proof) }),
+            )
+        }
+        ("merkle_verifyProof", [proof, rest @ ..]) if rest.len() <= 1 => {
+            let proof: MerkleProof = serde_json::from_value(proof.clone())
+                .map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+            let root_hash = match rest.first() {
+                Some(Value::String(root)) => parse_b256(root).map_err(invalid_params)?,
+                Some(_) => return Err((INVALID_PARAMS, "expected a root hash".to_string())),
+                None => *state.tree.read().await.root_hash(),
+            };
+            let valid = proof.verify(&root_hash).map_err(server_error)?;
+            Ok(json!({ "root": state.hash_format.encode(root_hash), "valid": valid }))
+        }
+        ("merkle_getRoot" | "merkle_getProof" | "merkle_verifyProof", _) => Err((
+            INVALID_PARAMS,
+            format!("wrong number of parameters for {}", method),
+        )),
+        _ => Err((METHOD_NOT_FOUND, format!("method {} not found", method))),
+    }
+}
+
+fn rpc_error(id: Value, code: i64, message: impl Into<String>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": { "code": code, "message": message.into() },
+        "id": id,
+    })
+}
@@ -0,0 +1,292 @@
+//! EIP-712 signed root attestations, for publishing roots that contracts can authenticate.
+//!
+//! The signed message is the typed struct
+//! `RootAttestation(bytes32 root,uint256 treeSize,uint256 validUntil)` in the attestation's
+//! [`Eip712Domain`]. A contract inheriting OpenZeppelin's `EIP712` with the same name and
+//! version checks one with:
+//!
+//! ```solidity
+//! bytes32 structHash = keccak256(abi.encode(TYPEHASH, root, treeSize, validUntil));
+//! require(ECDSA.recover(_hashTypedDataV4(structHash), signature) == signer);
+//! ```
+//!
+//! Unlike a [`SignedRoot`](crate::SignedRoot), an attestation expires and is bound to one
+//! chain and contract.
+
+use crate::error::MerkleTreeError;
+use crate::merkle_tree::MerkleTree;
+use crate::schema::{self, Versioned};
+use crate::serialization::HashFormat;
+
+use alloy_primitives::{keccak256, Address, B256, U256};
+// Later alloy-primitives 0.8 releases deprecate the signature type alloy-signer 0.3 returns
+#[allow(deprecated)]
+use alloy_signer::Signature;
+use alloy_signer::{Signer, SignerSync};
+use serde::{Deserialize, Serialize};
+
+/// The EIP-712 type of the signed struct.
+pub const ATTESTATION_TYPE: &str =
+    "RootAttestation(bytes32 root,uint256 treeSize,uint256 validUntil)";
+
+/// The EIP-712 type of the domain.
+const DOMAIN_TYPE: &str =
+    "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)";
+
+/// Where an attestation is valid: the signing application, and the chain and contract
+/// that consume it.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Eip712Domain {
+    pub name: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub verifying_contract: Address,
+}
+
+impl Eip712Domain {
+    /// Returns the domain separator, `hashStruct(EIP712Domain)`.
+    pub fn separator(&self) -> B256 {
+        let mut encoded = Vec::with_capacity(5 * 32);
+        encoded.extend_from_slice(keccak256(DOMAIN_TYPE).as_slice());
+        encoded.extend_from_slice(keccak256(self.name.as_bytes()).as_slice());
+        encoded.extend_from_slice(keccak256(self.version.as_bytes()).as_slice());
+        encoded.extend_from_slice(&U256::from(self.chain_id).to_be_bytes::<32>());
+        encoded.extend_from_slice(self.verifying_contract.into_word().as_slice());
+        keccak256(encoded)
+    }
+}
+
+/// A tree's root, size and expiry, signed as EIP-712 typed data.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct RootAttestation {
+    /// The attested root hash.
+    #[serde(with = "crate::serialization::b256_hex")]
+    pub root: B256,
+
+    /// Number of leaves in the tree.
+    pub tree_size: u64,
+
+    /// Unix time in seconds after which the attestation no longer holds.
+    pub valid_until: u64,
+
+    /// Domain the attestation was signed in.
+    pub domain: Eip712Domain,
+
+    /// Address of the account that signed the attestation.
+    pub signer: Address,
+
+    /// The 65-byte `r || s || v` signature.
+    #[serde(with = "crate::serialization::bytes_hex")]
+    pub signature: Vec<u8>,
+}
+
+impl RootAttestation {
+    /// Signs the root and size of `tree`, valid until the Unix time `valid_until`.
+    pub fn sign<S>(
+        tree: &MerkleTree,
+        valid_until: u64,
+        domain: Eip712Domain,
+        signer: &S,
+    ) -> Result<Self, MerkleTreeError>
+    where
+        S: Signer + SignerSync,
+    {
+        let mut attestation = RootAttestation {
+            root: *tree.root_hash(),
+            tree_size: tree.leaf_count() as u64,
+            valid_until,
+            domain,
+            signer: Address::from_slice(signer.address().as_slice()),
+            signature: Vec::new(),
+        };
+        let signature = signer
+            .sign_hash_sync(&attestation.signing_hash().0.into())
+            .map_err(|e| MerkleTreeError::SignatureError(e.to_string()))?;
+        attestation.signature = signature.as_bytes().to_vec();
+        Ok(attestation)
+    }
+
+    /// Returns `hashStruct(RootAttestation)`.
+    pub fn struct_hash(&self) -> B256 {
+        let mut encoded = Vec::with_capacity(4 * 32);
+        encoded.extend_from_slice(keccak256(ATTESTATION_TYPE).as_slice());
+        encoded.extend_from_slice(self.root.as_slice());
+        encoded.extend_from_slice(&U256::from(self.tree_size).to_be_bytes::<32>());
+        encoded.extend_from_slice(&U256::from(self.valid_until).to_be_bytes::<32>());
+        keccak256(encoded)
+    }
+
+    /// Returns the digest that is signed, `keccak256("\x19\x01" || domainSeparator ||
+    /// hashStruct(message))`.
+    pub fn signing_hash(&self) -> B256 {
+        let mut encoded = Vec::with_capacity(2 + 2 * 32);
+        encoded.extend_from_slice(&[0x19, 0x01]);
+        encoded.extend_from_slice(self.domain.separator().as_slice());
+        encoded.extend_from_slice(self.struct_hash().as_slice());
+        keccak256(encoded)
+    }
+
+    /// Returns whether the signature was made by `signer`, regardless of expiry.
+    ///
+    /// Malformed signatures are errors; a well-formed signature by another account is not.
+    #[allow(deprecated)]
+    pub fn verify(&self) -> Result<bool, MerkleTreeError> {
+        let signature = Signature::try_from(self.signature.as_slice())
+            .map_err(|e| MerkleTreeError::SignatureError(e.to_string()))?;
+        let recovered = signature
+            .recover_address_from_prehash(&self.signing_hash().0.into())
+            .map_err(|e| MerkleTreeError::SignatureError(e.to_string()))?;
+        Ok(recovered.as_slice() == self.signer.as_slice())
+    }
+
+    /// Returns whether the signature is valid and the attestation has not expired at the
+    /// Unix time `now`.
+    pub fn verify_at(&self, now: u64) -> Result<bool, MerkleTreeError> {
+        Ok(!self.is_expired(now) && self.verify()?)
+    }
+
+    /// Returns whether the attestation has expired at the Unix time `now`.
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.valid_until
+    }
+
+    /// Returns whether the attestation is for `tree`'s root and size.
+    pub fn covers(&self, tree: &MerkleTree) -> bool {
+        self.root == *tree.root_hash() && self.tree_size == tree.leaf_count() as u64
+    }
+
+    /// Serializes the attestation to a JSON string.
+    pub fn to_json(&self) -> Result<String, MerkleTreeError> {
+        self.to_json_with(HashFormat::default())
+    }
+
+    /// Serializes the attestation to a JSON string, writing hashes in `format`.
+    pub fn to_json_with(&self, format: HashFormat) -> Result<String, MerkleTreeError> {
+        serde_json::to_string_pretty(&format.encoded(Versioned::new(self)))
+            .map_err(MerkleTreeError::SerdeError)
+    }
+
+    /// Deserializes an attestation from a JSON string.
+    pub fn from_json(json_str: &str) -> Result<Self, MerkleTreeError> {
+        schema::from_slice(json_str.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, b256};
+
+    fn domain() -> Eip712Domain {
+        Eip712Domain {
+            name: "Merkle Roots".to_string(),
+            version: "1".to_string(),
+            chain_id: 1,
+            verifying_contract: address!("CcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"),
+        }
+    }
+
+    #[test]
+    fn matches_eip712_vectors() {
+        // The domain of the `Mail` example in EIP-712
+        let mail = Eip712Domain {
+            name: "Ether Mail".to_string(),
+            ..domain()
+        };
+        assert_eq!(
+            mail.separator(),
+            b256!("f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f")
+        );
+
+        let attestation = RootAttestation {
+            root: B256::repeat_byte(0x11),
+            tree_size: 4,
+            valid_until: 1_700_000_000,
+            domain: domain(),
+            signer: Address::ZERO,
+            signature: Vec::new(),
+        };
+        assert_eq!(
+            attestation.domain.separator(),
+            b256!("3313b616ac816ac5afa344a84e49047e157076b399e0f491f96ad4c40d51d5d1")
+        );
+        assert_eq!(
+            attestation.struct_hash(),
+            b256!("3ccaec02b97c0131e8b9af768384f94c43c69abf05a998824e711e0ef43f6de8")
+        );
+        assert_eq!(
+            attestation.signing_hash(),
+            b256!("d97eb66541d5ec0102f6d6a5d23a157e73ef1f6df5d10b2e5b4cafad3015682a")
+        );
+    }
+
+    /// A fixed local key. alloy-signer-local, which provides local keys, is a dependency of
+    /// the CLI only.
+    #[cfg(feature = "cli")]
+    fn signer(key: u8) -> alloy_signer_local::PrivateKeySigner {
+        alloy_signer_local::PrivateKeySigner::from_bytes(&[key; 32].into()).unwrap()
+    }
+
+    #[cfg(feature = "cli")]
+    fn tree() -> MerkleTree {
+        let leaves: Vec<Vec<u8>> = ["a", "b", "c"].map(|leaf| leaf.into()).to_vec();
+        MerkleTree::new(&leaves).unwrap()
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn signs_and_verifies_until_expiry() {
+        let tree = tree();
+        let attestation =
+            RootAttestation::sign(&tree, 1_700_000_000, domain(), &signer(1)).unwrap();
+        assert_eq!(
+            attestation.signer.as_slice(),
+            signer(1).address().as_slice()
+        );
+        assert!(attestation.covers(&tree));
+        assert!(attestation.verify().unwrap());
+        assert!(attestation.verify_at(1_699_999_999).unwrap());
+        assert!(attestation.verify_at(1_700_000_000).unwrap());
+        assert!(!attestation.verify_at(1_700_000_001).unwrap());
+        assert!(attestation.is_expired(1_700_000_001));
+
+        let reloaded = RootAttestation::from_json(&attestation.to_json().unwrap()).unwrap();
+        assert_eq!(reloaded, attestation);
+        assert!(reloaded.verify().unwrap());
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn rejects_other_signers_and_tampering() {
+        let tree = tree();
+        let attestation =
+            RootAttestation::sign(&tree, 1_700_000_000, domain(), &signer(1)).unwrap();
+
+        let mut forged = attestation.clone();
+        forged.signer = RootAttestation::sign(&tree, 1_700_000_000, domain(), &signer(2))
+            .unwrap()
+            .signer;
+        assert!(!forged.verify().unwrap());
+
+        let tamperings: [fn(&mut RootAttestation); 5] = [
+            |attestation| attestation.root = B256::repeat_byte(1),
+            |attestation| attestation.tree_size += 1,
+            |attestation| attestation.valid_until += 1,
+            |attestation| attestation.domain.chain_id = 2,
+            |attestation| attestation.domain.verifying_contract = Address::ZERO,
+        ];
+        for tamper in tamperings {
+            let mut tampered = attestation.clone();
+            tamper(&mut tampered);
+            assert!(!tampered.verify().unwrap());
+            assert!(!tampered.verify_at(0).unwrap());
+        }
+
+        let mut malformed = attestation;
+        malformed.signature.truncate(64);
+        assert!(matches!(
+            malformed.verify(),
+            Err(MerkleTreeError::SignatureError(_))
+        ));
+    }
+}
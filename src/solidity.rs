@@ -0,0 +1,353 @@
+//! Solidity verifiers generated from a tree's configuration.
+//!
+//! The generated library hashes leaves and pairs exactly as the tree was built: with the
+//! same hash function, prefixes, pair ordering and [`LeafEncoding`], so on-chain verification cannot
+//! drift from the Rust side. BLAKE3 has no EVM precompile, so its trees cannot be verified
+//! on-chain.
+
+use crate::error::MerkleTreeError;
+use crate::hasher::HashAlgorithm;
+use crate::leaf_encoding::LeafEncoding;
+use crate::merkle_tree::MerkleTree;
+use crate::proof::{MerkleProof, ProofStep};
+
+use alloy_dyn_abi::DynSolType;
+use alloy_primitives::U256;
+use std::fmt::Write;
+
+/// What a generated verifier must match.
+#[derive(Clone, PartialEq, Debug)]
+pub struct VerifierOptions {
+    /// Name of the generated library.
+    pub name: String,
+    pub hash_algorithm: HashAlgorithm,
+    /// Whether leaves and pairs are prefixed before hashing.
+    pub domain_separation: bool,
+    pub sorted_pairs: bool,
+    /// How leaf values are encoded before hashing, or `None` for leaves passed as raw
+    /// bytes.
+    pub leaf_encoding: Option<LeafEncoding>,
+}
+
+impl Default for VerifierOptions {
+    fn default() -> Self {
+        VerifierOptions {
+            name: "MerkleVerifier".to_string(),
+            hash_algorithm: HashAlgorithm::default(),
+            domain_separation: false,
+            sorted_pairs: false,
+            leaf_encoding: None,
+        }
+    }
+}
+
+impl VerifierOptions {
+    /// Returns the options matching how `tree` hashes nodes. Trees do not record how their
+    /// leaves were encoded, so set `leaf_encoding` separately.
+    pub fn for_tree(tree: &MerkleTree) -> Self {
+        VerifierOptions {
+            hash_algorithm: tree.hash_algorithm,
+            domain_separation: tree.domain_separation,
+            sorted_pairs: tree.sorted_pairs,
+            ..VerifierOptions::default()
+        }
+    }
+}
+
+/// Returns the `sides` argument of a generated verifier for unsorted pairs: bit `i` is set
+/// when the `i`-th sibling of `proof` is on the left.
+pub fn proof_sides(proof: &MerkleProof) -> Result<U256, MerkleTreeError> {
+    if proof.proof_steps.len() > 256 {
+        return Err(MerkleTreeError::DepthExceeded {
+            depth: proof.proof_steps.len(),
+            max: 256,
+        });
+    }
+    Ok(proof
+        .proof_steps
+        .iter()
+        .enumerate()
+        .filter(|(_, step)| matches!(step, ProofStep::Left(_)))
+        .fold(U256::ZERO, |sides, (i, _)| sides | (U256::from(1) << i)))
+}
+
+/// Returns the Solidity expression hashing `packed`, an `abi.encodePacked` or `abi.encode`
+/// call.
+fn hash_expression(hash_algorithm: HashAlgorithm, packed: &str) -> Result<String, MerkleTreeError> {
+    match hash_algorithm {
+        HashAlgorithm::Keccak256 => Ok(format!("keccak256({})", packed)),
+        HashAlgorithm::Sha256 => Ok(format!("sha256({})", packed)),
+        algorithm @ HashAlgorithm::Blake3 => Err(MerkleTreeError::NoEvmPrecompile { algorithm }),
+    }
+}
+
+/// Returns a Solidity parameter declaration for a value of type `ty`.
+fn parameter(ty: &DynSolType, name: &str) -> Result<String, MerkleTreeError> {
+    match ty {
+        DynSolType::Tuple(_) => Err(MerkleTreeError::AbiError(format!(
+            "tuple leaf values are not supported, got {}",
+            ty
+        ))),
+        DynSolType::Bytes
+        | DynSolType::String
+        | DynSolType::Array(_)
+        | DynSolType::FixedArray(..) => Ok(format!("{} memory {}", ty, name)),
+        _ => Ok(format!("{} {}", ty, name)),
+    }
+}
+
+/// Generates a Solidity library verifying proofs of trees built with `options`.
+pub fn generate_verifier(options: &VerifierOptions) -> Result<String, MerkleTreeError> {
+    let pairs = if options.sorted_pairs {
+        "sorted"
+    } else {
+        "unsorted"
+    };
+    let encoding = match &options.leaf_encoding {
+        Some(encoding) => encoding.to_string(),
+        None => "raw bytes".to_string(),
+    };
+
+    // With domain separation, leaves and pairs are prefixed as in RFC 6962
+    let leaf_preimage = |packed: &str| {
+        if options.domain_separation {
+            format!("abi.encodePacked(bytes1(0x00), {})", packed)
+        } else {
+            packed.to_string()
+        }
+    };
+    let pair_preimage = if options.domain_separation {
+        "abi.encodePacked(bytes1(0x01), left, right)"
+    } else {
+        "abi.encodePacked(left, right)"
+    };
+
+    // Leaf hashing, from the encoded values or the raw leaf data
+    let (leaf_parameters, leaf_hash) = match &options.leaf_encoding {
+        Some(encoding) => {
+            let (function, types) = match encoding {
+                LeafEncoding::Packed(types) => ("abi.encodePacked", types),
+                LeafEncoding::Abi(types) => ("abi.encode", types),
+            };
+            let names: Vec<String> = (0..types.len()).map(|i| format!("value{}", i)).collect();
+            let parameters = types
+                .iter()
+                .zip(&names)
+                .map(|(ty, name)| parameter(ty, name))
+                .collect::<Result<Vec<_>, _>>()?;
+            let packed = format!("{}({})", function, names.join(", "));
+            (
+                parameters.join(", "),
+                hash_expression(options.hash_algorithm, &leaf_preimage(&packed))?,
+            )
+        }
+        None => (
+            "bytes memory data".to_string(),
+            hash_expression(options.hash_algorithm, &leaf_preimage("data"))?,
+        ),
+    };
+    let pair_hash = hash_expression(options.hash_algorithm, pair_preimage)?;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "// SPDX-License-Identifier: MIT");
+    let _ = writeln!(out, "pragma solidity ^0.8.20;");
+    let _ = writeln!(out);
+    let _ = writeln!(
+        out,
+        "/// @notice Verifies proofs of Merkle Trees built with {} hashing, {} pairs and",
+        options.hash_algorithm, pairs
+    );
+    let _ = writeln!(out, "/// leaves encoded as {}.", encoding);
+    if options.domain_separation {
+        let _ = writeln!(
+            out,
+            "/// @dev Leaves and pairs are prefixed with 0x00 and 0x01 before hashing, as in \
+             RFC 6962."
+        );
+    }
+    let _ = writeln!(
+        out,
+        "/// @dev Generated by merkle_tree; regenerate it when the tree's options change."
+    );
+    let _ = writeln!(out, "library {} {{", options.name);
+
+    let _ = writeln!(out, "    /// @notice Hashes a leaf as the tree does.");
+    let _ = writeln!(
+        out,
+        "    function leafHash({}) internal pure returns (bytes32) {{",
+        leaf_parameters
+    );
+    let _ = writeln!(out, "        return {};", leaf_hash);
+    let _ = writeln!(out, "    }}");
+    let _ = writeln!(out);
+
+    if options.sorted_pairs {
+        let _ = writeln!(
+            out,
+            "    /// @notice Returns whether `proof` leads from `leaf` to `root`."
+        );
+        let _ = writeln!(
+            out,
+            "    function verify(bytes32[] memory proof, bytes32 root, bytes32 leaf) \
+             internal pure returns (bool) {{"
+        );
+        let _ = writeln!(out, "        return processProof(proof, leaf) == root;");
+        let _ = writeln!(out, "    }}");
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "    function processProof(bytes32[] memory proof, bytes32 leaf) \
+             internal pure returns (bytes32 computed) {{"
+        );
+        let _ = writeln!(out, "        computed = leaf;");
+        let _ = writeln!(out, "        for (uint256 i = 0; i < proof.length; i++) {{");
+        let _ = writeln!(
+            out,
+            "            computed = computed < proof[i] \
+             ? hashPair(computed, proof[i]) : hashPair(proof[i], computed);"
+        );
+        let _ = writeln!(out, "        }}");
+        let _ = writeln!(out, "    }}");
+    } else {
+        let _ = writeln!(
+            out,
+            "    /// @notice Returns whether `proof` leads from `leaf` to `root`."
+        );
+        let _ = writeln!(
+            out,
+            "    /// @dev Bit `i` of `sides` is set when the `i`-th sibling is on the left."
+        );
+        let _ = writeln!(
+            out,
+            "    function verify(bytes32[] memory proof, uint256 sides, bytes32 root, \
+             bytes32 leaf) internal pure returns (bool) {{"
+        );
+        let _ = writeln!(
+            out,
+            "        return processProof(proof, sides, leaf) == root;"
+        );
+        let _ = writeln!(out, "    }}");
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "    function processProof(bytes32[] memory proof, uint256 sides, bytes32 leaf) \
+             internal pure returns (bytes32 computed) {{"
+        );
+        let _ = writeln!(out, "        computed = leaf;");
+        let _ = writeln!(out, "        for (uint256 i = 0; i < proof.length; i++) {{");
+        let _ = writeln!(
+            out,
+            "            computed = (sides >> i) & 1 == 1 \
+             ? hashPair(proof[i], computed) : hashPair(computed, proof[i]);"
+        );
+        let _ = writeln!(out, "        }}");
+        let _ = writeln!(out, "    }}");
+    }
+    let _ = writeln!(out);
+
+    let _ = writeln!(
+        out,
+        "    function hashPair(bytes32 left, bytes32 right) private pure returns (bytes32) {{"
+    );
+    let _ = writeln!(out, "        return {};", pair_hash);
+    let _ = writeln!(out, "    }}");
+    let _ = writeln!(out, "}}");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::OddNodePolicy;
+    use alloy_primitives::{keccak256, B256};
+
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| format!("leaf {i}").into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn generates_golden_verifiers() {
+        let cases = [
+            (
+                VerifierOptions::default(),
+                include_str!("../testdata/verifier_unsorted.sol"),
+            ),
+            (
+                VerifierOptions {
+                    sorted_pairs: true,
+                    ..VerifierOptions::default()
+                },
+                include_str!("../testdata/verifier_sorted.sol"),
+            ),
+            (
+                VerifierOptions {
+                    name: "Rfc6962Verifier".to_string(),
+                    hash_algorithm: HashAlgorithm::Sha256,
+                    domain_separation: true,
+                    ..VerifierOptions::default()
+                },
+                include_str!("../testdata/verifier_domain_separated_sha256.sol"),
+            ),
+            (
+                VerifierOptions {
+                    name: "AirdropVerifier".to_string(),
+                    sorted_pairs: true,
+                    leaf_encoding: Some("abi(address,uint256)".parse().unwrap()),
+                    ..VerifierOptions::default()
+                },
+                include_str!("../testdata/verifier_abi_leaves.sol"),
+            ),
+        ];
+        for (options, golden) in cases {
+            assert_eq!(generate_verifier(&options).unwrap(), golden, "{options:?}");
+        }
+
+        let blake3 = VerifierOptions {
+            hash_algorithm: HashAlgorithm::Blake3,
+            ..VerifierOptions::default()
+        };
+        assert!(matches!(
+            generate_verifier(&blake3),
+            Err(MerkleTreeError::NoEvmPrecompile { .. })
+        ));
+    }
+
+    /// Runs the unsorted verifier's `processProof` as the EVM would.
+    fn process_proof(proof: &MerkleProof, sides: U256) -> B256 {
+        proof
+            .proof_steps
+            .iter()
+            .enumerate()
+            .fold(proof.leaf_hash, |computed, (i, step)| {
+                let sibling = match step {
+                    ProofStep::Left(hash) | ProofStep::Right(hash) => hash,
+                };
+                if sides.bit(i) {
+                    keccak256([sibling.as_slice(), computed.as_slice()].concat())
+                } else {
+                    keccak256([computed.as_slice(), sibling.as_slice()].concat())
+                }
+            })
+    }
+
+    #[test]
+    fn duplicating_trees_verify_with_the_unsorted_verifier() {
+        for leaf_count in [5, 6, 7] {
+            let tree = MerkleTree::builder()
+                .odd_nodes(OddNodePolicy::Duplicate)
+                .build(&leaves(leaf_count))
+                .unwrap();
+            let options = VerifierOptions::for_tree(&tree);
+            assert_eq!(
+                generate_verifier(&options).unwrap(),
+                include_str!("../testdata/verifier_unsorted.sol")
+            );
+            for proof in tree.generate_all_proofs().unwrap() {
+                let sides = proof_sides(&proof).unwrap();
+                assert_eq!(process_proof(&proof, sides), *tree.root_hash());
+            }
+        }
+    }
+}
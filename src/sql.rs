@@ -0,0 +1,261 @@
+//! Leaves read from, and proofs written to, SQLite or Postgres tables.
+//!
+//! Services whose allowlists live in a database can build trees straight from a table and
+//! store every proof next to it, without export files in between. Table and column names
+//! are interpolated into queries, so they are restricted to plain identifiers.
+
+use crate::error::MerkleTreeError;
+use crate::merkle_tree::MerkleTree;
+
+use alloy_primitives::hex::encode_prefixed;
+
+/// A value read from a leaf column.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SqlValue {
+    /// A `BLOB` or `BYTEA` value, used as leaf data as is.
+    Binary(Vec<u8>),
+    /// Any other value, rendered as text.
+    Text(String),
+}
+
+impl SqlValue {
+    /// Returns the value as leaf data: binary values as is, text as its UTF-8 bytes.
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            SqlValue::Binary(bytes) => bytes,
+            SqlValue::Text(text) => text.into_bytes(),
+        }
+    }
+}
+
+/// Where to read leaves from.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LeafQuery {
+    pub table: String,
+    pub column: String,
+    /// Column ordering the leaves. Without one, the order is whatever the database returns,
+    /// which may change between runs.
+    pub order_by: Option<String>,
+}
+
+impl LeafQuery {
+    /// Reads `column` of every row of `table`.
+    pub fn new(table: impl Into<String>, column: impl Into<String>) -> Self {
+        LeafQuery {
+            table: table.into(),
+            column: column.into(),
+            order_by: None,
+        }
+    }
+
+    /// Orders the leaves by `column`.
+    pub fn order_by(mut self, column: impl Into<String>) -> Self {
+        self.order_by = Some(column.into());
+        self
+    }
+
+    fn sql(&self) -> Result<String, MerkleTreeError> {
+        let mut sql = format!(
+            "SELECT {} FROM {}",
+            identifier(&self.column)?,
+            identifier(&self.table)?
+        );
+        if let Some(order_by) = &self.order_by {
+            sql.push_str(&format!(" ORDER BY {}", identifier(order_by)?));
+        }
+        Ok(sql)
+    }
+}
+
+/// A connection to a SQLite or Postgres database.
+pub enum Database {
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Connection),
+    #[cfg(feature = "postgres")]
+    Postgres(postgres::Client),
+}
+
+impl Database {
+    /// Connects to `url`: a `postgres://` or `postgresql://` URL, or the path of a SQLite
+    /// database, optionally prefixed with `sqlite:`.
+    pub fn connect(url: &str) -> Result<Self, MerkleTreeError> {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            #[cfg(feature = "postgres")]
+            return postgres::Client::connect(url, postgres::NoTls)
+                .map(Database::Postgres)
+                .map_err(|e| MerkleTreeError::StorageError(e.to_string()));
+            #[cfg(not(feature = "postgres"))]
+            return Err(MerkleTreeError::StorageError(
+                "Postgres support requires the `postgres` feature".to_string(),
+            ));
+        }
+
+        #[cfg(feature = "sqlite")]
+        {
+            let path = url
+                .trim_start_matches("sqlite://")
+                .trim_start_matches("sqlite:");
+            rusqlite::Connection::open(path)
+                .map(Database::Sqlite)
+                .map_err(|e| MerkleTreeError::StorageError(e.to_string()))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        Err(MerkleTreeError::StorageError(
+            "SQLite support requires the `sqlite` feature".to_string(),
+        ))
+    }
+
+    /// Reads the leaf column of every row selected by `query`.
+    pub fn read_leaves(&mut self, query: &LeafQuery) -> Result<Vec<SqlValue>, MerkleTreeError> {
+        let sql = query.sql()?;
+        match self {
+            #[cfg(feature = "sqlite")]
+            Database::Sqlite(connection) => {
+                use rusqlite::types::ValueRef;
+
+                let mut statement = connection.prepare(&sql).map_err(sqlite_error)?;
+                let rows = statement
+                    .query_map([], |row| {
+                        Ok(match row.get_ref(0)? {
+                            ValueRef::Blob(bytes) => SqlValue::Binary(bytes.to_vec()),
+                            ValueRef::Text(text) => {
+                                SqlValue::Text(String::from_utf8_lossy(text).into_owned())
+                            }
+                            ValueRef::Integer(value) => SqlValue::Text(value.to_string()),
+                            ValueRef::Real(value) => SqlValue::Text(value.to_string()),
+                            ValueRef::Null => SqlValue::Text(String::new()),
+                        })
+                    })
+                    .map_err(sqlite_error)?;
+                rows.collect::<Result<_, _>>().map_err(sqlite_error)
+            }
+            #[cfg(feature = "postgres")]
+            Database::Postgres(client) => client
+                .query(sql.as_str(), &[])
+                .map_err(postgres_error)?
+                .iter()
+                .map(|row| postgres_value(row).map_err(postgres_error))
+                .collect(),
+        }
+    }
+
+    /// Writes a proof of every leaf of `tree` to `table`, creating it if needed, and
+    /// returns the number of proofs written.
+    ///
+    /// Rows are keyed by root and leaf index, so several trees can share a table and
+    /// writing a tree again replaces its rows. All rows are written in one transaction.
+    pub fn write_proofs(
+        &mut self,
+        table: &str,
+        tree: &MerkleTree,
+    ) -> Result<usize, MerkleTreeError> {
+        let table = identifier(table)?;
+        let create = format!(
+            "CREATE TABLE IF NOT EXISTS {} (root TEXT NOT NULL, leaf_index BIGINT NOT NULL, \
+             leaf_hash TEXT NOT NULL, proof TEXT NOT NULL, PRIMARY KEY (root, leaf_index))",
+            table
+        );
+        let root = encode_prefixed(tree.root_hash());
+        let proofs = tree.generate_all_proofs()?;
+
+        match self {
+            #[cfg(feature = "sqlite")]
+            Database::Sqlite(connection) => {
+                let transaction = connection.transaction().map_err(sqlite_error)?;
+                transaction.execute(&create, []).map_err(sqlite_error)?;
+                {
+                    let mut statement = transaction
+                        .prepare(&upsert(table, "?1", "?2", "?3", "?4"))
+                        .map_err(sqlite_error)?;
+                    for (index, proof) in proofs.iter().enumerate() {
+                        statement
+                            .execute(rusqlite::params![
+                                root,
+                                index as i64,
+                                encode_prefixed(proof.leaf_hash),
+                                proof.to_json()?
+                            ])
+                            .map_err(sqlite_error)?;
+                    }
+                }
+                transaction.commit().map_err(sqlite_error)?;
+            }
+            #[cfg(feature = "postgres")]
+            Database::Postgres(client) => {
+                let mut transaction = client.transaction().map_err(postgres_error)?;
+                transaction.batch_execute(&create).map_err(postgres_error)?;
+                let statement = transaction
+                    .prepare(&upsert(table, "$1", "$2", "$3", "$4"))
+                    .map_err(postgres_error)?;
+                for (index, proof) in proofs.iter().enumerate() {
+                    transaction
+                        .execute(
+                            &statement,
+                            &[
+                                &root,
+                                &(index as i64),
+                                &encode_prefixed(proof.leaf_hash),
+                                &proof.to_json()?,
+                            ],
+                        )
+                        .map_err(postgres_error)?;
+                }
+                transaction.commit().map_err(postgres_error)?;
+            }
+        }
+        Ok(proofs.len())
+    }
+}
+
+/// Returns the statement inserting or replacing a proof row, with the given placeholders.
+fn upsert(table: &str, root: &str, index: &str, leaf_hash: &str, proof: &str) -> String {
+    format!(
+        "INSERT INTO {} (root, leaf_index, leaf_hash, proof) VALUES ({}, {}, {}, {}) \
+         ON CONFLICT (root, leaf_index) DO UPDATE SET leaf_hash = excluded.leaf_hash, \
+         proof = excluded.proof",
+        table, root, index, leaf_hash, proof
+    )
+}
+
+/// Checks that `name` is a plain, optionally schema-qualified, identifier.
+fn identifier(name: &str) -> Result<&str, MerkleTreeError> {
+    let valid = !name.is_empty()
+        && name.split('.').all(|part| {
+            part.chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+    if valid {
+        Ok(name)
+    } else {
+        Err(MerkleTreeError::StorageError(format!(
+            "'{}' is not a valid table or column name",
+            name
+        )))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_error(e: rusqlite::Error) -> MerkleTreeError {
+    MerkleTreeError::StorageError(e.to_string())
+}
+
+/// Reads the first column of a Postgres row.
+#[cfg(feature = "postgres")]
+fn postgres_value(row: &postgres::Row) -> Result<SqlValue, postgres::Error> {
+    use postgres::types::Type;
+
+    Ok(match *row.columns()[0].type_() {
+        Type::BYTEA => SqlValue::Binary(row.try_get(0)?),
+        Type::INT2 => SqlValue::Text(row.try_get::<_, i16>(0)?.to_string()),
+        Type::INT4 => SqlValue::Text(row.try_get::<_, i32>(0)?.to_string()),
+        Type::INT8 => SqlValue::Text(row.try_get::<_, i64>(0)?.to_string()),
+        _ => SqlValue::Text(row.try_get(0)?),
+    })
+}
+
+#[cfg(feature = "postgres")]
+fn postgres_error(e: postgres::Error) -> MerkleTreeError {
+    MerkleTreeError::StorageError(e.to_string())
+}
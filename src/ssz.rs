@@ -0,0 +1,171 @@
+//! SSZ encoding of proofs and SSZ-style merkleization (`hash_tree_root`) helpers.
+//!
+//! Merkleization follows the consensus specs: chunks are hashed pairwise with SHA-256, the
+//! chunk count is padded to a power of two with zero hashes, and lists mix in their length.
+
+use crate::error::MerkleTreeError;
+use crate::hasher::HashAlgorithm;
+use crate::proof::{MerkleProof, ProofStep};
+
+use alloy_primitives::B256;
+use sha2::{Digest, Sha256};
+
+/// Size of the fixed part of an encoded proof: the leaf hash and the steps offset.
+const PROOF_FIXED_SIZE: usize = 32 + 4;
+
+/// Size of an encoded step: one side byte and the sibling hash.
+const STEP_SIZE: usize = 1 + 32;
+
+fn sha256_pair(left: &B256, right: &B256) -> B256 {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    B256::from_slice(&hasher.finalize())
+}
+
+/// Packs bytes into 32-byte chunks, right-padding the last chunk with zeros.
+pub fn pack_bytes(bytes: &[u8]) -> Vec<B256> {
+    bytes
+        .chunks(32)
+        .map(|chunk| {
+            let mut padded = [0u8; 32];
+            padded[..chunk.len()].copy_from_slice(chunk);
+            B256::from(padded)
+        })
+        .collect()
+}
+
+/// Merkleizes chunks, padding the chunk count to the next power of two of `limit`
+/// (or of the number of chunks when no limit is given).
+pub fn merkleize(chunks: &[B256], limit: Option<usize>) -> Result<B256, MerkleTreeError> {
+    let limit = limit.unwrap_or(chunks.len());
+    if chunks.len() > limit {
+        return Err(MerkleTreeError::SszError(format!(
+            "{} chunks exceed the limit of {}",
+            chunks.len(),
+            limit
+        )));
+    }
+
+    let depth = limit.next_power_of_two().trailing_zeros();
+    let mut zero_hash = B256::ZERO;
+    let mut layer = chunks.to_vec();
+
+    for _ in 0..depth {
+        if layer.len() % 2 == 1 {
+            layer.push(zero_hash);
+        }
+        layer = layer
+            .chunks(2)
+            .map(|pair| sha256_pair(&pair[0], &pair[1]))
+            .collect();
+        zero_hash = sha256_pair(&zero_hash, &zero_hash);
+    }
+
+    Ok(layer.first().copied().unwrap_or(zero_hash))
+}
+
+/// Mixes a list length into its merkleized root.
+pub fn mix_in_length(root: &B256, length: usize) -> B256 {
+    let mut length_chunk = [0u8; 32];
+    length_chunk[..8].copy_from_slice(&(length as u64).to_le_bytes());
+    sha256_pair(root, &B256::from(length_chunk))
+}
+
+/// Computes the `hash_tree_root` of a container from the roots of its fields.
+pub fn ssz_hash_tree_root_container(field_roots: &[B256]) -> Result<B256, MerkleTreeError> {
+    merkleize(field_roots, None)
+}
+
+/// Computes the `hash_tree_root` of a `List[T, limit]` of composite elements from the
+/// roots of its elements.
+pub fn ssz_hash_tree_root_list(
+    element_roots: &[B256],
+    limit: usize,
+) -> Result<B256, MerkleTreeError> {
+    let root = merkleize(element_roots, Some(limit))?;
+    Ok(mix_in_length(&root, element_roots.len()))
+}
+
+/// Computes the `hash_tree_root` of a `ByteList[limit]`.
+pub fn ssz_hash_tree_root_bytes(bytes: &[u8], limit: usize) -> Result<B256, MerkleTreeError> {
+    let chunk_limit = limit.div_ceil(32);
+    let root = merkleize(&pack_bytes(bytes), Some(chunk_limit))?;
+    Ok(mix_in_length(&root, bytes.len()))
+}
+
+impl MerkleProof {
+    /// Encodes the proof as the SSZ container
+    /// `{ leaf_hash: Bytes32, proof_steps: List[{ side: uint8, sibling: Bytes32 }] }`,
+    /// where `side` is `0` for left and `1` for right.
+    pub fn to_ssz(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(PROOF_FIXED_SIZE + STEP_SIZE * self.proof_steps.len());
+        out.extend_from_slice(self.leaf_hash.as_slice());
+        out.extend_from_slice(&(PROOF_FIXED_SIZE as u32).to_le_bytes());
+
+        for step in &self.proof_steps {
+            let (side, sibling_hash) = match step {
+                ProofStep::Left(sibling_hash) => (0u8, sibling_hash),
+                ProofStep::Right(sibling_hash) => (1u8, sibling_hash),
+            };
+            out.push(side);
+            out.extend_from_slice(sibling_hash.as_slice());
+        }
+
+        out
+    }
+
+    /// Decodes a proof produced by [`MerkleProof::to_ssz`].
+    pub fn from_ssz(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        if bytes.len() < PROOF_FIXED_SIZE {
+            return Err(MerkleTreeError::SszError(format!(
+                "expected at least {} bytes, got {}",
+                PROOF_FIXED_SIZE,
+                bytes.len()
+            )));
+        }
+
+        let leaf_hash = B256::from_slice(&bytes[..32]);
+        let mut offset = [0u8; 4];
+        offset.copy_from_slice(&bytes[32..PROOF_FIXED_SIZE]);
+        let offset = u32::from_le_bytes(offset) as usize;
+        if offset != PROOF_FIXED_SIZE {
+            return Err(MerkleTreeError::SszError(format!(
+                "invalid proof steps offset {}",
+                offset
+            )));
+        }
+
+        let steps = &bytes[PROOF_FIXED_SIZE..];
+        if !steps.len().is_multiple_of(STEP_SIZE) {
+            return Err(MerkleTreeError::SszError(format!(
+                "proof steps length {} is not a multiple of {}",
+                steps.len(),
+                STEP_SIZE
+            )));
+        }
+
+        let proof_steps = steps
+            .chunks(STEP_SIZE)
+            .map(|step| {
+                let sibling_hash = B256::from_slice(&step[1..]);
+                match step[0] {
+                    0 => Ok(ProofStep::Left(sibling_hash)),
+                    1 => Ok(ProofStep::Right(sibling_hash)),
+                    side => Err(MerkleTreeError::SszError(format!(
+                        "invalid proof step side {}",
+                        side
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(MerkleProof {
+            leaf_hash,
+            proof_steps,
+            hash_algorithm: HashAlgorithm::Keccak256,
+            sorted_pairs: false,
+            domain_separation: false,
+        })
+    }
+}
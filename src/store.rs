@@ -0,0 +1,725 @@
+#[cfg(feature = "sled")]
+use crate::builder::MerkleTreeBuilder;
+use crate::error::MerkleTreeError;
+#[cfg(feature = "sled")]
+use crate::file_format::{FLAG_DOMAIN_SEPARATION, FLAG_DUPLICATE_ODD, FLAG_SORTED_PAIRS};
+use crate::hasher::{HashAlgorithm, OddNodePolicy};
+use crate::merkle_node::{DeserializeLimits, MerkleNode};
+use crate::merkle_tree::{duplicated_child, index_proof_steps, shape_mismatch, MerkleTree};
+use crate::proof::MerkleProof;
+
+use alloy_primitives::hex::encode;
+use alloy_primitives::B256;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// A node as persisted in a [`NodeStore`]: its hash plus the hashes of its children.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct StoredNode {
+    #[serde(
+        serialize_with = "crate::serialization::b256_hex::serialize",
+        deserialize_with = "crate::serialization::b256_hex::deserialize"
+    )]
+    pub hash: B256,
+
+    /// Left child hash. `None` if this is a leaf node.
+    #[serde(
+        default,
+        serialize_with = "crate::serialization::option_b256_hex::serialize",
+        deserialize_with = "crate::serialization::option_b256_hex::deserialize"
+    )]
+    pub left: Option<B256>,
+
+    /// Right child hash. `None` if this is a leaf node.
+    #[serde(
+        default,
+        serialize_with = "crate::serialization::option_b256_hex::serialize",
+        deserialize_with = "crate::serialization::option_b256_hex::deserialize"
+    )]
+    pub right: Option<B256>,
+}
+
+impl StoredNode {
+    /// Creates the stored form of a node, referencing its children by hash.
+    pub fn from_node(node: &MerkleNode) -> Self {
+        StoredNode {
+            hash: node.hash,
+            left: node.left.as_ref().map(|left| left.hash),
+            right: node.right.as_ref().map(|right| right.hash),
+        }
+    }
+}
+
+/// The root of a persisted tree, with what the store does not record: the number of
+/// leaves and how nodes were hashed.
+///
+/// Returned by [`MerkleTree::persist`] and needed by [`LazyMerkleTree::open`].
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StoredRoot {
+    #[serde(
+        serialize_with = "crate::serialization::b256_hex::serialize",
+        deserialize_with = "crate::serialization::b256_hex::deserialize"
+    )]
+    pub root_hash: B256,
+
+    pub leaf_count: usize,
+
+    /// Hash function of the tree.
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+
+    /// Whether the tree sorted each pair before hashing.
+    #[serde(default)]
+    pub sorted_pairs: bool,
+
+    /// Whether the tree prefixed leaves and nodes before hashing.
+    #[serde(default)]
+    pub domain_separation: bool,
+
+    /// What happened to the last node of each odd level.
+    #[serde(default)]
+    pub odd_nodes: OddNodePolicy,
+}
+
+impl fmt::Display for StoredRoot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "root {} of {} leaves ({}, {} odd nodes",
+            encode(self.root_hash),
+            self.leaf_count,
+            self.hash_algorithm,
+            self.odd_nodes
+        )?;
+        if self.sorted_pairs {
+            write!(f, ", sorted pairs")?;
+        }
+        if self.domain_separation {
+            write!(f, ", domain separation")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Content-addressed storage for tree nodes, keyed by node hash.
+pub trait NodeStore {
+    /// Fetches the node with the given hash, or `None` if it is not stored.
+    fn get(&self, hash: &B256) -> Result<Option<StoredNode>, MerkleTreeError>;
+
+    /// Stores a node under its hash.
+    fn put(&mut self, node: StoredNode) -> Result<(), MerkleTreeError>;
+
+    /// Stores several nodes. Stores that can write them atomically do, so a crash never
+    /// leaves a tree half persisted.
+    fn put_all(&mut self, nodes: Vec<StoredNode>) -> Result<(), MerkleTreeError> {
+        for node in nodes {
+            self.put(node)?;
+        }
+        Ok(())
+    }
+
+    /// Fetches the node with the given hash, failing if it is not stored.
+    fn fetch(&self, hash: &B256) -> Result<StoredNode, MerkleTreeError> {
+        self.get(hash)?.ok_or_else(|| {
+            MerkleTreeError::StorageError(format!("Node {} not found in store", encode(hash)))
+        })
+    }
+}
+
+/// A [`NodeStore`] that keeps nodes in memory.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryNodeStore {
+    nodes: HashMap<B256, StoredNode>,
+}
+
+impl MemoryNodeStore {
+    /// Creates an empty in-memory store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of stored nodes.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if no nodes are stored.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl NodeStore for MemoryNodeStore {
+    fn get(&self, hash: &B256) -> Result<Option<StoredNode>, MerkleTreeError> {
+        Ok(self.nodes.get(hash).cloned())
+    }
+
+    fn put(&mut self, node: StoredNode) -> Result<(), MerkleTreeError> {
+        self.nodes.insert(node.hash, node);
+        Ok(())
+    }
+}
+
+/// A [`NodeStore`] that keeps one JSON file per node in a directory.
+#[derive(Clone, Debug)]
+pub struct FileNodeStore {
+    dir: PathBuf,
+}
+
+impl FileNodeStore {
+    /// Opens a store in `dir`, creating the directory if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, MerkleTreeError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FileNodeStore { dir })
+    }
+
+    fn node_path(&self, hash: &B256) -> PathBuf {
+        self.dir.join(format!("{}.json", encode(hash)))
+    }
+}
+
+impl NodeStore for FileNodeStore {
+    fn get(&self, hash: &B256) -> Result<Option<StoredNode>, MerkleTreeError> {
+        match fs::read_to_string(self.node_path(hash)) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn put(&mut self, node: StoredNode) -> Result<(), MerkleTreeError> {
+        let json = serde_json::to_string(&node)?;
+        fs::write(self.node_path(&node.hash), json)?;
+        Ok(())
+    }
+}
+
+/// A [`NodeStore`] in an embedded [sled](https://docs.rs/sled) database.
+///
+/// Nodes are stored as their 32-byte hash mapped to the 64 bytes of their children's
+/// hashes, or nothing for leaves. Writes are crash-safe: [`NodeStore::put_all`] is atomic,
+/// and [`SledNodeStore::flush`] makes them durable. The store also remembers the roots
+/// recorded with [`SledNodeStore::record_root`], with how their trees were hashed, so a
+/// tree can be reopened with [`LazyMerkleTree::reopen`] after a restart.
+#[cfg(feature = "sled")]
+#[derive(Clone, Debug)]
+pub struct SledNodeStore {
+    db: sled::Db,
+    nodes: sled::Tree,
+    roots: sled::Tree,
+}
+
+#[cfg(feature = "sled")]
+impl SledNodeStore {
+    /// Opens the database at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, MerkleTreeError> {
+        Self::from_db(&sled::open(path).map_err(sled_error)?)
+    }
+
+    /// Uses the `nodes` and `roots` trees of an already open database.
+    pub fn from_db(db: &sled::Db) -> Result<Self, MerkleTreeError> {
+        Ok(SledNodeStore {
+            db: db.clone(),
+            nodes: db.open_tree("nodes").map_err(sled_error)?,
+            roots: db.open_tree("roots").map_err(sled_error)?,
+        })
+    }
+
+    /// Records `root` as the latest root.
+    ///
+    /// The record holds the root hash, the leaf count as a big-endian `u64`, then the hasher
+    /// id and flags of the [compact file format](crate::file_format).
+    pub fn record_root(&self, root: &StoredRoot) -> Result<(), MerkleTreeError> {
+        // Big-endian sequence numbers keep the roots in insertion order
+        let id = self.db.generate_id().map_err(sled_error)?;
+        let mut flags = 0;
+        if root.sorted_pairs {
+            flags |= FLAG_SORTED_PAIRS;
+        }
+        if root.odd_nodes == OddNodePolicy::Duplicate {
+            flags |= FLAG_DUPLICATE_ODD;
+        }
+        if root.domain_separation {
+            flags |= FLAG_DOMAIN_SEPARATION;
+        }
+        let mut value = root.root_hash.to_vec();
+        value.extend_from_slice(&(root.leaf_count as u64).to_be_bytes());
+        value.extend_from_slice(&[root.hash_algorithm.id(), flags]);
+        self.roots
+            .insert(id.to_be_bytes(), value)
+            .map_err(sled_error)?;
+        Ok(())
+    }
+
+    /// Returns the most recently recorded root.
+    pub fn latest_root(&self) -> Result<Option<StoredRoot>, MerkleTreeError> {
+        let Some((_, value)) = self.roots.last().map_err(sled_error)? else {
+            return Ok(None);
+        };
+        let corrupt = || MerkleTreeError::StorageError("Corrupt root record".to_string());
+        if value.len() != 42 {
+            return Err(corrupt());
+        }
+        let leaf_count = u64::from_be_bytes(value[32..40].try_into().expect("8 bytes"));
+        let hash_algorithm = HashAlgorithm::from_id(value[40]).ok_or_else(corrupt)?;
+        let flags = value[41];
+        if flags & !(FLAG_SORTED_PAIRS | FLAG_DUPLICATE_ODD | FLAG_DOMAIN_SEPARATION) != 0 {
+            return Err(corrupt());
+        }
+        Ok(Some(StoredRoot {
+            root_hash: B256::from_slice(&value[..32]),
+            leaf_count: usize::try_from(leaf_count).map_err(|_| corrupt())?,
+            hash_algorithm,
+            sorted_pairs: flags & FLAG_SORTED_PAIRS != 0,
+            domain_separation: flags & FLAG_DOMAIN_SEPARATION != 0,
+            odd_nodes: if flags & FLAG_DUPLICATE_ODD != 0 {
+                OddNodePolicy::Duplicate
+            } else {
+                OddNodePolicy::Promote
+            },
+        }))
+    }
+
+    /// Waits until every write so far is on disk.
+    pub fn flush(&self) -> Result<(), MerkleTreeError> {
+        self.nodes.flush().map_err(sled_error)?;
+        self.roots.flush().map_err(sled_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sled")]
+impl NodeStore for SledNodeStore {
+    fn get(&self, hash: &B256) -> Result<Option<StoredNode>, MerkleTreeError> {
+        let Some(children) = self.nodes.get(hash).map_err(sled_error)? else {
+            return Ok(None);
+        };
+        match children.len() {
+            0 => Ok(Some(StoredNode {
+                hash: *hash,
+                left: None,
+                right: None,
+            })),
+            64 => Ok(Some(StoredNode {
+                hash: *hash,
+                left: Some(B256::from_slice(&children[..32])),
+                right: Some(B256::from_slice(&children[32..])),
+            })),
+            _ => Err(MerkleTreeError::StorageError(format!(
+                "Node {} is corrupt",
+                encode(hash)
+            ))),
+        }
+    }
+
+    fn put(&mut self, node: StoredNode) -> Result<(), MerkleTreeError> {
+        self.nodes
+            .insert(node.hash, sled_children(&node))
+            .map_err(sled_error)?;
+        Ok(())
+    }
+
+    fn put_all(&mut self, nodes: Vec<StoredNode>) -> Result<(), MerkleTreeError> {
+        let mut batch = sled::Batch::default();
+        for node in &nodes {
+            batch.insert(node.hash.as_slice(), sled_children(node));
+        }
+        self.nodes.apply_batch(batch).map_err(sled_error)
+    }
+}
+
+#[cfg(feature = "sled")]
+impl LazyMerkleTree<SledNodeStore> {
+    /// Reopens the tree at the latest root recorded in `store`, or returns `None` if no root
+    /// was recorded.
+    ///
+    /// Fails if the tree was hashed differently from the trees `builder` builds, since its
+    /// proofs would not verify where the builder's settings are expected.
+    pub fn reopen(
+        store: SledNodeStore,
+        builder: &MerkleTreeBuilder,
+    ) -> Result<Option<Self>, MerkleTreeError> {
+        let Some(root) = store.latest_root()? else {
+            return Ok(None);
+        };
+        let hasher = builder.tree_hasher();
+        let expected = StoredRoot {
+            hash_algorithm: hasher.hash_algorithm,
+            sorted_pairs: hasher.sorted_pairs,
+            domain_separation: hasher.domain_separation,
+            odd_nodes: hasher.odd_nodes,
+            ..root
+        };
+        if root != expected {
+            return Err(MerkleTreeError::StoredRootMismatch {
+                expected,
+                got: root,
+            });
+        }
+        Self::open(store, root).map(Some)
+    }
+}
+
+/// Returns the stored value of a node: its children's hashes, if any.
+#[cfg(feature = "sled")]
+fn sled_children(node: &StoredNode) -> Vec<u8> {
+    match (node.left, node.right) {
+        (Some(left), Some(right)) => [left.as_slice(), right.as_slice()].concat(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(feature = "sled")]
+fn sled_error(e: sled::Error) -> MerkleTreeError {
+    MerkleTreeError::StorageError(e.to_string())
+}
+
+impl MerkleTree {
+    /// Returns the root of the tree as [`MerkleTree::persist`] records it.
+    pub fn stored_root(&self) -> StoredRoot {
+        StoredRoot {
+            root_hash: *self.root_hash(),
+            leaf_count: self.leaf_count(),
+            hash_algorithm: self.hash_algorithm,
+            sorted_pairs: self.sorted_pairs,
+            domain_separation: self.domain_separation,
+            odd_nodes: self.odd_nodes,
+        }
+    }
+
+    /// Writes every node of the tree to `store`, returning the root to reopen it with.
+    ///
+    /// Nodes are keyed by hash, so repeated subtrees are written once. The copy a
+    /// duplicating tree pairs an odd node with has no children of its own; it is not
+    /// written, so that it does not replace its original, and is restored by
+    /// [`LazyMerkleTree::materialize`].
+    pub fn persist<S: NodeStore>(&self, store: &mut S) -> Result<StoredRoot, MerkleTreeError> {
+        let mut nodes = Vec::new();
+        let mut seen = HashSet::new();
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            if !seen.insert(node.hash) {
+                continue;
+            }
+            nodes.push(StoredNode::from_node(node));
+            if let Some(child) = duplicated_child(node) {
+                stack.push(child);
+                continue;
+            }
+            if let Some(left) = &node.left {
+                stack.push(left);
+            }
+            if let Some(right) = &node.right {
+                stack.push(right);
+            }
+        }
+        store.put_all(nodes)?;
+        Ok(self.stored_root())
+    }
+}
+
+/// A tree backed by a [`NodeStore`] whose nodes are loaded only when needed.
+///
+/// Proof generation reads just the nodes along the path to the leaf, so serving proofs
+/// from a huge persisted tree does not require deserializing the whole tree.
+pub struct LazyMerkleTree<S: NodeStore> {
+    store: S,
+    root: StoredRoot,
+}
+
+impl<S: NodeStore> LazyMerkleTree<S> {
+    /// Opens the tree with the given root in `store`.
+    pub fn open(store: S, root: StoredRoot) -> Result<Self, MerkleTreeError> {
+        store.fetch(&root.root_hash)?;
+        Ok(LazyMerkleTree { store, root })
+    }
+
+    /// Returns the root hash of the tree.
+    pub fn root_hash(&self) -> &B256 {
+        &self.root.root_hash
+    }
+
+    /// Returns the number of leaves in the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.root.leaf_count
+    }
+
+    /// Returns the root the tree was opened with.
+    pub fn stored_root(&self) -> &StoredRoot {
+        &self.root
+    }
+
+    /// Returns the underlying store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Generates a Merkle Proof for the leaf at `index`, loading only the nodes on its path.
+    pub fn generate_proof_by_index(&self, index: usize) -> Result<MerkleProof, MerkleTreeError> {
+        let root = self.store.fetch(self.root_hash())?;
+
+        let (leaf, proof_steps) = index_proof_steps(
+            root,
+            self.leaf_count(),
+            index,
+            self.root.odd_nodes,
+            |node| match (node.left, node.right) {
+                (Some(left), Some(right)) => {
+                    Ok(Some((self.store.fetch(&left)?, self.store.fetch(&right)?)))
+                }
+                _ => Ok(None),
+            },
+            |node| node.hash,
+        )?;
+
+        Ok(MerkleProof {
+            leaf_hash: leaf.hash,
+            proof_steps,
+            hash_algorithm: self.root.hash_algorithm,
+            sorted_pairs: self.root.sorted_pairs,
+            domain_separation: self.root.domain_separation,
+        })
+    }
+
+    /// Loads the full subtree rooted at `hash` from the store.
+    ///
+    /// Nodes are loaded by hash alone, so in a duplicating tree the copies of odd nodes come
+    /// back as full subtrees; [`LazyMerkleTree::materialize`] restores the tree as built.
+    pub fn load_subtree(&self, hash: &B256) -> Result<MerkleNode, MerkleTreeError> {
+        let stored = self.store.fetch(hash)?;
+        match (stored.left, stored.right) {
+            (Some(left), Some(right)) => Ok(MerkleNode {
+                hash: stored.hash,
+                left: Some(Box::new(self.load_subtree(&left)?)),
+                right: Some(Box::new(self.load_subtree(&right)?)),
+            }),
+            (None, None) => Ok(MerkleNode {
+                hash: stored.hash,
+                left: None,
+                right: None,
+            }),
+            _ => Err(MerkleTreeError::StorageError(format!(
+                "Node {} has only one child",
+                encode(hash)
+            ))),
+        }
+    }
+
+    /// Loads the node with `hash`, which sits at `index` on `level`, and everything below
+    /// it, as [`MerkleTree::persist`] was given them.
+    ///
+    /// Node `j` at level `k` covers leaves `[j * 2^k, (j + 1) * 2^k)`. A node whose range
+    /// fits in its left half was promoted and also sits on the level below, or, under
+    /// [`OddNodePolicy::Duplicate`], joins its left child with a copy without children.
+    fn load_node(
+        &self,
+        hash: &B256,
+        level: u32,
+        index: usize,
+    ) -> Result<MerkleNode, MerkleTreeError> {
+        let stored = self.store.fetch(hash)?;
+        if level == 0 {
+            return match (stored.left, stored.right) {
+                (None, None) => Ok(MerkleNode {
+                    hash: stored.hash,
+                    left: None,
+                    right: None,
+                }),
+                _ => Err(shape_mismatch()),
+            };
+        }
+
+        let mid = (index << level) + (1 << (level - 1));
+        let (left, right) = match (stored.left, stored.right) {
+            _ if self.leaf_count() <= mid && self.root.odd_nodes == OddNodePolicy::Promote => {
+                return self.load_node(hash, level - 1, 2 * index);
+            }
+            (Some(left), Some(right)) if self.leaf_count() <= mid && left == right => {
+                let copy = MerkleNode {
+                    hash: right,
+                    left: None,
+                    right: None,
+                };
+                (self.load_node(&left, level - 1, 2 * index)?, copy)
+            }
+            (Some(left), Some(right)) if self.leaf_count() > mid => (
+                self.load_node(&left, level - 1, 2 * index)?,
+                self.load_node(&right, level - 1, 2 * index + 1)?,
+            ),
+            _ => return Err(shape_mismatch()),
+        };
+        Ok(MerkleNode {
+            hash: stored.hash,
+            left: Some(Box::new(left)),
+            right: Some(Box::new(right)),
+        })
+    }
+
+    /// Loads the whole tree into memory.
+    ///
+    /// Raw leaf data is not stored, so the returned tree has an empty `leaves` map.
+    pub fn materialize(&self) -> Result<MerkleTree, MerkleTreeError> {
+        let level = self.leaf_count().next_power_of_two().trailing_zeros();
+        let mut tree = MerkleTree {
+            root: self.load_node(self.root_hash(), level, 0)?,
+            leaves: HashMap::new(),
+            // Copies of odd nodes look like leaves, so leaves are read by position
+            leaf_hashes: vec![B256::ZERO; self.leaf_count()],
+            hash_algorithm: self.root.hash_algorithm,
+            sorted_pairs: self.root.sorted_pairs,
+            domain_separation: self.root.domain_separation,
+            odd_nodes: self.root.odd_nodes,
+            limits: DeserializeLimits::default(),
+            allow_64_byte_leaves: false,
+            strict: false,
+        };
+        tree.leaf_hashes = tree.levels()?.swap_remove(0);
+        tree.verify_structure()?;
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::MerkleTreeBuilder;
+
+    fn leaves(count: usize) -> Vec<Vec<u8>> {
+        (0..count)
+            .map(|i| format!("leaf {i}").into_bytes())
+            .collect()
+    }
+
+    /// Every combination of hash algorithm, odd node policy, pair sorting and domain
+    /// separation.
+    fn builders() -> Vec<MerkleTreeBuilder> {
+        let mut builders = Vec::new();
+        for algorithm in [
+            HashAlgorithm::Keccak256,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Blake3,
+        ] {
+            for policy in [OddNodePolicy::Promote, OddNodePolicy::Duplicate] {
+                for sorted in [false, true] {
+                    for separated in [false, true] {
+                        builders.push(
+                            MerkleTreeBuilder::new()
+                                .hash_algorithm(algorithm)
+                                .odd_nodes(policy)
+                                .sorted_pairs(sorted)
+                                .domain_separation(separated),
+                        );
+                    }
+                }
+            }
+        }
+        builders
+    }
+
+    #[test]
+    fn lazy_proofs_match_eager_proofs() {
+        for builder in builders() {
+            for leaf_count in 1..=9 {
+                let mut tree = builder.build(&leaves(leaf_count)).unwrap();
+                let mut store = MemoryNodeStore::new();
+                let root = tree.persist(&mut store).unwrap();
+                let lazy = LazyMerkleTree::open(store, root).unwrap();
+                for index in 0..leaf_count {
+                    let proof = lazy.generate_proof_by_index(index).unwrap();
+                    assert_eq!(proof, tree.generate_proof_by_index(index).unwrap());
+                    assert!(proof.verify(tree.root_hash()).unwrap());
+                }
+                tree.clear_leaf_data();
+                assert_eq!(lazy.materialize().unwrap(), tree);
+            }
+        }
+    }
+
+    #[test]
+    fn copies_of_odd_nodes_share_their_entry() {
+        let tree = MerkleTreeBuilder::new()
+            .odd_nodes(OddNodePolicy::Duplicate)
+            .build(&leaves(5))
+            .unwrap();
+        let mut store = MemoryNodeStore::new();
+        tree.persist(&mut store).unwrap();
+        // 5 leaves and 3 + 2 + 1 parents; the copies of leaf 4 and of its parent are not stored
+        assert_eq!(store.len(), 11);
+    }
+
+    #[test]
+    fn lazy_proofs_use_the_tree_hash_algorithm() {
+        for algorithm in [
+            HashAlgorithm::Keccak256,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Blake3,
+        ] {
+            let mut tree = MerkleTreeBuilder::new()
+                .hash_algorithm(algorithm)
+                .build(&leaves(5))
+                .unwrap();
+            let mut store = MemoryNodeStore::new();
+            let root = tree.persist(&mut store).unwrap();
+            let lazy = LazyMerkleTree::open(store, root).unwrap();
+            for index in 0..5 {
+                let proof = lazy.generate_proof_by_index(index).unwrap();
+                assert_eq!(proof, tree.generate_proof_by_index(index).unwrap());
+                assert!(proof.verify(tree.root_hash()).unwrap());
+            }
+            tree.clear_leaf_data();
+            assert_eq!(lazy.materialize().unwrap(), tree);
+        }
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn sled_roots_keep_the_tree_settings() {
+        let path = std::env::temp_dir().join(format!("merkle_tree_sled_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        let builder = MerkleTreeBuilder::new()
+            .hash_algorithm(HashAlgorithm::Sha256)
+            .odd_nodes(OddNodePolicy::Duplicate)
+            .sorted_pairs(true)
+            .domain_separation(true);
+        let tree = builder.build(&leaves(5)).unwrap();
+        // sled releases its file lock from a background thread, so the database is kept
+        // open and only the store is dropped
+        let db = sled::open(&path).unwrap();
+        {
+            let mut store = SledNodeStore::from_db(&db).unwrap();
+            let root = tree.persist(&mut store).unwrap();
+            store.record_root(&root).unwrap();
+            store.flush().unwrap();
+        }
+
+        let store = SledNodeStore::from_db(&db).unwrap();
+        assert_eq!(store.latest_root().unwrap(), Some(tree.stored_root()));
+        let lazy = LazyMerkleTree::reopen(store.clone(), &builder)
+            .unwrap()
+            .unwrap();
+        for index in 0..5 {
+            let proof = lazy.generate_proof_by_index(index).unwrap();
+            assert!(proof.verify(tree.root_hash()).unwrap());
+        }
+
+        for other in [
+            builder.clone().hash_algorithm(HashAlgorithm::Keccak256),
+            builder.clone().odd_nodes(OddNodePolicy::Promote),
+            builder.clone().sorted_pairs(false),
+            builder.clone().domain_separation(false),
+        ] {
+            let result = LazyMerkleTree::reopen(store.clone(), &other);
+            assert!(matches!(
+                result,
+                Err(MerkleTreeError::StoredRootMismatch { .. })
+            ));
+        }
+        drop((lazy, store, db));
+        let _ = fs::remove_dir_all(&path);
+    }
+}
@@ -0,0 +1,488 @@
+//! Reconciling the leaves of two replicas over a transport provided by the caller.
+//!
+//! One replica answers [`SyncRequest`]s with a [`SyncResponder`]; the other runs
+//! [`reconcile`], which compares roots, fetches the child hashes of every differing node
+//! level by level, and finally fetches the leaves that differ. Subtrees whose hashes match
+//! are never descended into, so replicas that differ in `d` leaves exchange `O(d log n)`
+//! hashes over `O(log n)` round trips.
+//!
+//! Nodes are compared by position: node `j` of level `k` covers leaves `j * 2^k` up to
+//! `(j + 1) * 2^k`. This suits replicas that append leaves in the same order, where one
+//! replica's leaves are a prefix of the other's; inserting a leaf in the middle changes
+//! every later position.
+
+use crate::error::MerkleTreeError;
+use crate::hasher::{HashAlgorithm, OddNodePolicy, TreeHasher};
+use crate::merkle_tree::MerkleTree;
+
+use alloy_primitives::B256;
+use serde::{Deserialize, Serialize};
+
+/// A request sent to the replica being synced from.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyncRequest {
+    /// The root, leaf count and tree options.
+    Summary,
+    /// The hashes of nodes at `level`, counting from the leaves at 0.
+    Nodes { level: usize, indices: Vec<usize> },
+    /// The leaves at `indices`, with their data if the replica holds it.
+    Leaves { indices: Vec<usize> },
+}
+
+/// The answer to a [`SyncRequest`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyncResponse {
+    Summary {
+        #[serde(with = "crate::serialization::b256_hex")]
+        root: B256,
+        leaf_count: usize,
+        hash_algorithm: HashAlgorithm,
+        sorted_pairs: bool,
+        odd_nodes: OddNodePolicy,
+        #[serde(default)]
+        domain_separation: bool,
+    },
+    /// Node hashes, in the order they were requested.
+    Nodes {
+        #[serde(with = "crate::serialization::b256_vec_hex")]
+        hashes: Vec<B256>,
+    },
+    /// Leaves, in the order they were requested.
+    Leaves { leaves: Vec<SyncLeaf> },
+}
+
+/// A leaf of the remote replica.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct SyncLeaf {
+    pub index: usize,
+    #[serde(with = "crate::serialization::b256_hex")]
+    pub hash: B256,
+    /// Raw leaf data, absent if the replica keeps only leaf hashes.
+    #[serde(default, with = "crate::serialization::option_bytes_hex")]
+    pub data: Option<Vec<u8>>,
+}
+
+/// Answers [`SyncRequest`]s about a tree.
+///
+/// The node hashes of every level are computed once, when the responder is created.
+pub struct SyncResponder<'a> {
+    tree: &'a MerkleTree,
+    levels: Vec<Vec<B256>>,
+}
+
+impl<'a> SyncResponder<'a> {
+    pub fn new(tree: &'a MerkleTree) -> Result<Self, MerkleTreeError> {
+        Ok(SyncResponder {
+            tree,
+            levels: tree.levels()?,
+        })
+    }
+
+    /// Answers `request`, failing if it names a node or leaf the tree does not have.
+    pub fn respond(&self, request: &SyncRequest) -> Result<SyncResponse, MerkleTreeError> {
+        match request {
+            SyncRequest::Summary => Ok(SyncResponse::Summary {
+                root: *self.tree.root_hash(),
+                leaf_count: self.levels[0].len(),
+                hash_algorithm: self.tree.hash_algorithm,
+                sorted_pairs: self.tree.sorted_pairs,
+                odd_nodes: self.tree.odd_nodes,
+                domain_separation: self.tree.domain_separation,
+            }),
+            SyncRequest::Nodes { level, indices } => {
+                let hashes = indices
+                    .iter()
+                    .map(|&index| self.node(*level, index))
+                    .collect::<Result<_, _>>()?;
+                Ok(SyncResponse::Nodes { hashes })
+            }
+            SyncRequest::Leaves { indices } => {
+                let leaves = indices
+                    .iter()
+                    .map(|&index| {
+                        let hash = self.node(0, index)?;
+                        Ok(SyncLeaf {
+                            index,
+                            hash,
+                            data: self.tree.leaves.get(&hash).cloned(),
+                        })
+                    })
+                    .collect::<Result<_, MerkleTreeError>>()?;
+                Ok(SyncResponse::Leaves { leaves })
+            }
+        }
+    }
+
+    fn node(&self, level: usize, index: usize) -> Result<B256, MerkleTreeError> {
+        self.levels
+            .get(level)
+            .and_then(|nodes| nodes.get(index))
+            .copied()
+            .ok_or(MerkleTreeError::NodeOutOfRange { level, index })
+    }
+}
+
+/// What [`reconcile`] found out about the remote replica.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SyncPlan {
+    pub remote_root: B256,
+    pub remote_leaf_count: usize,
+    /// Remote leaves that differ from the local leaf at the same index, or lie past the
+    /// end of the local tree, in index order.
+    pub fetched: Vec<SyncLeaf>,
+    /// Indices of local leaves past the end of the remote tree.
+    pub missing_remotely: Vec<usize>,
+    /// Number of requests sent.
+    pub round_trips: usize,
+}
+
+impl SyncPlan {
+    /// Returns `true` if both replicas hold the same leaves.
+    pub fn is_empty(&self) -> bool {
+        self.fetched.is_empty() && self.missing_remotely.is_empty()
+    }
+}
+
+/// Compares `local` with a remote replica reached through `exchange`, which sends a
+/// request and returns the remote's response, and fetches the leaves that differ.
+///
+/// Both replicas must be built with the same hash function, domain separation, pair
+/// sorting and odd node policy. Fetched leaves are checked against the hashes the remote sent for them.
+pub fn reconcile<F>(local: &MerkleTree, mut exchange: F) -> Result<SyncPlan, MerkleTreeError>
+where
+    F: FnMut(SyncRequest) -> Result<SyncResponse, MerkleTreeError>,
+{
+    let (remote_root, remote_leaf_count) = match exchange(SyncRequest::Summary)? {
+        SyncResponse::Summary {
+            root,
+            leaf_count,
+            hash_algorithm,
+            sorted_pairs,
+            odd_nodes,
+            domain_separation,
+        } => {
+            local.hasher().check_matches(TreeHasher {
+                hash_algorithm,
+                domain_separation,
+                sorted_pairs,
+                odd_nodes,
+            })?;
+            (root, leaf_count)
+        }
+        _ => return Err(unexpected_response("summary")),
+    };
+
+    let local_levels = local.levels()?;
+    let local_leaf_count = local_levels[0].len();
+    let mut plan = SyncPlan {
+        remote_root,
+        remote_leaf_count,
+        fetched: Vec::new(),
+        missing_remotely: (remote_leaf_count..local_leaf_count).collect(),
+        round_trips: 1,
+    };
+    if remote_root == *local.root_hash() && remote_leaf_count == local_leaf_count {
+        return Ok(plan);
+    }
+
+    let mut level_sizes = vec![remote_leaf_count];
+    while level_sizes[level_sizes.len() - 1] > 1 {
+        level_sizes.push(level_sizes[level_sizes.len() - 1].div_ceil(2));
+    }
+
+    // The differing nodes of the current level, with their remote hashes
+    let mut level = level_sizes.len() - 1;
+    let mut differing = vec![(0, remote_root)];
+    while level > 0 && !differing.is_empty() {
+        level -= 1;
+        let indices: Vec<usize> = differing
+            .iter()
+            .flat_map(|&(index, _)| [2 * index, 2 * index + 1])
+            .filter(|&index| index < level_sizes[level])
+            .collect();
+        let hashes = match exchange(SyncRequest::Nodes {
+            level,
+            indices: indices.clone(),
+        })? {
+            SyncResponse::Nodes { hashes } => hashes,
+            _ => return Err(unexpected_response("node hashes")),
+        };
+        plan.round_trips += 1;
+        if hashes.len() != indices.len() {
+            return Err(MerkleTreeError::LengthMismatch {
+                expected: indices.len(),
+                got: hashes.len(),
+            });
+        }
+
+        let local_nodes = local_levels.get(level);
+        differing = indices
+            .into_iter()
+            .zip(hashes)
+            .filter(|(index, hash)| local_nodes.and_then(|nodes| nodes.get(*index)) != Some(hash))
+            .collect();
+    }
+
+    if !differing.is_empty() {
+        let indices: Vec<usize> = differing.iter().map(|&(index, _)| index).collect();
+        let leaves = match exchange(SyncRequest::Leaves { indices })? {
+            SyncResponse::Leaves { leaves } => leaves,
+            _ => return Err(unexpected_response("leaves")),
+        };
+        plan.round_trips += 1;
+        if leaves.len() != differing.len() {
+            return Err(MerkleTreeError::LengthMismatch {
+                expected: differing.len(),
+                got: leaves.len(),
+            });
+        }
+
+        for (leaf, (index, hash)) in leaves.iter().zip(&differing) {
+            let data_matches = leaf
+                .data
+                .as_ref()
+                .is_none_or(|data| local.hash_leaf(data) == leaf.hash);
+            if leaf.index != *index || leaf.hash != *hash || !data_matches {
+                return Err(MerkleTreeError::RemoteLeafMismatch { index: *index });
+            }
+        }
+        plan.fetched = leaves;
+    }
+
+    Ok(plan)
+}
+
+impl MerkleTree {
+    /// Adopts the leaves fetched by [`reconcile`], replacing differing leaves, appending the
+    /// ones past the end of the tree and dropping local leaves past the end of the remote.
+    ///
+    /// The changes are made to a copy of the tree, whose root must then equal the remote
+    /// root. Only then does the copy replace the tree, so on error it is left unchanged.
+    pub fn apply_sync(&mut self, plan: &SyncPlan) -> Result<(), MerkleTreeError> {
+        let mut synced = self.clone();
+        synced.adopt(plan)?;
+        if *synced.root_hash() != plan.remote_root {
+            return Err(MerkleTreeError::RootMismatch {
+                expected: plan.remote_root,
+                got: *synced.root_hash(),
+            });
+        }
+        *self = synced;
+        Ok(())
+    }
+
+    /// Makes the changes of [`MerkleTree::apply_sync`] in place.
+    fn adopt(&mut self, plan: &SyncPlan) -> Result<(), MerkleTreeError> {
+        self.truncate(plan.remote_leaf_count)?;
+        let leaf_count = self.leaf_count();
+        let mut appended = Vec::new();
+        for leaf in &plan.fetched {
+            if leaf.index >= leaf_count {
+                appended.push(leaf);
+                continue;
+            }
+            match &leaf.data {
+                Some(data) => self.update(leaf.index, data)?,
+                None => self.update_leaf_hash(leaf.index, leaf.hash)?,
+            }
+        }
+
+        if !appended.is_empty() {
+            self.append_leaf_hashes(appended.iter().map(|leaf| leaf.hash).collect())?;
+            for leaf in appended {
+                if let Some(data) = &leaf.data {
+                    self.leaves.insert(leaf.hash, data.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn unexpected_response(expected: &'static str) -> MerkleTreeError {
+    MerkleTreeError::UnexpectedResponse { expected }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::MerkleTreeBuilder;
+    use crate::hasher::TreeOption;
+
+    fn tree(leaves: &[&str]) -> MerkleTree {
+        let data: Vec<Vec<u8>> = leaves.iter().map(|leaf| leaf.as_bytes().to_vec()).collect();
+        MerkleTreeBuilder::new().build(&data).unwrap()
+    }
+
+    /// Reconciles `local` with `remote`, passing each response through `tamper`.
+    fn sync_with(
+        local: &MerkleTree,
+        remote: &MerkleTree,
+        mut tamper: impl FnMut(SyncResponse) -> SyncResponse,
+    ) -> Result<SyncPlan, MerkleTreeError> {
+        let responder = SyncResponder::new(remote).unwrap();
+        reconcile(local, |request| {
+            responder.respond(&request).map(&mut tamper)
+        })
+    }
+
+    fn sync(local: &MerkleTree, remote: &MerkleTree) -> SyncPlan {
+        sync_with(local, remote, |response| response).unwrap()
+    }
+
+    #[test]
+    fn identical_replicas_need_nothing() {
+        let local = tree(&["a", "b", "c"]);
+        let plan = sync(&local, &local.clone());
+        assert!(plan.is_empty());
+        assert_eq!(plan.round_trips, 1);
+    }
+
+    #[test]
+    fn divergent_replicas_converge() {
+        let mut local = tree(&["a", "b", "c", "d", "e"]);
+        let remote = tree(&["a", "B", "c", "d", "E", "f", "g"]);
+        let plan = sync(&local, &remote);
+        let indices: Vec<usize> = plan.fetched.iter().map(|leaf| leaf.index).collect();
+        assert_eq!(indices, [1, 4, 5, 6]);
+        assert!(plan.missing_remotely.is_empty());
+
+        local.apply_sync(&plan).unwrap();
+        assert_eq!(local, remote);
+        assert!(sync(&local, &remote).is_empty());
+    }
+
+    #[test]
+    fn truncated_remotes_are_root_checked() {
+        let mut local = tree(&["a", "b", "c", "d", "e", "f"]);
+        let remote = tree(&["a", "b", "C"]);
+        let plan = sync(&local, &remote);
+        assert_eq!(plan.missing_remotely, [3, 4, 5]);
+        assert_eq!(plan.fetched.len(), 1);
+
+        local.apply_sync(&plan).unwrap();
+        assert_eq!(local, remote);
+
+        // A plan claiming a root the kept leaves do not produce is rejected
+        let mut local = tree(&["a", "b", "c", "d", "e", "f"]);
+        let before = local.clone();
+        let mut plan = sync(&local, &tree(&["a", "b", "c"]));
+        plan.remote_root = *tree(&["a", "b", "x"]).root_hash();
+        assert!(matches!(
+            local.apply_sync(&plan),
+            Err(MerkleTreeError::RootMismatch { .. })
+        ));
+        assert_eq!(local, before);
+    }
+
+    #[test]
+    fn tampered_leaves_are_rejected() {
+        let local = tree(&["a", "b", "c", "d"]);
+        let remote = tree(&["a", "b", "x", "d"]);
+
+        // A leaf whose hash differs from the node hash sent before it
+        let result = sync_with(&local, &remote, |response| match response {
+            SyncResponse::Leaves { mut leaves } => {
+                leaves[0].hash = B256::repeat_byte(1);
+                SyncResponse::Leaves { leaves }
+            }
+            other => other,
+        });
+        assert!(matches!(
+            result,
+            Err(MerkleTreeError::RemoteLeafMismatch { index: 2 })
+        ));
+
+        // Data that does not hash to the leaf hash
+        let result = sync_with(&local, &remote, |response| match response {
+            SyncResponse::Leaves { mut leaves } => {
+                leaves[0].data = Some(b"y".to_vec());
+                SyncResponse::Leaves { leaves }
+            }
+            other => other,
+        });
+        assert!(matches!(
+            result,
+            Err(MerkleTreeError::RemoteLeafMismatch { index: 2 })
+        ));
+    }
+
+    #[test]
+    fn consistently_forged_leaves_leave_the_tree_unchanged() {
+        let mut local = tree(&["a", "b", "c", "d"]);
+        let before = local.clone();
+        let remote = tree(&["a", "b", "x", "d"]);
+
+        // The remote forges leaf 2 in both its node and leaf answers, without its data
+        let forged = B256::repeat_byte(1);
+        let plan = sync_with(&local, &remote, |response| match response {
+            SyncResponse::Nodes { mut hashes } => {
+                for hash in &mut hashes {
+                    if *hash == remote.leaf_hashes[2] {
+                        *hash = forged;
+                    }
+                }
+                SyncResponse::Nodes { hashes }
+            }
+            SyncResponse::Leaves { mut leaves } => {
+                leaves[0].hash = forged;
+                leaves[0].data = None;
+                SyncResponse::Leaves { leaves }
+            }
+            other => other,
+        })
+        .unwrap();
+        assert_eq!(plan.fetched[0].hash, forged);
+
+        assert!(matches!(
+            local.apply_sync(&plan),
+            Err(MerkleTreeError::RootMismatch { .. })
+        ));
+        assert_eq!(local, before);
+    }
+
+    #[test]
+    fn rejects_replicas_with_other_options() {
+        let local = tree(&["a", "b"]);
+        let remote = MerkleTreeBuilder::new()
+            .sorted_pairs(true)
+            .build(&[b"a".to_vec(), b"b".to_vec()])
+            .unwrap();
+        assert!(matches!(
+            sync_with(&local, &remote, |response| response),
+            Err(MerkleTreeError::OptionMismatch {
+                expected: TreeOption::SortedPairs(false),
+                got: TreeOption::SortedPairs(true),
+            })
+        ));
+
+        let remote = MerkleTreeBuilder::new()
+            .domain_separation(true)
+            .build(&[b"a".to_vec(), b"b".to_vec()])
+            .unwrap();
+        assert!(matches!(
+            sync_with(&local, &remote, |response| response),
+            Err(MerkleTreeError::OptionMismatch {
+                expected: TreeOption::DomainSeparation(false),
+                got: TreeOption::DomainSeparation(true),
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_unexpected_responses() {
+        let local = tree(&["a", "b"]);
+        let remote = tree(&["a", "x"]);
+        let result = sync_with(&local, &remote, |_| SyncResponse::Leaves {
+            leaves: Vec::new(),
+        });
+        assert!(matches!(
+            result,
+            Err(MerkleTreeError::UnexpectedResponse {
+                expected: "summary"
+            })
+        ));
+    }
+}
@@ -0,0 +1,155 @@
+//! [`Arbitrary`] implementations and proptest strategies for property-testing code that
+//! consumes trees and proofs.
+//!
+//! Generated trees are built from generated leaves with generated options, and generated
+//! proofs are taken from such trees, so both are always valid. Leaves of 64 bytes are
+//! allowed, so every leaf length up to the maximum is covered. Individual [`ProofStep`]s
+//! are random.
+
+use crate::builder::MerkleTreeBuilder;
+use crate::hasher::{HashAlgorithm, OddNodePolicy};
+use crate::merkle_tree::MerkleTree;
+use crate::proof::{MerkleProof, ProofStep};
+
+use alloy_primitives::B256;
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+/// Largest number of leaves in trees built by the [`Arbitrary`] implementations.
+const MAX_ARBITRARY_LEAVES: usize = 256;
+
+impl<'a> Arbitrary<'a> for HashAlgorithm {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&[
+            HashAlgorithm::Keccak256,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Blake3,
+        ])?)
+    }
+}
+
+impl<'a> Arbitrary<'a> for OddNodePolicy {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&[OddNodePolicy::Promote, OddNodePolicy::Duplicate])?)
+    }
+}
+
+impl<'a> Arbitrary<'a> for ProofStep {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let hash = B256::from(u.arbitrary::<[u8; 32]>()?);
+        Ok(if u.arbitrary()? {
+            ProofStep::Left(hash)
+        } else {
+            ProofStep::Right(hash)
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for MerkleTree {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let leaf_count = u.int_in_range(1..=MAX_ARBITRARY_LEAVES)?;
+        let leaves = (0..leaf_count)
+            .map(|_| u.arbitrary())
+            .collect::<arbitrary::Result<Vec<Vec<u8>>>>()?;
+        let builder = MerkleTreeBuilder::new()
+            .hash_algorithm(u.arbitrary()?)
+            .sorted_pairs(u.arbitrary()?)
+            .odd_nodes(u.arbitrary()?)
+            .domain_separation(u.arbitrary()?)
+            .sort_leaves(u.arbitrary()?)
+            .allow_64_byte_leaves(true);
+        builder
+            .build(&leaves)
+            .map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for MerkleProof {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let tree = MerkleTree::arbitrary(u)?;
+        let index = u.choose_index(tree.leaf_count())?;
+        tree.generate_proof_by_index(index)
+            .map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+/// Generates any hash function.
+pub fn hash_algorithm() -> impl Strategy<Value = HashAlgorithm> {
+    prop_oneof![
+        Just(HashAlgorithm::Keccak256),
+        Just(HashAlgorithm::Sha256),
+        Just(HashAlgorithm::Blake3),
+    ]
+}
+
+/// Generates either odd node policy.
+pub fn odd_node_policy() -> impl Strategy<Value = OddNodePolicy> {
+    prop_oneof![Just(OddNodePolicy::Promote), Just(OddNodePolicy::Duplicate)]
+}
+
+/// Generates a random hash.
+pub fn b256() -> impl Strategy<Value = B256> {
+    any::<[u8; 32]>().prop_map(B256::from)
+}
+
+/// Generates a step with a random sibling on a random side.
+pub fn proof_step() -> impl Strategy<Value = ProofStep> {
+    (any::<bool>(), b256()).prop_map(|(left, hash)| {
+        if left {
+            ProofStep::Left(hash)
+        } else {
+            ProofStep::Right(hash)
+        }
+    })
+}
+
+/// Generates between 1 and `max_leaves` leaves of up to 64 bytes each.
+pub fn leaves(max_leaves: usize) -> impl Strategy<Value = Vec<Vec<u8>>> {
+    vec(vec(any::<u8>(), 0..=64), 1..=max_leaves.max(1))
+}
+
+/// Generates a tree of up to `max_leaves` leaves, with random options.
+pub fn tree(max_leaves: usize) -> impl Strategy<Value = MerkleTree> {
+    (
+        leaves(max_leaves),
+        hash_algorithm(),
+        any::<bool>(),
+        odd_node_policy(),
+        any::<bool>(),
+        any::<bool>(),
+    )
+        .prop_map(
+            |(leaves, hash_algorithm, sorted_pairs, odd_nodes, domain_separation, sort_leaves)| {
+                MerkleTreeBuilder::new()
+                    .hash_algorithm(hash_algorithm)
+                    .sorted_pairs(sorted_pairs)
+                    .odd_nodes(odd_nodes)
+                    .domain_separation(domain_separation)
+                    .sort_leaves(sort_leaves)
+                    .allow_64_byte_leaves(true)
+                    .build(&leaves)
+                    .expect("leaves are not empty")
+            },
+        )
+}
+
+/// Generates a tree of up to `max_leaves` leaves with the index of one of its leaves and
+/// that leaf's proof.
+pub fn tree_with_proof(
+    max_leaves: usize,
+) -> impl Strategy<Value = (MerkleTree, usize, MerkleProof)> {
+    tree(max_leaves).prop_flat_map(|tree| {
+        (0..tree.leaf_count()).prop_map(move |index| {
+            let proof = tree
+                .generate_proof_by_index(index)
+                .expect("index is in range");
+            (tree.clone(), index, proof)
+        })
+    })
+}
+
+/// Generates a valid proof from a tree of up to `max_leaves` leaves.
+pub fn proof(max_leaves: usize) -> impl Strategy<Value = MerkleProof> {
+    tree_with_proof(max_leaves).prop_map(|(_, _, proof)| proof)
+}
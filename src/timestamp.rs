@@ -0,0 +1,578 @@
+//! OpenTimestamps proofs that a root existed at a point in time.
+//!
+//! The timestamped "file" is the 32 root bytes, so a proof written with
+//! [`TimestampProof::to_bytes`] is a standard detached `.ots` file that the `ots` client
+//! also verifies. Calendars first return pending attestations; once they have committed
+//! the root to Bitcoin, usually within a few hours, [`TimestampProof::upgrade`] fetches the
+//! complete path to a block.
+//!
+//! The digest is submitted without a nonce, so calendars learn the root. Verification stops
+//! at the Bitcoin block: checking that its header holds the computed merkle root needs a
+//! Bitcoin node or block explorer.
+
+use crate::error::MerkleTreeError;
+use crate::merkle_tree::MerkleTree;
+
+use alloy_primitives::hex::encode;
+use alloy_primitives::{keccak256, B256};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+
+/// Public calendars stamps are submitted to when none are given.
+pub const DEFAULT_CALENDARS: &[&str] = &[
+    "https://a.pool.opentimestamps.org",
+    "https://b.pool.opentimestamps.org",
+    "https://a.pool.eternitywall.com",
+];
+
+/// Magic bytes starting a detached timestamp file.
+const HEADER_MAGIC: &[u8] = b"\x00OpenTimestamps\x00\x00Proof\x00\xbf\x89\xe2\xe8\x84\xe8\x92\x94";
+const MAJOR_VERSION: u64 = 1;
+
+const PENDING_TAG: [u8; 8] = [0x83, 0xdf, 0xe3, 0x0d, 0x2e, 0xf9, 0x0c, 0x8e];
+const BITCOIN_TAG: [u8; 8] = [0x05, 0x88, 0x96, 0x0d, 0x73, 0xd7, 0x19, 0x01];
+
+/// Limits on untrusted timestamps, as in the reference implementation.
+const MAX_DEPTH: usize = 256;
+const MAX_LENGTH: usize = 4096;
+
+/// A claim that a message existed at some time.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Attestation {
+    /// A calendar will commit the message to Bitcoin; upgrade the proof to get the path.
+    Pending { uri: String },
+    /// The message is the merkle root of the Bitcoin block at `height`.
+    Bitcoin { height: u64 },
+    /// An attestation this crate does not interpret, kept as is.
+    Unknown { tag: [u8; 8], payload: Vec<u8> },
+}
+
+/// A step from one message to the next.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Operation {
+    Sha256,
+    Keccak256,
+    Append(Vec<u8>),
+    Prepend(Vec<u8>),
+}
+
+impl Operation {
+    fn apply(&self, msg: &[u8]) -> Vec<u8> {
+        match self {
+            Operation::Sha256 => Sha256::digest(msg).to_vec(),
+            Operation::Keccak256 => keccak256(msg).to_vec(),
+            Operation::Append(suffix) => [msg, suffix].concat(),
+            Operation::Prepend(prefix) => [prefix, msg].concat(),
+        }
+    }
+}
+
+/// The attestations of a message, and the operations leading to further messages.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Timestamp {
+    msg: Vec<u8>,
+    attestations: Vec<Attestation>,
+    operations: Vec<(Operation, Timestamp)>,
+}
+
+impl Timestamp {
+    fn new(msg: Vec<u8>) -> Self {
+        Timestamp {
+            msg,
+            attestations: Vec::new(),
+            operations: Vec::new(),
+        }
+    }
+
+    /// Returns the message this timestamp starts from.
+    pub fn msg(&self) -> &[u8] {
+        &self.msg
+    }
+
+    /// Returns every attestation reachable from this timestamp, with the message it
+    /// attests.
+    pub fn all_attestations(&self) -> Vec<(&Attestation, &[u8])> {
+        let mut all: Vec<_> = self
+            .attestations
+            .iter()
+            .map(|attestation| (attestation, self.msg.as_slice()))
+            .collect();
+        for (_, stamp) in &self.operations {
+            all.extend(stamp.all_attestations());
+        }
+        all
+    }
+
+    /// Adds the attestations and operations of `other`, a timestamp of the same message.
+    fn merge(&mut self, other: Timestamp) {
+        for attestation in other.attestations {
+            if !self.attestations.contains(&attestation) {
+                self.attestations.push(attestation);
+            }
+        }
+        for (operation, stamp) in other.operations {
+            match self.operations.iter_mut().find(|(op, _)| *op == operation) {
+                Some((_, existing)) => existing.merge(stamp),
+                None => self.operations.push((operation, stamp)),
+            }
+        }
+    }
+
+    /// Parses a timestamp of `msg`.
+    fn deserialize(
+        reader: &mut Reader<'_>,
+        msg: Vec<u8>,
+        depth: usize,
+    ) -> Result<Self, MerkleTreeError> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("timestamp is nested too deeply"));
+        }
+        let mut stamp = Timestamp::new(msg);
+        loop {
+            // 0xff marks every item but the last
+            let tag = reader.byte()?;
+            let (tag, last) = match tag {
+                0xff => (reader.byte()?, false),
+                tag => (tag, true),
+            };
+            if tag == 0x00 {
+                stamp.attestations.push(read_attestation(reader)?);
+            } else {
+                let operation = read_operation(reader, tag)?;
+                let result = operation.apply(&stamp.msg);
+                if result.len() > MAX_LENGTH {
+                    return Err(invalid("timestamp message is too long"));
+                }
+                let next = Timestamp::deserialize(reader, result, depth + 1)?;
+                stamp.operations.push((operation, next));
+            }
+            if last {
+                return Ok(stamp);
+            }
+        }
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        let mut attestations: Vec<&Attestation> = self.attestations.iter().collect();
+        attestations.sort();
+        let mut operations: Vec<&(Operation, Timestamp)> = self.operations.iter().collect();
+        operations.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let count = attestations.len() + operations.len();
+        for (i, attestation) in attestations.into_iter().enumerate() {
+            if i + 1 < count {
+                out.push(0xff);
+            }
+            out.push(0x00);
+            write_attestation(attestation, out);
+        }
+        let offset = count - operations.len();
+        for (i, (operation, stamp)) in operations.into_iter().enumerate() {
+            if offset + i + 1 < count {
+                out.push(0xff);
+            }
+            write_operation(operation, out);
+            stamp.serialize(out);
+        }
+    }
+
+    /// Replaces pending attestations by what their calendars have since committed,
+    /// returning whether any were upgraded.
+    fn upgrade(&mut self) -> Result<bool, MerkleTreeError> {
+        let mut upgraded = false;
+        for (_, stamp) in &mut self.operations {
+            upgraded |= stamp.upgrade()?;
+        }
+
+        let uris: Vec<String> = self
+            .attestations
+            .iter()
+            .filter_map(|attestation| match attestation {
+                Attestation::Pending { uri } => Some(uri.clone()),
+                _ => None,
+            })
+            .collect();
+        for uri in uris {
+            let url = format!(
+                "{}/timestamp/{}",
+                uri.trim_end_matches('/'),
+                encode(&self.msg)
+            );
+            let Some(body) = fetch(ureq::get(&url), None)? else {
+                // Not committed yet
+                continue;
+            };
+            let mut reader = Reader::new(&body);
+            let stamp = Timestamp::deserialize(&mut reader, self.msg.clone(), 0)?;
+            reader.finish()?;
+            self.attestations.retain(|attestation| match attestation {
+                Attestation::Pending { uri: pending } => *pending != uri,
+                _ => true,
+            });
+            self.merge(stamp);
+            upgraded = true;
+        }
+        Ok(upgraded)
+    }
+}
+
+/// An OpenTimestamps proof of a tree's root.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TimestampProof {
+    /// SHA-256 of the root bytes, the timestamped file's digest.
+    pub digest: B256,
+    pub timestamp: Timestamp,
+}
+
+/// An attestation found by [`TimestampProof::verify`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct VerifiedAttestation {
+    pub attestation: Attestation,
+    /// The attested message. For a Bitcoin attestation, the block's merkle root in the
+    /// header's byte order, the reverse of how block explorers print it.
+    pub commitment: Vec<u8>,
+}
+
+impl TimestampProof {
+    /// Submits the root of `tree` to each calendar and combines their pending attestations.
+    ///
+    /// Fails only if no calendar accepts the root.
+    pub fn stamp<S: AsRef<str>>(
+        tree: &MerkleTree,
+        calendars: &[S],
+    ) -> Result<Self, MerkleTreeError> {
+        let digest = B256::from_slice(&Sha256::digest(tree.root_hash()));
+        let mut timestamp = Timestamp::new(digest.to_vec());
+
+        let mut errors = Vec::new();
+        for calendar in calendars {
+            let url = format!("{}/digest", calendar.as_ref().trim_end_matches('/'));
+            let submitted = fetch(ureq::post(&url), Some(digest.as_slice())).and_then(|body| {
+                let body = body.ok_or_else(|| {
+                    MerkleTreeError::TimestampError(format!("{} was not found", url))
+                })?;
+                let mut reader = Reader::new(&body);
+                let stamp = Timestamp::deserialize(&mut reader, digest.to_vec(), 0)?;
+                reader.finish()?;
+                Ok(stamp)
+            });
+            match submitted {
+                Ok(stamp) => timestamp.merge(stamp),
+                Err(e) => {
+                    log::warn!("Calendar {} failed: {}", calendar.as_ref(), e);
+                    errors.push(e.to_string());
+                }
+            }
+        }
+        if errors.len() == calendars.len() {
+            return Err(MerkleTreeError::TimestampError(format!(
+                "no calendar accepted the root: {}",
+                errors.join("; ")
+            )));
+        }
+        Ok(TimestampProof { digest, timestamp })
+    }
+
+    /// Fetches the Bitcoin attestations of pending calendars, returning whether any were
+    /// found. Calendars that have not committed the root yet are left pending.
+    pub fn upgrade(&mut self) -> Result<bool, MerkleTreeError> {
+        self.timestamp.upgrade()
+    }
+
+    /// Returns whether the proof is for the root of `tree`.
+    pub fn covers(&self, tree: &MerkleTree) -> bool {
+        self.digest.as_slice() == Sha256::digest(tree.root_hash()).as_slice()
+    }
+
+    /// Returns the attestations of the root of `tree`, pending ones included, or an
+    /// [`ChecksumMismatch`](MerkleTreeError::ChecksumMismatch) error if the proof is for
+    /// another root.
+    pub fn verify(&self, tree: &MerkleTree) -> Result<Vec<VerifiedAttestation>, MerkleTreeError> {
+        if !self.covers(tree) {
+            return Err(MerkleTreeError::ChecksumMismatch {
+                expected: self.digest,
+                got: B256::from_slice(&Sha256::digest(tree.root_hash())),
+            });
+        }
+        Ok(self
+            .timestamp
+            .all_attestations()
+            .into_iter()
+            .map(|(attestation, commitment)| VerifiedAttestation {
+                attestation: attestation.clone(),
+                commitment: commitment.to_vec(),
+            })
+            .collect())
+    }
+
+    /// Serializes the proof as a detached `.ots` file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = HEADER_MAGIC.to_vec();
+        write_varuint(MAJOR_VERSION, &mut out);
+        write_operation(&Operation::Sha256, &mut out);
+        out.extend_from_slice(self.digest.as_slice());
+        self.timestamp.serialize(&mut out);
+        out
+    }
+
+    /// Deserializes a detached `.ots` file of a SHA-256 digest.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleTreeError> {
+        let mut reader = Reader::new(bytes);
+        if reader.bytes(HEADER_MAGIC.len())? != HEADER_MAGIC {
+            return Err(invalid("not an OpenTimestamps proof"));
+        }
+        let version = reader.varuint()?;
+        if version != MAJOR_VERSION {
+            return Err(invalid(&format!("unsupported version {}", version)));
+        }
+        if reader.byte()? != 0x08 {
+            return Err(invalid("timestamped file digest is not SHA-256"));
+        }
+        let digest = B256::from_slice(reader.bytes(32)?);
+        let timestamp = Timestamp::deserialize(&mut reader, digest.to_vec(), 0)?;
+        reader.finish()?;
+        Ok(TimestampProof { digest, timestamp })
+    }
+}
+
+/// Sends a calendar request, returning the response body or `None` if it was not found.
+fn fetch(request: ureq::Request, body: Option<&[u8]>) -> Result<Option<Vec<u8>>, MerkleTreeError> {
+    let request = request
+        .set("Accept", "application/vnd.opentimestamps.v1")
+        .set(
+            "User-Agent",
+            concat!("merkle_tree/", env!("CARGO_PKG_VERSION")),
+        );
+    let sent = match body {
+        Some(body) => request.send_bytes(body),
+        None => request.call(),
+    };
+    let response = match sent {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(None),
+        Err(e) => return Err(MerkleTreeError::TimestampError(e.to_string())),
+    };
+    let mut contents = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_LENGTH as u64 * 16)
+        .read_to_end(&mut contents)?;
+    Ok(Some(contents))
+}
+
+fn invalid(message: &str) -> MerkleTreeError {
+    MerkleTreeError::TimestampError(format!("invalid timestamp: {}", message))
+}
+
+fn read_attestation(reader: &mut Reader<'_>) -> Result<Attestation, MerkleTreeError> {
+    let tag: [u8; 8] = reader.bytes(8)?.try_into().expect("8 bytes");
+    let payload = reader.varbytes()?;
+    let mut payload_reader = Reader::new(payload);
+    let attestation = match tag {
+        PENDING_TAG => {
+            let uri = String::from_utf8(payload_reader.varbytes()?.to_vec())
+                .map_err(|_| invalid("calendar URI is not UTF-8"))?;
+            Attestation::Pending { uri }
+        }
+        BITCOIN_TAG => Attestation::Bitcoin {
+            height: payload_reader.varuint()?,
+        },
+        _ => {
+            return Ok(Attestation::Unknown {
+                tag,
+                payload: payload.to_vec(),
+            })
+        }
+    };
+    payload_reader.finish()?;
+    Ok(attestation)
+}
+
+fn write_attestation(attestation: &Attestation, out: &mut Vec<u8>) {
+    let mut payload = Vec::new();
+    let tag = match attestation {
+        Attestation::Pending { uri } => {
+            write_varbytes(uri.as_bytes(), &mut payload);
+            PENDING_TAG
+        }
+        Attestation::Bitcoin { height } => {
+            write_varuint(*height, &mut payload);
+            BITCOIN_TAG
+        }
+        Attestation::Unknown { tag, payload: raw } => {
+            payload.extend_from_slice(raw);
+            *tag
+        }
+    };
+    out.extend_from_slice(&tag);
+    write_varbytes(&payload, out);
+}
+
+fn read_operation(reader: &mut Reader<'_>, tag: u8) -> Result<Operation, MerkleTreeError> {
+    match tag {
+        0x08 => Ok(Operation::Sha256),
+        0x67 => Ok(Operation::Keccak256),
+        0xf0 => Ok(Operation::Append(reader.varbytes()?.to_vec())),
+        0xf1 => Ok(Operation::Prepend(reader.varbytes()?.to_vec())),
+        tag => Err(invalid(&format!("unsupported operation 0x{:02x}", tag))),
+    }
+}
+
+fn write_operation(operation: &Operation, out: &mut Vec<u8>) {
+    match operation {
+        Operation::Sha256 => out.push(0x08),
+        Operation::Keccak256 => out.push(0x67),
+        Operation::Append(suffix) => {
+            out.push(0xf0);
+            write_varbytes(suffix, out);
+        }
+        Operation::Prepend(prefix) => {
+            out.push(0xf1);
+            write_varbytes(prefix, out);
+        }
+    }
+}
+
+fn write_varuint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_varbytes(bytes: &[u8], out: &mut Vec<u8>) {
+    write_varuint(bytes.len() as u64, out);
+    out.extend_from_slice(bytes);
+}
+
+/// A cursor over serialized timestamp data.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], MerkleTreeError> {
+        if len > self.data.len() {
+            return Err(invalid("unexpected end of data"));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, MerkleTreeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn varuint(&mut self) -> Result<u64, MerkleTreeError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("integer is too large"))
+    }
+
+    fn varbytes(&mut self) -> Result<&'a [u8], MerkleTreeError> {
+        let len = self.varuint()?;
+        if len > MAX_LENGTH as u64 {
+            return Err(invalid("field is too long"));
+        }
+        self.bytes(len as usize)
+    }
+
+    /// Fails if data is left over.
+    fn finish(&self) -> Result<(), MerkleTreeError> {
+        if self.data.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid("trailing data"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::hex;
+
+    /// A detached `.ots` file for the root of [`tree`], assembled byte by byte from the
+    /// format specification: a pending attestation and a path to block 358391.
+    const PROOF: &[u8] = include_bytes!("../testdata/timestamp_abcd.ots");
+
+    fn tree() -> MerkleTree {
+        let leaves: Vec<Vec<u8>> = ["a", "b", "c", "d"].map(|leaf| leaf.into()).to_vec();
+        MerkleTree::new(&leaves).unwrap()
+    }
+
+    #[test]
+    fn parses_and_serializes_ots_files() {
+        let proof = TimestampProof::from_bytes(PROOF).unwrap();
+        assert_eq!(proof.to_bytes(), PROOF);
+        assert!(proof.covers(&tree()));
+
+        let attestations = proof.verify(&tree()).unwrap();
+        assert_eq!(attestations.len(), 2);
+        assert_eq!(
+            attestations[0].attestation,
+            Attestation::Pending {
+                uri: "https://alice.btc.calendar.opentimestamps.org".to_string()
+            }
+        );
+        assert_eq!(attestations[0].commitment, proof.digest.as_slice());
+        assert_eq!(
+            attestations[1].attestation,
+            Attestation::Bitcoin { height: 358391 }
+        );
+        assert_eq!(
+            attestations[1].commitment,
+            hex!("72c0fd50eed01d08af065d5ff2c953b2c59a3bf743e5c6e5285deca80c1b9f21")
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_and_tampered_proofs() {
+        for len in [0, HEADER_MAGIC.len(), 64, PROOF.len() - 1] {
+            assert!(
+                matches!(
+                    TimestampProof::from_bytes(&PROOF[..len]),
+                    Err(MerkleTreeError::TimestampError(_))
+                ),
+                "{len} bytes"
+            );
+        }
+
+        let tampered = |offset: usize, byte: u8| {
+            let mut bytes = PROOF.to_vec();
+            bytes[offset] = byte;
+            TimestampProof::from_bytes(&bytes)
+        };
+        // Magic, version, file hash operation and an operation tag
+        for (offset, byte) in [(1, b'X'), (31, 2), (32, 0x67), (122, 0x02)] {
+            assert!(
+                matches!(
+                    tampered(offset, byte),
+                    Err(MerkleTreeError::TimestampError(_))
+                ),
+                "byte {offset}"
+            );
+        }
+        assert!(TimestampProof::from_bytes(&[PROOF, &[0]].concat()).is_err());
+
+        // A proof of another digest parses, but does not cover the tree
+        let other = tampered(33, 0).unwrap();
+        assert!(!other.covers(&tree()));
+        assert!(matches!(
+            other.verify(&tree()),
+            Err(MerkleTreeError::ChecksumMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,174 @@
+//! Proof verification and root computation that only need `core` and `alloc`.
+//!
+//! This is what remains of the crate with `default-features = false`, for zkVM guests,
+//! light clients on microcontrollers and other runtimes without `std`. It computes the
+//! same hashes as [`MerkleTree`](crate::MerkleTree) and
+//! [`MerkleProof::verify`](crate::MerkleProof::verify), which are built on it.
+
+use crate::hasher::{HashAlgorithm, OddNodePolicy, TreeHasher};
+
+use alloc::vec::Vec;
+use alloy_primitives::B256;
+use subtle::ConstantTimeEq;
+
+/// Represents a single step in the Merkle Proof.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum ProofStep {
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(schema_with = "crate::json_schema::hash_schema")
+    )]
+    #[cfg_attr(
+        feature = "std",
+        serde(
+            serialize_with = "crate::serialization::b256_hex::serialize",
+            deserialize_with = "crate::serialization::b256_hex::deserialize"
+        )
+    )]
+    Left(B256), // Sibling hash is on the left
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(schema_with = "crate::json_schema::hash_schema")
+    )]
+    #[cfg_attr(
+        feature = "std",
+        serde(
+            serialize_with = "crate::serialization::b256_hex::serialize",
+            deserialize_with = "crate::serialization::b256_hex::deserialize"
+        )
+    )]
+    Right(B256), // Sibling hash is on the right
+}
+
+/// Hashes `leaf_hash` up through `steps` and returns the root it leads to.
+///
+/// With `domain_separation`, each pair is prefixed with [`NODE_PREFIX`](crate::hasher::NODE_PREFIX) before hashing.
+/// With `sorted_pairs`, each pair is sorted before hashing and the sides of the steps do
+/// not matter. Pairs are hashed through a fixed 64-byte stack buffer, so this performs no
+/// heap allocations.
+pub fn process_proof(
+    hash_algorithm: HashAlgorithm,
+    domain_separation: bool,
+    sorted_pairs: bool,
+    leaf_hash: B256,
+    steps: &[ProofStep],
+) -> B256 {
+    let hasher = TreeHasher {
+        hash_algorithm,
+        domain_separation,
+        sorted_pairs,
+        odd_nodes: OddNodePolicy::default(),
+    };
+    steps
+        .iter()
+        .fold(leaf_hash, |computed_hash, step| match step {
+            ProofStep::Left(sibling_hash) => hasher.hash_pair(sibling_hash, &computed_hash),
+            ProofStep::Right(sibling_hash) => hasher.hash_pair(&computed_hash, sibling_hash),
+        })
+}
+
+/// Compares two hashes in constant time, so the time taken does not reveal how many
+/// leading bytes of a forged hash were right.
+pub fn hashes_equal(a: &B256, b: &B256) -> bool {
+    a.as_slice().ct_eq(b.as_slice()).into()
+}
+
+/// Returns whether `steps` lead from `leaf_hash` to `root_hash`.
+///
+/// The computed root is compared with [`hashes_equal`].
+pub fn verify_proof(
+    hash_algorithm: HashAlgorithm,
+    domain_separation: bool,
+    sorted_pairs: bool,
+    leaf_hash: B256,
+    steps: &[ProofStep],
+    root_hash: &B256,
+) -> bool {
+    hashes_equal(
+        &process_proof(
+            hash_algorithm,
+            domain_separation,
+            sorted_pairs,
+            leaf_hash,
+            steps,
+        ),
+        root_hash,
+    )
+}
+
+/// Returns whether `steps` lead from `leaf_hash` to `root_hash` without pairing a node
+/// with a copy of itself on its left.
+///
+/// Trees pair a node with its copy on the right when duplicating the odd last node of a
+/// level. A copy on the left stands for a leaf appended to forge the same root
+/// (CVE-2012-2459), so such proofs are rejected even if the root matches. This also rejects
+/// proofs of the right one of two equal sibling subtrees, so trees verified this way should
+/// not hold repeated leaves.
+///
+/// With `sorted_pairs`, the side of a sibling does not change the parent hash, so a copy on
+/// either side is rejected, including the copies of odd nodes in trees that duplicate them.
+pub fn verify_proof_strict(
+    hash_algorithm: HashAlgorithm,
+    domain_separation: bool,
+    sorted_pairs: bool,
+    leaf_hash: B256,
+    steps: &[ProofStep],
+    root_hash: &B256,
+) -> bool {
+    let hasher = TreeHasher {
+        hash_algorithm,
+        domain_separation,
+        sorted_pairs,
+        odd_nodes: OddNodePolicy::default(),
+    };
+    let mut computed_hash = leaf_hash;
+    for step in steps {
+        computed_hash = match step {
+            ProofStep::Left(sibling_hash) if *sibling_hash == computed_hash => return false,
+            ProofStep::Right(sibling_hash) if sorted_pairs && *sibling_hash == computed_hash => {
+                return false;
+            }
+            ProofStep::Left(sibling_hash) => hasher.hash_pair(sibling_hash, &computed_hash),
+            ProofStep::Right(sibling_hash) => hasher.hash_pair(&computed_hash, sibling_hash),
+        };
+    }
+    hashes_equal(&computed_hash, root_hash)
+}
+
+/// Computes the root of a tree over already hashed leaves, in order, or `None` if there
+/// are no leaves.
+///
+/// Leaves are hashed by the caller, with [`HashAlgorithm::hash`] or, with
+/// `domain_separation`, with [`HashAlgorithm::hash_prefixed`] and
+/// [`LEAF_PREFIX`](crate::hasher::LEAF_PREFIX). The
+/// result matches [`MerkleTree::root_hash`](crate::MerkleTree::root_hash) for a tree built
+/// with the same options.
+pub fn compute_root(
+    hash_algorithm: HashAlgorithm,
+    domain_separation: bool,
+    sorted_pairs: bool,
+    odd_nodes: OddNodePolicy,
+    leaf_hashes: &[B256],
+) -> Option<B256> {
+    let hasher = TreeHasher {
+        hash_algorithm,
+        domain_separation,
+        sorted_pairs,
+        odd_nodes,
+    };
+    let mut level: Vec<B256> = leaf_hashes.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => hasher.hash_pair(left, right),
+                [odd] if hasher.duplicates_odd() => hasher.hash_pair(odd, odd),
+                [odd] => *odd,
+                _ => unreachable!("chunks of two hashes"),
+            })
+            .collect();
+    }
+    level.pop()
+}
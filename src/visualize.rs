@@ -0,0 +1,304 @@
+//! Diagrams of a tree's structure, for documentation and debugging of small trees.
+//!
+//! Each node is labelled with the first bytes of its hash. When a leaf index is given, the
+//! nodes on that leaf's path to the root and the siblings that make up its proof are
+//! highlighted. Copies made for odd nodes under [`OddNodePolicy::Duplicate`] are drawn
+//! dashed.
+
+use crate::error::MerkleTreeError;
+use crate::hasher::OddNodePolicy;
+use crate::merkle_node::MerkleNode;
+use crate::merkle_tree::MerkleTree;
+use crate::mutate::tree_depth;
+
+use alloy_primitives::hex::encode;
+use alloy_primitives::B256;
+use std::fmt::Write;
+
+/// Bytes of each hash shown in node labels.
+const LABEL_BYTES: usize = 4;
+
+const PATH_COLOR: &str = "#ffd966";
+const SIBLING_COLOR: &str = "#9fc5e8";
+
+/// Horizontal space per leaf and vertical space per level in SVG output.
+const SVG_COLUMN: usize = 110;
+const SVG_ROW: usize = 70;
+const SVG_BOX_WIDTH: usize = 96;
+const SVG_BOX_HEIGHT: usize = 28;
+
+/// How a node relates to the highlighted proof.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Plain,
+    Path,
+    Sibling,
+}
+
+/// A node placed at the level and index where it was created, skipping promotions.
+struct Placed {
+    level: usize,
+    index: usize,
+    hash: B256,
+    has_children: bool,
+    is_copy: bool,
+    role: Role,
+}
+
+impl Placed {
+    fn id(&self) -> String {
+        node_id(self.level, self.index)
+    }
+
+    fn label(&self) -> String {
+        format!("0x{}…", encode(&self.hash[..LABEL_BYTES]))
+    }
+}
+
+fn node_id(level: usize, index: usize) -> String {
+    format!("n{}_{}", level, index)
+}
+
+/// The shape of the tree being drawn and the leaf whose proof is highlighted.
+#[derive(Clone, Copy)]
+struct Layout {
+    leaf_count: usize,
+    odd_nodes: OddNodePolicy,
+    proof_index: Option<usize>,
+}
+
+impl MerkleTree {
+    /// Renders the tree as a Graphviz DOT graph, optionally highlighting the proof for the
+    /// leaf at `proof_index`.
+    pub fn to_dot(&self, proof_index: Option<usize>) -> Result<String, MerkleTreeError> {
+        let (nodes, layout) = self.place_nodes(proof_index)?;
+
+        let mut dot = String::from("digraph merkle_tree {\n");
+        dot.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+        for node in &nodes {
+            let dashed = if node.is_copy { "dashed," } else { "" };
+            let style = match node.role {
+                Role::Plain if node.is_copy => ", style=dashed".to_string(),
+                Role::Plain => String::new(),
+                Role::Path => format!(", style=\"{}filled\", fillcolor=\"{}\"", dashed, PATH_COLOR),
+                Role::Sibling => format!(
+                    ", style=\"{}filled\", fillcolor=\"{}\"",
+                    dashed, SIBLING_COLOR
+                ),
+            };
+            let _ = writeln!(
+                dot,
+                "  {} [label=\"{}\"{}];",
+                node.id(),
+                node.label(),
+                style
+            );
+        }
+        for node in nodes.iter().filter(|node| node.has_children) {
+            for (level, index) in children(node.level, node.index, layout) {
+                let _ = writeln!(dot, "  {} -> {};", node.id(), node_id(level, index));
+            }
+        }
+        dot.push_str("}\n");
+        Ok(dot)
+    }
+
+    /// Renders the tree as a Mermaid flowchart, optionally highlighting the proof for the
+    /// leaf at `proof_index`.
+    pub fn to_mermaid(&self, proof_index: Option<usize>) -> Result<String, MerkleTreeError> {
+        let (nodes, layout) = self.place_nodes(proof_index)?;
+
+        let mut mermaid = String::from("graph TD\n");
+        for node in &nodes {
+            let _ = writeln!(mermaid, "  {}[\"{}\"]", node.id(), node.label());
+        }
+        for node in nodes.iter().filter(|node| node.has_children) {
+            for (level, index) in children(node.level, node.index, layout) {
+                let _ = writeln!(mermaid, "  {} --> {}", node.id(), node_id(level, index));
+            }
+        }
+        if nodes.iter().any(|node| node.is_copy) {
+            let _ = writeln!(mermaid, "  classDef copy stroke-dasharray:4");
+            for node in nodes.iter().filter(|node| node.is_copy) {
+                let _ = writeln!(mermaid, "  class {} copy", node.id());
+            }
+        }
+        if proof_index.is_some() {
+            let _ = writeln!(mermaid, "  classDef path fill:{}", PATH_COLOR);
+            let _ = writeln!(mermaid, "  classDef sibling fill:{}", SIBLING_COLOR);
+            for node in &nodes {
+                match node.role {
+                    Role::Plain => {}
+                    Role::Path => {
+                        let _ = writeln!(mermaid, "  class {} path", node.id());
+                    }
+                    Role::Sibling => {
+                        let _ = writeln!(mermaid, "  class {} sibling", node.id());
+                    }
+                }
+            }
+        }
+        Ok(mermaid)
+    }
+
+    /// Renders the tree as a standalone SVG image, optionally highlighting the proof for
+    /// the leaf at `proof_index`.
+    ///
+    /// Leaves are laid out left to right along the bottom, with each node centred over
+    /// the leaves it covers. Copies of odd nodes stand in for whole subtrees, so duplicating
+    /// trees are laid out over `2^depth` columns.
+    pub fn to_svg(&self, proof_index: Option<usize>) -> Result<String, MerkleTreeError> {
+        let (nodes, layout) = self.place_nodes(proof_index)?;
+        let depth = self.depth();
+        let columns = match self.odd_nodes {
+            OddNodePolicy::Promote => self.leaf_count(),
+            OddNodePolicy::Duplicate => 1 << depth,
+        };
+
+        let center = |level: usize, index: usize| {
+            let first = index << level;
+            let end = ((index + 1) << level).min(columns);
+            let x = (first + end) * SVG_COLUMN / 2;
+            let y = (depth - level) * SVG_ROW + SVG_ROW / 2;
+            (x, y)
+        };
+
+        let width = columns * SVG_COLUMN;
+        let height = (depth + 1) * SVG_ROW;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             font-family=\"monospace\" font-size=\"12\">\n",
+            width, height
+        );
+        for node in nodes.iter().filter(|node| node.has_children) {
+            let (x, y) = center(node.level, node.index);
+            for (level, index) in children(node.level, node.index, layout) {
+                let (child_x, child_y) = center(level, index);
+                let _ = writeln!(
+                    svg,
+                    "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\"/>",
+                    x, y, child_x, child_y
+                );
+            }
+        }
+        for node in &nodes {
+            let (x, y) = center(node.level, node.index);
+            let fill = match node.role {
+                Role::Plain => "white",
+                Role::Path => PATH_COLOR,
+                Role::Sibling => SIBLING_COLOR,
+            };
+            let dash = if node.is_copy {
+                " stroke-dasharray=\"4\""
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                svg,
+                "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"black\"{}/>",
+                x - SVG_BOX_WIDTH / 2,
+                y - SVG_BOX_HEIGHT / 2,
+                SVG_BOX_WIDTH,
+                SVG_BOX_HEIGHT,
+                fill,
+                dash
+            );
+            let _ = writeln!(
+                svg,
+                "  <text x=\"{}\" y=\"{}\" text-anchor=\"middle\" dominant-baseline=\"middle\">{}</text>",
+                x,
+                y,
+                node.label()
+            );
+        }
+        svg.push_str("</svg>\n");
+        Ok(svg)
+    }
+
+    /// Lists the nodes in pre-order with their positions and proof roles.
+    fn place_nodes(
+        &self,
+        proof_index: Option<usize>,
+    ) -> Result<(Vec<Placed>, Layout), MerkleTreeError> {
+        let leaf_count = self.leaf_count();
+        if let Some(index) = proof_index {
+            if index >= leaf_count {
+                return Err(MerkleTreeError::IndexOutOfRange { index, leaf_count });
+            }
+        }
+
+        let role = match proof_index {
+            Some(_) => Role::Path,
+            None => Role::Plain,
+        };
+        let layout = Layout {
+            leaf_count,
+            odd_nodes: self.odd_nodes,
+            proof_index,
+        };
+        let mut nodes = Vec::with_capacity(self.node_count());
+        place(
+            &self.root,
+            tree_depth(leaf_count),
+            0,
+            layout,
+            role,
+            &mut nodes,
+        );
+        Ok((nodes, layout))
+    }
+}
+
+/// Returns the positions of the children of the node at `index` on `level`.
+fn children(level: usize, index: usize, layout: Layout) -> [(usize, usize); 2] {
+    [
+        natural_position(level - 1, 2 * index, layout),
+        natural_position(level - 1, 2 * index + 1, layout),
+    ]
+}
+
+/// Moves a position down past promotions to the level where its node was created.
+fn natural_position(mut level: usize, mut index: usize, layout: Layout) -> (usize, usize) {
+    if layout.odd_nodes == OddNodePolicy::Duplicate {
+        return (level, index);
+    }
+    while level > 0 && layout.leaf_count <= (index << level) + (1 << (level - 1)) {
+        level -= 1;
+        index *= 2;
+    }
+    (level, index)
+}
+
+fn place(
+    node: &MerkleNode,
+    level: usize,
+    index: usize,
+    layout: Layout,
+    role: Role,
+    nodes: &mut Vec<Placed>,
+) {
+    let (level, index) = natural_position(level, index, layout);
+    let children = match (&node.left, &node.right) {
+        (Some(left), Some(right)) if level > 0 => Some((left, right)),
+        _ => None,
+    };
+    nodes.push(Placed {
+        level,
+        index,
+        hash: node.hash,
+        has_children: children.is_some(),
+        is_copy: index << level >= layout.leaf_count,
+        role,
+    });
+
+    if let Some((left, right)) = children {
+        let mid = (index << level) + (1 << (level - 1));
+        let (left_role, right_role) = match layout.proof_index {
+            Some(leaf) if role == Role::Path && leaf < mid => (Role::Path, Role::Sibling),
+            Some(_) if role == Role::Path => (Role::Sibling, Role::Path),
+            _ => (Role::Plain, Role::Plain),
+        };
+        place(left, level - 1, 2 * index, layout, left_role, nodes);
+        place(right, level - 1, 2 * index + 1, layout, right_role, nodes);
+    }
+}
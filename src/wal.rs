@@ -0,0 +1,521 @@
+//! Crash-safe persistence of a mutable tree with a write-ahead log and snapshots.
+//!
+//! A [`DurableTree`] keeps two files in its directory:
+//!
+//! - `snapshot.json`, the tree with its leaf data, version and root history as of some
+//!   version, replaced atomically by writing a new file and renaming it over the old one;
+//! - `wal.log`, every change made since, each written and synced before it is applied.
+//!
+//! Log records are framed as a 4-byte little-endian length, the first 4 bytes of the
+//! SHA-256 of the payload, and the payload, a JSON object naming the change and the version
+//! it produced. Opening the directory loads the snapshot and replays the log after it. A
+//! record cut short or corrupted by a crash ends the log: it and anything after it are
+//! discarded, which loses at most the change that was being written.
+
+use crate::error::MerkleTreeError;
+use crate::merkle_node::DeserializeLimits;
+use crate::merkle_tree::MerkleTree;
+use crate::serialization::{HexB256, HexBytes};
+
+use alloy_primitives::B256;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const WAL_FILE: &str = "wal.log";
+
+/// Bytes before each log record's payload: its length and checksum.
+const FRAME_HEADER: usize = 8;
+
+/// The root of a [`DurableTree`] after a change.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RootRecord {
+    /// Number of changes made since the tree was created, 0 for the initial tree.
+    pub version: u64,
+    #[serde(with = "crate::serialization::b256_hex")]
+    pub root: B256,
+    pub leaf_count: usize,
+}
+
+/// A change recorded in the log.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Change {
+    Append { leaves: Vec<HexBytes> },
+    AppendLeafHashes { leaf_hashes: Vec<HexB256> },
+    Update { index: usize, data: HexBytes },
+    UpdateLeafHash { index: usize, leaf_hash: HexB256 },
+    Remove { index: usize },
+}
+
+#[derive(Serialize, Deserialize)]
+struct LogRecord {
+    version: u64,
+    #[serde(flatten)]
+    change: Change,
+}
+
+impl Change {
+    fn apply(&self, tree: &mut MerkleTree) -> Result<(), MerkleTreeError> {
+        match self {
+            Change::Append { leaves } => {
+                let data: Vec<Vec<u8>> = leaves.iter().map(|leaf| leaf.0.clone()).collect();
+                tree.append(&data)
+            }
+            Change::AppendLeafHashes { leaf_hashes } => {
+                tree.append_leaf_hashes(leaf_hashes.iter().map(|hash| hash.0).collect())
+            }
+            Change::Update { index, data } => tree.update(*index, &data.0),
+            Change::UpdateLeafHash { index, leaf_hash } => {
+                tree.update_leaf_hash(*index, leaf_hash.0)
+            }
+            Change::Remove { index } => tree.remove(*index).map(|_| ()),
+        }
+    }
+}
+
+/// A tree whose every change is logged to disk before it is applied.
+pub struct DurableTree {
+    dir: PathBuf,
+    tree: MerkleTree,
+    version: u64,
+    roots: Vec<RootRecord>,
+    wal: File,
+    wal_len: u64,
+    logged: usize,
+    snapshot_every: Option<usize>,
+}
+
+impl DurableTree {
+    /// Starts persisting `tree` in `dir`, creating the directory if needed and writing the
+    /// first snapshot. Fails if `dir` already holds a snapshot.
+    ///
+    /// Changes are bounded by the tree's [`limits`](MerkleTree::limits).
+    pub fn create(dir: impl Into<PathBuf>, tree: MerkleTree) -> Result<Self, MerkleTreeError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        if dir.join(SNAPSHOT_FILE).exists() {
+            return Err(MerkleTreeError::StorageError(format!(
+                "{} already holds a tree",
+                dir.display()
+            )));
+        }
+
+        let roots = vec![RootRecord {
+            version: 0,
+            root: *tree.root_hash(),
+            leaf_count: tree.leaf_count(),
+        }];
+        let mut durable = DurableTree {
+            wal: open_wal(&dir)?,
+            dir,
+            tree,
+            version: 0,
+            roots,
+            wal_len: 0,
+            logged: 0,
+            snapshot_every: None,
+        };
+        durable.snapshot()?;
+        Ok(durable)
+    }
+
+    /// Recovers the tree persisted in `dir`: loads the snapshot and replays the log.
+    ///
+    /// Snapshots do not record limits, so the recovered tree has none; use
+    /// [`DurableTree::open_with_limits`] to bound it.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, MerkleTreeError> {
+        Self::open_with_limits(dir, DeserializeLimits::default())
+    }
+
+    /// Recovers the tree persisted in `dir` like [`DurableTree::open`], loading the
+    /// snapshot under `limits` and bounding replayed and later changes by them.
+    pub fn open_with_limits(
+        dir: impl Into<PathBuf>,
+        limits: DeserializeLimits,
+    ) -> Result<Self, MerkleTreeError> {
+        let dir = dir.into();
+        let snapshot: Value = serde_json::from_slice(&fs::read(dir.join(SNAPSHOT_FILE))?)?;
+        let (tree, version, roots) = match snapshot {
+            Value::Object(mut fields) => {
+                let tree = fields.remove("tree").ok_or_else(|| corrupt("no tree"))?;
+                let version = fields
+                    .remove("version")
+                    .ok_or_else(|| corrupt("no version"))?;
+                let roots = fields.remove("roots").ok_or_else(|| corrupt("no roots"))?;
+                (
+                    MerkleTree::from_json_value_with_limits(tree, limits)?,
+                    serde_json::from_value::<u64>(version)?,
+                    serde_json::from_value::<Vec<RootRecord>>(roots)?,
+                )
+            }
+            _ => return Err(corrupt("not an object")),
+        };
+
+        let mut durable = DurableTree {
+            wal: open_wal(&dir)?,
+            dir,
+            tree,
+            version,
+            roots,
+            wal_len: 0,
+            logged: 0,
+            snapshot_every: None,
+        };
+        durable.replay()?;
+        Ok(durable)
+    }
+
+    /// Takes a snapshot, and empties the log, after every `changes` logged changes.
+    pub fn snapshot_every(mut self, changes: usize) -> Self {
+        self.snapshot_every = Some(changes.max(1));
+        self
+    }
+
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    /// Returns the number of changes made since the tree was created.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns the root after every change, oldest first.
+    pub fn roots(&self) -> &[RootRecord] {
+        &self.roots
+    }
+
+    /// Appends leaves built from `data`, as [`MerkleTree::append`] does.
+    pub fn append(&mut self, data: &[Vec<u8>]) -> Result<(), MerkleTreeError> {
+        let leaves = data.iter().cloned().map(HexBytes).collect();
+        self.commit(Change::Append { leaves })
+    }
+
+    /// Appends already hashed leaves, as [`MerkleTree::append_leaf_hashes`] does.
+    pub fn append_leaf_hashes(&mut self, leaf_hashes: Vec<B256>) -> Result<(), MerkleTreeError> {
+        let leaf_hashes = leaf_hashes.into_iter().map(HexB256).collect();
+        self.commit(Change::AppendLeafHashes { leaf_hashes })
+    }
+
+    /// Replaces the leaf at `index`, as [`MerkleTree::update`] does.
+    pub fn update(&mut self, index: usize, data: &[u8]) -> Result<(), MerkleTreeError> {
+        let data = HexBytes(data.to_vec());
+        self.commit(Change::Update { index, data })
+    }
+
+    /// Replaces the hash of the leaf at `index`, as [`MerkleTree::update_leaf_hash`] does.
+    pub fn update_leaf_hash(
+        &mut self,
+        index: usize,
+        leaf_hash: B256,
+    ) -> Result<(), MerkleTreeError> {
+        let leaf_hash = HexB256(leaf_hash);
+        self.commit(Change::UpdateLeafHash { index, leaf_hash })
+    }
+
+    /// Removes the leaf at `index`, as [`MerkleTree::remove`] does.
+    pub fn remove(&mut self, index: usize) -> Result<(), MerkleTreeError> {
+        self.commit(Change::Remove { index })
+    }
+
+    /// Writes a snapshot of the current state and empties the log.
+    pub fn snapshot(&mut self) -> Result<(), MerkleTreeError> {
+        let tree: Value = serde_json::from_str(&self.tree.to_json_with_leaf_data()?)?;
+        let snapshot = json!({
+            "version": self.version,
+            "roots": self.roots,
+            "tree": tree,
+        });
+
+        let path = self.dir.join(SNAPSHOT_FILE);
+        let temp_path = self.dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        let mut temp = File::create(&temp_path)?;
+        temp.write_all(&serde_json::to_vec(&snapshot)?)?;
+        temp.sync_all()?;
+        fs::rename(&temp_path, &path)?;
+        sync_dir(&self.dir)?;
+
+        // A crash before the log is emptied leaves records the snapshot already covers,
+        // which replay skips by version
+        self.wal.set_len(0)?;
+        self.wal.seek(SeekFrom::Start(0))?;
+        self.wal.sync_all()?;
+        self.wal_len = 0;
+        self.logged = 0;
+        Ok(())
+    }
+
+    /// Logs `change`, then applies it. A change that fails to be logged or that the tree
+    /// rejects is removed from the log.
+    fn commit(&mut self, change: Change) -> Result<(), MerkleTreeError> {
+        let record = LogRecord {
+            version: self.version + 1,
+            change,
+        };
+        let payload = serde_json::to_vec(&record)?;
+        let mut frame = Vec::with_capacity(FRAME_HEADER + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&Sha256::digest(&payload)[..4]);
+        frame.extend_from_slice(&payload);
+        let written = self
+            .wal
+            .write_all(&frame)
+            .and_then(|()| self.wal.sync_data());
+        if let Err(e) = written {
+            // Cut off whatever part of the record made it to disk, or replay would stop at
+            // it and drop every later record. The write error is the one worth reporting.
+            let _ = self.rewind_log();
+            return Err(e.into());
+        }
+
+        if let Err(e) = record.change.apply(&mut self.tree) {
+            self.rewind_log()?;
+            return Err(e);
+        }
+        self.wal_len += frame.len() as u64;
+        self.logged += 1;
+        self.record_root(record.version);
+
+        if self
+            .snapshot_every
+            .is_some_and(|every| self.logged >= every)
+        {
+            self.snapshot()?;
+        }
+        Ok(())
+    }
+
+    /// Truncates the log to its last committed record and moves the write position there.
+    fn rewind_log(&mut self) -> Result<(), MerkleTreeError> {
+        self.wal.set_len(self.wal_len)?;
+        self.wal.seek(SeekFrom::Start(self.wal_len))?;
+        self.wal.sync_data()?;
+        Ok(())
+    }
+
+    /// Applies the logged changes newer than the snapshot, and cuts the log after the last
+    /// intact record.
+    fn replay(&mut self) -> Result<(), MerkleTreeError> {
+        let mut records = Vec::new();
+        self.wal.read_to_end(&mut records)?;
+
+        let mut offset = 0;
+        while let Some(payload) = next_record(&records[offset..]) {
+            let record: LogRecord = serde_json::from_slice(payload)?;
+            if record.version > self.version {
+                if record.version != self.version + 1 {
+                    return Err(MerkleTreeError::StorageError(
+                        "write-ahead log skips a version".to_string(),
+                    ));
+                }
+                record.change.apply(&mut self.tree)?;
+                self.record_root(record.version);
+                self.logged += 1;
+            }
+            offset += FRAME_HEADER + payload.len();
+        }
+
+        if offset < records.len() {
+            log::warn!(
+                "Discarding {} bytes of incomplete write-ahead log",
+                records.len() - offset
+            );
+            self.wal.set_len(offset as u64)?;
+            self.wal.sync_data()?;
+        }
+        self.wal.seek(SeekFrom::Start(offset as u64))?;
+        self.wal_len = offset as u64;
+        Ok(())
+    }
+
+    fn record_root(&mut self, version: u64) {
+        self.version = version;
+        self.roots.push(RootRecord {
+            version,
+            root: *self.tree.root_hash(),
+            leaf_count: self.tree.leaf_count(),
+        });
+    }
+}
+
+/// Returns the payload of the record at the start of `log`, or `None` if it is cut short
+/// or fails its checksum.
+fn next_record(log: &[u8]) -> Option<&[u8]> {
+    let header = log.get(..FRAME_HEADER)?;
+    let length = u32::from_le_bytes(header[..4].try_into().ok()?) as usize;
+    let payload = log.get(FRAME_HEADER..FRAME_HEADER.checked_add(length)?)?;
+    (Sha256::digest(payload)[..4] == header[4..]).then_some(payload)
+}
+
+fn open_wal(dir: &Path) -> Result<File, MerkleTreeError> {
+    Ok(OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(dir.join(WAL_FILE))?)
+}
+
+/// Makes a rename in `dir` durable. Directories cannot be opened for syncing on every
+/// platform, in which case this does nothing.
+fn sync_dir(dir: &Path) -> Result<(), MerkleTreeError> {
+    match File::open(dir).and_then(|dir| dir.sync_all()) {
+        Ok(()) => Ok(()),
+        Err(e)
+            if matches!(
+                e.kind(),
+                ErrorKind::PermissionDenied | ErrorKind::Unsupported
+            ) =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn corrupt(reason: &str) -> MerkleTreeError {
+    MerkleTreeError::StorageError(format!("corrupt snapshot: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    /// A fresh directory under the system temp directory, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("merkle_tree_wal_{}_{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+            TempDir(path)
+        }
+
+        fn wal(&self) -> PathBuf {
+            self.0.join(WAL_FILE)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn leaf(name: &str) -> Vec<u8> {
+        name.as_bytes().to_vec()
+    }
+
+    /// Creates a tree in `dir` and makes two changes, returning the log length after the
+    /// first one and the tree after each version.
+    fn two_changes(dir: &TempDir) -> (u64, Vec<MerkleTree>) {
+        let tree = MerkleTree::new(&[leaf("a"), leaf("b")]).unwrap();
+        let mut durable = DurableTree::create(&dir.0, tree).unwrap();
+        let mut trees = vec![durable.tree().clone()];
+        durable.append(&[leaf("c")]).unwrap();
+        trees.push(durable.tree().clone());
+        let first_len = fs::metadata(dir.wal()).unwrap().len();
+        durable.update(0, b"d").unwrap();
+        trees.push(durable.tree().clone());
+        (first_len, trees)
+    }
+
+    fn assert_recovered(durable: &DurableTree, version: u64, tree: &MerkleTree) {
+        assert_eq!(durable.version(), version);
+        assert_eq!(durable.tree(), tree);
+        assert_eq!(durable.roots().len(), version as usize + 1);
+        assert_eq!(durable.roots().last().unwrap().root, *tree.root_hash());
+    }
+
+    #[test]
+    fn replays_the_log() {
+        let dir = TempDir::new("replay");
+        let (_, trees) = two_changes(&dir);
+
+        let mut durable = DurableTree::open(&dir.0).unwrap();
+        assert_recovered(&durable, 2, &trees[2]);
+        let roots: Vec<B256> = durable.roots().iter().map(|record| record.root).collect();
+        let expected: Vec<B256> = trees.iter().map(|tree| *tree.root_hash()).collect();
+        assert_eq!(roots, expected);
+
+        durable.remove(1).unwrap();
+        let tree = durable.tree().clone();
+        drop(durable);
+        assert_recovered(&DurableTree::open(&dir.0).unwrap(), 3, &tree);
+    }
+
+    #[test]
+    fn discards_a_truncated_final_record() {
+        let dir = TempDir::new("truncated");
+        let (first_len, trees) = two_changes(&dir);
+        let log = fs::read(dir.wal()).unwrap();
+
+        // Cut inside the payload, and inside the frame header
+        for len in [log.len() - 1, first_len as usize + FRAME_HEADER / 2] {
+            fs::write(dir.wal(), &log[..len]).unwrap();
+            let mut durable = DurableTree::open(&dir.0).unwrap();
+            assert_recovered(&durable, 1, &trees[1]);
+            assert_eq!(fs::metadata(dir.wal()).unwrap().len(), first_len);
+
+            // Later changes are appended after the last intact record
+            durable.update(0, b"d").unwrap();
+            drop(durable);
+            assert_recovered(&DurableTree::open(&dir.0).unwrap(), 2, &trees[2]);
+        }
+    }
+
+    #[test]
+    fn discards_records_failing_their_checksum() {
+        let dir = TempDir::new("checksum");
+        let (first_len, trees) = two_changes(&dir);
+        let log = fs::read(dir.wal()).unwrap();
+
+        let mut torn = log.clone();
+        *torn.last_mut().unwrap() ^= 1;
+        fs::write(dir.wal(), &torn).unwrap();
+        assert_recovered(&DurableTree::open(&dir.0).unwrap(), 1, &trees[1]);
+        assert_eq!(fs::metadata(dir.wal()).unwrap().len(), first_len);
+
+        // A corrupted record ends the log, even if intact records follow it
+        let mut corrupted = log;
+        corrupted[FRAME_HEADER] ^= 1;
+        fs::write(dir.wal(), &corrupted).unwrap();
+        assert_recovered(&DurableTree::open(&dir.0).unwrap(), 0, &trees[0]);
+        assert_eq!(fs::metadata(dir.wal()).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn skips_records_covered_by_the_snapshot() {
+        let dir = TempDir::new("snapshot");
+        let (_, trees) = two_changes(&dir);
+        let log = fs::read(dir.wal()).unwrap();
+
+        // A crash after the snapshot is written but before the log is emptied
+        let mut durable = DurableTree::open(&dir.0).unwrap();
+        durable.snapshot().unwrap();
+        drop(durable);
+        fs::write(dir.wal(), &log).unwrap();
+
+        assert_recovered(&DurableTree::open(&dir.0).unwrap(), 2, &trees[2]);
+    }
+
+    #[test]
+    fn rejected_changes_are_not_logged() {
+        let dir = TempDir::new("rejected");
+        let (_, trees) = two_changes(&dir);
+        let mut durable = DurableTree::open(&dir.0).unwrap();
+        let len = fs::metadata(dir.wal()).unwrap().len();
+
+        assert!(durable.update(7, b"e").is_err());
+        assert_eq!(fs::metadata(dir.wal()).unwrap().len(), len);
+        drop(durable);
+        assert_recovered(&DurableTree::open(&dir.0).unwrap(), 2, &trees[2]);
+    }
+}
@@ -0,0 +1,115 @@
+//! JavaScript bindings for browsers and Node.
+//!
+//! The crate builds as an rlib only, so build the module as a `cdylib` with `cargo rustc
+//! --lib --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib`,
+//! then generate the JavaScript glue with `wasm-bindgen` on the resulting `.wasm` file.
+//!
+//! Proofs are exchanged as the JSON written by [`MerkleProof::to_json`], and hashes and
+//! leaves as hex strings, `0x` optional. Proofs verify with the same code that generated
+//! them on the server.
+
+use crate::hasher::HashAlgorithm;
+use crate::merkle_tree::MerkleTree;
+use crate::proof::MerkleProof;
+use crate::serialization::parse_b256;
+
+use alloy_primitives::hex::{decode, encode_prefixed};
+use std::str::FromStr;
+use wasm_bindgen::prelude::*;
+
+/// Verifies a proof, given as JSON, against a root hash.
+#[wasm_bindgen(js_name = verifyProof)]
+pub fn verify_proof(proof_json: &str, root: &str) -> Result<bool, JsError> {
+    Ok(MerkleProof::from_json(proof_json)?.verify(&parse_b256(root)?)?)
+}
+
+/// A parsed Merkle Proof.
+#[wasm_bindgen(js_name = MerkleProof)]
+pub struct JsMerkleProof(MerkleProof);
+
+#[wasm_bindgen(js_class = MerkleProof)]
+impl JsMerkleProof {
+    /// Parses a proof from JSON, migrating older schema versions.
+    #[wasm_bindgen(js_name = fromJson)]
+    pub fn from_json(json: &str) -> Result<JsMerkleProof, JsError> {
+        Ok(JsMerkleProof(MerkleProof::from_json(json)?))
+    }
+
+    /// Serializes the proof to JSON.
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> Result<String, JsError> {
+        Ok(self.0.to_json()?)
+    }
+
+    /// The hash of the proven leaf.
+    #[wasm_bindgen(getter, js_name = leafHash)]
+    pub fn leaf_hash(&self) -> String {
+        encode_prefixed(self.0.leaf_hash)
+    }
+
+    /// The name of the hash function the proof uses.
+    #[wasm_bindgen(getter, js_name = hashAlgorithm)]
+    pub fn hash_algorithm(&self) -> String {
+        self.0.hash_algorithm.to_string()
+    }
+
+    /// Verifies the proof against a root hash.
+    pub fn verify(&self, root: &str) -> Result<bool, JsError> {
+        Ok(self.0.verify(&parse_b256(root)?)?)
+    }
+}
+
+/// A Merkle Tree built in the page, for small leaf sets.
+#[wasm_bindgen(js_name = MerkleTree)]
+pub struct JsMerkleTree(MerkleTree);
+
+#[wasm_bindgen(js_class = MerkleTree)]
+impl JsMerkleTree {
+    /// Builds a tree over hex-encoded leaves. `hashAlgorithm` defaults to keccak256, and
+    /// `sortedPairs` and `domainSeparation` to false.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        leaves: Vec<String>,
+        hash_algorithm: Option<String>,
+        sorted_pairs: Option<bool>,
+        domain_separation: Option<bool>,
+    ) -> Result<JsMerkleTree, JsError> {
+        let hash_algorithm = match hash_algorithm {
+            Some(name) => HashAlgorithm::from_str(&name).map_err(|e| JsError::new(&e))?,
+            None => HashAlgorithm::default(),
+        };
+        let data = leaves
+            .iter()
+            .map(|leaf| decode(leaf.trim()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let tree = MerkleTree::builder()
+            .hash_algorithm(hash_algorithm)
+            .sorted_pairs(sorted_pairs.unwrap_or(false))
+            .domain_separation(domain_separation.unwrap_or(false))
+            .build(&data)?;
+        Ok(JsMerkleTree(tree))
+    }
+
+    /// The root hash.
+    #[wasm_bindgen(getter)]
+    pub fn root(&self) -> String {
+        encode_prefixed(self.0.root_hash())
+    }
+
+    /// Number of leaves.
+    #[wasm_bindgen(getter, js_name = leafCount)]
+    pub fn leaf_count(&self) -> usize {
+        self.0.leaf_count()
+    }
+
+    /// Generates the proof of the leaf at `index`.
+    pub fn proof(&self, index: usize) -> Result<JsMerkleProof, JsError> {
+        Ok(JsMerkleProof(self.0.generate_proof_by_index(index)?))
+    }
+
+    /// Serializes the tree to JSON.
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> Result<String, JsError> {
+        Ok(self.0.to_json()?)
+    }
+}
@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+/// @notice Verifies proofs of Merkle Trees built with keccak256 hashing, sorted pairs and
+/// leaves encoded as abi(address,uint256).
+/// @dev Generated by merkle_tree; regenerate it when the tree's options change.
+library AirdropVerifier {
+    /// @notice Hashes a leaf as the tree does.
+    function leafHash(address value0, uint256 value1) internal pure returns (bytes32) {
+        return keccak256(abi.encode(value0, value1));
+    }
+
+    /// @notice Returns whether `proof` leads from `leaf` to `root`.
+    function verify(bytes32[] memory proof, bytes32 root, bytes32 leaf) internal pure returns (bool) {
+        return processProof(proof, leaf) == root;
+    }
+
+    function processProof(bytes32[] memory proof, bytes32 leaf) internal pure returns (bytes32 computed) {
+        computed = leaf;
+        for (uint256 i = 0; i < proof.length; i++) {
+            computed = computed < proof[i] ? hashPair(computed, proof[i]) : hashPair(proof[i], computed);
+        }
+    }
+
+    function hashPair(bytes32 left, bytes32 right) private pure returns (bytes32) {
+        return keccak256(abi.encodePacked(left, right));
+    }
+}
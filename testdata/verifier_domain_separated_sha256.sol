@@ -0,0 +1,30 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+/// @notice Verifies proofs of Merkle Trees built with sha256 hashing, unsorted pairs and
+/// leaves encoded as raw bytes.
+/// @dev Leaves and pairs are prefixed with 0x00 and 0x01 before hashing, as in RFC 6962.
+/// @dev Generated by merkle_tree; regenerate it when the tree's options change.
+library Rfc6962Verifier {
+    /// @notice Hashes a leaf as the tree does.
+    function leafHash(bytes memory data) internal pure returns (bytes32) {
+        return sha256(abi.encodePacked(bytes1(0x00), data));
+    }
+
+    /// @notice Returns whether `proof` leads from `leaf` to `root`.
+    /// @dev Bit `i` of `sides` is set when the `i`-th sibling is on the left.
+    function verify(bytes32[] memory proof, uint256 sides, bytes32 root, bytes32 leaf) internal pure returns (bool) {
+        return processProof(proof, sides, leaf) == root;
+    }
+
+    function processProof(bytes32[] memory proof, uint256 sides, bytes32 leaf) internal pure returns (bytes32 computed) {
+        computed = leaf;
+        for (uint256 i = 0; i < proof.length; i++) {
+            computed = (sides >> i) & 1 == 1 ? hashPair(proof[i], computed) : hashPair(computed, proof[i]);
+        }
+    }
+
+    function hashPair(bytes32 left, bytes32 right) private pure returns (bytes32) {
+        return sha256(abi.encodePacked(bytes1(0x01), left, right));
+    }
+}